@@ -0,0 +1,522 @@
+//! Typed async client for the Harmony server.
+//!
+//! Speaks bincode over the versioned routes, so a server upgrade cannot change
+//! the shape of what this client decodes without a protocol version bump.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), harmony_client::Error> {
+//! let mut client = harmony_client::Client::new("http://192.168.2.23:3000");
+//! client.login("benevole", "secret").await?;
+//! let beneficiaries = client.beneficiaries().await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::{Display, Formatter};
+use bincode::{config, Decode, Encode};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::Method;
+use harmony_protocol::{media, v1, v2, Version, VERSION_HEADER};
+
+#[derive(Debug)]
+pub enum Error {
+    Http(reqwest::Error),
+    Status(u16, String),
+    Encode(bincode::error::EncodeError),
+    Decode(bincode::error::DecodeError),
+    NotLoggedIn,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "request failed: {e}"),
+            Error::Status(status, message) => write!(f, "server answered {status}: {message}"),
+            Error::Encode(e) => write!(f, "could not encode request: {e}"),
+            Error::Decode(e) => write!(f, "could not decode response: {e}"),
+            Error::NotLoggedIn => write!(f, "not logged in"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    version: Version,
+    token: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Client {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            version: Version::V1,
+            token: None,
+        }
+    }
+
+    /// Reuses a session token obtained elsewhere instead of calling [`Client::login`].
+    pub fn with_token(mut self, token: impl Into<String>) -> Client {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    fn session(&self) -> Result<String, Error> {
+        self.token.clone().ok_or(Error::NotLoggedIn)
+    }
+
+    async fn send<B: Encode>(&self, method: Method, path: &str, body: &B) -> Result<Vec<u8>, Error> {
+        self.send_as(self.version, method, path, body).await
+    }
+
+    /// Sends one request in another protocol version than the client's.
+    async fn send_as<B: Encode>(&self, version: Version, method: Method, path: &str, body: &B) -> Result<Vec<u8>, Error> {
+        let body = bincode::encode_to_vec(body, config::standard()).map_err(Error::Encode)?;
+        let response = self.http
+            .request(method, format!("{}{}{}", self.base_url, version.path_prefix(), path))
+            .header(ACCEPT, media::BINCODE)
+            .header(CONTENT_TYPE, media::BINCODE)
+            .header(VERSION_HEADER, version.as_str())
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let bytes = response.bytes().await?;
+        if !status.is_success() {
+            return Err(Error::Status(status.as_u16(), String::from_utf8_lossy(&bytes).into_owned()));
+        }
+        Ok(bytes.to_vec())
+    }
+
+    async fn call<B: Encode, R: Decode>(&self, method: Method, path: &str, body: &B) -> Result<R, Error> {
+        self.call_as(self.version, method, path, body).await
+    }
+
+    async fn call_as<B: Encode, R: Decode>(&self, version: Version, method: Method, path: &str, body: &B) -> Result<R, Error> {
+        let bytes = self.send_as(version, method, path, body).await?;
+        bincode::decode_from_slice(&bytes, config::standard())
+            .map(|(value, _)| value)
+            .map_err(Error::Decode)
+    }
+
+    /// Opens a session and keeps its token for the following calls.
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<v1::Connection, Error> {
+        let login = v1::UserLogin { Username: username.to_string(), Password: password.to_string() };
+        let connection: v1::Connection = self.call(Method::POST, "/user/login", &login).await?;
+        self.token = Some(connection.Token.clone());
+        Ok(connection)
+    }
+
+    pub async fn users(&self) -> Result<Vec<v1::User>, Error> {
+        self.call(Method::POST, "/user/select", &v1::Token { Token: self.session()? }).await
+    }
+
+    pub async fn create_user(&self, user: v1::User) -> Result<v1::User, Error> {
+        self.call(Method::POST, "/user", &v1::UserToken { Token: self.session()?, User: user }).await
+    }
+
+    pub async fn update_user(&self, user: v1::User) -> Result<(), Error> {
+        self.send(Method::PUT, "/user", &v1::UserToken { Token: self.session()?, User: user }).await.map(|_| ())
+    }
+
+    pub async fn delete_user(&self, user: v1::User) -> Result<(), Error> {
+        self.send(Method::DELETE, "/user", &v1::UserToken { Token: self.session()?, User: user }).await.map(|_| ())
+    }
+
+    pub async fn beneficiaries(&self) -> Result<Vec<v1::Beneficiary>, Error> {
+        self.call(Method::POST, "/beneficiary/select", &v1::Token { Token: self.session()? }).await
+    }
+
+    pub async fn search_beneficiaries(&self, search: &str) -> Result<Vec<v1::Beneficiary>, Error> {
+        let search = v1::TokenSearch { Token: self.session()?, Search: search.to_string() };
+        self.call(Method::POST, "/beneficiary/search", &search).await
+    }
+
+    pub async fn beneficiary(&self, id: i32) -> Result<v1::BeneficiaryDetails, Error> {
+        let request = v1::TokenBeneId { Token: self.session()?, Id: id };
+        self.call(Method::POST, &format!("/beneficiary/select/{id}"), &request).await
+    }
+
+    pub async fn create_beneficiary(&self) -> Result<v1::Beneficiary, Error> {
+        self.call(Method::POST, "/beneficiary", &v1::Token { Token: self.session()? }).await
+    }
+
+    pub async fn update_beneficiary(&self, beneficiary: v1::Beneficiary) -> Result<(), Error> {
+        let request = v1::TokenBene { Token: self.session()?, Beneficiary: beneficiary };
+        self.send(Method::PUT, "/beneficiary", &request).await.map(|_| ())
+    }
+
+    pub async fn insert_allergy(&self, allergy: v1::BeneficiaryAllergy) -> Result<(), Error> {
+        let request = v1::TokenAllergy { Token: self.session()?, Allergy: allergy };
+        self.send(Method::POST, "/allergy", &request).await.map(|_| ())
+    }
+
+    pub async fn delete_allergy(&self, allergy: v1::BeneficiaryAllergy) -> Result<(), Error> {
+        let request = v1::TokenAllergy { Token: self.session()?, Allergy: allergy };
+        self.send(Method::DELETE, "/allergy", &request).await.map(|_| ())
+    }
+
+    pub async fn insert_presence(&self, presence: v1::BeneficiaryPresence) -> Result<(), Error> {
+        let request = v1::TokenPresence { Token: self.session()?, Presence: presence };
+        self.send(Method::POST, "/presence", &request).await.map(|_| ())
+    }
+
+    pub async fn delete_presence(&self, presence: v1::BeneficiaryPresence) -> Result<(), Error> {
+        let request = v1::TokenPresence { Token: self.session()?, Presence: presence };
+        self.send(Method::DELETE, "/presence", &request).await.map(|_| ())
+    }
+
+    pub async fn create_note(&self, note: v1::BeneficiaryNotes) -> Result<(), Error> {
+        let request = v1::TokenNote { Token: self.session()?, Content: note };
+        self.send(Method::POST, "/note", &request).await.map(|_| ())
+    }
+
+    pub async fn update_note(&self, note: v1::BeneficiaryNotes) -> Result<(), Error> {
+        let request = v1::TokenNote { Token: self.session()?, Content: note };
+        self.send(Method::PUT, "/note", &request).await.map(|_| ())
+    }
+
+    pub async fn delete_note(&self, note: v1::BeneficiaryNotes) -> Result<(), Error> {
+        let request = v1::TokenNote { Token: self.session()?, Content: note };
+        self.send(Method::DELETE, "/note", &request).await.map(|_| ())
+    }
+
+    pub async fn categories(&self) -> Result<Vec<v1::Categories>, Error> {
+        self.call(Method::POST, "/category/select", &v1::Token { Token: self.session()? }).await
+    }
+
+    pub async fn create_category(&self, category: v1::Categories) -> Result<Vec<v1::Categories>, Error> {
+        let request = v1::TokenCategory { Token: self.session()?, Category: category };
+        self.call(Method::POST, "/category", &request).await
+    }
+
+    pub async fn update_category(&self, category: v1::Categories) -> Result<Vec<v1::Categories>, Error> {
+        let request = v1::TokenCategory { Token: self.session()?, Category: category };
+        self.call(Method::PUT, "/category", &request).await
+    }
+
+    pub async fn delete_category(&self, category: v1::Categories) -> Result<Vec<v1::Categories>, Error> {
+        let request = v1::TokenCategory { Token: self.session()?, Category: category };
+        self.call(Method::DELETE, "/category", &request).await
+    }
+
+    pub async fn stats(&self) -> Result<v1::Stats, Error> {
+        self.call(Method::POST, "/stats/select", &v1::Token { Token: self.session()? }).await
+    }
+
+    /// Audit entries matching every filter given, newest first. Admin only.
+    pub async fn audit_log(&self, beneficiary: Option<i32>, user: Option<i32>, from: Option<&str>, to: Option<&str>) -> Result<Vec<v1::AuditEntry>, Error> {
+        let query = v1::TokenAuditQuery {
+            Token: self.session()?,
+            BeneficiaryId: beneficiary,
+            UserId: user,
+            From: from.map(str::to_string),
+            To: to.map(str::to_string),
+        };
+        self.call(Method::POST, "/audit/select", &query).await
+    }
+
+    /// Every recorded version of a beneficiary, oldest first.
+    pub async fn beneficiary_history(&self, id: i32) -> Result<Vec<v1::BeneficiaryVersion>, Error> {
+        let request = v1::TokenBeneId { Token: self.session()?, Id: id };
+        self.call(Method::POST, "/beneficiary/history/select", &request).await
+    }
+
+    /// The version of a beneficiary in effect at `date` (`YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS`).
+    pub async fn beneficiary_as_of(&self, id: i32, date: &str) -> Result<v1::BeneficiaryVersion, Error> {
+        let request = v1::TokenBeneAsOf { Token: self.session()?, Id: id, Date: date.to_string() };
+        self.call(Method::POST, "/beneficiary/asof/select", &request).await
+    }
+
+    /// Deleted allergies, presences and notes, of one beneficiary or all of them. Admin only.
+    pub async fn trash(&self, beneficiary: Option<i32>) -> Result<Vec<v1::TrashEntry>, Error> {
+        let query = v1::TokenTrashQuery { Token: self.session()?, BeneficiaryId: beneficiary };
+        self.call(Method::POST, "/trash/select", &query).await
+    }
+
+    /// Puts back an entry returned by `trash`. Admin only.
+    pub async fn restore(&self, entry: v1::TrashEntry) -> Result<(), Error> {
+        let request = v1::TokenTrashEntry { Token: self.session()?, Entry: entry };
+        self.send(Method::POST, "/trash/restore", &request).await.map(|_| ())
+    }
+
+    /// Notes of a beneficiary the session role may see, with their ids.
+    pub async fn notes(&self, beneficiary: i32) -> Result<Vec<v1::Note>, Error> {
+        let request = v1::TokenBeneId { Token: self.session()?, Id: beneficiary };
+        self.call(Method::POST, "/note/select", &request).await
+    }
+
+    pub async fn edit_note(&self, id: i32, note: &str) -> Result<(), Error> {
+        let request = v1::TokenNoteText { Token: self.session()?, Note: note.to_string() };
+        self.send(Method::PUT, &format!("/note/{id}"), &request).await.map(|_| ())
+    }
+
+    pub async fn delete_note_by_id(&self, id: i32) -> Result<(), Error> {
+        self.send(Method::DELETE, &format!("/note/{id}"), &v1::Token { Token: self.session()? }).await.map(|_| ())
+    }
+
+    /// Every note type with the roles that may read or write it.
+    pub async fn note_types(&self) -> Result<Vec<v1::NoteType>, Error> {
+        self.call(Method::POST, "/notetype/select", &v1::Token { Token: self.session()? }).await
+    }
+
+    /// Admin only, the `Id` of `note_type` is ignored.
+    pub async fn create_note_type(&self, note_type: v1::NoteType) -> Result<Vec<v1::NoteType>, Error> {
+        let request = v1::TokenNoteType { Token: self.session()?, NoteType: note_type };
+        self.call(Method::POST, "/notetype", &request).await
+    }
+
+    /// Admin only, replaces the whole access matrix of the type.
+    pub async fn update_note_type(&self, note_type: v1::NoteType) -> Result<Vec<v1::NoteType>, Error> {
+        let request = v1::TokenNoteType { Token: self.session()?, NoteType: note_type };
+        self.call(Method::PUT, "/notetype", &request).await
+    }
+
+    /// Open follow-ups, earliest due first, of the session user or of `assignee` (Admin only).
+    pub async fn follow_ups(&self, assignee: Option<i32>) -> Result<Vec<v1::FollowUp>, Error> {
+        let query = v1::TokenFollowUpQuery { Token: self.session()?, AssigneeId: assignee };
+        self.call(Method::POST, "/followup/select", &query).await
+    }
+
+    /// Due on `due_date` (`YYYY-MM-DD`), assigned to the session user when `assignee` is empty.
+    pub async fn set_follow_up(&self, note: i32, due_date: &str, assignee: Option<i32>) -> Result<(), Error> {
+        let request = v1::TokenFollowUp { Token: self.session()?, DueDate: due_date.to_string(), AssigneeId: assignee };
+        self.send(Method::PUT, &format!("/note/{note}/followup"), &request).await.map(|_| ())
+    }
+
+    pub async fn clear_follow_up(&self, note: i32) -> Result<(), Error> {
+        self.send(Method::DELETE, &format!("/note/{note}/followup"), &v1::Token { Token: self.session()? }).await.map(|_| ())
+    }
+
+    pub async fn complete_follow_up(&self, note: i32) -> Result<(), Error> {
+        self.send(Method::POST, &format!("/note/{note}/followup/complete"), &v1::Token { Token: self.session()? }).await.map(|_| ())
+    }
+
+    /// The allergen and diet catalog.
+    pub async fn allergens(&self) -> Result<Vec<v1::Allergen>, Error> {
+        self.call(Method::POST, "/allergen/select", &v1::Token { Token: self.session()? }).await
+    }
+
+    /// Admin only, the `Id` of `allergen` is ignored.
+    pub async fn create_allergen(&self, allergen: v1::Allergen) -> Result<Vec<v1::Allergen>, Error> {
+        let request = v1::TokenAllergen { Token: self.session()?, Allergen: allergen };
+        self.call(Method::POST, "/allergen", &request).await
+    }
+
+    /// Admin only, replaces every label of the entry.
+    pub async fn update_allergen(&self, allergen: v1::Allergen) -> Result<Vec<v1::Allergen>, Error> {
+        let request = v1::TokenAllergen { Token: self.session()?, Allergen: allergen };
+        self.call(Method::PUT, "/allergen", &request).await
+    }
+
+    /// Allergies and diets of a beneficiary, from the catalog or free text.
+    pub async fn allergies(&self, beneficiary: i32) -> Result<Vec<v1::Allergy>, Error> {
+        let request = v1::TokenBeneId { Token: self.session()?, Id: beneficiary };
+        self.call(Method::POST, "/allergy/select", &request).await
+    }
+
+    /// Links a beneficiary to a catalog entry, or changes the severity of the link.
+    pub async fn link_allergen(&self, beneficiary: i32, allergen: i32, severity: Option<&str>) -> Result<(), Error> {
+        let request = v1::TokenAllergenLink { Token: self.session()?, BeneficiaryId: beneficiary, AllergenId: allergen, Severity: severity.map(str::to_string) };
+        self.send(Method::POST, "/allergen/link", &request).await.map(|_| ())
+    }
+
+    pub async fn unlink_allergen(&self, beneficiary: i32, allergen: i32) -> Result<(), Error> {
+        let request = v1::TokenAllergenLink { Token: self.session()?, BeneficiaryId: beneficiary, AllergenId: allergen, Severity: None };
+        self.send(Method::DELETE, "/allergen/link", &request).await.map(|_| ())
+    }
+
+    /// Members of the household of a beneficiary, as the session role sees them.
+    pub async fn members(&self, beneficiary: i32) -> Result<Vec<v1::HouseholdMember>, Error> {
+        let request = v1::TokenBeneId { Token: self.session()?, Id: beneficiary };
+        self.call(Method::POST, "/member/select", &request).await
+    }
+
+    /// The `Id` of `member` is ignored, the created member is returned with its own.
+    pub async fn create_member(&self, member: v1::HouseholdMember) -> Result<v1::HouseholdMember, Error> {
+        let request = v1::TokenMember { Token: self.session()?, Member: member };
+        self.call(Method::POST, "/member", &request).await
+    }
+
+    /// Replaces every field and allergy of the member.
+    pub async fn update_member(&self, member: v1::HouseholdMember) -> Result<v1::HouseholdMember, Error> {
+        let request = v1::TokenMember { Token: self.session()?, Member: member };
+        self.call(Method::PUT, "/member", &request).await
+    }
+
+    pub async fn delete_member(&self, id: i32) -> Result<(), Error> {
+        self.send(Method::DELETE, &format!("/member/{id}"), &v1::Token { Token: self.session()? }).await.map(|_| ())
+    }
+
+    /// Records a visit now. Fails with 409 when the beneficiary already came today.
+    pub async fn check_in(&self, beneficiary: i32) -> Result<v1::CheckIn, Error> {
+        let request = v1::TokenCheckIn { Token: self.session()?, BeneficiaryId: Some(beneficiary), CardCode: None };
+        self.call(Method::POST, "/checkin", &request).await
+    }
+
+    /// Same as [`Client::check_in`], for the code read from a membership card.
+    pub async fn check_in_card(&self, code: &str) -> Result<v1::CheckIn, Error> {
+        let request = v1::TokenCheckIn { Token: self.session()?, BeneficiaryId: None, CardCode: Some(code.to_string()) };
+        self.call(Method::POST, "/checkin", &request).await
+    }
+
+    /// Checks in past the weekly and monthly limits, Admin only. The reason is kept.
+    pub async fn override_check_in(&self, beneficiary: i32, reason: &str) -> Result<v1::CheckIn, Error> {
+        let request = v1::TokenCheckInOverride { Token: self.session()?, BeneficiaryId: Some(beneficiary), CardCode: None, Reason: reason.to_string() };
+        self.call(Method::POST, "/checkin/override", &request).await
+    }
+
+    /// Whether the beneficiary may come on `date`, `YYYY-MM-DD`, or today when empty.
+    pub async fn eligibility(&self, beneficiary: i32, date: Option<&str>) -> Result<v1::Eligibility, Error> {
+        let request = v1::TokenEligibility { Token: self.session()?, BeneficiaryId: beneficiary, Date: date.map(str::to_string) };
+        self.call(Method::POST, "/eligibility/select", &request).await
+    }
+
+    /// Records what was given at a presence. The server sets the category, fee and author.
+    pub async fn record_distribution(&self, distribution: v1::Distribution) -> Result<v1::Distribution, Error> {
+        let request = v1::TokenDistribution { Token: self.session()?, Distribution: distribution };
+        self.call(Method::POST, "/distribution", &request).await
+    }
+
+    /// Replaces the distribution with the same `Id`, returning the correction.
+    pub async fn correct_distribution(&self, distribution: v1::Distribution) -> Result<v1::Distribution, Error> {
+        let request = v1::TokenDistribution { Token: self.session()?, Distribution: distribution };
+        self.call(Method::PUT, "/distribution", &request).await
+    }
+
+    pub async fn distributions(&self, beneficiary: i32) -> Result<Vec<v1::Distribution>, Error> {
+        let request = v1::TokenBeneId { Token: self.session()?, Id: beneficiary };
+        self.call(Method::POST, "/distribution/select", &request).await
+    }
+
+    /// Totals between two days included, per `day`, `week`, `month` or `year`.
+    pub async fn distribution_totals(&self, beneficiary: Option<i32>, from: &str, to: &str, period: &str) -> Result<Vec<v1::DistributionTotal>, Error> {
+        let request = v1::TokenDistributionTotals {
+            Token: self.session()?,
+            BeneficiaryId: beneficiary,
+            From: from.to_string(),
+            To: to.to_string(),
+            Period: period.to_string(),
+        };
+        self.call(Method::POST, "/distribution/totals/select", &request).await
+    }
+
+    /// Records a payment received now. The server sets `PaidAt` and the author.
+    pub async fn record_payment(&self, payment: v1::Payment) -> Result<v1::Payment, Error> {
+        let request = v1::TokenPayment { Token: self.session()?, Payment: payment };
+        self.call(Method::POST, "/payment", &request).await
+    }
+
+    pub async fn void_payment(&self, id: i32) -> Result<(), Error> {
+        self.send(Method::DELETE, &format!("/payment/{id}"), &v1::Token { Token: self.session()? }).await.map(|_| ())
+    }
+
+    /// Charges and payments between two days included, `YYYY-MM-DD`, either left open with `None`.
+    pub async fn statement(&self, beneficiary: i32, from: Option<&str>, to: Option<&str>) -> Result<v1::Statement, Error> {
+        let request = v1::TokenStatementQuery {
+            Token: self.session()?,
+            BeneficiaryId: beneficiary,
+            From: from.map(str::to_string),
+            To: to.map(str::to_string),
+        };
+        self.call(Method::POST, "/statement/select", &request).await
+    }
+
+    pub async fn balances(&self) -> Result<Vec<v1::Balance>, Error> {
+        self.call(Method::POST, "/balance/select", &v1::Token { Token: self.session()? }).await
+    }
+
+    /// The receipt of a payment, as PDF bytes.
+    pub async fn receipt(&self, payment: i32) -> Result<Vec<u8>, Error> {
+        self.send(Method::POST, &format!("/payment/{payment}/receipt"), &v1::Token { Token: self.session()? }).await
+    }
+
+    /// The slip of a presence, as PDF bytes.
+    pub async fn visit_slip(&self, beneficiary: i32, date: &str) -> Result<Vec<u8>, Error> {
+        let request = v1::TokenPresence {
+            Token: self.session()?,
+            Presence: v1::BeneficiaryPresence { BeneficiaryId: beneficiary, Date: date.to_string() },
+        };
+        self.send(Method::POST, "/presence/slip", &request).await
+    }
+
+    /// The card of a beneficiary, issued on the first call.
+    pub async fn card(&self, beneficiary: i32) -> Result<v1::Card, Error> {
+        self.call(Method::POST, "/card", &v1::TokenBeneId { Token: self.session()?, Id: beneficiary }).await
+    }
+
+    /// The beneficiary holding a scanned or typed card code.
+    pub async fn look_up_card(&self, code: &str) -> Result<v1::Beneficiary, Error> {
+        self.call(Method::POST, "/card/select", &v1::TokenCardCode { Token: self.session()?, Code: code.to_string() }).await
+    }
+
+    /// The printable card, `png` or `pdf`, as bytes.
+    pub async fn print_card(&self, beneficiary: i32, format: &str) -> Result<Vec<u8>, Error> {
+        let request = v1::TokenCardPrint { Token: self.session()?, BeneficiaryId: beneficiary, Format: format.to_string() };
+        self.send(Method::POST, "/card/print", &request).await
+    }
+
+    /// Revokes the card of a beneficiary and issues a new one.
+    pub async fn reissue_card(&self, beneficiary: i32) -> Result<v1::Card, Error> {
+        self.call(Method::POST, "/card/reissue", &v1::TokenBeneId { Token: self.session()?, Id: beneficiary }).await
+    }
+
+    pub async fn revoke_card(&self, beneficiary: i32) -> Result<(), Error> {
+        self.send(Method::DELETE, "/card", &v1::TokenBeneId { Token: self.session()?, Id: beneficiary }).await.map(|_| ())
+    }
+
+    /// Deletes a category, moving its beneficiaries to `reassign_to` first. A category
+    /// still in use cannot be deleted without one.
+    pub async fn delete_category_by_id(&self, id: i32, reassign_to: Option<i32>) -> Result<Vec<v1::Categories>, Error> {
+        let request = v1::TokenCategoryDelete { Token: self.session()?, ReassignTo: reassign_to };
+        self.call(Method::DELETE, &format!("/category/{id}"), &request).await
+    }
+
+    /// Moves the beneficiaries listed, or all of them when empty, from one category to another.
+    pub async fn reassign_category(&self, from: i32, to: i32, beneficiaries: Vec<i32>) -> Result<Vec<v1::Categories>, Error> {
+        let request = v1::TokenCategoryReassign { Token: self.session()?, From: from, To: to, BeneficiaryIds: beneficiaries };
+        self.call(Method::POST, "/category/reassign", &request).await
+    }
+
+    /// Sets the fees of a category from a day on. Returns every fee change of the category.
+    pub async fn set_category_fee(&self, fee: v1::CategoryFee) -> Result<Vec<v1::CategoryFee>, Error> {
+        self.call(Method::POST, "/category/fee", &v1::TokenCategoryFee { Token: self.session()?, Fee: fee }).await
+    }
+
+    pub async fn category_fees(&self, category: i32) -> Result<Vec<v1::CategoryFee>, Error> {
+        self.call(Method::POST, "/category/fee/select", &v1::TokenCategoryId { Token: self.session()?, CategoryId: category }).await
+    }
+
+    /// Recomputes the statistics of every period from `from` to `to`, written `YYYY-MM-DD`.
+    pub async fn compute_stats(&self, from: &str, to: &str) -> Result<(), Error> {
+        let request = v1::TokenStatsBackfill { Token: self.session()?, From: from.to_string(), To: to.to_string() };
+        self.send(Method::POST, "/stats/compute", &request).await.map(|_| ())
+    }
+
+    /// The statistics between two days, either left out as None, rolled up per `day`,
+    /// `week`, `month` or `year`. An empty `series` reads every one. This body only
+    /// exists from v2 on, so this call is always sent as v2.
+    pub async fn stats_rollup(&self, from: Option<&str>, to: Option<&str>, period: &str, series: &[&str]) -> Result<v1::Stats, Error> {
+        let request = v2::TokenStatsQuery {
+            Token: self.session()?,
+            From: from.map(str::to_string),
+            To: to.map(str::to_string),
+            Period: period.to_string(),
+            Series: series.iter().map(|name| name.to_string()).collect(),
+        };
+        self.call_as(Version::V2, Method::POST, "/stats/select", &request).await
+    }
+}
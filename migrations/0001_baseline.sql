@@ -0,0 +1,198 @@
+-- Schema the server was written against. Every statement is idempotent so that
+-- databases created by hand before migrations existed adopt this as their baseline.
+
+CREATE TABLE IF NOT EXISTS User (
+    Id INT NOT NULL AUTO_INCREMENT,
+    Username VARCHAR(255) NOT NULL,
+    Password VARCHAR(255) NOT NULL,
+    Role VARCHAR(32) NOT NULL,
+    PRIMARY KEY (Id),
+    UNIQUE KEY UserUsername (Username)
+);
+
+CREATE TABLE IF NOT EXISTS UserSession (
+    UserId INT NOT NULL,
+    Token VARCHAR(255) NOT NULL,
+    ConnectionDate DATETIME NOT NULL,
+    Expires DATETIME NOT NULL,
+    PRIMARY KEY (Token),
+    KEY UserSessionUser (UserId)
+);
+
+CREATE TABLE IF NOT EXISTS Beneficiary (
+    Id INT NOT NULL AUTO_INCREMENT,
+    FirstName VARCHAR(255) NOT NULL DEFAULT '',
+    LastName VARCHAR(255) NOT NULL DEFAULT '',
+    Email VARCHAR(512) NOT NULL DEFAULT '',
+    Phone VARCHAR(512) NOT NULL DEFAULT '',
+    Address VARCHAR(512) NOT NULL DEFAULT '',
+    PostalCode VARCHAR(512) NOT NULL DEFAULT '',
+    Kid TINYINT UNSIGNED NOT NULL DEFAULT 0,
+    Adult TINYINT UNSIGNED NOT NULL DEFAULT 0,
+    MonthlyAmount DOUBLE NOT NULL DEFAULT 0,
+    WeeklyAmount DOUBLE NOT NULL DEFAULT 0,
+    Category INT NOT NULL DEFAULT 0,
+    MonthlyLimit DOUBLE NOT NULL DEFAULT 0,
+    WeeklyLimit DOUBLE NOT NULL DEFAULT 0,
+    Birth DATE NULL,
+    LastPresence DATE NOT NULL DEFAULT (CURRENT_DATE),
+    Sexe VARCHAR(255) NOT NULL DEFAULT '',
+    Language VARCHAR(255) NOT NULL DEFAULT '',
+    Origin VARCHAR(255) NOT NULL DEFAULT '',
+    City VARCHAR(255) NOT NULL DEFAULT '',
+    Study VARCHAR(255) NOT NULL DEFAULT '',
+    Income VARCHAR(255) NOT NULL DEFAULT '',
+    FamilySituation VARCHAR(255) NOT NULL DEFAULT '',
+    IsActive BOOLEAN NOT NULL DEFAULT FALSE,
+    IsSdf BOOLEAN NOT NULL DEFAULT FALSE,
+    IsEmployed BOOLEAN NOT NULL DEFAULT FALSE,
+    HasAllergies BOOLEAN NOT NULL DEFAULT FALSE,
+    HasGeneralNote BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (Id)
+);
+
+CREATE TABLE IF NOT EXISTS BeneficiaryAllergies (
+    BeneficiaryId INT NOT NULL,
+    Allergy VARCHAR(255) NOT NULL,
+    KEY BeneficiaryAllergiesBeneficiary (BeneficiaryId)
+);
+
+CREATE TABLE IF NOT EXISTS BeneficiaryPresences (
+    BeneficiaryId INT NOT NULL,
+    PresenceDate DATETIME NOT NULL,
+    KEY BeneficiaryPresencesBeneficiary (BeneficiaryId, PresenceDate)
+);
+
+CREATE TABLE IF NOT EXISTS BeneficiaryNotes (
+    BeneficiaryId INT NOT NULL,
+    Date DATETIME NOT NULL,
+    Type TINYINT NOT NULL DEFAULT 0,
+    Note TEXT NOT NULL,
+    KEY BeneficiaryNotesBeneficiary (BeneficiaryId, Date)
+);
+
+CREATE TABLE IF NOT EXISTS Categories (
+    Id INT NOT NULL AUTO_INCREMENT,
+    Category VARCHAR(255) NOT NULL,
+    MonthlyFee FLOAT NOT NULL DEFAULT 0,
+    WeeklyFee FLOAT NOT NULL DEFAULT 0,
+    UsedBy INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Id)
+);
+
+CREATE TABLE IF NOT EXISTS Presence (
+    Date DATE NOT NULL,
+    Total INT UNSIGNED NOT NULL DEFAULT 0,
+    Active INT UNSIGNED NOT NULL DEFAULT 0,
+    Visits INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Amounts (
+    Date DATE NOT NULL,
+    TotalWeekly INT UNSIGNED NOT NULL DEFAULT 0,
+    TotalMonthly INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Age (
+    Date DATE NOT NULL,
+    Age_0_19 INT UNSIGNED NOT NULL DEFAULT 0,
+    Age_20_29 INT UNSIGNED NOT NULL DEFAULT 0,
+    Age_30_39 INT UNSIGNED NOT NULL DEFAULT 0,
+    Age_40_49 INT UNSIGNED NOT NULL DEFAULT 0,
+    Age_50_59 INT UNSIGNED NOT NULL DEFAULT 0,
+    Age_60_69 INT UNSIGNED NOT NULL DEFAULT 0,
+    Age_70_Plus INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS City (
+    Date DATE NOT NULL,
+    Carignan INT UNSIGNED NOT NULL DEFAULT 0,
+    Chambly INT UNSIGNED NOT NULL DEFAULT 0,
+    Marieville INT UNSIGNED NOT NULL DEFAULT 0,
+    Richelieu INT UNSIGNED NOT NULL DEFAULT 0,
+    StMathias INT UNSIGNED NOT NULL DEFAULT 0,
+    Other INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Employment (
+    Date DATE NOT NULL,
+    Unemployed INT UNSIGNED NOT NULL DEFAULT 0,
+    Employed INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS FamilySituation (
+    Date DATE NOT NULL,
+    Single INT UNSIGNED NOT NULL DEFAULT 0,
+    Couple INT UNSIGNED NOT NULL DEFAULT 0,
+    CoupleKids INT UNSIGNED NOT NULL DEFAULT 0,
+    Recomposed INT UNSIGNED NOT NULL DEFAULT 0,
+    SingleParent INT UNSIGNED NOT NULL DEFAULT 0,
+    Other INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Income (
+    Date DATE NOT NULL,
+    NoIncome INT UNSIGNED NOT NULL DEFAULT 0,
+    Income_1_14999 INT UNSIGNED NOT NULL DEFAULT 0,
+    Income_15000_29999 INT UNSIGNED NOT NULL DEFAULT 0,
+    Income_30000_More INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Kid (
+    Date DATE NOT NULL,
+    NoKids INT UNSIGNED NOT NULL DEFAULT 0,
+    OneKid INT UNSIGNED NOT NULL DEFAULT 0,
+    TwoKids INT UNSIGNED NOT NULL DEFAULT 0,
+    ThreeToFourKids INT UNSIGNED NOT NULL DEFAULT 0,
+    FivePlusKids INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Language (
+    Date DATE NOT NULL,
+    French INT UNSIGNED NOT NULL DEFAULT 0,
+    English INT UNSIGNED NOT NULL DEFAULT 0,
+    Spanish INT UNSIGNED NOT NULL DEFAULT 0,
+    Arabic INT UNSIGNED NOT NULL DEFAULT 0,
+    Mandarin INT UNSIGNED NOT NULL DEFAULT 0,
+    Other INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Origin (
+    Date DATE NOT NULL,
+    NorthAmerican INT UNSIGNED NOT NULL DEFAULT 0,
+    SouthAmerican INT UNSIGNED NOT NULL DEFAULT 0,
+    CentralAmerican INT UNSIGNED NOT NULL DEFAULT 0,
+    Asian INT UNSIGNED NOT NULL DEFAULT 0,
+    African INT UNSIGNED NOT NULL DEFAULT 0,
+    European INT UNSIGNED NOT NULL DEFAULT 0,
+    Other INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Sexe (
+    Date DATE NOT NULL,
+    Male INT UNSIGNED NOT NULL DEFAULT 0,
+    Female INT UNSIGNED NOT NULL DEFAULT 0,
+    Other INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
+
+CREATE TABLE IF NOT EXISTS Study (
+    Date DATE NOT NULL,
+    NoStudy INT UNSIGNED NOT NULL DEFAULT 0,
+    PrimarySchool INT UNSIGNED NOT NULL DEFAULT 0,
+    HighSchool INT UNSIGNED NOT NULL DEFAULT 0,
+    College INT UNSIGNED NOT NULL DEFAULT 0,
+    University INT UNSIGNED NOT NULL DEFAULT 0,
+    Other INT UNSIGNED NOT NULL DEFAULT 0,
+    PRIMARY KEY (Date)
+);
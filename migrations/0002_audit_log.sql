@@ -0,0 +1,18 @@
+-- Who read or changed what, one row per audited request. Personal data in Changes is
+-- masked before it is written.
+
+CREATE TABLE IF NOT EXISTS AuditLog (
+    Id BIGINT NOT NULL AUTO_INCREMENT,
+    Date DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UserId INT NOT NULL,
+    Username VARCHAR(255) NOT NULL,
+    Role VARCHAR(32) NOT NULL,
+    Action VARCHAR(64) NOT NULL,
+    BeneficiaryId INT NULL,
+    RequestId VARCHAR(64) NOT NULL DEFAULT '',
+    Changes TEXT NOT NULL,
+    PRIMARY KEY (Id),
+    KEY AuditLogBeneficiary (BeneficiaryId, Date),
+    KEY AuditLogUser (UserId, Date),
+    KEY AuditLogDate (Date)
+);
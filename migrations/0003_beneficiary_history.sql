@@ -0,0 +1,54 @@
+-- Every version of a beneficiary row. A version is valid from ValidFrom until the
+-- ValidFrom of the next one. Rows that already exist become version 1, with no author.
+
+CREATE TABLE IF NOT EXISTS BeneficiaryHistory (
+    BeneficiaryId INT NOT NULL,
+    Version INT NOT NULL,
+    ValidFrom DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UserId INT NULL,
+    FirstName VARCHAR(255) NOT NULL DEFAULT '',
+    LastName VARCHAR(255) NOT NULL DEFAULT '',
+    Email VARCHAR(512) NOT NULL DEFAULT '',
+    Phone VARCHAR(512) NOT NULL DEFAULT '',
+    Address VARCHAR(512) NOT NULL DEFAULT '',
+    PostalCode VARCHAR(512) NOT NULL DEFAULT '',
+    Kid TINYINT UNSIGNED NOT NULL DEFAULT 0,
+    Adult TINYINT UNSIGNED NOT NULL DEFAULT 0,
+    MonthlyAmount DOUBLE NOT NULL DEFAULT 0,
+    WeeklyAmount DOUBLE NOT NULL DEFAULT 0,
+    Category INT NOT NULL DEFAULT 0,
+    MonthlyLimit DOUBLE NOT NULL DEFAULT 0,
+    WeeklyLimit DOUBLE NOT NULL DEFAULT 0,
+    Birth DATE NULL,
+    LastPresence DATE NOT NULL,
+    Sexe VARCHAR(255) NOT NULL DEFAULT '',
+    Language VARCHAR(255) NOT NULL DEFAULT '',
+    Origin VARCHAR(255) NOT NULL DEFAULT '',
+    City VARCHAR(255) NOT NULL DEFAULT '',
+    Study VARCHAR(255) NOT NULL DEFAULT '',
+    Income VARCHAR(255) NOT NULL DEFAULT '',
+    FamilySituation VARCHAR(255) NOT NULL DEFAULT '',
+    IsActive BOOLEAN NOT NULL DEFAULT FALSE,
+    IsSdf BOOLEAN NOT NULL DEFAULT FALSE,
+    IsEmployed BOOLEAN NOT NULL DEFAULT FALSE,
+    HasAllergies BOOLEAN NOT NULL DEFAULT FALSE,
+    HasGeneralNote BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (BeneficiaryId, Version),
+    KEY BeneficiaryHistoryValidFrom (BeneficiaryId, ValidFrom)
+);
+
+INSERT INTO BeneficiaryHistory (
+    BeneficiaryId, Version, ValidFrom, UserId,
+    FirstName, LastName, Email, Phone, Address, PostalCode, Kid, Adult,
+    MonthlyAmount, WeeklyAmount, Category, MonthlyLimit, WeeklyLimit,
+    Birth, LastPresence, Sexe, Language, Origin, City, Study, Income, FamilySituation,
+    IsActive, IsSdf, IsEmployed, HasAllergies, HasGeneralNote
+)
+SELECT
+    Id, 1, NOW(), NULL,
+    FirstName, LastName, Email, Phone, Address, PostalCode, Kid, Adult,
+    MonthlyAmount, WeeklyAmount, Category, MonthlyLimit, WeeklyLimit,
+    Birth, LastPresence, Sexe, Language, Origin, City, Study, Income, FamilySituation,
+    IsActive, IsSdf, IsEmployed, HasAllergies, HasGeneralNote
+FROM Beneficiary
+WHERE Id NOT IN (SELECT BeneficiaryId FROM BeneficiaryHistory);
@@ -0,0 +1,17 @@
+-- Allergies, presences and notes are no longer deleted right away. A delete marks the row
+-- with its author and time, the purge job removes it once the retention period is over.
+
+ALTER TABLE BeneficiaryAllergies
+    ADD COLUMN DeletedAt DATETIME NULL,
+    ADD COLUMN DeletedBy INT NULL,
+    ADD KEY BeneficiaryAllergiesDeletedAt (DeletedAt);
+
+ALTER TABLE BeneficiaryPresences
+    ADD COLUMN DeletedAt DATETIME NULL,
+    ADD COLUMN DeletedBy INT NULL,
+    ADD KEY BeneficiaryPresencesDeletedAt (DeletedAt);
+
+ALTER TABLE BeneficiaryNotes
+    ADD COLUMN DeletedAt DATETIME NULL,
+    ADD COLUMN DeletedBy INT NULL,
+    ADD KEY BeneficiaryNotesDeletedAt (DeletedAt);
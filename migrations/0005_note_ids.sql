@@ -0,0 +1,13 @@
+-- Notes get their own id, so that two notes written in the same second no longer collide.
+-- Existing notes are numbered in storage order. Their author is unknown and they count as
+-- created at their own Date.
+
+ALTER TABLE BeneficiaryNotes
+    ADD COLUMN Id INT NOT NULL AUTO_INCREMENT FIRST,
+    ADD PRIMARY KEY (Id),
+    ADD COLUMN AuthorId INT NULL,
+    ADD COLUMN CreatedAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN EditedAt DATETIME NULL,
+    ADD COLUMN EditedBy INT NULL;
+
+UPDATE BeneficiaryNotes SET CreatedAt = Date;
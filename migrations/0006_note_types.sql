@@ -0,0 +1,43 @@
+-- Note types and which role may read or write each of them. BeneficiaryNotes.Type refers
+-- to NoteType.Id. The seed keeps what each role could see: type 0 for everyone, type 1
+-- for Admin and Dev, any other type for TS. Follow-up is new and shared by every role.
+
+CREATE TABLE IF NOT EXISTS NoteType (
+    Id TINYINT NOT NULL,
+    Name VARCHAR(64) NOT NULL,
+    Description VARCHAR(255) NOT NULL DEFAULT '',
+    PRIMARY KEY (Id),
+    UNIQUE KEY NoteTypeName (Name)
+);
+
+CREATE TABLE IF NOT EXISTS NoteTypeAccess (
+    NoteTypeId TINYINT NOT NULL,
+    Role VARCHAR(32) NOT NULL,
+    CanRead BOOLEAN NOT NULL DEFAULT FALSE,
+    CanWrite BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (NoteTypeId, Role)
+);
+
+INSERT IGNORE INTO NoteType (Id, Name, Description) VALUES
+    (0, 'general', 'Seen by every volunteer'),
+    (1, 'admin-only', 'Administration of the file'),
+    (2, 'confidential-social-work', 'Follow-up by the social workers');
+
+INSERT IGNORE INTO NoteType (Id, Name, Description)
+SELECT DISTINCT Type, CONCAT('type-', Type), '' FROM BeneficiaryNotes WHERE Type NOT IN (0, 1, 2);
+
+INSERT IGNORE INTO NoteType (Id, Name, Description)
+SELECT GREATEST(3, MAX(Id) + 1), 'follow-up', 'Something to do for this beneficiary' FROM NoteType;
+
+INSERT IGNORE INTO NoteTypeAccess (NoteTypeId, Role, CanRead, CanWrite)
+SELECT Id, Role, Allowed, Allowed
+FROM (
+    SELECT NoteType.Id, Roles.Role,
+        CASE
+            WHEN NoteType.Id = 0 OR NoteType.Name = 'follow-up' THEN TRUE
+            WHEN NoteType.Id = 1 THEN Roles.Role IN ('Admin', 'Dev')
+            ELSE Roles.Role = 'TS'
+        END AS Allowed
+    FROM NoteType
+    CROSS JOIN (SELECT 'Admin' AS Role UNION ALL SELECT 'Dev' UNION ALL SELECT 'TS' UNION ALL SELECT 'User') AS Roles
+) AS Seed;
@@ -0,0 +1,9 @@
+-- A note can carry a follow-up: a due date and the user who has to act on it, until
+-- someone marks it completed.
+
+ALTER TABLE BeneficiaryNotes
+    ADD COLUMN DueDate DATE NULL,
+    ADD COLUMN AssigneeId INT NULL,
+    ADD COLUMN CompletedAt DATETIME NULL,
+    ADD COLUMN CompletedBy INT NULL,
+    ADD KEY BeneficiaryNotesFollowUp (AssigneeId, CompletedAt, DueDate);
@@ -0,0 +1,93 @@
+-- A managed catalog of allergens and diets, labelled in each language. An allergy row
+-- links to it by AllergenId; a row without one is free text, as before. Existing free
+-- text matching a label, give or take a final "s", is linked. HasAllergies is then set
+-- from the rows themselves, and each beneficiary it changes for gets a new version.
+
+CREATE TABLE IF NOT EXISTS Allergen (
+    Id INT NOT NULL AUTO_INCREMENT,
+    Code VARCHAR(64) NOT NULL,
+    Kind VARCHAR(16) NOT NULL DEFAULT 'allergen',
+    IsActive BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (Id),
+    UNIQUE KEY AllergenCode (Code)
+);
+
+CREATE TABLE IF NOT EXISTS AllergenLabel (
+    AllergenId INT NOT NULL,
+    Language VARCHAR(8) NOT NULL,
+    Label VARCHAR(128) NOT NULL,
+    PRIMARY KEY (AllergenId, Language)
+);
+
+ALTER TABLE BeneficiaryAllergies
+    ADD COLUMN AllergenId INT NULL,
+    ADD COLUMN Severity VARCHAR(16) NULL,
+    ADD KEY BeneficiaryAllergiesAllergen (AllergenId);
+
+INSERT IGNORE INTO Allergen (Code, Kind) VALUES
+    ('gluten', 'allergen'), ('crustaceans', 'allergen'), ('eggs', 'allergen'), ('fish', 'allergen'),
+    ('peanuts', 'allergen'), ('soy', 'allergen'), ('milk', 'allergen'), ('tree-nuts', 'allergen'),
+    ('celery', 'allergen'), ('mustard', 'allergen'), ('sesame', 'allergen'), ('sulphites', 'allergen'),
+    ('lupin', 'allergen'), ('molluscs', 'allergen'),
+    ('halal', 'diet'), ('kosher', 'diet'), ('vegetarian', 'diet'), ('vegan', 'diet'),
+    ('gluten-free', 'diet'), ('lactose-free', 'diet'), ('diabetic', 'diet');
+
+INSERT IGNORE INTO AllergenLabel (AllergenId, Language, Label)
+SELECT Allergen.Id, Labels.Language, Labels.Label
+FROM Allergen
+JOIN (
+    SELECT 'gluten' AS Code, 'fr' AS Language, 'Gluten' AS Label UNION ALL SELECT 'gluten', 'en', 'Gluten'
+    UNION ALL SELECT 'crustaceans', 'fr', 'Crustacés' UNION ALL SELECT 'crustaceans', 'en', 'Crustaceans'
+    UNION ALL SELECT 'eggs', 'fr', 'Oeufs' UNION ALL SELECT 'eggs', 'en', 'Eggs'
+    UNION ALL SELECT 'fish', 'fr', 'Poisson' UNION ALL SELECT 'fish', 'en', 'Fish'
+    UNION ALL SELECT 'peanuts', 'fr', 'Arachides' UNION ALL SELECT 'peanuts', 'en', 'Peanuts'
+    UNION ALL SELECT 'soy', 'fr', 'Soja' UNION ALL SELECT 'soy', 'en', 'Soy'
+    UNION ALL SELECT 'milk', 'fr', 'Lait' UNION ALL SELECT 'milk', 'en', 'Milk'
+    UNION ALL SELECT 'tree-nuts', 'fr', 'Noix' UNION ALL SELECT 'tree-nuts', 'en', 'Tree nuts'
+    UNION ALL SELECT 'celery', 'fr', 'Céleri' UNION ALL SELECT 'celery', 'en', 'Celery'
+    UNION ALL SELECT 'mustard', 'fr', 'Moutarde' UNION ALL SELECT 'mustard', 'en', 'Mustard'
+    UNION ALL SELECT 'sesame', 'fr', 'Sésame' UNION ALL SELECT 'sesame', 'en', 'Sesame'
+    UNION ALL SELECT 'sulphites', 'fr', 'Sulfites' UNION ALL SELECT 'sulphites', 'en', 'Sulphites'
+    UNION ALL SELECT 'lupin', 'fr', 'Lupin' UNION ALL SELECT 'lupin', 'en', 'Lupin'
+    UNION ALL SELECT 'molluscs', 'fr', 'Mollusques' UNION ALL SELECT 'molluscs', 'en', 'Molluscs'
+    UNION ALL SELECT 'halal', 'fr', 'Halal' UNION ALL SELECT 'halal', 'en', 'Halal'
+    UNION ALL SELECT 'kosher', 'fr', 'Casher' UNION ALL SELECT 'kosher', 'en', 'Kosher'
+    UNION ALL SELECT 'vegetarian', 'fr', 'Végétarien' UNION ALL SELECT 'vegetarian', 'en', 'Vegetarian'
+    UNION ALL SELECT 'vegan', 'fr', 'Végétalien' UNION ALL SELECT 'vegan', 'en', 'Vegan'
+    UNION ALL SELECT 'gluten-free', 'fr', 'Sans gluten' UNION ALL SELECT 'gluten-free', 'en', 'Gluten-free'
+    UNION ALL SELECT 'lactose-free', 'fr', 'Sans lactose' UNION ALL SELECT 'lactose-free', 'en', 'Lactose-free'
+    UNION ALL SELECT 'diabetic', 'fr', 'Diabétique' UNION ALL SELECT 'diabetic', 'en', 'Diabetic'
+) AS Labels ON Labels.Code = Allergen.Code;
+
+UPDATE BeneficiaryAllergies
+SET AllergenId = (
+    SELECT MIN(AllergenLabel.AllergenId) FROM AllergenLabel
+    WHERE TRIM(BeneficiaryAllergies.Allergy) IN (AllergenLabel.Label, CONCAT(AllergenLabel.Label, 's'))
+    OR CONCAT(TRIM(BeneficiaryAllergies.Allergy), 's') = AllergenLabel.Label
+)
+WHERE AllergenId IS NULL;
+
+CREATE TEMPORARY TABLE AllergyFlagFix AS
+SELECT Id FROM Beneficiary
+WHERE HasAllergies <> EXISTS (
+    SELECT 1 FROM BeneficiaryAllergies
+    WHERE BeneficiaryAllergies.BeneficiaryId = Beneficiary.Id AND BeneficiaryAllergies.DeletedAt IS NULL
+);
+
+UPDATE Beneficiary JOIN AllergyFlagFix USING (Id) SET Beneficiary.HasAllergies = NOT Beneficiary.HasAllergies;
+
+INSERT INTO BeneficiaryHistory (
+    BeneficiaryId, Version, UserId,
+    FirstName, LastName, Email, Phone, Address, PostalCode, Kid, Adult,
+    MonthlyAmount, WeeklyAmount, Category, MonthlyLimit, WeeklyLimit,
+    Birth, LastPresence, Sexe, Language, Origin, City, Study, Income, FamilySituation,
+    IsActive, IsSdf, IsEmployed, HasAllergies, HasGeneralNote
+)
+SELECT Beneficiary.Id, (SELECT COALESCE(MAX(Version), 0) + 1 FROM BeneficiaryHistory WHERE BeneficiaryId = Beneficiary.Id), NULL,
+    FirstName, LastName, Email, Phone, Address, PostalCode, Kid, Adult,
+    MonthlyAmount, WeeklyAmount, Category, MonthlyLimit, WeeklyLimit,
+    Birth, LastPresence, Sexe, Language, Origin, City, Study, Income, FamilySituation,
+    IsActive, IsSdf, IsEmployed, HasAllergies, HasGeneralNote
+FROM Beneficiary JOIN AllergyFlagFix USING (Id);
+
+DROP TEMPORARY TABLE AllergyFlagFix;
@@ -0,0 +1,25 @@
+-- The people living with a beneficiary, the beneficiary not included. Allergy rows may
+-- belong to one of them through MemberId; rows without one belong to the beneficiary.
+-- Members are deleted to the trash like allergies, and their allergy rows stay hidden
+-- with them until they are restored or purged.
+
+CREATE TABLE IF NOT EXISTS HouseholdMember (
+    Id INT NOT NULL AUTO_INCREMENT,
+    BeneficiaryId INT NOT NULL,
+    FirstName VARCHAR(255) NOT NULL DEFAULT '',
+    LastName VARCHAR(255) NOT NULL DEFAULT '',
+    Birth DATE NULL,
+    Relationship VARCHAR(16) NOT NULL DEFAULT 'other',
+    Sexe VARCHAR(255) NOT NULL DEFAULT '',
+    CreatedAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CreatedBy INT NULL,
+    DeletedAt DATETIME NULL,
+    DeletedBy INT NULL,
+    PRIMARY KEY (Id),
+    KEY HouseholdMemberBeneficiary (BeneficiaryId),
+    KEY HouseholdMemberDeletedAt (DeletedAt)
+);
+
+ALTER TABLE BeneficiaryAllergies
+    ADD COLUMN MemberId INT NULL,
+    ADD KEY BeneficiaryAllergiesMember (MemberId);
@@ -0,0 +1,16 @@
+-- Membership cards, looked up by the code printed on them. A beneficiary keeps every card
+-- ever issued; only those not revoked resolve at check-in.
+
+CREATE TABLE IF NOT EXISTS BeneficiaryCard (
+    Code VARCHAR(32) NOT NULL,
+    BeneficiaryId INT NOT NULL,
+    IssuedAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    IssuedBy INT NULL,
+    RevokedAt DATETIME NULL,
+    RevokedBy INT NULL,
+    PRIMARY KEY (Code),
+    KEY BeneficiaryCardBeneficiary (BeneficiaryId)
+);
+
+ALTER TABLE BeneficiaryPresences
+    ADD KEY BeneficiaryPresencesDay (BeneficiaryId, DeletedAt, PresenceDate);
@@ -0,0 +1,12 @@
+-- Check-ins an Admin let through past the weekly or monthly limit, and why.
+
+CREATE TABLE IF NOT EXISTS EligibilityOverride (
+    Id INT NOT NULL AUTO_INCREMENT,
+    BeneficiaryId INT NOT NULL,
+    PresenceDate DATETIME NOT NULL,
+    Reason VARCHAR(512) NOT NULL,
+    CreatedAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CreatedBy INT NULL,
+    PRIMARY KEY (Id),
+    KEY EligibilityOverrideBeneficiary (BeneficiaryId, PresenceDate)
+);
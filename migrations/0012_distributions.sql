@@ -0,0 +1,29 @@
+-- What each household received at a visit. A distribution belongs to the presence with
+-- the same BeneficiaryId and PresenceDate. Rows are never updated in place: a correction
+-- inserts a new row pointing to the one it Supersedes, which gets SupersededBy. The
+-- category and fee are copied at the time of the visit.
+
+CREATE TABLE IF NOT EXISTS Distribution (
+    Id INT NOT NULL AUTO_INCREMENT,
+    BeneficiaryId INT NOT NULL,
+    PresenceDate DATETIME NOT NULL,
+    Amount DOUBLE NOT NULL DEFAULT 0,
+    CategoryId INT NULL,
+    Fee DOUBLE NOT NULL DEFAULT 0,
+    VolunteerId INT NULL,
+    Note VARCHAR(512) NOT NULL DEFAULT '',
+    CreatedAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CreatedBy INT NULL,
+    Supersedes INT NULL,
+    SupersededBy INT NULL,
+    PRIMARY KEY (Id),
+    KEY DistributionPresence (BeneficiaryId, PresenceDate),
+    KEY DistributionDate (PresenceDate)
+);
+
+CREATE TABLE IF NOT EXISTS DistributionItem (
+    DistributionId INT NOT NULL,
+    Item VARCHAR(255) NOT NULL,
+    Quantity DOUBLE NOT NULL DEFAULT 1,
+    KEY DistributionItemDistribution (DistributionId)
+);
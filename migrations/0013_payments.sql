@@ -0,0 +1,29 @@
+-- What beneficiaries owe and paid. Visit fees live on Distribution.Fee; in period billing
+-- mode, Charge holds one row per beneficiary and started week or month, made unique so the
+-- job charging them can run any number of times. Payments are voided, never deleted.
+
+CREATE TABLE IF NOT EXISTS Charge (
+    Id INT NOT NULL AUTO_INCREMENT,
+    BeneficiaryId INT NOT NULL,
+    Kind VARCHAR(16) NOT NULL,
+    PeriodStart DATE NOT NULL,
+    CategoryId INT NULL,
+    Amount DOUBLE NOT NULL DEFAULT 0,
+    CreatedAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (Id),
+    UNIQUE KEY ChargePeriod (BeneficiaryId, Kind, PeriodStart)
+);
+
+CREATE TABLE IF NOT EXISTS Payment (
+    Id INT NOT NULL AUTO_INCREMENT,
+    BeneficiaryId INT NOT NULL,
+    Amount DOUBLE NOT NULL,
+    Method VARCHAR(16) NOT NULL,
+    PaidAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    Note VARCHAR(512) NOT NULL DEFAULT '',
+    CreatedBy INT NULL,
+    VoidedAt DATETIME NULL,
+    VoidedBy INT NULL,
+    PRIMARY KEY (Id),
+    KEY PaymentBeneficiary (BeneficiaryId, PaidAt)
+);
@@ -0,0 +1,19 @@
+-- Fees of each category over time. The fee in effect on a day is the row with the latest
+-- EffectiveFrom not after it; Categories.MonthlyFee and WeeklyFee are only a fallback for
+-- days before the first row. Every existing category starts with its current fees.
+
+CREATE TABLE IF NOT EXISTS CategoryFee (
+    CategoryId INT NOT NULL,
+    EffectiveFrom DATE NOT NULL,
+    MonthlyFee FLOAT NOT NULL DEFAULT 0,
+    WeeklyFee FLOAT NOT NULL DEFAULT 0,
+    CreatedAt DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CreatedBy INT NULL,
+    PRIMARY KEY (CategoryId, EffectiveFrom)
+);
+
+INSERT IGNORE INTO CategoryFee (CategoryId, EffectiveFrom, MonthlyFee, WeeklyFee)
+    SELECT Id, '1970-01-01', MonthlyFee, WeeklyFee FROM Categories;
+
+ALTER TABLE Beneficiary
+    ADD KEY BeneficiaryCategory (Category);
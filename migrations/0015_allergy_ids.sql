@@ -0,0 +1,7 @@
+-- Allergies get their own id, so that restoring one from the trash no longer restores the
+-- rows of the same text deleted in the same second, such as those of household members.
+-- Existing allergies are numbered in storage order.
+
+ALTER TABLE BeneficiaryAllergies
+    ADD COLUMN Id INT NOT NULL AUTO_INCREMENT FIRST,
+    ADD PRIMARY KEY (Id);
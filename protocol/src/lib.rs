@@ -0,0 +1,61 @@
+//! Wire types exchanged between the Harmony server and its clients.
+//!
+//! Every module below is a frozen contract: `v1` is what the desktop client was
+//! built against. A change to an existing type goes into a new version module
+//! and the golden fixtures in `tests/fixtures` keep the old ones honest.
+#![allow(non_snake_case)]
+
+pub mod v1;
+pub mod v2;
+
+/// Header carrying the protocol version of a request, echoed on the response.
+pub const VERSION_HEADER: &str = "x-harmony-version";
+
+pub mod media {
+    pub const BINCODE: &str = "application/vnd.harmony.bincode";
+    pub const JSON: &str = "application/json";
+    pub const MSGPACK: &str = "application/msgpack";
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Version {
+    V1,
+    V2,
+}
+
+impl Version {
+    pub const SUPPORTED: &'static [Version] = &[Version::V1, Version::V2];
+
+    /// Version assumed when a request names none, i.e. every client deployed before versioning.
+    pub const LEGACY: Version = Version::V1;
+
+    pub const LATEST: Version = Version::V2;
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::V1 => "1",
+            Version::V2 => "2",
+        }
+    }
+
+    /// Path prefix under which the routes of this version are also mounted.
+    pub fn path_prefix(&self) -> &'static str {
+        match self {
+            Version::V1 => "/v1",
+            Version::V2 => "/v2",
+        }
+    }
+
+    /// Parses a header value such as `1` or `v1`.
+    pub fn parse(value: &str) -> Option<Version> {
+        let value = value.trim();
+        let number = value.strip_prefix(['v', 'V']).unwrap_or(value);
+        Version::SUPPORTED.iter().copied().find(|version| version.as_str() == number)
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "v{}", self.as_str())
+    }
+}
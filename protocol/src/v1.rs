@@ -0,0 +1,890 @@
+//! Version 1 of the protocol, as spoken by the desktop client before versioning existed.
+//!
+//! Bincode encodes fields in declaration order: never reorder, add or remove a field here.
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Beneficiary {
+    pub Id: i32,
+    pub FirstName: String,
+    pub LastName: String,
+    pub Email: String,
+    pub Phone: String,
+    pub Address: String,
+    pub PostalCode: String,
+    pub Kid: u8,
+    pub Adult: u8,
+    pub MonthlyAmount: f64,
+    pub WeeklyAmount: f64,
+    pub Category: i32,
+    pub MonthlyLimit: f64,
+    pub WeeklyLimit: f64,
+    pub Birth: Option<String>,
+    pub LastPresence: String,
+    pub Sexe: String,
+    pub Language: String,
+    pub Origin: String,
+    pub City: String,
+    pub Study: String,
+    pub Income: String,
+    pub FamilySituation: String,
+    pub IsActive: bool,
+    pub IsSdf: bool,
+    pub IsEmployed: bool,
+    pub HasAllergies: bool,
+    pub HasGeneralNote: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BeneficiaryAllergy {
+    pub BeneficiaryId: i32,
+    pub Allergy: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BeneficiaryPresence {
+    pub BeneficiaryId: i32,
+    pub Date: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BeneficiaryNotes {
+    pub BeneficiaryId: i32,
+    pub Date: String,
+    pub Type: i8,
+    pub Note: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Details {
+    pub Id: i32,
+    pub Presences: Vec<BeneficiaryPresence>,
+    pub Allergies: Vec<BeneficiaryAllergy>,
+    pub Notes: Vec<BeneficiaryNotes>,
+}
+
+/// Body of `/beneficiary/select/:id`.
+pub type BeneficiaryDetails = (Beneficiary, Details);
+
+/// OpenAPI description of [`BeneficiaryDetails`], which goes over JSON as a two element array.
+#[cfg(feature = "openapi")]
+pub struct BeneficiaryDetailsSchema;
+
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for BeneficiaryDetailsSchema {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{AllOfBuilder, ArrayBuilder, ArrayItems};
+        use utoipa::openapi::Ref;
+
+        ArrayBuilder::new()
+            .prefix_items([
+                AllOfBuilder::new().item(Ref::from_schema_name("Beneficiary")).build(),
+                AllOfBuilder::new().item(Ref::from_schema_name("Details")).build(),
+            ])
+            .items(ArrayItems::False)
+            .build()
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for BeneficiaryDetailsSchema {
+    fn name() -> std::borrow::Cow<'static, str> {
+        "BeneficiaryDetails".into()
+    }
+
+    fn schemas(schemas: &mut Vec<(String, utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>)>) {
+        use utoipa::{PartialSchema, ToSchema};
+
+        schemas.push((Beneficiary::name().into(), Beneficiary::schema()));
+        schemas.push((Details::name().into(), Details::schema()));
+        <Details as ToSchema>::schemas(schemas);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct User {
+    pub Id: i32,
+    pub Username: String,
+    pub Password: String,
+    pub Role: String,
+}
+
+/// Body of `/user/login`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Connection {
+    pub Token: String,
+    pub Role: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Categories {
+    pub Id: i32,
+    pub Category: String,
+    pub MonthlyFee: f32,
+    pub WeeklyFee: f32,
+    pub UsedBy: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Stats {
+    pub Presences: Vec<Presence>,
+    pub Amounts: Vec<Amounts>,
+    pub Ages: Vec<Age>,
+    pub Cities: Vec<City>,
+    pub Employments: Vec<Employment>,
+    pub FamilySituations: Vec<FamilySituation>,
+    pub Incomes: Vec<Income>,
+    pub Kids: Vec<Kid>,
+    pub Languages: Vec<Language>,
+    pub Origins: Vec<Origin>,
+    pub Sexes: Vec<Sexe>,
+    pub Studies: Vec<Study>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Presence {
+    pub Date: String,
+    pub Total: u32,
+    pub Active: u32,
+    pub Visits: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Amounts {
+    pub Date: String,
+    pub TotalWeekly: u32,
+    pub TotalMonthly: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Age {
+    pub Date: String,
+    pub Age_0_19: u32,
+    pub Age_20_29: u32,
+    pub Age_30_39: u32,
+    pub Age_40_49: u32,
+    pub Age_50_59: u32,
+    pub Age_60_69: u32,
+    pub Age_70_Plus: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct City {
+    pub Date: String,
+    pub Carignan: u32,
+    pub Chambly: u32,
+    pub Marieville: u32,
+    pub Richelieu: u32,
+    pub StMathias: u32,
+    pub Other: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Employment {
+    pub Date: String,
+    pub Unemployed: u32,
+    pub Employed: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct FamilySituation {
+    pub Date: String,
+    pub Single: u32,
+    pub Couple: u32,
+    pub CoupleKids: u32,
+    pub Recomposed: u32,
+    pub SingleParent: u32,
+    pub Other: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Income {
+    pub Date: String,
+    pub NoIncome: u32,
+    pub Income_1_14999: u32,
+    pub Income_15000_29999: u32,
+    pub Income_30000_More: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Kid {
+    pub Date: String,
+    pub NoKids: u32,
+    pub OneKid: u32,
+    pub TwoKids: u32,
+    pub ThreeToFourKids: u32,
+    pub FivePlusKids: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Language {
+    pub Date: String,
+    pub French: u32,
+    pub English: u32,
+    pub Spanish: u32,
+    pub Arabic: u32,
+    pub Mandarin: u32,
+    pub Other: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Origin {
+    pub Date: String,
+    pub NorthAmerican: u32,
+    pub SouthAmerican: u32,
+    pub CentralAmerican: u32,
+    pub Asian: u32,
+    pub African: u32,
+    pub European: u32,
+    pub Other: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Sexe {
+    pub Date: String,
+    pub Male: u32,
+    pub Female: u32,
+    pub Other: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "sqlx", derive(sqlx::FromRow))]
+pub struct Study {
+    pub Date: String,
+    pub NoStudy: u32,
+    pub PrimarySchool: u32,
+    pub HighSchool: u32,
+    pub College: u32,
+    pub University: u32,
+    pub Other: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Token {
+    pub Token: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenBeneId {
+    pub Token: String,
+    pub Id: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenBene {
+    pub Token: String,
+    pub Beneficiary: Beneficiary,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenSearch {
+    pub Token: String,
+    pub Search: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserLogin {
+    pub Username: String,
+    pub Password: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserToken {
+    pub Token: String,
+    pub User: User,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCategory {
+    pub Token: String,
+    pub Category: Categories,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenAllergy {
+    pub Token: String,
+    pub Allergy: BeneficiaryAllergy,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenPresence {
+    pub Token: String,
+    pub Presence: BeneficiaryPresence,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenNote {
+    pub Token: String,
+    pub Content: BeneficiaryNotes,
+}
+
+/// A field that differs between two versions of a record.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditChange {
+    pub Field: String,
+    pub Before: Option<String>,
+    pub After: Option<String>,
+}
+
+/// Body of `/audit/select`. Personal data in `Changes` is masked before it is stored.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditEntry {
+    pub Id: i64,
+    pub Date: String,
+    pub UserId: i32,
+    pub Username: String,
+    pub Role: String,
+    pub Action: String,
+    pub BeneficiaryId: Option<i32>,
+    pub RequestId: String,
+    pub Changes: Vec<AuditChange>,
+}
+
+/// Filters of `/audit/select`, dates written `YYYY-MM-DD` and both ends included.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenAuditQuery {
+    pub Token: String,
+    pub BeneficiaryId: Option<i32>,
+    pub UserId: Option<i32>,
+    pub From: Option<String>,
+    pub To: Option<String>,
+}
+
+/// A beneficiary as it was from `ValidFrom` until the next version, with the fields changed
+/// since the previous one. `UserId` is empty for versions recorded before history existed.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BeneficiaryVersion {
+    pub Version: i32,
+    pub ValidFrom: String,
+    pub UserId: Option<i32>,
+    pub Beneficiary: Beneficiary,
+    pub Changes: Vec<AuditChange>,
+}
+
+/// Body of `/beneficiary/asof/select`, `Date` written `YYYY-MM-DD` (end of that day) or
+/// `YYYY-MM-DD HH:MM:SS`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenBeneAsOf {
+    pub Token: String,
+    pub Id: i32,
+    pub Date: String,
+}
+
+/// A deleted allergy, presence, household member or note still in the trash. `Kind` is
+/// `allergy`, `presence`, `member` or `note`. `Key` is the date of the presence or the id of
+/// the allergy, member or note, and `Content` is the allergy, the member name or the note
+/// text. The entry can be restored until `PurgeAfter`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrashEntry {
+    pub Kind: String,
+    pub BeneficiaryId: i32,
+    pub Key: String,
+    pub Content: String,
+    pub DeletedAt: String,
+    pub DeletedBy: Option<i32>,
+    pub PurgeAfter: String,
+}
+
+/// Body of `/trash/select`, every beneficiary when `BeneficiaryId` is empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenTrashQuery {
+    pub Token: String,
+    pub BeneficiaryId: Option<i32>,
+}
+
+/// Body of `/trash/restore`, an entry as returned by `/trash/select`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenTrashEntry {
+    pub Token: String,
+    pub Entry: TrashEntry,
+}
+
+/// A note with its own id. `Date` is the one given at creation, `CreatedAt` and `EditedAt`
+/// are set by the server. Notes written before ids existed have no `AuthorId`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Note {
+    pub Id: i32,
+    pub BeneficiaryId: i32,
+    pub Date: String,
+    pub Type: i8,
+    pub Note: String,
+    pub AuthorId: Option<i32>,
+    pub CreatedAt: String,
+    pub EditedAt: Option<String>,
+    pub EditedBy: Option<i32>,
+}
+
+/// Body of `PUT /note/{id}`, the new text of the note.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenNoteText {
+    pub Token: String,
+    pub Note: String,
+}
+
+/// What one role may do with the notes of a type.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NoteTypeAccess {
+    pub Role: String,
+    pub CanRead: bool,
+    pub CanWrite: bool,
+}
+
+/// A note type, the `Type` of a note. Roles missing from `Access` can neither read nor write it.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NoteType {
+    pub Id: i8,
+    pub Name: String,
+    pub Description: String,
+    pub Access: Vec<NoteTypeAccess>,
+}
+
+/// Body of `POST /notetype` and `PUT /notetype`. The `Id` is chosen by the server on creation.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenNoteType {
+    pub Token: String,
+    pub NoteType: NoteType,
+}
+
+/// A follow-up and the note it is attached to. `Overdue` is true when it is still open
+/// after `DueDate`, by the date of the server.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FollowUp {
+    pub NoteId: i32,
+    pub BeneficiaryId: i32,
+    pub Type: i8,
+    pub Note: String,
+    pub DueDate: String,
+    pub AssigneeId: i32,
+    pub Overdue: bool,
+    pub CompletedAt: Option<String>,
+    pub CompletedBy: Option<i32>,
+}
+
+/// Body of `PUT /note/{id}/followup`, `DueDate` written `YYYY-MM-DD`. The caller is the
+/// assignee when `AssigneeId` is empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenFollowUp {
+    pub Token: String,
+    pub DueDate: String,
+    pub AssigneeId: Option<i32>,
+}
+
+/// Body of `/followup/select`, the caller's own follow-ups when `AssigneeId` is empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenFollowUpQuery {
+    pub Token: String,
+    pub AssigneeId: Option<i32>,
+}
+
+/// The name of a catalog entry in one language, `Language` as in `Beneficiary.Language`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AllergenLabel {
+    pub Language: String,
+    pub Label: String,
+}
+
+/// An entry of the allergen catalog. `Kind` is `allergen` or `diet`. `Beneficiaries`
+/// counts who is linked to it and is ignored on writes.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Allergen {
+    pub Id: i32,
+    pub Code: String,
+    pub Kind: String,
+    pub IsActive: bool,
+    pub Labels: Vec<AllergenLabel>,
+    pub Beneficiaries: u32,
+}
+
+/// Body of `POST /allergen` and `PUT /allergen`. The `Id` is chosen by the server on creation.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenAllergen {
+    pub Token: String,
+    pub Allergen: Allergen,
+}
+
+/// Body of `/allergen/link`. `Severity` is `mild`, `moderate` or `severe`, or empty, and is
+/// ignored when unlinking.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenAllergenLink {
+    pub Token: String,
+    pub BeneficiaryId: i32,
+    pub AllergenId: i32,
+    pub Severity: Option<String>,
+}
+
+/// An allergy or diet of a beneficiary. Free text has no `AllergenId` nor `Kind`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Allergy {
+    pub BeneficiaryId: i32,
+    pub AllergenId: Option<i32>,
+    pub Kind: Option<String>,
+    pub Allergy: String,
+    pub Severity: Option<String>,
+}
+
+/// Someone living with a beneficiary. `Relationship` is `spouse`, `child`, `parent`,
+/// `sibling` or `other`, `Birth` is written `YYYY-MM-DD`. `BeneficiaryId` and `Kind` of
+/// the allergies are ignored on writes.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HouseholdMember {
+    pub Id: i32,
+    pub BeneficiaryId: i32,
+    pub FirstName: String,
+    pub LastName: String,
+    pub Birth: Option<String>,
+    pub Relationship: String,
+    pub Sexe: String,
+    pub Allergies: Vec<Allergy>,
+}
+
+/// Body of `POST /member` and `PUT /member`. The `Id` is chosen by the server on creation.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenMember {
+    pub Token: String,
+    pub Member: HouseholdMember,
+}
+
+/// Body of `POST /checkin`, naming the beneficiary by `BeneficiaryId` or by `CardCode`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCheckIn {
+    pub Token: String,
+    pub BeneficiaryId: Option<i32>,
+    pub CardCode: Option<String>,
+}
+
+/// What is left of a limit over a period. `Remaining` is empty when `Limit` is 0, which
+/// means no limit.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Allowance {
+    pub Limit: f64,
+    pub Used: f64,
+    pub Remaining: Option<f64>,
+}
+
+/// A recorded visit. `Date` is the presence written by the server, `Beneficiary` is
+/// projected for the caller role and `GeneralNote` is the latest `general` note.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CheckIn {
+    pub Date: String,
+    pub Beneficiary: Beneficiary,
+    pub Allergies: Vec<Allergy>,
+    pub GeneralNote: Option<String>,
+    pub Weekly: Allowance,
+    pub Monthly: Allowance,
+}
+
+/// Body of `/eligibility/select`, `Date` written `YYYY-MM-DD` and today when empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenEligibility {
+    pub Token: String,
+    pub BeneficiaryId: i32,
+    pub Date: Option<String>,
+}
+
+/// Whether a beneficiary may come on `Date`, from the visits of its week and month up to
+/// that day. `Reasons` names each limit reached. `Blocking` is true when a check-in would
+/// be refused rather than only warned about.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Eligibility {
+    pub BeneficiaryId: i32,
+    pub Date: String,
+    pub WeekStart: String,
+    pub MonthStart: String,
+    pub Weekly: Allowance,
+    pub Monthly: Allowance,
+    pub Eligible: bool,
+    pub Blocking: bool,
+    pub Reasons: Vec<String>,
+}
+
+/// Body of `POST /checkin/override`, a check-in past the limits for the given `Reason`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCheckInOverride {
+    pub Token: String,
+    pub BeneficiaryId: Option<i32>,
+    pub CardCode: Option<String>,
+    pub Reason: String,
+}
+
+/// A line of a distribution, e.g. 2 of `conserves`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DistributionItem {
+    pub Item: String,
+    pub Quantity: f64,
+}
+
+/// What a household received at a visit, `PresenceDate` naming the presence as in
+/// `BeneficiaryPresence.Date`. The category, the fee charged and the author are set by the
+/// server. A correction replaces the distribution it `Supersedes`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Distribution {
+    pub Id: i32,
+    pub BeneficiaryId: i32,
+    pub PresenceDate: String,
+    pub Amount: f64,
+    pub Items: Vec<DistributionItem>,
+    pub CategoryId: Option<i32>,
+    pub Fee: f64,
+    pub VolunteerId: Option<i32>,
+    pub Note: String,
+    pub CreatedAt: String,
+    pub CreatedBy: Option<i32>,
+    pub Supersedes: Option<i32>,
+}
+
+/// Body of `POST /distribution` and `PUT /distribution`, the caller being the volunteer
+/// when `VolunteerId` is empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenDistribution {
+    pub Token: String,
+    pub Distribution: Distribution,
+}
+
+/// Body of `/distribution/totals/select`. `From` and `To` are written `YYYY-MM-DD` and
+/// included, `Period` is `day`, `week`, `month` or `year`. Every beneficiary is counted
+/// when `BeneficiaryId` is empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenDistributionTotals {
+    pub Token: String,
+    pub BeneficiaryId: Option<i32>,
+    pub From: String,
+    pub To: String,
+    pub Period: String,
+}
+
+/// What a beneficiary received over the period starting on `PeriodStart`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DistributionTotal {
+    pub BeneficiaryId: i32,
+    pub PeriodStart: String,
+    pub Distributions: u32,
+    pub Amount: f64,
+    pub Fees: f64,
+}
+
+/// Money received from a beneficiary. `Method` is `cash`, `card`, `cheque`, `transfer` or
+/// `other`. The server sets `PaidAt` and `CreatedBy`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Payment {
+    pub Id: i32,
+    pub BeneficiaryId: i32,
+    pub Amount: f64,
+    pub Method: String,
+    pub PaidAt: String,
+    pub Note: String,
+    pub CreatedBy: Option<i32>,
+}
+
+/// Body of `POST /payment`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenPayment {
+    pub Token: String,
+    pub Payment: Payment,
+}
+
+/// Body of `/statement/select`, `From` and `To` written `YYYY-MM-DD`, included, and open
+/// when empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenStatementQuery {
+    pub Token: String,
+    pub BeneficiaryId: i32,
+    pub From: Option<String>,
+    pub To: Option<String>,
+}
+
+/// A line of a statement. `Kind` is `visit`, `week` or `month` for a charge and `payment`
+/// for a payment. `Reference` is the id of the distribution, charge or payment. `Balance`
+/// is what is owed after this line.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StatementEntry {
+    pub Date: String,
+    pub Kind: String,
+    pub Reference: i32,
+    pub Method: Option<String>,
+    pub Charge: f64,
+    pub Payment: f64,
+    pub Balance: f64,
+}
+
+/// What a beneficiary was charged and paid between two days. A positive balance is owed.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Statement {
+    pub BeneficiaryId: i32,
+    pub OpeningBalance: f64,
+    pub Entries: Vec<StatementEntry>,
+    pub ClosingBalance: f64,
+}
+
+/// What a beneficiary owes over its whole history.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Balance {
+    pub BeneficiaryId: i32,
+    pub Charged: f64,
+    pub Paid: f64,
+    pub Balance: f64,
+    pub LastPayment: Option<String>,
+}
+
+/// The membership card a beneficiary holds, `Code` grouped by four as printed.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Card {
+    pub Code: String,
+    pub BeneficiaryId: i32,
+    pub IssuedAt: String,
+}
+
+/// Body of `/card/select`, with a code as typed or scanned.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCardCode {
+    pub Token: String,
+    pub Code: String,
+}
+
+/// Body of `/card/print`, `Format` being `png` or `pdf`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCardPrint {
+    pub Token: String,
+    pub BeneficiaryId: i32,
+    pub Format: String,
+}
+
+/// Fees of a category from `EffectiveFrom`, written `YYYY-MM-DD`, until the next change.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CategoryFee {
+    pub CategoryId: i32,
+    pub EffectiveFrom: String,
+    pub MonthlyFee: f32,
+    pub WeeklyFee: f32,
+}
+
+/// Body of `POST /category/fee`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCategoryFee {
+    pub Token: String,
+    pub Fee: CategoryFee,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCategoryId {
+    pub Token: String,
+    pub CategoryId: i32,
+}
+
+/// Body of `POST /category/reassign`: moves the beneficiaries listed, or every one when
+/// the list is empty, from one category to another.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCategoryReassign {
+    pub Token: String,
+    pub From: i32,
+    pub To: i32,
+    pub BeneficiaryIds: Vec<i32>,
+}
+
+/// Body of `DELETE /category/{id}`. A category still in use needs `ReassignTo`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenCategoryDelete {
+    pub Token: String,
+    pub ReassignTo: Option<i32>,
+}
+
+/// Body of `POST /stats/compute`: the days, written `YYYY-MM-DD`, whose statistics are
+/// computed again.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TokenStatsBackfill {
+    pub Token: String,
+    pub From: String,
+    pub To: String,
+}
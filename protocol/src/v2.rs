@@ -0,0 +1,32 @@
+//! Version 2: the login response counts the caller's overdue follow-ups, and statistics
+//! can be read over a range of days. Every other type is the one of `v1`.
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+pub use crate::v1::*;
+
+/// Body of `/user/login`.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema), schema(as = v2::Connection))]
+pub struct Connection {
+    pub Token: String,
+    pub Role: String,
+    /// Open follow-ups assigned to the user whose due date has passed.
+    pub OverdueFollowUps: u32,
+}
+
+/// Body of `/stats/select`. `From` and `To`, written `YYYY-MM-DD`, may be left out.
+/// `Period` is `day`, `week`, `month` or `year`, `day` when empty, and `Series` names
+/// fields of `Stats`, every one when empty.
+#[derive(Clone, Debug, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema), schema(as = v2::TokenStatsQuery))]
+pub struct TokenStatsQuery {
+    pub Token: String,
+    #[serde(default)]
+    pub From: Option<String>,
+    #[serde(default)]
+    pub To: Option<String>,
+    #[serde(default)]
+    pub Period: String,
+    #[serde(default)]
+    pub Series: Vec<String>,
+}
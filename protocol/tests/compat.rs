@@ -0,0 +1,395 @@
+//! Golden fixtures of every released protocol version.
+//!
+//! The files under `tests/fixtures` were produced by a released server and must keep
+//! decoding to the same values. Regenerate them only when adding a new version:
+//! `HARMONY_WRITE_FIXTURES=1 cargo test -p harmony-protocol --test compat`.
+use std::fmt::Debug;
+use std::path::PathBuf;
+use bincode::{config, Decode, Encode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use harmony_protocol::{v1, v2};
+
+fn fixture(version: &str, name: &str, extension: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(version)
+        .join(format!("{name}.{extension}"))
+}
+
+fn check<T>(version: &str, name: &str, expected: T)
+where
+    T: Encode + Decode + Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let bin = fixture(version, name, "bin");
+    let json = fixture(version, name, "json");
+
+    if std::env::var_os("HARMONY_WRITE_FIXTURES").is_some() {
+        std::fs::write(&bin, bincode::encode_to_vec(&expected, config::standard()).unwrap()).unwrap();
+        std::fs::write(&json, serde_json::to_vec_pretty(&expected).unwrap()).unwrap();
+    }
+
+    let bytes = std::fs::read(&bin).unwrap_or_else(|e| panic!("missing fixture {}: {e}", bin.display()));
+    let (decoded, read): (T, usize) = bincode::decode_from_slice(&bytes, config::standard())
+        .unwrap_or_else(|e| panic!("{version}/{name}.bin no longer decodes: {e}"));
+    assert_eq!(read, bytes.len(), "{version}/{name}.bin has trailing bytes");
+    assert_eq!(decoded, expected, "{version}/{name}.bin decodes to a different value");
+
+    let bytes = std::fs::read(&json).unwrap_or_else(|e| panic!("missing fixture {}: {e}", json.display()));
+    let decoded: T = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|e| panic!("{version}/{name}.json no longer decodes: {e}"));
+    assert_eq!(decoded, expected, "{version}/{name}.json decodes to a different value");
+}
+
+fn v1_beneficiary() -> v1::Beneficiary {
+    v1::Beneficiary {
+        Id: 42,
+        FirstName: "Marie".to_string(),
+        LastName: "Tremblay".to_string(),
+        Email: "marie@example.com".to_string(),
+        Phone: "450-555-0100".to_string(),
+        Address: "12 rue Principale".to_string(),
+        PostalCode: "J3L 1A1".to_string(),
+        Kid: 2,
+        Adult: 1,
+        MonthlyAmount: 40.0,
+        WeeklyAmount: 10.0,
+        Category: 1,
+        MonthlyLimit: 4.0,
+        WeeklyLimit: 1.0,
+        Birth: Some("1985-04-12".to_string()),
+        LastPresence: "2024-02-01".to_string(),
+        Sexe: "F".to_string(),
+        Language: "French".to_string(),
+        Origin: "NorthAmerican".to_string(),
+        City: "Chambly".to_string(),
+        Study: "College".to_string(),
+        Income: "Income_15000_29999".to_string(),
+        FamilySituation: "SingleParent".to_string(),
+        IsActive: true,
+        IsSdf: false,
+        IsEmployed: true,
+        HasAllergies: true,
+        HasGeneralNote: true,
+    }
+}
+
+fn v1_details() -> v1::Details {
+    v1::Details {
+        Id: 42,
+        Presences: vec![v1::BeneficiaryPresence { BeneficiaryId: 42, Date: "2024-02-01 10:15:00".to_string() }],
+        Allergies: vec![v1::BeneficiaryAllergy { BeneficiaryId: 42, Allergy: "Arachides".to_string() }],
+        Notes: vec![v1::BeneficiaryNotes {
+            BeneficiaryId: 42,
+            Date: "2024-02-01 10:20:00".to_string(),
+            Type: 0,
+            Note: "Préfère les rendez-vous du matin".to_string(),
+        }],
+    }
+}
+
+fn v1_user() -> v1::User {
+    v1::User { Id: 7, Username: "benevole".to_string(), Password: String::new(), Role: "User".to_string() }
+}
+
+fn v1_category() -> v1::Categories {
+    v1::Categories { Id: 1, Category: "A".to_string(), MonthlyFee: 20.0, WeeklyFee: 5.0, UsedBy: 12 }
+}
+
+fn v1_stats() -> v1::Stats {
+    v1::Stats {
+        Presences: vec![v1::Presence { Date: "2024-01-31".to_string(), Total: 120, Active: 95, Visits: 310 }],
+        Amounts: vec![v1::Amounts { Date: "2024-01-31".to_string(), TotalWeekly: 250, TotalMonthly: 1000 }],
+        Ages: vec![v1::Age { Date: "2024-01-31".to_string(), Age_0_19: 1, Age_20_29: 2, Age_30_39: 3, Age_40_49: 4, Age_50_59: 5, Age_60_69: 6, Age_70_Plus: 7 }],
+        Cities: vec![v1::City { Date: "2024-01-31".to_string(), Carignan: 1, Chambly: 2, Marieville: 3, Richelieu: 4, StMathias: 5, Other: 6 }],
+        Employments: vec![v1::Employment { Date: "2024-01-31".to_string(), Unemployed: 40, Employed: 55 }],
+        FamilySituations: vec![v1::FamilySituation { Date: "2024-01-31".to_string(), Single: 1, Couple: 2, CoupleKids: 3, Recomposed: 4, SingleParent: 5, Other: 6 }],
+        Incomes: vec![v1::Income { Date: "2024-01-31".to_string(), NoIncome: 1, Income_1_14999: 2, Income_15000_29999: 3, Income_30000_More: 4 }],
+        Kids: vec![v1::Kid { Date: "2024-01-31".to_string(), NoKids: 1, OneKid: 2, TwoKids: 3, ThreeToFourKids: 4, FivePlusKids: 5 }],
+        Languages: vec![v1::Language { Date: "2024-01-31".to_string(), French: 1, English: 2, Spanish: 3, Arabic: 4, Mandarin: 5, Other: 6 }],
+        Origins: vec![v1::Origin { Date: "2024-01-31".to_string(), NorthAmerican: 1, SouthAmerican: 2, CentralAmerican: 3, Asian: 4, African: 5, European: 6, Other: 7 }],
+        Sexes: vec![v1::Sexe { Date: "2024-01-31".to_string(), Male: 40, Female: 54, Other: 1 }],
+        Studies: vec![v1::Study { Date: "2024-01-31".to_string(), NoStudy: 1, PrimarySchool: 2, HighSchool: 3, College: 4, University: 5, Other: 6 }],
+    }
+}
+
+fn v1_audit_entry() -> v1::AuditEntry {
+    v1::AuditEntry {
+        Id: 3,
+        Date: "2024-02-01 10:20:00".to_string(),
+        UserId: 7,
+        Username: "benevole".to_string(),
+        Role: "User".to_string(),
+        Action: "beneficiary.update".to_string(),
+        BeneficiaryId: Some(42),
+        RequestId: "0b7e5a52-3a51-4c8e-9f3e-2f1c54d1e8a0".to_string(),
+        Changes: vec![
+            v1::AuditChange { Field: "LastName".to_string(), Before: Some("<redacted:8 chars>".to_string()), After: Some("<redacted:6 chars>".to_string()) },
+            v1::AuditChange { Field: "WeeklyLimit".to_string(), Before: Some("1.0".to_string()), After: Some("2.0".to_string()) },
+        ],
+    }
+}
+
+fn v1_trash_entry() -> v1::TrashEntry {
+    v1::TrashEntry {
+        Kind: "note".to_string(),
+        BeneficiaryId: 42,
+        Key: "2024-02-01 10:20:00".to_string(),
+        Content: "Rappeler lundi".to_string(),
+        DeletedAt: "2024-02-03 09:00:00".to_string(),
+        DeletedBy: Some(7),
+        PurgeAfter: "2024-03-04 09:00:00".to_string(),
+    }
+}
+
+fn v1_note_type() -> v1::NoteType {
+    v1::NoteType {
+        Id: 2,
+        Name: "confidential-social-work".to_string(),
+        Description: "Follow-up by the social workers".to_string(),
+        Access: vec![
+            v1::NoteTypeAccess { Role: "TS".to_string(), CanRead: true, CanWrite: true },
+            v1::NoteTypeAccess { Role: "Admin".to_string(), CanRead: true, CanWrite: false },
+        ],
+    }
+}
+
+fn v1_allergen() -> v1::Allergen {
+    v1::Allergen {
+        Id: 5,
+        Code: "peanuts".to_string(),
+        Kind: "allergen".to_string(),
+        IsActive: true,
+        Labels: vec![
+            v1::AllergenLabel { Language: "fr".to_string(), Label: "Arachides".to_string() },
+            v1::AllergenLabel { Language: "en".to_string(), Label: "Peanuts".to_string() },
+        ],
+        Beneficiaries: 12,
+    }
+}
+
+fn v1_member() -> v1::HouseholdMember {
+    v1::HouseholdMember {
+        Id: 3,
+        BeneficiaryId: 42,
+        FirstName: "Léa".to_string(),
+        LastName: "Tremblay".to_string(),
+        Birth: Some("2016-05-04".to_string()),
+        Relationship: "child".to_string(),
+        Sexe: "F".to_string(),
+        Allergies: vec![v1::Allergy { BeneficiaryId: 42, AllergenId: Some(5), Kind: Some("allergen".to_string()), Allergy: "peanuts".to_string(), Severity: Some("severe".to_string()) }],
+    }
+}
+
+fn v1_distribution() -> v1::Distribution {
+    v1::Distribution {
+        Id: 12,
+        BeneficiaryId: 42,
+        PresenceDate: "2024-01-15 10:32:07".to_string(),
+        Amount: 35.5,
+        Items: vec![v1::DistributionItem { Item: "conserves".to_string(), Quantity: 2.0 }],
+        CategoryId: Some(1),
+        Fee: 2.5,
+        VolunteerId: Some(7),
+        Note: String::new(),
+        CreatedAt: "2024-01-15 10:40:00".to_string(),
+        CreatedBy: Some(7),
+        Supersedes: None,
+    }
+}
+
+fn v1_payment() -> v1::Payment {
+    v1::Payment {
+        Id: 9,
+        BeneficiaryId: 42,
+        Amount: 5.0,
+        Method: "cash".to_string(),
+        PaidAt: "2024-01-15 10:45:12".to_string(),
+        Note: String::new(),
+        CreatedBy: Some(7),
+    }
+}
+
+#[test]
+fn v1_responses_decode(){
+    check("v1", "connection", v1::Connection { Token: "benevole-8c3f".to_string(), Role: "User".to_string() });
+    check("v1", "users", vec![v1_user()]);
+    check("v1", "user", v1_user());
+    check("v1", "beneficiaries", vec![v1_beneficiary()]);
+    check::<v1::BeneficiaryDetails>("v1", "beneficiary_details", (v1_beneficiary(), v1_details()));
+    check("v1", "categories", vec![v1_category()]);
+    check("v1", "stats", v1_stats());
+    check("v1", "audit_entries", vec![v1_audit_entry()]);
+    check("v1", "beneficiary_versions", vec![v1::BeneficiaryVersion {
+        Version: 2,
+        ValidFrom: "2024-02-01 10:20:00".to_string(),
+        UserId: Some(7),
+        Beneficiary: v1_beneficiary(),
+        Changes: vec![v1::AuditChange { Field: "WeeklyLimit".to_string(), Before: Some("2.0".to_string()), After: Some("1.0".to_string()) }],
+    }]);
+    check("v1", "trash_entries", vec![v1_trash_entry()]);
+    check("v1", "note_types", vec![v1_note_type()]);
+    check("v1", "follow_ups", vec![v1::FollowUp {
+        NoteId: 12,
+        BeneficiaryId: 42,
+        Type: 2,
+        Note: "Rappeler pour le logement".to_string(),
+        DueDate: "2024-02-15".to_string(),
+        AssigneeId: 7,
+        Overdue: true,
+        CompletedAt: None,
+        CompletedBy: None,
+    }]);
+    check("v1", "allergens", vec![v1_allergen()]);
+    check("v1", "allergies", vec![
+        v1::Allergy { BeneficiaryId: 42, AllergenId: Some(5), Kind: Some("allergen".to_string()), Allergy: "peanuts".to_string(), Severity: Some("severe".to_string()) },
+        v1::Allergy { BeneficiaryId: 42, AllergenId: None, Kind: None, Allergy: "Kiwi".to_string(), Severity: None },
+    ]);
+    check("v1", "members", vec![v1_member()]);
+    check("v1", "check_in", v1::CheckIn {
+        Date: "2024-01-15 10:32:07".to_string(),
+        Beneficiary: v1_beneficiary(),
+        Allergies: v1_member().Allergies,
+        GeneralNote: Some("Préfère les conserves".to_string()),
+        Weekly: v1::Allowance { Limit: 1.0, Used: 1.0, Remaining: Some(0.0) },
+        Monthly: v1::Allowance { Limit: 0.0, Used: 3.0, Remaining: None },
+    });
+    check("v1", "distributions", vec![v1_distribution()]);
+    check("v1", "distribution_totals", vec![v1::DistributionTotal {
+        BeneficiaryId: 42,
+        PeriodStart: "2024-01-01".to_string(),
+        Distributions: 3,
+        Amount: 102.0,
+        Fees: 7.5,
+    }]);
+    check("v1", "payment", v1_payment());
+    check("v1", "category_fees", vec![
+        v1::CategoryFee { CategoryId: 1, EffectiveFrom: "1970-01-01".to_string(), MonthlyFee: 10.0, WeeklyFee: 2.5 },
+        v1::CategoryFee { CategoryId: 1, EffectiveFrom: "2024-02-01".to_string(), MonthlyFee: 12.0, WeeklyFee: 3.0 },
+    ]);
+    check("v1", "card", v1::Card {
+        Code: "7KQ2-M9XD-4HPT-R8WB".to_string(),
+        BeneficiaryId: 42,
+        IssuedAt: "2024-01-15 10:32:07".to_string(),
+    });
+    check("v1", "statement", v1::Statement {
+        BeneficiaryId: 42,
+        OpeningBalance: 2.5,
+        Entries: vec![
+            v1::StatementEntry { Date: "2024-01-15 10:32:07".to_string(), Kind: "visit".to_string(), Reference: 12, Method: None, Charge: 2.5, Payment: 0.0, Balance: 5.0 },
+            v1::StatementEntry { Date: "2024-01-15 10:45:12".to_string(), Kind: "payment".to_string(), Reference: 9, Method: Some("cash".to_string()), Charge: 0.0, Payment: 5.0, Balance: 0.0 },
+        ],
+        ClosingBalance: 0.0,
+    });
+    check("v1", "balances", vec![v1::Balance {
+        BeneficiaryId: 42,
+        Charged: 30.0,
+        Paid: 20.0,
+        Balance: 10.0,
+        LastPayment: Some("2024-01-15 10:45:12".to_string()),
+    }]);
+    check("v1", "eligibility", v1::Eligibility {
+        BeneficiaryId: 42,
+        Date: "2024-01-17".to_string(),
+        WeekStart: "2024-01-15".to_string(),
+        MonthStart: "2024-01-01".to_string(),
+        Weekly: v1::Allowance { Limit: 1.0, Used: 1.0, Remaining: Some(0.0) },
+        Monthly: v1::Allowance { Limit: 4.0, Used: 2.0, Remaining: Some(2.0) },
+        Eligible: false,
+        Blocking: true,
+        Reasons: vec!["Weekly limit of 1 reached".to_string()],
+    });
+    check("v1", "member", v1_member());
+    check("v1", "notes", vec![v1::Note {
+        Id: 12,
+        BeneficiaryId: 42,
+        Date: "2024-02-01 10:20:00".to_string(),
+        Type: 0,
+        Note: "Rappeler lundi".to_string(),
+        AuthorId: Some(7),
+        CreatedAt: "2024-02-01 10:20:03".to_string(),
+        EditedAt: Some("2024-02-02 08:00:00".to_string()),
+        EditedBy: Some(3),
+    }]);
+}
+
+#[test]
+fn v1_requests_decode(){
+    let token = "benevole-8c3f".to_string();
+    check("v1", "token", v1::Token { Token: token.clone() });
+    check("v1", "token_bene_id", v1::TokenBeneId { Token: token.clone(), Id: 42 });
+    check("v1", "token_bene", v1::TokenBene { Token: token.clone(), Beneficiary: v1_beneficiary() });
+    check("v1", "token_search", v1::TokenSearch { Token: token.clone(), Search: "Tremblay".to_string() });
+    check("v1", "user_login", v1::UserLogin { Username: "benevole".to_string(), Password: "secret".to_string() });
+    check("v1", "user_token", v1::UserToken { Token: token.clone(), User: v1_user() });
+    check("v1", "token_category", v1::TokenCategory { Token: token.clone(), Category: v1_category() });
+    check("v1", "token_allergy", v1::TokenAllergy { Token: token.clone(), Allergy: v1_details().Allergies.remove(0) });
+    check("v1", "token_presence", v1::TokenPresence { Token: token.clone(), Presence: v1_details().Presences.remove(0) });
+    check("v1", "token_note", v1::TokenNote { Token: token.clone(), Content: v1_details().Notes.remove(0) });
+    check("v1", "token_bene_as_of", v1::TokenBeneAsOf { Token: token.clone(), Id: 42, Date: "2024-01-15".to_string() });
+    check("v1", "token_note_text", v1::TokenNoteText { Token: token.clone(), Note: "Rappeler mardi".to_string() });
+    check("v1", "token_note_type", v1::TokenNoteType { Token: token.clone(), NoteType: v1_note_type() });
+    check("v1", "token_follow_up", v1::TokenFollowUp { Token: token.clone(), DueDate: "2024-02-15".to_string(), AssigneeId: Some(7) });
+    check("v1", "token_follow_up_query", v1::TokenFollowUpQuery { Token: token.clone(), AssigneeId: None });
+    check("v1", "token_allergen", v1::TokenAllergen { Token: token.clone(), Allergen: v1_allergen() });
+    check("v1", "token_allergen_link", v1::TokenAllergenLink { Token: token.clone(), BeneficiaryId: 42, AllergenId: 5, Severity: Some("severe".to_string()) });
+    check("v1", "token_member", v1::TokenMember { Token: token.clone(), Member: v1_member() });
+    check("v1", "token_distribution", v1::TokenDistribution { Token: token.clone(), Distribution: v1_distribution() });
+    check("v1", "token_distribution_totals", v1::TokenDistributionTotals {
+        Token: token.clone(),
+        BeneficiaryId: None,
+        From: "2024-01-01".to_string(),
+        To: "2024-03-31".to_string(),
+        Period: "month".to_string(),
+    });
+    check("v1", "token_card_code", v1::TokenCardCode { Token: token.clone(), Code: "7kq2 m9xd 4hpt r8wb".to_string() });
+    check("v1", "token_card_print", v1::TokenCardPrint { Token: token.clone(), BeneficiaryId: 42, Format: "pdf".to_string() });
+    check("v1", "token_category_fee", v1::TokenCategoryFee {
+        Token: token.clone(),
+        Fee: v1::CategoryFee { CategoryId: 1, EffectiveFrom: "2024-02-01".to_string(), MonthlyFee: 12.0, WeeklyFee: 3.0 },
+    });
+    check("v1", "token_category_id", v1::TokenCategoryId { Token: token.clone(), CategoryId: 1 });
+    check("v1", "token_category_reassign", v1::TokenCategoryReassign { Token: token.clone(), From: 1, To: 2, BeneficiaryIds: vec![42, 43] });
+    check("v1", "token_category_delete", v1::TokenCategoryDelete { Token: token.clone(), ReassignTo: Some(2) });
+    check("v1", "token_stats_backfill", v1::TokenStatsBackfill { Token: token.clone(), From: "2024-01-01".to_string(), To: "2024-03-31".to_string() });
+    check("v1", "token_payment", v1::TokenPayment { Token: token.clone(), Payment: v1_payment() });
+    check("v1", "token_statement_query", v1::TokenStatementQuery {
+        Token: token.clone(),
+        BeneficiaryId: 42,
+        From: Some("2024-01-01".to_string()),
+        To: None,
+    });
+    check("v1", "token_eligibility", v1::TokenEligibility { Token: token.clone(), BeneficiaryId: 42, Date: Some("2024-01-17".to_string()) });
+    check("v1", "token_check_in_override", v1::TokenCheckInOverride {
+        Token: token.clone(),
+        BeneficiaryId: Some(42),
+        CardCode: None,
+        Reason: "Urgence, logement perdu".to_string(),
+    });
+    check("v1", "token_check_in", v1::TokenCheckIn { Token: token.clone(), BeneficiaryId: None, CardCode: Some("K7QM-2XWD-9F4T".to_string()) });
+    check("v1", "token_trash_query", v1::TokenTrashQuery { Token: token.clone(), BeneficiaryId: Some(42) });
+    check("v1", "token_trash_entry", v1::TokenTrashEntry { Token: token.clone(), Entry: v1_trash_entry() });
+    check("v1", "token_audit_query", v1::TokenAuditQuery {
+        Token: token,
+        BeneficiaryId: Some(42),
+        UserId: None,
+        From: Some("2024-01-01".to_string()),
+        To: Some("2024-01-31".to_string()),
+    });
+}
+
+#[test]
+fn v2_responses_decode(){
+    check("v2", "connection", v2::Connection { Token: "benevole-8c3f".to_string(), Role: "TS".to_string(), OverdueFollowUps: 3 });
+}
+
+#[test]
+fn v2_requests_decode(){
+    check("v2", "token_stats_query", v2::TokenStatsQuery {
+        Token: "benevole-8c3f".to_string(),
+        From: Some("2024-01-01".to_string()),
+        To: None,
+        Period: "month".to_string(),
+        Series: vec!["Presences".to_string(), "Ages".to_string()],
+    });
+}
@@ -0,0 +1,313 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Duration;
+use chrono::Weekday;
+use dotenv::dotenv;
+
+/// Server settings read once at startup, next to the `DB_*` variables of the `.env` file.
+pub struct Config {
+    pub(crate) metrics: MetricsConfig,
+    pub(crate) health: HealthConfig,
+    pub(crate) shutdown: ShutdownConfig,
+    pub(crate) rate_limit: RateLimitConfig,
+    pub(crate) trash: TrashConfig,
+    pub(crate) eligibility: EligibilityConfig,
+    pub(crate) billing: BillingConfig,
+    pub(crate) organization: OrganizationConfig,
+    pub(crate) stats: StatsConfig,
+}
+
+pub(crate) struct MetricsConfig {
+    /// Addresses allowed to scrape `/metrics` without a session token.
+    pub(crate) allowlist: Vec<IpAddr>,
+}
+
+pub(crate) struct HealthConfig {
+    /// Budget of each readiness check before it counts as failed.
+    pub(crate) timeout: Duration,
+}
+
+pub(crate) struct ShutdownConfig {
+    /// Time given to in-flight requests and jobs once a shutdown signal arrived.
+    pub(crate) drain: Duration,
+}
+
+pub(crate) struct TrashConfig {
+    /// Time a deleted allergy, presence or note can be restored before it is purged.
+    pub(crate) retention: Duration,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct EligibilityConfig {
+    /// Day weekly limits start counting from.
+    pub(crate) week_start: Weekday,
+    /// Day of the month monthly limits start counting from, 1 to 28.
+    pub(crate) month_start: u32,
+    pub(crate) basis: EligibilityBasis,
+    pub(crate) mode: EligibilityMode,
+}
+
+/// What the weekly and monthly limits count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EligibilityBasis {
+    Visits,
+    /// The `Amount` of the distributions.
+    Amount,
+}
+
+impl FromStr for EligibilityBasis {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "visits" => Ok(EligibilityBasis::Visits),
+            "amount" => Ok(EligibilityBasis::Amount),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What a check-in does once a limit is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EligibilityMode {
+    /// Refuse it, unless an Admin overrides.
+    Block,
+    /// Record it, the allowance showing more used than the limit.
+    Warn,
+}
+
+impl FromStr for EligibilityMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "block" => Ok(EligibilityMode::Block),
+            "warn" => Ok(EligibilityMode::Warn),
+            _ => Err(()),
+        }
+    }
+}
+
+pub(crate) struct BillingConfig {
+    pub(crate) mode: BillingMode,
+}
+
+/// When category fees are charged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BillingMode {
+    /// With the distributions, the first of each week and month.
+    Visit,
+    /// To every active beneficiary at the start of each week and month, visit or not.
+    Period,
+}
+
+impl FromStr for BillingMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "visit" => Ok(BillingMode::Visit),
+            "period" => Ok(BillingMode::Period),
+            _ => Err(()),
+        }
+    }
+}
+
+pub(crate) struct StatsConfig {
+    /// Whether the job keeps the statistics tables up to date.
+    pub(crate) enabled: bool,
+    pub(crate) period: StatsPeriod,
+}
+
+/// What one row of the statistics tables covers, dated from its first day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StatsPeriod {
+    Day,
+    /// Starting on the eligibility week start.
+    Week,
+    /// Starting on the eligibility month start.
+    Month,
+}
+
+impl FromStr for StatsPeriod {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "day" => Ok(StatsPeriod::Day),
+            "week" => Ok(StatsPeriod::Week),
+            "month" => Ok(StatsPeriod::Month),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Printed at the top of receipts and visit slips.
+pub(crate) struct OrganizationConfig {
+    pub(crate) name: String,
+    pub(crate) address: String,
+}
+
+pub(crate) struct RateLimitConfig {
+    pub(crate) enabled: bool,
+    pub(crate) login: Limits,
+    pub(crate) read: Limits,
+    pub(crate) write: Limits,
+}
+
+/// Budgets of a route group, one bucket per session token (or username on login) and one per address.
+#[derive(Clone, Copy)]
+pub(crate) struct Limits {
+    pub(crate) per_user: Budget,
+    pub(crate) per_ip: Budget,
+}
+
+/// `requests` allowed in a burst, refilled evenly over `per`. Written `requests/seconds`, e.g. `5/60`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Budget {
+    pub(crate) requests: u32,
+    pub(crate) per: Duration,
+}
+
+impl Budget {
+    pub(crate) const fn new(requests: u32, seconds: u64) -> Budget {
+        Budget { requests, per: Duration::from_secs(seconds) }
+    }
+}
+
+impl FromStr for Budget {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (requests, seconds) = value.split_once('/').ok_or(())?;
+        let requests = requests.trim().parse().map_err(|_| ())?;
+        let seconds: u64 = seconds.trim().parse().map_err(|_| ())?;
+        if requests == 0 || seconds == 0 {
+            return Err(());
+        }
+        Ok(Budget::new(requests, seconds))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            metrics: MetricsConfig {
+                allowlist: vec![IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)],
+            },
+            health: HealthConfig {
+                timeout: Duration::from_secs(2),
+            },
+            shutdown: ShutdownConfig {
+                drain: Duration::from_secs(30),
+            },
+            rate_limit: RateLimitConfig {
+                enabled: true,
+                login: Limits { per_user: Budget::new(5, 60), per_ip: Budget::new(20, 60) },
+                read: Limits { per_user: Budget::new(60, 60), per_ip: Budget::new(300, 60) },
+                write: Limits { per_user: Budget::new(120, 60), per_ip: Budget::new(600, 60) },
+            },
+            trash: TrashConfig {
+                retention: Duration::from_secs(30 * 24 * 60 * 60),
+            },
+            eligibility: EligibilityConfig {
+                week_start: Weekday::Mon,
+                month_start: 1,
+                basis: EligibilityBasis::Visits,
+                mode: EligibilityMode::Warn,
+            },
+            billing: BillingConfig {
+                mode: BillingMode::Visit,
+            },
+            organization: OrganizationConfig {
+                name: "Harmony".to_string(),
+                address: String::new(),
+            },
+            stats: StatsConfig {
+                enabled: true,
+                period: StatsPeriod::Day,
+            },
+        }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        dotenv().ok();
+        let mut config = Config::default();
+
+        if let Ok(allowlist) = dotenv::var("METRICS_ALLOWLIST") {
+            config.metrics.allowlist = parse_list(&allowlist, "METRICS_ALLOWLIST");
+        }
+        if let Ok(timeout) = dotenv::var("HEALTH_TIMEOUT_MS") {
+            config.health.timeout = Duration::from_millis(parse(&timeout, "HEALTH_TIMEOUT_MS"));
+        }
+        if let Ok(drain) = dotenv::var("SHUTDOWN_DRAIN_SECS") {
+            config.shutdown.drain = Duration::from_secs(parse(&drain, "SHUTDOWN_DRAIN_SECS"));
+        }
+
+        if let Ok(enabled) = dotenv::var("RATE_LIMIT_ENABLED") {
+            config.rate_limit.enabled = parse(&enabled, "RATE_LIMIT_ENABLED");
+        }
+        for (limits, group) in [
+            (&mut config.rate_limit.login, "LOGIN"),
+            (&mut config.rate_limit.read, "READ"),
+            (&mut config.rate_limit.write, "WRITE"),
+        ] {
+            for (budget, key) in [(&mut limits.per_user, "USER"), (&mut limits.per_ip, "IP")] {
+                let name = format!("RATE_LIMIT_{group}_{key}");
+                if let Ok(value) = dotenv::var(&name) {
+                    *budget = parse(&value, &name);
+                }
+            }
+        }
+        if let Ok(days) = dotenv::var("TRASH_RETENTION_DAYS") {
+            config.trash.retention = Duration::from_secs(parse::<u64>(&days, "TRASH_RETENTION_DAYS") * 24 * 60 * 60);
+        }
+        if let Ok(day) = dotenv::var("ELIGIBILITY_WEEK_START") {
+            config.eligibility.week_start = parse(&day, "ELIGIBILITY_WEEK_START");
+        }
+        if let Ok(day) = dotenv::var("ELIGIBILITY_MONTH_START") {
+            config.eligibility.month_start = parse(&day, "ELIGIBILITY_MONTH_START");
+            if !(1..=28).contains(&config.eligibility.month_start) {
+                panic!("ELIGIBILITY_MONTH_START is invalid: {day}");
+            }
+        }
+        if let Ok(basis) = dotenv::var("ELIGIBILITY_BASIS") {
+            config.eligibility.basis = parse(&basis, "ELIGIBILITY_BASIS");
+        }
+        if let Ok(mode) = dotenv::var("ELIGIBILITY_MODE") {
+            config.eligibility.mode = parse(&mode, "ELIGIBILITY_MODE");
+        }
+        if let Ok(mode) = dotenv::var("BILLING_MODE") {
+            config.billing.mode = parse(&mode, "BILLING_MODE");
+        }
+        if let Ok(name) = dotenv::var("ORGANIZATION_NAME") {
+            config.organization.name = name.trim().to_string();
+        }
+        if let Ok(address) = dotenv::var("ORGANIZATION_ADDRESS") {
+            config.organization.address = address.trim().to_string();
+        }
+        if let Ok(enabled) = dotenv::var("STATS_JOB_ENABLED") {
+            config.stats.enabled = parse(&enabled, "STATS_JOB_ENABLED");
+        }
+        if let Ok(period) = dotenv::var("STATS_PERIOD") {
+            config.stats.period = parse(&period, "STATS_PERIOD");
+        }
+
+        config
+    }
+}
+
+fn parse<T: FromStr>(value: &str, name: &str) -> T {
+    value.trim().parse().unwrap_or_else(|_| panic!("{name} is invalid: {value}"))
+}
+
+fn parse_list<T: FromStr>(value: &str, name: &str) -> Vec<T> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| item.parse().unwrap_or_else(|_| panic!("{name} has an invalid entry: {item}")))
+        .collect()
+}
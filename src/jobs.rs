@@ -0,0 +1,118 @@
+use std::future::Future;
+use std::time::Duration;
+use sqlx::MySqlPool;
+use tokio::time::{timeout_at, Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, info, warn};
+use chrono::Local;
+use crate::config::{BillingMode, Config, EligibilityConfig, StatsPeriod};
+use crate::schema::{household, payment, snapshot, trash};
+
+/// Periodic work running next to the server, stopped with it on shutdown.
+pub(crate) struct Jobs {
+    tracker: TaskTracker,
+    shutdown: CancellationToken,
+}
+
+impl Jobs {
+    pub(crate) fn new(shutdown: CancellationToken) -> Jobs {
+        Jobs { tracker: TaskTracker::new(), shutdown }
+    }
+
+    /// Runs `job` every `period`, the first time right away. A run in progress when shutdown
+    /// starts is finished, no new one is started.
+    pub(crate) fn every<F, Fut>(&self, name: &'static str, period: Duration, job: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let shutdown = self.shutdown.clone();
+        self.tracker.spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = interval.tick() => {
+                        debug!(job = name, "Job started");
+                        job().await;
+                    },
+                }
+            }
+            debug!(job = name, "Job stopped");
+        });
+    }
+
+    /// Waits for every job to notice the shutdown, returns false when one was still busy at `deadline`.
+    pub(crate) async fn stop(&self, deadline: Instant) -> bool {
+        self.shutdown.cancel();
+        self.tracker.close();
+        match timeout_at(deadline, self.tracker.wait()).await {
+            Ok(()) => true,
+            Err(_) => {
+                warn!(running = self.tracker.len(), "Jobs still running at the drain deadline");
+                false
+            }
+        }
+    }
+}
+
+/// Registers the schedule of the server.
+pub(crate) fn schedule(jobs: &Jobs, pool: MySqlPool, config: &Config) {
+    let retention = config.trash.retention;
+    let sessions = pool.clone();
+    jobs.every("prune_sessions", Duration::from_secs(60 * 60), move || prune_sessions(sessions.clone()));
+    let households = pool.clone();
+    jobs.every("refresh_household_counts", Duration::from_secs(60 * 60), move || refresh_household_counts(households.clone()));
+    if config.billing.mode == BillingMode::Period {
+        let charges = pool.clone();
+        let eligibility = config.eligibility;
+        jobs.every("charge_fees", Duration::from_secs(60 * 60), move || charge_fees(charges.clone(), eligibility));
+    }
+    if config.stats.enabled {
+        let stats = pool.clone();
+        let (period, eligibility) = (config.stats.period, config.eligibility);
+        jobs.every("compute_stats", Duration::from_secs(60 * 60), move || compute_stats(stats.clone(), period, eligibility));
+    }
+    jobs.every("purge_trash", Duration::from_secs(60 * 60), move || purge_trash(pool.clone(), retention));
+}
+
+async fn prune_sessions(pool: MySqlPool) {
+    match sqlx::query("DELETE FROM UserSession WHERE Expires < NOW()").execute(&pool).await {
+        Ok(result) if result.rows_affected() > 0 => info!(removed = result.rows_affected(), "Expired sessions pruned"),
+        Ok(_) => {},
+        Err(e) => error!(error = %e, "Could not prune expired sessions"),
+    }
+}
+
+async fn refresh_household_counts(pool: MySqlPool) {
+    match household::refresh_counts(&pool).await {
+        Ok(0) => {},
+        Ok(changed) => info!(changed, "Household counts refreshed"),
+        Err(e) => error!(error = %e, "Could not refresh household counts"),
+    }
+}
+
+async fn charge_fees(pool: MySqlPool, config: EligibilityConfig) {
+    match payment::charge_periods(&pool, Local::now().date_naive(), &config).await {
+        Ok(0) => {},
+        Ok(charged) => info!(charged, "Period fees charged"),
+        Err(e) => error!(error = %e, "Could not charge period fees"),
+    }
+}
+
+async fn compute_stats(pool: MySqlPool, period: StatsPeriod, config: EligibilityConfig) {
+    match snapshot::refresh(&pool, Local::now().date_naive(), period, &config).await {
+        Ok(()) => debug!("Statistics computed"),
+        Err(e) => error!(error = %e, "Could not compute statistics"),
+    }
+}
+
+async fn purge_trash(pool: MySqlPool, retention: Duration) {
+    match trash::purge(&pool, retention).await {
+        Ok(0) => {},
+        Ok(removed) => info!(removed, "Expired trash purged"),
+        Err(e) => error!(error = %e, "Could not purge the trash"),
+    }
+}
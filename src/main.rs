@@ -2,27 +2,64 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use dotenv::dotenv;
+use sqlx::migrate::Migrator;
 use sqlx::mysql::{MySqlPoolOptions};
 use sqlx::{Error, MySql, Pool};
+use tokio::time::timeout_at;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use crate::config::Config;
+use crate::jobs::Jobs;
 use crate::route::get_routes;
 
 mod schema;
 mod route;
+mod config;
+mod metrics;
+mod telemetry;
+mod jobs;
+mod pdf;
+mod bitmap;
+mod shutdown;
+#[cfg(test)]
 mod test;
 
+pub(crate) static MIGRATOR: Migrator = sqlx::migrate!();
+
 #[tokio::main]
 async fn main() {
     run().await;
 }
 
 pub async fn run() {
+    telemetry::init();
     let database_url = get_db_url();
     let pool = get_pool(database_url).await.unwrap();
-    let app = get_routes(Arc::new(pool));
+    // Migrations are only applied when asked for, never by a plain restart.
+    if std::env::args().skip(1).any(|arg| arg == "--migrate") {
+        MIGRATOR.run(&pool).await.expect("failed to apply database migrations");
+        info!("Migrations applied");
+        pool.close().await;
+        return;
+    }
+    let config = Arc::new(Config::from_env());
+
+    let shutdown = CancellationToken::new();
+    shutdown::cancel_on_signal(shutdown.clone());
+    let jobs = Jobs::new(shutdown.child_token());
+    jobs::schedule(&jobs, pool.clone(), &config);
+
+    let app = get_routes(Arc::new(pool.clone()), config.clone());
     let addr = SocketAddr::from(([192, 168, 2, 23], 3000));
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    println!("Listening on {}", addr);
-    axum::serve(listener, app).await.unwrap();
+    info!(%addr, "Listening");
+    let deadline = shutdown::serve(listener, app, shutdown, config.shutdown.drain).await;
+
+    jobs.stop(deadline).await;
+    if timeout_at(deadline, pool.close()).await.is_err() {
+        warn!("Database connections still in use at the drain deadline");
+    }
+    info!("Stopped");
 }
 
 pub(crate) fn get_db_url() -> String{
@@ -43,8 +80,7 @@ pub(crate) async fn get_pool(url: String) -> Result<Pool<MySql>, Error>{
         if i > 5{
             return Err(e);
         }
-        println!("Failed to connect to database: {}", e);
-        println!("Retrying in 2 seconds");
+        warn!(error = %e, "Failed to connect to database, retrying in 3 seconds");
         tokio::time::sleep(Duration::from_secs(3)).await;
         i += 1;
     }
@@ -0,0 +1,93 @@
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+use sqlx::MySqlPool;
+
+/// Every metric exposed on `/metrics`, registered once in a registry of its own.
+pub(crate) struct Metrics {
+    registry: Registry,
+    pub(crate) http_requests: IntCounterVec,
+    pub(crate) http_latency: HistogramVec,
+    pub(crate) pool_size: IntGauge,
+    pub(crate) pool_idle: IntGauge,
+    pub(crate) pool_acquire: Histogram,
+    pub(crate) logins: IntCounterVec,
+    pub(crate) active_sessions: IntGauge,
+    pub(crate) rate_limited: IntCounterVec,
+}
+
+pub(crate) static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some("harmony".to_string()), None).unwrap();
+        let metrics = Metrics {
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "Requests served, by route and status"),
+                &["method", "route", "status"],
+            ).unwrap(),
+            http_latency: HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "Time spent serving a request, by route"),
+                &["method", "route"],
+            ).unwrap(),
+            pool_size: IntGauge::new("db_pool_connections", "Connections currently opened by the pool").unwrap(),
+            pool_idle: IntGauge::new("db_pool_idle_connections", "Opened connections waiting for a request").unwrap(),
+            pool_acquire: Histogram::with_opts(HistogramOpts::new(
+                "db_pool_acquire_seconds",
+                "Time a request waited for a database connection",
+            ).buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 3.0])).unwrap(),
+            logins: IntCounterVec::new(Opts::new("logins_total", "Login attempts, by outcome"), &["outcome"]).unwrap(),
+            active_sessions: IntGauge::new("active_sessions", "Sessions that have not expired yet").unwrap(),
+            rate_limited: IntCounterVec::new(
+                Opts::new("rate_limited_total", "Requests refused for exceeding a budget, by route group and key"),
+                &["group", "key"],
+            ).unwrap(),
+            registry,
+        };
+
+        metrics.registry.register(Box::new(metrics.http_requests.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.http_latency.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.pool_size.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.pool_idle.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.pool_acquire.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.logins.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.active_sessions.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.rate_limited.clone())).unwrap();
+        metrics
+    }
+
+    /// Refreshes the gauges read from the pool and renders everything in the Prometheus text format.
+    pub(crate) fn render(&self, pool: &MySqlPool) -> String {
+        self.pool_size.set(pool.size() as i64);
+        self.pool_idle.set(pool.num_idle() as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+pub(crate) fn login(outcome: &str) {
+    METRICS.logins.with_label_values(&[outcome]).inc();
+}
+
+pub(crate) fn acquire_wait(wait: Duration) {
+    METRICS.pool_acquire.observe(wait.as_secs_f64());
+}
+
+/// Counts and times every request under its route template, so `/beneficiary/select/:id`
+/// stays a single series whatever the id.
+pub(crate) async fn track(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string()).unwrap_or_else(|| "unmatched".to_string());
+    let start = Instant::now();
+
+    let response = next.run(req).await;
+
+    METRICS.http_latency.with_label_values(&[&method, &route]).observe(start.elapsed().as_secs_f64());
+    METRICS.http_requests.with_label_values(&[&method, &route, response.status().as_str()]).inc();
+    response
+}
@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+/// Points per millimetre.
+pub(crate) const MM: f32 = 72.0 / 25.4;
+
+/// One page of text and filled rectangles in the standard Helvetica fonts, so no font file
+/// is embedded and nothing outside the server is needed to render it.
+pub(crate) struct Page {
+    width: f32,
+    height: f32,
+    content: String,
+}
+
+impl Page {
+    /// An empty page of `width` by `height` points.
+    pub(crate) fn new(width: f32, height: f32) -> Page {
+        Page { width, height, content: String::new() }
+    }
+
+    pub(crate) fn a4() -> Page {
+        Page::new(210.0 * MM, 297.0 * MM)
+    }
+
+    pub(crate) fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Writes `text` with its baseline starting at `x`, `y` from the bottom left corner.
+    pub(crate) fn text(&mut self, x: f32, y: f32, size: f32, bold: bool, text: &str) {
+        let font = if bold { "F2" } else { "F1" };
+        let _ = writeln!(self.content, "BT /{font} {size:.1} Tf {x:.2} {y:.2} Td ({}) Tj ET", escape(text));
+    }
+
+    /// Fills a rectangle in black, `x`, `y` being its bottom left corner.
+    pub(crate) fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        let _ = writeln!(self.content, "{x:.2} {y:.2} {width:.2} {height:.2} re f");
+    }
+
+    /// The PDF file, the same bytes for the same page.
+    pub(crate) fn render(&self) -> Vec<u8> {
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                /Resources << /Font << /F1 4 0 R /F2 5 0 R >> >> /Contents 6 0 R >>",
+                self.width, self.height
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_string(),
+            format!("<< /Length {} >>\nstream\n{}endstream", self.content.len(), self.content),
+        ];
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{object}\nendobj\n", i + 1).as_bytes());
+        }
+        let xref = pdf.len();
+        let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(trailer, "{offset:010} 00000 n ");
+        }
+        let _ = write!(trailer, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n", objects.len() + 1);
+        pdf.extend_from_slice(trailer.as_bytes());
+        pdf
+    }
+}
+
+/// A PDF string in WinAnsiEncoding: Latin-1 letters are kept, other characters outside
+/// ASCII become `?`.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        let byte = match c {
+            '€' => 0x80,
+            '’' => 0x92,
+            c if (c as u32) < 0x100 => c as u32 as u8,
+            _ => b'?',
+        };
+        match byte {
+            b'(' | b')' | b'\\' => {
+                escaped.push('\\');
+                escaped.push(byte as char);
+            },
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => {
+                let _ = write!(escaped, "\\{byte:03o}");
+            },
+        }
+    }
+    escaped
+}
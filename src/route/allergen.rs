@@ -0,0 +1,147 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::route::acquire_connection;
+use crate::schema::allergen::{self, allergen_changes, TokenAllergen, TokenAllergenLink};
+use crate::schema::audit::{change, Audit, AuditAction};
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::user::{Token, TokenBeneId};
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/allergen/select", tag = "details",
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "The allergen and diet catalog, with labels and how many beneficiaries each has", body = Vec<v1::Allergen>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_allergens(State(pool): State<Arc<MySqlPool>>, format: Format, payload: Payload<Token>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(_) => allergen::select_allergens(acquire_connection(pool.clone()).await?, format).await,
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/allergen", tag = "details",
+    request_body = v1::TokenAllergen,
+    responses(
+        (status = 200, description = "The catalog after the insert", body = Vec<v1::Allergen>),
+        (status = 400, description = "No code or label, an unknown kind or a repeated language"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+        (status = 409, description = "An allergen already has this code"),
+    )
+)]
+pub(crate) async fn create_allergen(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenAllergen>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let audit = Audit::new(&user, request_id);
+                let allergens = allergen::save(acquire_connection(pool.clone()).await?, &payload.Allergen, true, format).await?;
+                audit.record(&pool, AuditAction::CreateAllergen, None, allergen_changes(None, &payload.Allergen)).await;
+                Ok(allergens)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(put, path = "/allergen", tag = "details",
+    request_body = v1::TokenAllergen,
+    responses(
+        (status = 200, description = "The catalog after the update", body = Vec<v1::Allergen>),
+        (status = 400, description = "No code or label, an unknown kind or a repeated language"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+        (status = 404, description = "No such allergen"),
+        (status = 409, description = "An allergen already has this code"),
+    )
+)]
+pub(crate) async fn update_allergen(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenAllergen>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let audit = Audit::new(&user, request_id);
+                let before = allergen::find(acquire_connection(pool.clone()).await?.as_mut(), payload.Allergen.Id).await;
+                let allergens = allergen::save(acquire_connection(pool.clone()).await?, &payload.Allergen, false, format).await?;
+                audit.record(&pool, AuditAction::UpdateAllergen, None, allergen_changes(before.as_ref(), &payload.Allergen)).await;
+                Ok(allergens)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/allergy/select", tag = "details",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "Allergies and diets of the beneficiary, catalog entries first", body = Vec<v1::Allergy>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_allergies(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let allergies = allergen::select_allergies(acquire_connection(pool.clone()).await?, payload.Id, format).await?;
+            audit.record(&pool, AuditAction::ReadAllergies, Some(payload.Id), Vec::new()).await;
+            Ok(allergies)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/allergen/link", tag = "details",
+    request_body = v1::TokenAllergenLink,
+    responses(
+        (status = 200, description = "Beneficiary linked, or the severity of the link changed"),
+        (status = 400, description = "Unknown severity"),
+        (status = 401, description = "Invalid token"),
+        (status = 404, description = "No such active allergen"),
+    )
+)]
+pub(crate) async fn link_allergen(State(pool): State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenAllergenLink>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let (code, created) = allergen::link(acquire_connection(pool.clone()).await?, &payload, &user).await?;
+            audit.record(&pool, AuditAction::LinkAllergen, Some(payload.BeneficiaryId), vec![
+                change("Allergen", (!created).then(|| code.clone()), Some(code)),
+                change("Severity", None, payload.Severity.clone()),
+            ]).await;
+            Ok(StatusCode::OK)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(delete, path = "/allergen/link", tag = "details",
+    request_body = v1::TokenAllergenLink,
+    responses(
+        (status = 200, description = "Link moved to the trash"),
+        (status = 401, description = "Invalid token"),
+        (status = 404, description = "The beneficiary is not linked to this allergen"),
+    )
+)]
+pub(crate) async fn unlink_allergen(State(pool): State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenAllergenLink>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let code = allergen::unlink(acquire_connection(pool.clone()).await?, &payload, &user).await?;
+            audit.record(&pool, AuditAction::UnlinkAllergen, Some(payload.BeneficiaryId), vec![change("Allergen", Some(code), None)]).await;
+            Ok(StatusCode::OK)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::route::acquire_connection;
+use crate::schema::audit::{select_entries, TokenAuditQuery};
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::validate_token;
+
+#[utoipa::path(post, path = "/audit/select", tag = "audit",
+    request_body = v1::TokenAuditQuery,
+    responses(
+        (status = 200, description = "Most recent matching entries, at most 1000", body = Vec<v1::AuditEntry>),
+        (status = 400, description = "A date is not written YYYY-MM-DD"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+    )
+)]
+pub(crate) async fn audit_log(State(pool): State<Arc<MySqlPool>>, format: Format, payload: Payload<TokenAuditQuery>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let conn = acquire_connection(pool.clone()).await?;
+                select_entries(conn, &payload, format).await
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
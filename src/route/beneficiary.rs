@@ -1,62 +1,169 @@
 use std::sync::Arc;
 use axum::extract::State;
 use axum::http::StatusCode;
-use axum::Json;
 use sqlx::MySqlPool;
 
+use harmony_protocol::v1;
 use crate::route::acquire_connection;
+use crate::schema::audit::{change, Audit, AuditAction};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format, Payload};
 use crate::schema::beneficiary::{Beneficiary, BeneficiaryAction};
+use crate::schema::history::{select_as_of, select_versions, TokenBeneAsOf};
 use crate::schema::user::{Token, TokenBene, TokenBeneId, TokenSearch};
 use crate::schema::validate_token;
+use crate::telemetry::RequestId;
 
-pub(crate) async fn beneficiaries(State(pool) : State<Arc<MySqlPool>>, payload : Json<Token>) -> Result<Vec<u8>, (StatusCode, String)> {
+#[utoipa::path(post, path = "/beneficiary/select", tag = "beneficiary",
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Active beneficiaries, projected for the caller role", body = Vec<v1::Beneficiary>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn beneficiaries(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<Token>) -> Result<Encoded, (StatusCode, String)> {
     let conn = acquire_connection(pool.clone()).await?;
 
     if let Ok(value) = validate_token(conn, &payload.Token).await {
-            Beneficiary::get_beneficiaries(acquire_connection(pool.clone()).await?, value).await
+        let audit = Audit::new(&value, request_id);
+        let listed = Beneficiary::get_beneficiaries(acquire_connection(pool.clone()).await?, value, format).await?;
+        audit.record(&pool, AuditAction::ListBeneficiaries, None, Vec::new()).await;
+        Ok(listed)
     } else {
         Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
     }
 }
 
-pub(crate) async fn search_beneficiaries(State(pool) : State<Arc<MySqlPool>>, payload : Json<TokenSearch>) -> Result<Vec<u8>, (StatusCode, String)> {
+#[utoipa::path(post, path = "/beneficiary/search", tag = "beneficiary",
+    request_body = v1::TokenSearch,
+    responses(
+        (status = 200, description = "Matching beneficiaries", body = Vec<v1::Beneficiary>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn search_beneficiaries(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenSearch>) -> Result<Encoded, (StatusCode, String)> {
     let conn = acquire_connection(pool.clone()).await?;
 
     if let Ok(value) = validate_token(conn, &payload.Token).await {
-        Beneficiary::search(acquire_connection(pool.clone()).await?, value, &payload.Search).await
+        let audit = Audit::new(&value, request_id);
+        let found = Beneficiary::search(acquire_connection(pool.clone()).await?, value, &payload.Search, format).await?;
+        audit.record(&pool, AuditAction::SearchBeneficiaries, None, vec![change("Search", None, Some(payload.Search.clone()))]).await;
+        Ok(found)
     } else {
         Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
     }
 }
 
-pub(crate) async fn beneficiary(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenBeneId>) -> Result<Vec<u8>, (StatusCode, String)> {
+#[utoipa::path(post, path = "/beneficiary/select/{id}", tag = "beneficiary",
+    params(("id" = i32, Path, description = "Beneficiary id, the body Id is the one used")),
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "Beneficiary and its details", body = v1::BeneficiaryDetailsSchema),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn beneficiary(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)> {
     let conn = acquire_connection(pool.clone()).await?;
 
     if let Ok(user) = validate_token(conn, &payload.Token).await{
+        let audit = Audit::new(&user, request_id);
         let conn = acquire_connection(pool.clone()).await?;
-        Beneficiary::get_beneficiary(conn, user, payload.Id).await
+        let details = Beneficiary::get_beneficiary(conn, user, payload.Id, format).await?;
+        audit.record(&pool, AuditAction::ReadBeneficiary, Some(payload.Id), Vec::new()).await;
+        Ok(details)
     }else{
         Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
     }
 }
 
-pub(crate) async fn create_beneficiary(State(pool) : State<Arc<MySqlPool>>, payload: Json<Token>) -> Result<Vec<u8>, (StatusCode, String)>{
+#[utoipa::path(post, path = "/beneficiary", tag = "beneficiary",
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Created beneficiary", body = v1::Beneficiary),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn create_beneficiary(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<Token>) -> Result<Encoded, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(user) => Beneficiary::create_beneficiary(acquire_connection(pool.clone()).await?, user).await,
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let bene = Beneficiary::insert_beneficiary(acquire_connection(pool.clone()).await?, user).await?;
+            audit.record(&pool, AuditAction::CreateBeneficiary, Some(bene.Id), Vec::new()).await;
+            encode(v1::Beneficiary::from(bene), format)
+        },
         Err(_) => Err((StatusCode::UNAUTHORIZED,"Invalid token".to_string()))
     }
 }
 
-pub(crate) async fn update_beneficiary(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenBene>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(put, path = "/beneficiary", tag = "beneficiary",
+    request_body = v1::TokenBene,
+    responses(
+        (status = 200, description = "Beneficiary updated"),
+        (status = 403, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn update_beneficiary(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenBene>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
 
-    let res = match validate_token(conn, &payload.Token).await {
-        Ok(user) => Beneficiary::update_beneficiary(acquire_connection(pool.clone()).await?, user, payload.Beneficiary.clone()).await,
+    let id = payload.Beneficiary.Id;
+    let (audit, res) = match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            (audit, Beneficiary::update_beneficiary(acquire_connection(pool.clone()).await?, user, payload.Beneficiary.clone()).await)
+        },
         Err(_) => return Err((StatusCode::FORBIDDEN, "Invalid token".to_string())),
     };
     match res {
-        Ok(_) => Ok(StatusCode::OK),
+        Ok(changes) => {
+            audit.record(&pool, AuditAction::UpdateBeneficiary, Some(id), changes).await;
+            Ok(StatusCode::OK)
+        },
         Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string())),
     }
 }
+
+#[utoipa::path(post, path = "/beneficiary/history/select", tag = "beneficiary",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "Every version, oldest first, with the fields changed since the previous one", body = Vec<v1::BeneficiaryVersion>),
+        (status = 401, description = "Invalid token"),
+        (status = 404, description = "No history for this beneficiary"),
+    )
+)]
+pub(crate) async fn beneficiary_history(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let versions = select_versions(acquire_connection(pool.clone()).await?, user, payload.Id, format).await?;
+            audit.record(&pool, AuditAction::ReadHistory, Some(payload.Id), Vec::new()).await;
+            Ok(versions)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/beneficiary/asof/select", tag = "beneficiary",
+    request_body = v1::TokenBeneAsOf,
+    responses(
+        (status = 200, description = "The version in effect at that date", body = v1::BeneficiaryVersion),
+        (status = 400, description = "The date is not written YYYY-MM-DD or YYYY-MM-DD HH:MM:SS"),
+        (status = 401, description = "Invalid token"),
+        (status = 404, description = "No history before the first version, whose date is given, or none at all"),
+    )
+)]
+pub(crate) async fn beneficiary_as_of(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneAsOf>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let version = select_as_of(acquire_connection(pool.clone()).await?, user, payload.Id, &payload.Date, format).await?;
+            audit.record(&pool, AuditAction::ReadHistory, Some(payload.Id), vec![change("AsOf", None, Some(payload.Date.clone()))]).await;
+            Ok(version)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::config::Config;
+use crate::route::acquire_connection;
+use crate::schema::audit::{Audit, AuditAction};
+use crate::schema::card::{self, can_revoke, card_changes, CardFormat, TokenCardCode, TokenCardPrint};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::receipt;
+use crate::schema::user::TokenBeneId;
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/card", tag = "details",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "The current card of the beneficiary, issued if it held none", body = v1::Card),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such beneficiary"),
+    )
+)]
+pub(crate) async fn select_card(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" | "TS" | "User" => {
+                let audit = Audit::new(&user, request_id);
+                let (card, issued) = card::current(acquire_connection(pool.clone()).await?, payload.Id, &user).await?;
+                if issued {
+                    audit.record(&pool, AuditAction::IssueCard, Some(payload.Id), card_changes(&card)).await;
+                }
+                encode(card, format)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/card/select", tag = "details",
+    request_body = v1::TokenCardCode,
+    responses(
+        (status = 200, description = "The beneficiary holding the card, as the caller role sees it", body = v1::Beneficiary),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "Unknown or revoked card"),
+    )
+)]
+pub(crate) async fn look_up_card(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenCardCode>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let beneficiary = card::lookup(acquire_connection(pool.clone()).await?, &payload.Code, &user).await?;
+            audit.record(&pool, AuditAction::LookUpCard, Some(beneficiary.Id), Vec::new()).await;
+            encode(beneficiary, format)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/card/print", tag = "details",
+    request_body = v1::TokenCardPrint,
+    responses(
+        (status = 200, description = "The current card, issued if the beneficiary held none", content(("image/png"), ("application/pdf"))),
+        (status = 400, description = "Unknown format"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such beneficiary"),
+    )
+)]
+pub(crate) async fn print_card(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, request_id: RequestId, payload: Payload<TokenCardPrint>) -> Result<Response, (StatusCode, String)> {
+    let format: CardFormat = payload.Format.parse()?;
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" | "TS" | "User" => {
+                let audit = Audit::new(&user, request_id);
+                let (card, issued) = card::current(acquire_connection(pool.clone()).await?, payload.BeneficiaryId, &user).await?;
+                if issued {
+                    audit.record(&pool, AuditAction::IssueCard, Some(payload.BeneficiaryId), card_changes(&card)).await;
+                }
+                let mut conn = acquire_connection(pool.clone()).await?;
+                let holder = receipt::holder(conn.as_mut(), payload.BeneficiaryId, None, &user)
+                    .await
+                    .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Could not print the card".to_string()))?
+                    .ok_or((StatusCode::NOT_FOUND, "No such beneficiary".to_string()))?;
+                let (media_type, extension, bytes) = match format {
+                    CardFormat::Png => ("image/png", "png", card::png(&card, &holder, &config.organization)?),
+                    CardFormat::Pdf => ("application/pdf", "pdf", card::pdf(&card, &holder, &config.organization)?),
+                };
+                audit.record(&pool, AuditAction::PrintCard, Some(payload.BeneficiaryId), card_changes(&card)).await;
+                let disposition = format!("attachment; filename=\"card-{}.{extension}\"", payload.BeneficiaryId);
+                Ok(([(header::CONTENT_TYPE, media_type.to_string()), (header::CONTENT_DISPOSITION, disposition)], bytes).into_response())
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/card/reissue", tag = "details",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "The new card, every previous one being revoked", body = v1::Card),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller may not revoke cards"),
+        (status = 404, description = "No such beneficiary"),
+    )
+)]
+pub(crate) async fn reissue_card(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) if can_revoke(&user) => {
+            let audit = Audit::new(&user, request_id);
+            let card = card::reissue(acquire_connection(pool.clone()).await?, payload.Id, &user).await?;
+            audit.record(&pool, AuditAction::IssueCard, Some(payload.Id), card_changes(&card)).await;
+            encode(card, format)
+        },
+        Ok(_) => Err((StatusCode::FORBIDDEN, "Invalid role".to_string())),
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(delete, path = "/card", tag = "details",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "The card of the beneficiary no longer resolves"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller may not revoke cards"),
+        (status = 404, description = "The beneficiary holds no card"),
+    )
+)]
+pub(crate) async fn revoke_card(State(pool): State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) if can_revoke(&user) => {
+            let audit = Audit::new(&user, request_id);
+            let card = card::revoke(acquire_connection(pool.clone()).await?, payload.Id, &user).await?;
+            audit.record(&pool, AuditAction::RevokeCard, Some(payload.Id), card_changes(&card)).await;
+            Ok(StatusCode::OK)
+        },
+        Ok(_) => Err((StatusCode::FORBIDDEN, "Invalid role".to_string())),
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -1,26 +1,39 @@
 use std::sync::Arc;
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use axum::Json;
 use sqlx::MySqlPool;
+use harmony_protocol::v1;
 use crate::route::acquire_connection;
-use crate::schema::category::{Categories, TokenCategory};
+use crate::schema::audit::{change, Audit, AuditAction, AuditChange};
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::category::{self, Categories, TokenCategory, TokenCategoryDelete, TokenCategoryFee, TokenCategoryId, TokenCategoryReassign};
 use crate::schema::user::Token;
 use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+use tracing::{debug, error};
 
-pub(crate) async fn create_category(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenCategory>) -> Result<Vec<u8>, (StatusCode, String)>{
-    println!("->> {:>12} - Create category", "Handler");
+#[utoipa::path(post, path = "/category", tag = "category",
+    request_body = v1::TokenCategory,
+    responses(
+        (status = 200, description = "Every category after the insert", body = Vec<v1::Categories>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn create_category(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenCategory>) -> Result<Encoded, (StatusCode, String)>{
+    debug!("Create category");
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
             let conn = acquire_connection(pool.clone()).await?;
-            match payload.Category.create_category(conn).await {
+            match payload.Category.create_category(conn, format).await {
                 Ok(val) => {
-                    println!("->> {:>12} - Create category - SUCCESS", "Handler");
+                    audit.record(&pool, AuditAction::CreateCategory, None, category_changes(None, Some(&payload.Category))).await;
+                    debug!("Create category succeeded");
                     Ok(val)
                 }
                 Err(e) => {
-                    println!("->> {:>12} - Create category - FAILED : {:?}", "Handler", e);
+                    error!(error = ?e, "Create category failed");
                     Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not create a category".to_string()))
                 }
             }
@@ -29,19 +42,29 @@ pub(crate) async fn create_category(State(pool) : State<Arc<MySqlPool>>, payload
     }
 }
 
-pub(crate) async fn update_category(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenCategory>) -> Result<Vec<u8>, (StatusCode, String)>{
-    println!("->> {:>12} - Update category", "Handler");
+#[utoipa::path(put, path = "/category", tag = "category",
+    request_body = v1::TokenCategory,
+    responses(
+        (status = 200, description = "Every category after the update", body = Vec<v1::Categories>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn update_category(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenCategory>) -> Result<Encoded, (StatusCode, String)>{
+    debug!("Update category");
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let before = Categories::find(acquire_connection(pool.clone()).await?.as_mut(), payload.Category.Id).await;
             let conn = acquire_connection(pool.clone()).await?;
-            match payload.Category.update_category(conn).await {
+            match payload.Category.update_category(conn, format).await {
                 Ok(val) => {
-                    println!("->> {:>12} - Update category - SUCCESS", "Handler");
+                    audit.record(&pool, AuditAction::UpdateCategory, None, category_changes(before.as_ref(), Some(&payload.Category))).await;
+                    debug!("Update category succeeded");
                     Ok(val)
                 }
                 Err(e) => {
-                    println!("->> {:>12} - Update category - FAILED : {:?}", "Handler", e);
+                    error!(error = ?e, "Update category failed");
                     Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not update a category".to_string()))
                 }
             }
@@ -50,20 +73,31 @@ pub(crate) async fn update_category(State(pool) : State<Arc<MySqlPool>>, payload
     }
 }
 
-pub(crate) async fn delete_category(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenCategory>) -> Result<Vec<u8>, (StatusCode, String)>{
-    println!("->> {:>12} - Delete category", "Handler");
+#[utoipa::path(delete, path = "/category", tag = "category",
+    request_body = v1::TokenCategory,
+    responses(
+        (status = 200, description = "Every category after the delete", body = Vec<v1::Categories>),
+        (status = 401, description = "Invalid token"),
+        (status = 409, description = "Beneficiaries are still in the category"),
+    )
+)]
+pub(crate) async fn delete_category(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenCategory>) -> Result<Encoded, (StatusCode, String)>{
+    debug!("Delete category");
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let before = Categories::find(acquire_connection(pool.clone()).await?.as_mut(), payload.Category.Id).await;
             let conn = acquire_connection(pool.clone()).await?;
-            match payload.Category.delete_category(conn).await {
+            match payload.Category.delete_category(conn, format).await {
                 Ok(val) => {
-                    println!("->> {:>12} - Delete category - SUCCESS", "Handler");
+                    audit.record(&pool, AuditAction::DeleteCategory, None, category_changes(before.as_ref(), None)).await;
+                    debug!("Delete category succeeded");
                     Ok(val)
                 }
                 Err(e) => {
-                    println!("->> {:>12} - Delete category - FAILED : {:?}", "Handler", e);
-                    Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not delete a category".to_string()))
+                    error!(error = ?e, "Delete category failed");
+                    Err(e)
                 }
             }
         }
@@ -71,23 +105,155 @@ pub(crate) async fn delete_category(State(pool) : State<Arc<MySqlPool>>, payload
     }
 }
 
-pub(crate) async fn select_categories(State(pool) : State<Arc<MySqlPool>>, payload: Json<Token>) -> Result<Vec<u8>, (StatusCode, String)>{
-    println!("->> {:>12} - Select categories", "Handler");
+#[utoipa::path(post, path = "/category/select", tag = "category",
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Every category", body = Vec<v1::Categories>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_categories(State(pool) : State<Arc<MySqlPool>>, format: Format, payload: Payload<Token>) -> Result<Encoded, (StatusCode, String)>{
+    debug!("Select categories");
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
         Ok(_) => {
             let conn = acquire_connection(pool.clone()).await?;
-            match Categories::select_categories(conn).await {
+            match Categories::select_categories(conn, format).await {
                 Ok(val) => {
-                    println!("->> {:>12} - Select categories - SUCCESS", "Handler");
+                    debug!("Select categories succeeded");
                     Ok(val)
                 }
                 Err(e) => {
-                    println!("->> {:>12} - Select categories - FAILED : {:?}", "Handler", e);
+                    error!(error = ?e, "Select categories failed");
                     Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not select categories".to_string()))
                 }
             }
         }
         Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
     }
-}
\ No newline at end of file
+}
+
+#[utoipa::path(delete, path = "/category/{id}", tag = "category",
+    params(("id" = i32, Path, description = "Category id")),
+    request_body = v1::TokenCategoryDelete,
+    responses(
+        (status = 200, description = "Every category after the delete", body = Vec<v1::Categories>),
+        (status = 400, description = "The category cannot be reassigned to itself"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such category"),
+        (status = 409, description = "Beneficiaries are still in the category and no ReassignTo was given"),
+    )
+)]
+pub(crate) async fn delete_category_by_id(State(pool): State<Arc<MySqlPool>>, Path(id): Path<i32>, format: Format, request_id: RequestId, payload: Payload<TokenCategoryDelete>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" => {
+                let audit = Audit::new(&user, request_id);
+                let (before, moved) = category::delete(acquire_connection(pool.clone()).await?, id, payload.ReassignTo, &user).await?;
+                let mut changes = category_changes(Some(&before), None);
+                if let Some(to) = payload.ReassignTo {
+                    changes.push(change("ReassignTo", None, Some(to.to_string())));
+                    changes.push(change("Moved", None, Some(moved.to_string())));
+                }
+                audit.record(&pool, AuditAction::DeleteCategory, None, changes).await;
+                Categories::select_categories(acquire_connection(pool.clone()).await?, format).await
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/category/reassign", tag = "category",
+    request_body = v1::TokenCategoryReassign,
+    responses(
+        (status = 200, description = "Every category after the move", body = Vec<v1::Categories>),
+        (status = 400, description = "Both categories are the same"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such category to move to"),
+    )
+)]
+pub(crate) async fn reassign_category(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenCategoryReassign>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" => {
+                let audit = Audit::new(&user, request_id);
+                let moved = category::reassign(acquire_connection(pool.clone()).await?, &payload, &user).await?;
+                audit.record(&pool, AuditAction::ReassignCategory, None, vec![
+                    change("From", Some(payload.From.to_string()), None),
+                    change("To", None, Some(payload.To.to_string())),
+                    change("Moved", None, Some(moved.to_string())),
+                ]).await;
+                Categories::select_categories(acquire_connection(pool.clone()).await?, format).await
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/category/fee", tag = "category",
+    request_body = v1::TokenCategoryFee,
+    responses(
+        (status = 200, description = "Every fee change of the category", body = Vec<v1::CategoryFee>),
+        (status = 400, description = "Invalid date or fee"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such category"),
+    )
+)]
+pub(crate) async fn set_category_fee(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenCategoryFee>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" => {
+                let audit = Audit::new(&user, request_id);
+                let fee = &payload.Fee;
+                category::set_fee(acquire_connection(pool.clone()).await?, fee, &user).await?;
+                audit.record(&pool, AuditAction::SetCategoryFee, None, vec![
+                    change("CategoryId", None, Some(fee.CategoryId.to_string())),
+                    change("EffectiveFrom", None, Some(fee.EffectiveFrom.clone())),
+                    change("MonthlyFee", None, Some(fee.MonthlyFee.to_string())),
+                    change("WeeklyFee", None, Some(fee.WeeklyFee.to_string())),
+                ]).await;
+                category::select_fees(acquire_connection(pool.clone()).await?, fee.CategoryId, format).await
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/category/fee/select", tag = "category",
+    request_body = v1::TokenCategoryId,
+    responses(
+        (status = 200, description = "Every fee change of the category, oldest first", body = Vec<v1::CategoryFee>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_category_fees(State(pool): State<Arc<MySqlPool>>, format: Format, payload: Payload<TokenCategoryId>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(_) => category::select_fees(acquire_connection(pool.clone()).await?, payload.CategoryId, format).await,
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+/// The category name is always kept so that the entry says which category it was about.
+fn category_changes(before: Option<&Categories>, after: Option<&Categories>) -> Vec<AuditChange> {
+    let name = |category: Option<&Categories>| category.map(|category| category.Category.clone());
+    let mut changes = vec![change("Category", name(before), name(after))];
+    let fees = |category: Option<&Categories>| category.map(|category| (category.MonthlyFee, category.WeeklyFee));
+    let (before, after) = (fees(before), fees(after));
+    if before.map(|fee| fee.0) != after.map(|fee| fee.0) {
+        changes.push(change("MonthlyFee", before.map(|fee| fee.0.to_string()), after.map(|fee| fee.0.to_string())));
+    }
+    if before.map(|fee| fee.1) != after.map(|fee| fee.1) {
+        changes.push(change("WeeklyFee", before.map(|fee| fee.1.to_string()), after.map(|fee| fee.1.to_string())));
+    }
+    changes
+}
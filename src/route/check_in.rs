@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Extension;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::config::Config;
+use crate::route::acquire_connection;
+use crate::schema::audit::{change, Audit, AuditAction};
+use crate::schema::check_in::{self, validate_reason, TokenCheckIn, TokenCheckInOverride};
+use crate::schema::eligibility::{self, TokenEligibility};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/checkin", tag = "details",
+    request_body = v1::TokenCheckIn,
+    responses(
+        (status = 200, description = "Presence recorded, with the allergies, general note and allowance of the household", body = v1::CheckIn),
+        (status = 400, description = "Neither or both of an id and a card code"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such beneficiary, or an unknown or revoked card"),
+        (status = 409, description = "Already checked in today, or a limit is reached in block mode"),
+    )
+)]
+pub(crate) async fn check_in(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, format: Format, request_id: RequestId, payload: Payload<TokenCheckIn>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" | "TS" | "User" => {
+                let audit = Audit::new(&user, request_id);
+                let visit = check_in::check_in(acquire_connection(pool.clone()).await?, &payload, None, &user, &config.eligibility).await?;
+                audit.record(&pool, AuditAction::CheckIn, Some(visit.Beneficiary.Id), vec![change("Date", None, Some(visit.Date.clone()))]).await;
+                encode(visit, format)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/checkin/override", tag = "details",
+    request_body = v1::TokenCheckInOverride,
+    responses(
+        (status = 200, description = "Presence recorded whatever the limits", body = v1::CheckIn),
+        (status = 400, description = "No reason, or neither or both of an id and a card code"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+        (status = 404, description = "No such beneficiary, or an unknown or revoked card"),
+        (status = 409, description = "Already checked in today"),
+    )
+)]
+pub(crate) async fn override_check_in(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, format: Format, request_id: RequestId, payload: Payload<TokenCheckInOverride>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let reason = validate_reason(&payload.Reason)?;
+                let request = TokenCheckIn { Token: payload.Token.clone(), BeneficiaryId: payload.BeneficiaryId, CardCode: payload.CardCode.clone() };
+                let audit = Audit::new(&user, request_id);
+                let visit = check_in::check_in(acquire_connection(pool.clone()).await?, &request, Some(reason), &user, &config.eligibility).await?;
+                audit.record(&pool, AuditAction::OverrideCheckIn, Some(visit.Beneficiary.Id), vec![
+                    change("Date", None, Some(visit.Date.clone())),
+                    change("Reason", None, Some(reason.to_string())),
+                ]).await;
+                encode(visit, format)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/eligibility/select", tag = "details",
+    request_body = v1::TokenEligibility,
+    responses(
+        (status = 200, description = "Visits and limits of the week and month holding the date", body = v1::Eligibility),
+        (status = 400, description = "Invalid date"),
+        (status = 401, description = "Invalid token"),
+        (status = 404, description = "No such beneficiary"),
+    )
+)]
+pub(crate) async fn select_eligibility(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, format: Format, payload: Payload<TokenEligibility>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(_) => eligibility::select_eligibility(acquire_connection(pool.clone()).await?, &payload, &config.eligibility, format).await,
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
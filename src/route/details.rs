@@ -1,19 +1,36 @@
 use std::sync::Arc;
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::http::StatusCode;
-use axum::Json;
-use sqlx::MySqlPool;
+use sqlx::{MySqlConnection, MySqlPool};
+use harmony_protocol::v1;
 use crate::route::acquire_connection;
-use crate::schema::details::{TokenAllergy, TokenNote, TokenPresence};
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::allergen;
+use crate::schema::audit::{change, Audit, AuditAction};
+use crate::schema::details::{Note, TokenAllergy, TokenNote, TokenNoteText, TokenPresence};
+use crate::schema::user::{Token, TokenBeneId, UserRole};
 use crate::schema::validate_token;
+use crate::telemetry::RequestId;
 
-pub(crate) async fn insert_allergy(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenAllergy>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(post, path = "/allergy", tag = "details",
+    request_body = v1::TokenAllergy,
+    responses(
+        (status = 200, description = "Allergy added"),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn insert_allergy(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenAllergy>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
             let conn = acquire_connection(pool.clone()).await?;
             match payload.insert_allergy(conn).await {
-                Ok(_) => Ok(StatusCode::OK),
+                Ok(_) => {
+                    allergen::refresh(acquire_connection(pool.clone()).await?, payload.Allergy.BeneficiaryId, &user).await;
+                    audit.record(&pool, AuditAction::InsertAllergy, Some(payload.Allergy.BeneficiaryId), vec![change("Allergy", None, Some(payload.Allergy.Allergy.clone()))]).await;
+                    Ok(StatusCode::OK)
+                },
                 Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
             }
         },
@@ -21,26 +38,49 @@ pub(crate) async fn insert_allergy(State(pool) : State<Arc<MySqlPool>>, payload:
     }
 }
 
-pub(crate) async fn delete_allergy(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenAllergy>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(delete, path = "/allergy", tag = "details",
+    request_body = v1::TokenAllergy,
+    responses(
+        (status = 200, description = "Allergy removed"),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn delete_allergy(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenAllergy>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) =>{
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
             let conn = acquire_connection(pool.clone()).await?;
-            match payload.delete_allergy(conn).await {
-                Ok(_) => Ok(StatusCode::OK),
+            match payload.delete_allergy(conn, &user).await {
+                Ok(_) => {
+                    allergen::refresh(acquire_connection(pool.clone()).await?, payload.Allergy.BeneficiaryId, &user).await;
+                    audit.record(&pool, AuditAction::DeleteAllergy, Some(payload.Allergy.BeneficiaryId), vec![change("Allergy", Some(payload.Allergy.Allergy.clone()), None)]).await;
+                    Ok(StatusCode::OK)
+                },
                 Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
             }
         },
         Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
     }
 }
-pub(crate) async fn insert_presence(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenPresence>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(post, path = "/presence", tag = "details",
+    request_body = v1::TokenPresence,
+    responses(
+        (status = 200, description = "Presence recorded"),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn insert_presence(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenPresence>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) =>{
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
             let conn = acquire_connection(pool.clone()).await?;
-            match payload.insert_presence(conn).await {
-                Ok(_) => Ok(StatusCode::OK),
+            match payload.insert_presence(conn, &user).await {
+                Ok(_) => {
+                    audit.record(&pool, AuditAction::InsertPresence, Some(payload.Presence.BeneficiaryId), vec![change("Date", None, Some(payload.Presence.Date.clone()))]).await;
+                    Ok(StatusCode::OK)
+                },
                 Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
             }
         },
@@ -49,13 +89,24 @@ pub(crate) async fn insert_presence(State(pool) : State<Arc<MySqlPool>>, payload
 }
 
 
-pub(crate) async fn delete_presence(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenPresence>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(delete, path = "/presence", tag = "details",
+    request_body = v1::TokenPresence,
+    responses(
+        (status = 200, description = "Presence removed"),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn delete_presence(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenPresence>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
+        Ok(user) => {
+                let audit = Audit::new(&user, request_id);
                 let conn = acquire_connection(pool.clone()).await?;
-                match payload.delete_presence(conn).await {
-                    Ok(_) => Ok(StatusCode::OK),
+                match payload.delete_presence(conn, &user).await {
+                    Ok(_) => {
+                        audit.record(&pool, AuditAction::DeletePresence, Some(payload.Presence.BeneficiaryId), vec![change("Date", Some(payload.Presence.Date.clone()), None)]).await;
+                        Ok(StatusCode::OK)
+                    },
                     Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
                 }
             },
@@ -63,13 +114,30 @@ pub(crate) async fn delete_presence(State(pool) : State<Arc<MySqlPool>>, payload
     }
 }
 
-pub(crate) async fn create_note(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenNote>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(post, path = "/note", tag = "details",
+    request_body = v1::TokenNote,
+    responses(
+        (status = 200, description = "Note created"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may not write notes of this type"),
+    )
+)]
+pub(crate) async fn create_note(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenNote>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
             let conn = acquire_connection(pool.clone()).await?;
-            match payload.create_note(conn).await {
-                Ok(_) => Ok(StatusCode::OK),
+            match payload.create_note(conn, &user).await {
+                Ok(false) => Err((StatusCode::FORBIDDEN, "This role may not write notes of this type".to_string())),
+                Ok(true) => {
+                    audit.record(&pool, AuditAction::CreateNote, Some(payload.Content.BeneficiaryId), vec![
+                        change("Date", None, Some(payload.Content.Date.clone())),
+                        change("Type", None, Some(payload.Content.Type.to_string())),
+                        change("Note", None, Some(payload.Content.Note.clone())),
+                    ]).await;
+                    Ok(StatusCode::OK)
+                },
                 Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not create note".to_string()))
             }
         },
@@ -77,13 +145,107 @@ pub(crate) async fn create_note(State(pool) : State<Arc<MySqlPool>>, payload: Js
     }
 }
 
-pub(crate) async fn update_note(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenNote>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(put, path = "/note", tag = "details",
+    request_body = v1::TokenNote,
+    responses(
+        (status = 200, description = "The note of the beneficiary written at Date updated, prefer PUT /note/{id}"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may read but not write notes of this type"),
+        (status = 404, description = "No such note for the caller role"),
+        (status = 409, description = "Several notes were written at Date, use PUT /note/{id}"),
+    )
+)]
+pub(crate) async fn update_note(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenNote>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
             let conn = acquire_connection(pool.clone()).await?;
-            match payload.update_note(conn).await {
-                Ok(_) => Ok(StatusCode::OK),
+            let note = payload.update_note(conn, &user).await?;
+            audit.record(&pool, AuditAction::UpdateNote, Some(note.BeneficiaryId), vec![
+                change("Id", Some(note.Id.to_string()), Some(note.Id.to_string())),
+                change("Note", Some(note.Note), Some(payload.Content.Note.clone())),
+            ]).await;
+            Ok(StatusCode::OK)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(delete, path = "/note", tag = "details",
+    request_body = v1::TokenNote,
+    responses(
+        (status = 200, description = "The note of the beneficiary written at Date moved to the trash, prefer DELETE /note/{id}"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may read but not write notes of this type"),
+        (status = 404, description = "No such note for the caller role"),
+        (status = 409, description = "Several notes were written at Date, use DELETE /note/{id}"),
+    )
+)]
+pub(crate) async fn delete_note(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<TokenNote>) -> Result<StatusCode, (StatusCode, String)>{
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let conn = acquire_connection(pool.clone()).await?;
+            let note = payload.delete_note(conn, &user).await?;
+            audit.record(&pool, AuditAction::DeleteNote, Some(note.BeneficiaryId), vec![
+                change("Id", Some(note.Id.to_string()), None),
+                change("Note", Some(note.Note), None),
+            ]).await;
+            Ok(StatusCode::OK)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/note/select", tag = "details",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "Notes of the beneficiary the caller role may see, with their ids", body = Vec<v1::Note>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_notes(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)>{
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let conn = acquire_connection(pool.clone()).await?;
+            let notes = Note::select(conn, user, payload.Id, format).await?;
+            audit.record(&pool, AuditAction::ReadNotes, Some(payload.Id), Vec::new()).await;
+            Ok(notes)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(put, path = "/note/{id}", tag = "details",
+    params(("id" = i32, Path, description = "Note id")),
+    request_body = v1::TokenNoteText,
+    responses(
+        (status = 200, description = "Note updated"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may read but not write notes of this type"),
+        (status = 404, description = "No such note for the caller role"),
+    )
+)]
+pub(crate) async fn edit_note(State(pool) : State<Arc<MySqlPool>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<TokenNoteText>) -> Result<StatusCode, (StatusCode, String)>{
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let mut conn = acquire_connection(pool.clone()).await?;
+            let note = find_note(conn.as_mut(), id, &user).await?;
+            match Note::edit(conn.as_mut(), id, &payload.Note, &user).await {
+                Ok(false) => Err((StatusCode::FORBIDDEN, "This role may not write notes of this type".to_string())),
+                Ok(true) => {
+                    audit.record(&pool, AuditAction::UpdateNote, Some(note.BeneficiaryId), vec![
+                        change("Id", Some(id.to_string()), Some(id.to_string())),
+                        change("Note", Some(note.Note), Some(payload.Note.clone())),
+                    ]).await;
+                    Ok(StatusCode::OK)
+                },
                 Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not update note".to_string()))
             }
         },
@@ -91,16 +253,43 @@ pub(crate) async fn update_note(State(pool) : State<Arc<MySqlPool>>, payload: Js
     }
 }
 
-pub(crate) async fn delete_note(State(pool) : State<Arc<MySqlPool>>, payload: Json<TokenNote>) -> Result<StatusCode, (StatusCode, String)>{
+#[utoipa::path(delete, path = "/note/{id}", tag = "details",
+    params(("id" = i32, Path, description = "Note id")),
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Note moved to the trash"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may read but not write notes of this type"),
+        (status = 404, description = "No such note for the caller role"),
+    )
+)]
+pub(crate) async fn delete_note_by_id(State(pool) : State<Arc<MySqlPool>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<Token>) -> Result<StatusCode, (StatusCode, String)>{
     let conn = acquire_connection(pool.clone()).await?;
     match validate_token(conn, &payload.Token).await {
-        Ok(_) => {
-            let conn = acquire_connection(pool.clone()).await?;
-            match payload.delete_note(conn).await {
-                Ok(_) => Ok(StatusCode::OK),
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let mut conn = acquire_connection(pool.clone()).await?;
+            let note = find_note(conn.as_mut(), id, &user).await?;
+            match Note::delete(conn.as_mut(), id, &user).await {
+                Ok(false) => Err((StatusCode::FORBIDDEN, "This role may not write notes of this type".to_string())),
+                Ok(true) => {
+                    audit.record(&pool, AuditAction::DeleteNote, Some(note.BeneficiaryId), vec![
+                        change("Id", Some(id.to_string()), None),
+                        change("Note", Some(note.Note), None),
+                    ]).await;
+                    Ok(StatusCode::OK)
+                },
                 Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not delete note".to_string()))
             }
         },
         Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
     }
-}
\ No newline at end of file
+}
+
+pub(crate) async fn find_note(conn: &mut MySqlConnection, id: i32, user: &UserRole) -> Result<Note, (StatusCode, String)>{
+    match Note::find(conn, id, user).await {
+        Ok(Some(note)) => Ok(note),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "No such note".to_string())),
+        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get note".to_string())),
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Extension;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::config::Config;
+use crate::route::acquire_connection;
+use crate::schema::audit::{Audit, AuditAction};
+use crate::schema::distribution::{self, can_correct, distribution_changes, TokenDistribution, TokenDistributionTotals};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::user::TokenBeneId;
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/distribution", tag = "details",
+    request_body = v1::TokenDistribution,
+    responses(
+        (status = 200, description = "The distribution as recorded, with its fee", body = v1::Distribution),
+        (status = 400, description = "Invalid date, amount, item or volunteer"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such beneficiary or presence"),
+        (status = 409, description = "The presence already has a distribution"),
+    )
+)]
+pub(crate) async fn record_distribution(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, format: Format, request_id: RequestId, payload: Payload<TokenDistribution>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" | "TS" | "User" => {
+                let audit = Audit::new(&user, request_id);
+                let recorded = distribution::record(acquire_connection(pool.clone()).await?, &payload.Distribution, &user, &config.eligibility, config.billing.mode).await?;
+                audit.record(&pool, AuditAction::RecordDistribution, Some(recorded.BeneficiaryId), distribution_changes(None, &recorded)).await;
+                encode(recorded, format)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(put, path = "/distribution", tag = "details",
+    request_body = v1::TokenDistribution,
+    responses(
+        (status = 200, description = "The correction, which supersedes the distribution sent", body = v1::Distribution),
+        (status = 400, description = "Invalid amount, item or volunteer"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller may not correct distributions"),
+        (status = 404, description = "No such distribution"),
+        (status = 409, description = "The distribution was corrected already"),
+    )
+)]
+pub(crate) async fn correct_distribution(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenDistribution>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) if can_correct(&user) => {
+            let audit = Audit::new(&user, request_id);
+            let (before, after) = distribution::correct(acquire_connection(pool.clone()).await?, &payload.Distribution, &user).await?;
+            audit.record(&pool, AuditAction::CorrectDistribution, Some(after.BeneficiaryId), distribution_changes(Some(&before), &after)).await;
+            encode(after, format)
+        },
+        Ok(_) => Err((StatusCode::FORBIDDEN, "Invalid role".to_string())),
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/distribution/select", tag = "details",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "Current distributions of the beneficiary, latest first", body = Vec<v1::Distribution>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_distributions(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let distributions = distribution::select_distributions(acquire_connection(pool.clone()).await?, payload.Id, format).await?;
+            audit.record(&pool, AuditAction::ReadDistributions, Some(payload.Id), Vec::new()).await;
+            Ok(distributions)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/distribution/totals/select", tag = "details",
+    request_body = v1::TokenDistributionTotals,
+    responses(
+        (status = 200, description = "Distributions, amount and fees per beneficiary and period", body = Vec<v1::DistributionTotal>),
+        (status = 400, description = "Invalid dates or unknown period"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Totals of every beneficiary are for Admins"),
+    )
+)]
+pub(crate) async fn select_distribution_totals(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, format: Format, payload: Payload<TokenDistributionTotals>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match (user.Role.as_str(), payload.BeneficiaryId) {
+            ("Dev" | "Admin", _) | (_, Some(_)) => distribution::select_totals(acquire_connection(pool.clone()).await?, &payload, &config.eligibility, format).await,
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
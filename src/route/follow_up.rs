@@ -0,0 +1,156 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::route::acquire_connection;
+use crate::route::details::find_note;
+use crate::schema::audit::{change, Audit, AuditAction};
+use crate::schema::follow_up::{self, parse_due_date, TokenFollowUp, TokenFollowUpQuery};
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::user::Token;
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/followup/select", tag = "details",
+    request_body = v1::TokenFollowUpQuery,
+    responses(
+        (status = 200, description = "Open follow-ups of the assignee, earliest due first", body = Vec<v1::FollowUp>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Only an Admin may list the follow-ups of another user"),
+    )
+)]
+pub(crate) async fn select_follow_ups(State(pool): State<Arc<MySqlPool>>, format: Format, payload: Payload<TokenFollowUpQuery>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let assignee = payload.AssigneeId.unwrap_or(user.Id);
+            if assignee != user.Id && !matches!(user.Role.as_str(), "Dev" | "Admin") {
+                return Err((StatusCode::FORBIDDEN, "Invalid role".to_string()));
+            }
+            follow_up::select_open(acquire_connection(pool.clone()).await?, &user, assignee, format).await
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(put, path = "/note/{id}/followup", tag = "details",
+    params(("id" = i32, Path, description = "Note id")),
+    request_body = v1::TokenFollowUp,
+    responses(
+        (status = 200, description = "Follow-up set, open again if it was completed"),
+        (status = 400, description = "Bad due date, or an assignee who cannot read notes of this type"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may read but not write notes of this type"),
+        (status = 404, description = "No such note for the caller role"),
+    )
+)]
+pub(crate) async fn set_follow_up(State(pool): State<Arc<MySqlPool>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<TokenFollowUp>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let mut conn = acquire_connection(pool.clone()).await?;
+            let note = find_note(conn.as_mut(), id, &user).await?;
+            let due = parse_due_date(&payload.DueDate)?;
+            let assignee = payload.AssigneeId.unwrap_or(user.Id);
+            match follow_up::assignee_can_read(conn.as_mut(), assignee, note.Type).await {
+                Ok(true) => {},
+                Ok(false) => return Err((StatusCode::BAD_REQUEST, "The assignee cannot read notes of this type".to_string())),
+                Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not set follow-up".to_string())),
+            }
+            let before = follow_up::find(conn.as_mut(), id).await.ok().flatten();
+            match follow_up::set(conn.as_mut(), id, due, assignee, &user).await {
+                Ok(false) => Err((StatusCode::FORBIDDEN, "This role may not write notes of this type".to_string())),
+                Ok(true) => {
+                    audit.record(&pool, AuditAction::SetFollowUp, Some(note.BeneficiaryId), vec![
+                        change("Id", Some(id.to_string()), Some(id.to_string())),
+                        change("DueDate", before.as_ref().map(|before| before.DueDate.clone()), Some(due.to_string())),
+                        change("AssigneeId", before.as_ref().map(|before| before.AssigneeId.to_string()), Some(assignee.to_string())),
+                    ]).await;
+                    Ok(StatusCode::OK)
+                },
+                Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not set follow-up".to_string()))
+            }
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(delete, path = "/note/{id}/followup", tag = "details",
+    params(("id" = i32, Path, description = "Note id")),
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Follow-up removed, the note is kept"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may read but not write notes of this type"),
+        (status = 404, description = "No such note for the caller role, or it has no follow-up"),
+    )
+)]
+pub(crate) async fn clear_follow_up(State(pool): State<Arc<MySqlPool>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<Token>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let mut conn = acquire_connection(pool.clone()).await?;
+            let note = find_note(conn.as_mut(), id, &user).await?;
+            let before = match follow_up::find(conn.as_mut(), id).await {
+                Ok(Some(before)) => before,
+                Ok(None) => return Err((StatusCode::NOT_FOUND, "This note has no follow-up".to_string())),
+                Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not remove follow-up".to_string())),
+            };
+            match follow_up::clear(conn.as_mut(), id, &user).await {
+                Ok(false) => Err((StatusCode::FORBIDDEN, "This role may not write notes of this type".to_string())),
+                Ok(true) => {
+                    audit.record(&pool, AuditAction::ClearFollowUp, Some(note.BeneficiaryId), vec![
+                        change("Id", Some(id.to_string()), Some(id.to_string())),
+                        change("DueDate", Some(before.DueDate), None),
+                        change("AssigneeId", Some(before.AssigneeId.to_string()), None),
+                    ]).await;
+                    Ok(StatusCode::OK)
+                },
+                Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not remove follow-up".to_string()))
+            }
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/note/{id}/followup/complete", tag = "details",
+    params(("id" = i32, Path, description = "Note id")),
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Follow-up completed by the caller"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller is neither the assignee nor may write notes of this type"),
+        (status = 404, description = "No such note for the caller role, or it has no follow-up"),
+        (status = 409, description = "The follow-up is already completed"),
+    )
+)]
+pub(crate) async fn complete_follow_up(State(pool): State<Arc<MySqlPool>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<Token>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let mut conn = acquire_connection(pool.clone()).await?;
+            let note = find_note(conn.as_mut(), id, &user).await?;
+            match follow_up::find(conn.as_mut(), id).await {
+                Ok(Some(follow_up)) if follow_up.CompletedAt.is_some() => return Err((StatusCode::CONFLICT, "This follow-up is already completed".to_string())),
+                Ok(Some(_)) => {},
+                Ok(None) => return Err((StatusCode::NOT_FOUND, "This note has no follow-up".to_string())),
+                Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not complete follow-up".to_string())),
+            }
+            match follow_up::complete(conn.as_mut(), id, &user).await {
+                Ok(false) => Err((StatusCode::FORBIDDEN, "Only the assignee or a role that may write this note can complete it".to_string())),
+                Ok(true) => {
+                    audit.record(&pool, AuditAction::CompleteFollowUp, Some(note.BeneficiaryId), vec![
+                        change("Id", Some(id.to_string()), Some(id.to_string())),
+                    ]).await;
+                    Ok(StatusCode::OK)
+                },
+                Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not complete follow-up".to_string()))
+            }
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -0,0 +1,117 @@
+#![allow(non_snake_case)]
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use serde::Serialize;
+use sqlx::MySqlPool;
+use tracing::warn;
+use utoipa::ToSchema;
+use crate::config::Config;
+use crate::schema::{cipher, decrypt, encrypt};
+use crate::MIGRATOR;
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Health {
+    /// `ok` when every check passed, `fail` otherwise.
+    pub(crate) Status: &'static str,
+    pub(crate) Checks: Vec<Check>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Check {
+    pub(crate) Name: &'static str,
+    pub(crate) Status: &'static str,
+    pub(crate) LatencyMs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) Detail: Option<String>,
+}
+
+impl Health {
+    fn from_checks(checks: Vec<Check>) -> Health {
+        let ok = checks.iter().all(|check| check.Status == "ok");
+        Health { Status: if ok { "ok" } else { "fail" }, Checks: checks }
+    }
+}
+
+#[utoipa::path(get, path = "/health/live", tag = "health",
+    responses(
+        (status = 200, description = "The process is up and serving requests", body = Health),
+    )
+)]
+pub(crate) async fn live() -> Json<Health> {
+    Json(Health::from_checks(Vec::new()))
+}
+
+#[utoipa::path(get, path = "/health/ready", tag = "health",
+    responses(
+        (status = 200, description = "Every dependency is usable", body = Health),
+        (status = 503, description = "At least one check failed", body = Health),
+    )
+)]
+pub(crate) async fn ready(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>) -> (StatusCode, Json<Health>) {
+    let timeout = config.health.timeout;
+    let (database, migrations, encryption) = tokio::join!(
+        run("database", timeout, check_database(&pool)),
+        run("migrations", timeout, check_migrations(&pool)),
+        run("encryption_key", timeout, check_encryption()),
+    );
+    let checks = vec![database, migrations, encryption];
+
+    let health = Health::from_checks(checks);
+    if health.Status == "ok" {
+        (StatusCode::OK, Json(health))
+    } else {
+        warn!(checks = ?health.Checks.iter().filter(|check| check.Status != "ok").map(|check| check.Name).collect::<Vec<_>>(), "Not ready");
+        (StatusCode::SERVICE_UNAVAILABLE, Json(health))
+    }
+}
+
+async fn run(name: &'static str, timeout: Duration, check: impl Future<Output = Result<(), String>>) -> Check {
+    let start = Instant::now();
+    let result = match tokio::time::timeout(timeout, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {} ms", timeout.as_millis())),
+    };
+
+    Check {
+        Name: name,
+        Status: if result.is_ok() { "ok" } else { "fail" },
+        LatencyMs: start.elapsed().as_millis() as u64,
+        Detail: result.err(),
+    }
+}
+
+async fn check_database(pool: &MySqlPool) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_migrations(pool: &MySqlPool) -> Result<(), String> {
+    let expected = MIGRATOR.iter().map(|migration| migration.version).max().unwrap_or_default();
+    let applied: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success = TRUE")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    match applied {
+        Some(applied) if applied == expected => Ok(()),
+        Some(applied) => Err(format!("database is at version {applied}, expected {expected}")),
+        None => Err(format!("no migration applied, expected {expected}")),
+    }
+}
+
+async fn check_encryption() -> Result<(), String> {
+    cipher().map_err(|e| e.to_string())?;
+    let probe = b"harmony readiness probe";
+    match decrypt(&encrypt(probe)) {
+        Ok(plaintext) if plaintext == probe => Ok(()),
+        Ok(_) => Err("decrypted content differs".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
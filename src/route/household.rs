@@ -0,0 +1,106 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::route::acquire_connection;
+use crate::schema::audit::{Audit, AuditAction};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::household::{self, member_changes, TokenMember};
+use crate::schema::user::{Token, TokenBeneId};
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/member/select", tag = "details",
+    request_body = v1::TokenBeneId,
+    responses(
+        (status = 200, description = "Members of the household, eldest first, as the caller role sees them", body = Vec<v1::HouseholdMember>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_members(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenBeneId>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let members = household::select_members(acquire_connection(pool.clone()).await?, payload.Id, &user, format).await?;
+            audit.record(&pool, AuditAction::ReadMembers, Some(payload.Id), Vec::new()).await;
+            Ok(members)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/member", tag = "details",
+    request_body = v1::TokenMember,
+    responses(
+        (status = 200, description = "The member as stored, with its id", body = v1::HouseholdMember),
+        (status = 400, description = "No first name, an unknown relationship or severity, or a bad birth date"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may not write members"),
+        (status = 404, description = "No such active allergen"),
+    )
+)]
+pub(crate) async fn create_member(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenMember>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) if household::can_write(&user) => {
+            let audit = Audit::new(&user, request_id);
+            let member = household::save(acquire_connection(pool.clone()).await?, &payload.Member, true, &user).await?;
+            audit.record(&pool, AuditAction::CreateMember, Some(member.BeneficiaryId), member_changes(None, Some(&member))).await;
+            encode(member, format)
+        },
+        Ok(_) => Err((StatusCode::FORBIDDEN, "Invalid role".to_string())),
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(put, path = "/member", tag = "details",
+    request_body = v1::TokenMember,
+    responses(
+        (status = 200, description = "The member as stored", body = v1::HouseholdMember),
+        (status = 400, description = "No first name, an unknown relationship or severity, or a bad birth date"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may not write members"),
+        (status = 404, description = "No such member, or no such active allergen"),
+    )
+)]
+pub(crate) async fn update_member(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenMember>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) if household::can_write(&user) => {
+            let audit = Audit::new(&user, request_id);
+            let before = household::find(acquire_connection(pool.clone()).await?.as_mut(), payload.Member.Id).await.ok().flatten();
+            let member = household::save(acquire_connection(pool.clone()).await?, &payload.Member, false, &user).await?;
+            audit.record(&pool, AuditAction::UpdateMember, Some(member.BeneficiaryId), member_changes(before.as_ref(), Some(&member))).await;
+            encode(member, format)
+        },
+        Ok(_) => Err((StatusCode::FORBIDDEN, "Invalid role".to_string())),
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(delete, path = "/member/{id}", tag = "details",
+    params(("id" = i32, Path, description = "Member id")),
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Member and its allergies moved to the trash"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "The caller role may not write members"),
+        (status = 404, description = "No such member"),
+    )
+)]
+pub(crate) async fn delete_member(State(pool): State<Arc<MySqlPool>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<Token>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) if household::can_write(&user) => {
+            let audit = Audit::new(&user, request_id);
+            let member = household::delete(acquire_connection(pool.clone()).await?, id, &user).await?;
+            audit.record(&pool, AuditAction::DeleteMember, Some(member.BeneficiaryId), member_changes(Some(&member), None)).await;
+            Ok(StatusCode::OK)
+        },
+        Ok(_) => Err((StatusCode::FORBIDDEN, "Invalid role".to_string())),
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -0,0 +1,54 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use sqlx::MySqlPool;
+use tracing::warn;
+use crate::config::Config;
+use crate::metrics::METRICS;
+use crate::route::acquire_connection;
+use crate::schema::validate_token;
+
+#[utoipa::path(get, path = "/metrics", tag = "health",
+    responses(
+        (status = 200, description = "Prometheus text exposition", content_type = "text/plain"),
+        (status = 403, description = "Caller is neither allowlisted nor holding an Admin or Dev session token as bearer"),
+    )
+)]
+pub(crate) async fn metrics(
+    State(pool): State<Arc<MySqlPool>>,
+    Extension(config): Extension<Arc<Config>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let allowlisted = connect_info.is_some_and(|ConnectInfo(addr)| config.metrics.allowlist.contains(&addr.ip()));
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let token_ok = match bearer {
+        Some(token) if !allowlisted => {
+            let conn = acquire_connection(pool.clone()).await?;
+            validate_token(conn, &token.to_string()).await.is_ok_and(|user| matches!(user.Role.as_str(), "Admin" | "Dev"))
+        },
+        _ => false,
+    };
+
+    if !allowlisted && !token_ok {
+        warn!("Metrics scrape refused");
+        return Err((StatusCode::FORBIDDEN, "Not allowed to read metrics".to_string()));
+    }
+
+    if let Ok(mut conn) = acquire_connection(pool.clone()).await {
+        match sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM UserSession WHERE Expires > NOW()")
+            .fetch_one(conn.as_mut())
+            .await {
+                Ok(count) => METRICS.active_sessions.set(count),
+                Err(e) => warn!(error = %e, "Could not count active sessions"),
+        }
+    }
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], METRICS.render(&pool)))
+}
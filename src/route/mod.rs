@@ -3,30 +3,91 @@ mod user;
 mod stats;
 mod details;
 mod category;
+mod metrics;
+mod health;
+mod audit;
+mod trash;
+mod note_type;
+mod follow_up;
+mod allergen;
+mod household;
+mod check_in;
+mod distribution;
+mod payment;
+mod receipt;
+mod card;
+pub(crate) mod openapi;
+pub(crate) mod version;
+pub(crate) mod rate_limit;
 
 use std::sync::Arc;
 use axum::extract::State;
 use axum::http::StatusCode;
-use axum::Router;
+use std::time::Instant;
+use axum::{middleware, Extension, Router};
 use axum::routing::{delete, get, post, put};
 use sqlx::{MySql, MySqlPool, Pool};
 use sqlx::pool::PoolConnection;
+use harmony_protocol::Version;
 
 
 use crate::route::user::{create_user, delete_user, get_users, login, update_user};
-use crate::route::stats::stats;
-use crate::route::beneficiary::{beneficiaries, beneficiary, create_beneficiary, search_beneficiaries, update_beneficiary};
-use crate::route::category::{create_category, delete_category, select_categories, update_category};
-use crate::route::details::{create_note, delete_allergy, delete_note, delete_presence, insert_allergy, insert_presence, update_note};
+use crate::route::stats::{compute_stats, stats};
+use crate::route::beneficiary::{beneficiaries, beneficiary, beneficiary_as_of, beneficiary_history, create_beneficiary, search_beneficiaries, update_beneficiary};
+use crate::route::category::{create_category, delete_category, delete_category_by_id, reassign_category, select_categories, select_category_fees, set_category_fee, update_category};
+use crate::route::details::{create_note, delete_allergy, delete_note, delete_note_by_id, delete_presence, edit_note, insert_allergy, insert_presence, select_notes, update_note};
+use crate::route::version::negotiate_version;
+use crate::route::rate_limit::{rate_limit, RateLimiter};
+use crate::route::openapi::openapi;
+use crate::route::metrics::metrics;
+use crate::route::health::{live, ready};
+use crate::route::audit::audit_log;
+use crate::route::trash::{restore_trash, select_trash};
+use crate::route::note_type::{create_note_type, select_note_types, update_note_type};
+use crate::route::allergen::{create_allergen, link_allergen, select_allergens, select_allergies, unlink_allergen, update_allergen};
+use crate::route::household::{create_member, delete_member, select_members, update_member};
+use crate::route::check_in::{check_in, override_check_in, select_eligibility};
+use crate::route::distribution::{correct_distribution, record_distribution, select_distribution_totals, select_distributions};
+use crate::route::payment::{record_payment, select_balances, select_statement, void_payment};
+use crate::route::receipt::{print_receipt, print_visit_slip};
+use crate::route::card::{look_up_card, print_card, reissue_card, revoke_card, select_card};
+use crate::route::follow_up::{clear_follow_up, complete_follow_up, select_follow_ups, set_follow_up};
+use crate::config::Config;
+use crate::telemetry;
 
-pub fn get_routes(pool : Arc<Pool<MySql>>) -> Router{
-    Router::new()
+pub fn get_routes(pool : Arc<Pool<MySql>>, config: Arc<Config>) -> Router{
+    let api = Router::new()
         .route("/", get(test_connection)).with_state(pool.clone())
+        .route("/openapi.json", get(openapi))
+        .route("/metrics", get(metrics)).with_state(pool.clone())
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready)).with_state(pool.clone())
         .merge(user_routes(pool.clone()))
         .merge(beneficiary_routes(pool.clone()))
         .merge(details_routes(pool.clone()))
+        .merge(note_type_routes(pool.clone()))
+        .merge(follow_up_routes(pool.clone()))
+        .merge(allergen_routes(pool.clone()))
+        .merge(household_routes(pool.clone()))
+        .merge(card_routes(pool.clone()))
+        .merge(check_in_routes(pool.clone()))
+        .merge(distribution_routes(pool.clone()))
+        .merge(payment_routes(pool.clone()))
+        .merge(receipt_routes(pool.clone()))
         .merge(category_routes(pool.clone()))
         .merge(stats_routes(pool.clone()))
+        .merge(audit_routes(pool.clone()))
+        .merge(trash_routes(pool.clone()));
+
+    let router = Version::SUPPORTED
+        .iter()
+        .fold(api.clone(), |router, version| router.nest(version.path_prefix(), api.clone()))
+        .layer(middleware::from_fn_with_state(Arc::new(RateLimiter::new(config.clone(), pool.clone())), rate_limit))
+        .layer(middleware::from_fn(negotiate_version))
+        .layer(middleware::from_fn(crate::metrics::track))
+        .layer(Extension(config));
+
+    telemetry::instrument(router)
 }
 
 fn user_routes(pool : Arc<Pool<MySql>>) -> Router{
@@ -43,6 +104,8 @@ fn beneficiary_routes(pool : Arc<Pool<MySql>>) -> Router{
         .route("/beneficiary/select/:id", post(beneficiary)).with_state(pool.clone())
         .route("/beneficiary/select", post(beneficiaries)).with_state(pool.clone())
         .route("/beneficiary/search", post(search_beneficiaries)).with_state(pool.clone())
+        .route("/beneficiary/history/select", post(beneficiary_history)).with_state(pool.clone())
+        .route("/beneficiary/asof/select", post(beneficiary_as_of)).with_state(pool.clone())
         .route("/beneficiary", post(create_beneficiary)).with_state(pool.clone())
         .route("/beneficiary", put(update_beneficiary)).with_state(pool.clone())
 }
@@ -51,11 +114,85 @@ fn details_routes(pool : Arc<Pool<MySql>>) -> Router{
     Router::new()
         .route("/allergy", post(insert_allergy)).with_state(pool.clone())
         .route("/allergy", delete(delete_allergy)).with_state(pool.clone())
+        .route("/allergy/select", post(select_allergies)).with_state(pool.clone())
         .route("/presence", post(insert_presence)).with_state(pool.clone())
         .route("/presence", delete(delete_presence)).with_state(pool.clone())
         .route("/note", post(create_note)).with_state(pool.clone())
         .route("/note", put(update_note)).with_state(pool.clone())
         .route("/note", delete(delete_note)).with_state(pool.clone())
+        .route("/note/select", post(select_notes)).with_state(pool.clone())
+        .route("/note/:id", put(edit_note)).with_state(pool.clone())
+        .route("/note/:id", delete(delete_note_by_id)).with_state(pool.clone())
+}
+
+fn note_type_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/notetype/select", post(select_note_types)).with_state(pool.clone())
+        .route("/notetype", post(create_note_type)).with_state(pool.clone())
+        .route("/notetype", put(update_note_type)).with_state(pool.clone())
+}
+
+fn follow_up_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/note/:id/followup", put(set_follow_up)).with_state(pool.clone())
+        .route("/note/:id/followup", delete(clear_follow_up)).with_state(pool.clone())
+        .route("/note/:id/followup/complete", post(complete_follow_up)).with_state(pool.clone())
+        .route("/followup/select", post(select_follow_ups)).with_state(pool.clone())
+}
+
+fn allergen_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/allergen/select", post(select_allergens)).with_state(pool.clone())
+        .route("/allergen", post(create_allergen)).with_state(pool.clone())
+        .route("/allergen", put(update_allergen)).with_state(pool.clone())
+        .route("/allergen/link", post(link_allergen)).with_state(pool.clone())
+        .route("/allergen/link", delete(unlink_allergen)).with_state(pool.clone())
+}
+
+fn household_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/member/select", post(select_members)).with_state(pool.clone())
+        .route("/member", post(create_member)).with_state(pool.clone())
+        .route("/member", put(update_member)).with_state(pool.clone())
+        .route("/member/:id", delete(delete_member)).with_state(pool.clone())
+}
+
+fn card_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/card", post(select_card)).with_state(pool.clone())
+        .route("/card", delete(revoke_card)).with_state(pool.clone())
+        .route("/card/select", post(look_up_card)).with_state(pool.clone())
+        .route("/card/print", post(print_card)).with_state(pool.clone())
+        .route("/card/reissue", post(reissue_card)).with_state(pool.clone())
+}
+
+fn check_in_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/checkin", post(check_in)).with_state(pool.clone())
+        .route("/checkin/override", post(override_check_in)).with_state(pool.clone())
+        .route("/eligibility/select", post(select_eligibility)).with_state(pool.clone())
+}
+
+fn distribution_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/distribution", post(record_distribution)).with_state(pool.clone())
+        .route("/distribution", put(correct_distribution)).with_state(pool.clone())
+        .route("/distribution/select", post(select_distributions)).with_state(pool.clone())
+        .route("/distribution/totals/select", post(select_distribution_totals)).with_state(pool.clone())
+}
+
+fn payment_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/payment", post(record_payment)).with_state(pool.clone())
+        .route("/payment/:id", delete(void_payment)).with_state(pool.clone())
+        .route("/statement/select", post(select_statement)).with_state(pool.clone())
+        .route("/balance/select", post(select_balances)).with_state(pool.clone())
+}
+
+fn receipt_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/payment/:id/receipt", post(print_receipt)).with_state(pool.clone())
+        .route("/presence/slip", post(print_visit_slip)).with_state(pool.clone())
 }
 
 fn category_routes(pool : Arc<Pool<MySql>>) -> Router{
@@ -64,15 +201,37 @@ fn category_routes(pool : Arc<Pool<MySql>>) -> Router{
         .route("/category", post(create_category)).with_state(pool.clone())
         .route("/category", put(update_category)).with_state(pool.clone())
         .route("/category", delete(delete_category)).with_state(pool.clone())
+        .route("/category/:id", delete(delete_category_by_id)).with_state(pool.clone())
+        .route("/category/reassign", post(reassign_category)).with_state(pool.clone())
+        .route("/category/fee", post(set_category_fee)).with_state(pool.clone())
+        .route("/category/fee/select", post(select_category_fees)).with_state(pool.clone())
 }
 
 fn stats_routes(pool : Arc<Pool<MySql>>) -> Router{
     Router::new()
         .route("/stats/select", post(stats)).with_state(pool.clone())
+        .route("/stats/compute", post(compute_stats)).with_state(pool.clone())
+}
+
+fn audit_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/audit/select", post(audit_log)).with_state(pool.clone())
+}
+
+fn trash_routes(pool : Arc<Pool<MySql>>) -> Router{
+    Router::new()
+        .route("/trash/select", post(select_trash)).with_state(pool.clone())
+        .route("/trash/restore", post(restore_trash)).with_state(pool.clone())
 }
 
 
 
+#[utoipa::path(get, path = "/", tag = "health",
+    responses(
+        (status = 200, description = "The database answers"),
+        (status = 500, description = "The database is unreachable"),
+    )
+)]
 async fn test_connection(State(pool): State<Arc<MySqlPool>>) -> Result<StatusCode, (StatusCode, String)> {
     let mut conn = acquire_connection(pool.clone()).await?;
 
@@ -87,8 +246,12 @@ async fn test_connection(State(pool): State<Arc<MySqlPool>>) -> Result<StatusCod
 
 
 pub async fn acquire_connection(pool: Arc<MySqlPool>) -> Result<PoolConnection<MySql>, (StatusCode, String)>{
-    match pool.acquire().await {
+    let start = Instant::now();
+    let conn = pool.acquire().await;
+    crate::metrics::acquire_wait(start.elapsed());
+    match conn {
         Ok(conn) => Ok(conn) ,
+        Err(sqlx::Error::PoolTimedOut) => Err((StatusCode::SERVICE_UNAVAILABLE, "No database connection available, retry later".to_string())),
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to connect to the Database".to_string())),
     }
 }
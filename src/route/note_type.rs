@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::route::acquire_connection;
+use crate::schema::audit::{Audit, AuditAction};
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::note_type::{self, note_type_changes, TokenNoteType};
+use crate::schema::user::Token;
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/notetype/select", tag = "details",
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Every note type with the roles that may read or write it", body = Vec<v1::NoteType>),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_note_types(State(pool): State<Arc<MySqlPool>>, format: Format, payload: Payload<Token>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(_) => note_type::select_types(acquire_connection(pool.clone()).await?, format).await,
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/notetype", tag = "details",
+    request_body = v1::TokenNoteType,
+    responses(
+        (status = 200, description = "Every note type after the insert", body = Vec<v1::NoteType>),
+        (status = 400, description = "No name, or an unknown or repeated role"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+        (status = 409, description = "A note type already has this name"),
+    )
+)]
+pub(crate) async fn create_note_type(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenNoteType>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let audit = Audit::new(&user, request_id);
+                let types = note_type::save(acquire_connection(pool.clone()).await?, &payload.NoteType, true, format).await?;
+                audit.record(&pool, AuditAction::CreateNoteType, None, note_type_changes(None, &payload.NoteType)).await;
+                Ok(types)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(put, path = "/notetype", tag = "details",
+    request_body = v1::TokenNoteType,
+    responses(
+        (status = 200, description = "Every note type after the update", body = Vec<v1::NoteType>),
+        (status = 400, description = "No name, or an unknown or repeated role"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+        (status = 404, description = "No such note type"),
+        (status = 409, description = "A note type already has this name"),
+    )
+)]
+pub(crate) async fn update_note_type(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenNoteType>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let audit = Audit::new(&user, request_id);
+                let before = note_type::find(acquire_connection(pool.clone()).await?.as_mut(), payload.NoteType.Id).await;
+                let types = note_type::save(acquire_connection(pool.clone()).await?, &payload.NoteType, false, format).await?;
+                audit.record(&pool, AuditAction::UpdateNoteType, None, note_type_changes(before.as_ref(), &payload.NoteType)).await;
+                Ok(types)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -0,0 +1,112 @@
+use axum::Json;
+use utoipa::OpenApi;
+use harmony_protocol::{v1, v2};
+use crate::route::{allergen, audit, beneficiary, card, category, check_in, details, distribution, follow_up, household, note_type, payment, receipt, stats, trash, user};
+
+const DESCRIPTION: &str = "Every route is also mounted under `/v1` and `/v2`, and the protocol version can be chosen \
+with the `x-harmony-version` header. Bodies are documented as JSON, but requests may be sent as \
+`application/msgpack` or `application/vnd.harmony.bincode` through `Content-Type`, and responses follow \
+`Accept` (bincode when absent).";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Harmony", description = DESCRIPTION),
+    paths(
+        super::test_connection,
+        openapi,
+        super::metrics::metrics,
+        super::health::live,
+        super::health::ready,
+        user::login,
+        user::get_users,
+        user::create_user,
+        user::update_user,
+        user::delete_user,
+        beneficiary::beneficiary,
+        beneficiary::beneficiaries,
+        beneficiary::search_beneficiaries,
+        beneficiary::create_beneficiary,
+        beneficiary::update_beneficiary,
+        beneficiary::beneficiary_history,
+        beneficiary::beneficiary_as_of,
+        details::insert_allergy,
+        details::delete_allergy,
+        allergen::select_allergies,
+        allergen::select_allergens,
+        allergen::create_allergen,
+        allergen::update_allergen,
+        allergen::link_allergen,
+        allergen::unlink_allergen,
+        household::select_members,
+        household::create_member,
+        household::update_member,
+        household::delete_member,
+        details::insert_presence,
+        card::select_card,
+        card::look_up_card,
+        card::print_card,
+        card::reissue_card,
+        card::revoke_card,
+        check_in::check_in,
+        check_in::override_check_in,
+        check_in::select_eligibility,
+        distribution::record_distribution,
+        distribution::correct_distribution,
+        distribution::select_distributions,
+        distribution::select_distribution_totals,
+        payment::record_payment,
+        payment::void_payment,
+        payment::select_statement,
+        payment::select_balances,
+        receipt::print_receipt,
+        receipt::print_visit_slip,
+        details::delete_presence,
+        details::create_note,
+        details::update_note,
+        details::delete_note,
+        details::select_notes,
+        details::edit_note,
+        details::delete_note_by_id,
+        follow_up::set_follow_up,
+        follow_up::clear_follow_up,
+        follow_up::complete_follow_up,
+        follow_up::select_follow_ups,
+        note_type::select_note_types,
+        note_type::create_note_type,
+        note_type::update_note_type,
+        category::select_categories,
+        category::create_category,
+        category::update_category,
+        category::delete_category,
+        category::delete_category_by_id,
+        category::reassign_category,
+        category::set_category_fee,
+        category::select_category_fees,
+        stats::stats,
+        stats::compute_stats,
+        audit::audit_log,
+        trash::select_trash,
+        trash::restore_trash,
+    ),
+    components(schemas(v1::Beneficiary, v1::Details, v1::Stats, v2::Connection)),
+    tags(
+        (name = "health", description = "Probes"),
+        (name = "user", description = "Accounts and sessions"),
+        (name = "beneficiary", description = "Beneficiary files"),
+        (name = "details", description = "Allergies, presences and notes of a beneficiary"),
+        (name = "category", description = "Fee categories"),
+        (name = "stats", description = "Statistics snapshots"),
+        (name = "audit", description = "Who read or changed beneficiary data"),
+        (name = "trash", description = "Deleted allergies, presences and notes, until they are purged"),
+    )
+)]
+pub(crate) struct ApiDoc;
+
+#[utoipa::path(get, path = "/openapi.json", tag = "health",
+    responses(
+        (status = 200, description = "This document", content_type = "application/json"),
+    )
+)]
+pub(crate) async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
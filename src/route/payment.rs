@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::route::acquire_connection;
+use crate::schema::audit::{Audit, AuditAction};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::payment::{self, can_void, payment_changes, TokenPayment, TokenStatementQuery};
+use crate::schema::user::Token;
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/payment", tag = "details",
+    request_body = v1::TokenPayment,
+    responses(
+        (status = 200, description = "The payment as recorded", body = v1::Payment),
+        (status = 400, description = "Invalid amount, method or note"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such beneficiary"),
+    )
+)]
+pub(crate) async fn record_payment(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenPayment>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" | "TS" | "User" => {
+                let audit = Audit::new(&user, request_id);
+                let recorded = payment::record(acquire_connection(pool.clone()).await?, &payload.Payment, &user).await?;
+                audit.record(&pool, AuditAction::RecordPayment, Some(recorded.BeneficiaryId), payment_changes(&recorded)).await;
+                encode(recorded, format)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(delete, path = "/payment/{id}", tag = "details",
+    params(("id" = i32, Path, description = "Payment id")),
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Payment voided"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Payments are voided by Admins"),
+        (status = 404, description = "No such payment"),
+        (status = 409, description = "The payment was voided already"),
+    )
+)]
+pub(crate) async fn void_payment(State(pool): State<Arc<MySqlPool>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<Token>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) if can_void(&user) => {
+            let audit = Audit::new(&user, request_id);
+            let voided = payment::void(acquire_connection(pool.clone()).await?, id, &user).await?;
+            audit.record(&pool, AuditAction::VoidPayment, Some(voided.BeneficiaryId), payment_changes(&voided)).await;
+            Ok(StatusCode::OK)
+        },
+        Ok(_) => Err((StatusCode::FORBIDDEN, "Invalid role".to_string())),
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/statement/select", tag = "details",
+    request_body = v1::TokenStatementQuery,
+    responses(
+        (status = 200, description = "Charges and payments of the beneficiary with a running balance", body = v1::Statement),
+        (status = 400, description = "Invalid dates"),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn select_statement(State(pool): State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<TokenStatementQuery>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => {
+            let audit = Audit::new(&user, request_id);
+            let statement = payment::select_statement(acquire_connection(pool.clone()).await?, &payload, format).await?;
+            audit.record(&pool, AuditAction::ReadStatement, Some(payload.BeneficiaryId), Vec::new()).await;
+            Ok(statement)
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/balance/select", tag = "details",
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Beneficiaries owing something, the largest balance first", body = Vec<v1::Balance>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Balances are for Admins"),
+    )
+)]
+pub(crate) async fn select_balances(State(pool): State<Arc<MySqlPool>>, format: Format, payload: Payload<Token>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" => payment::select_balances(acquire_connection(pool.clone()).await?, format).await,
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::body::{to_bytes, Body};
+use axum::extract::{ConnectInfo, MatchedPath, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use sqlx::MySqlPool;
+use tracing::warn;
+use harmony_protocol::Version;
+use crate::config::{Budget, Config, Limits};
+use crate::metrics::METRICS;
+use crate::route::acquire_connection;
+use crate::schema::format::Format;
+use crate::schema::validate_token;
+
+/// Same ceiling as the `Bytes` extractor behind `Payload`.
+const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Buckets kept before the full ones, which carry no information, are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum RouteGroup {
+    Login,
+    Read,
+    Write,
+}
+
+impl RouteGroup {
+    /// Probes, metrics and the API document are never limited.
+    pub(crate) fn of(route: &str) -> Option<RouteGroup> {
+        let route = Version::SUPPORTED
+            .iter()
+            .find_map(|version| route.strip_prefix(version.path_prefix()))
+            .unwrap_or(route);
+        match route {
+            "" | "/" | "/metrics" | "/openapi.json" => None,
+            _ if route.starts_with("/health/") => None,
+            "/user/login" => Some(RouteGroup::Login),
+            _ if route.contains("/select") || route.contains("/search") => Some(RouteGroup::Read),
+            _ => Some(RouteGroup::Write),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Login => "login",
+            RouteGroup::Read => "read",
+            RouteGroup::Write => "write",
+        }
+    }
+}
+
+/// A user is only known once their token resolved to a session. A login attempt is keyed
+/// on the address and the username tried, so nobody can lock a user out from elsewhere.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Key {
+    User(i32),
+    Login(IpAddr, String),
+    Ip(IpAddr),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    budget: Budget,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let rate = self.budget.requests as f64 / self.budget.per.as_secs_f64();
+        self.tokens = (self.tokens + now.saturating_duration_since(self.updated).as_secs_f64() * rate).min(self.budget.requests as f64);
+        self.updated = now;
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.budget.requests as f64
+    }
+
+    fn wait(&self) -> Duration {
+        let rate = self.budget.requests as f64 / self.budget.per.as_secs_f64();
+        Duration::from_secs_f64(((1.0 - self.tokens) / rate).max(0.0))
+    }
+}
+
+/// Token buckets per route group, keyed by caller and by client address.
+pub(crate) struct RateLimiter {
+    config: Arc<Config>,
+    pool: Arc<MySqlPool>,
+    buckets: Mutex<HashMap<(RouteGroup, Key), Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: Arc<Config>, pool: Arc<MySqlPool>) -> RateLimiter {
+        RateLimiter { config, pool, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    fn limits(&self, group: RouteGroup) -> Limits {
+        match group {
+            RouteGroup::Login => self.config.rate_limit.login,
+            RouteGroup::Read => self.config.rate_limit.read,
+            RouteGroup::Write => self.config.rate_limit.write,
+        }
+    }
+
+    /// Takes one token from every bucket of the request, or none of them when one is empty,
+    /// in which case the wait before a retry can succeed is returned.
+    pub(crate) fn acquire(&self, group: RouteGroup, user: Option<&Key>, ip: Option<IpAddr>, now: Instant) -> Result<(), (Duration, &'static str)> {
+        let limits = self.limits(group);
+        let keys = [
+            user.map(|user| (user.clone(), limits.per_user, "user")),
+            ip.map(|ip| (Key::Ip(ip), limits.per_ip, "ip")),
+        ];
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now);
+                !bucket.is_full()
+            });
+        }
+
+        let mut refused = None;
+        for (key, budget, kind) in keys.iter().flatten() {
+            let bucket = buckets
+                .entry((group, key.clone()))
+                .or_insert_with(|| Bucket { tokens: budget.requests as f64, updated: now, budget: *budget });
+            bucket.budget = *budget;
+            bucket.refill(now);
+            if bucket.tokens < 1.0 {
+                let wait = bucket.wait();
+                if refused.is_none_or(|(longest, _)| wait > longest) {
+                    refused = Some((wait, *kind));
+                }
+            }
+        }
+        if let Some(refused) = refused {
+            return Err(refused);
+        }
+
+        for (key, _, _) in keys.iter().flatten() {
+            if let Some(bucket) = buckets.get_mut(&(group, key.clone())) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives back the tokens of a request that should not have counted.
+    pub(crate) fn refund(&self, group: RouteGroup, user: Option<&Key>, ip: Option<IpAddr>, now: Instant) {
+        let mut buckets = self.buckets.lock().unwrap();
+        for key in [user.cloned(), ip.map(Key::Ip)].into_iter().flatten() {
+            if let Some(bucket) = buckets.get_mut(&(group, key)) {
+                bucket.refill(now);
+                bucket.tokens = (bucket.tokens + 1.0).min(bucket.budget.requests as f64);
+            }
+        }
+    }
+
+    /// The user of a session token, nobody when it does not resolve.
+    async fn user(&self, token: &String) -> Result<Option<Key>, (StatusCode, String)> {
+        let conn = acquire_connection(self.pool.clone()).await?;
+        Ok(validate_token(conn, token).await.ok().map(|user| Key::User(user.Id)))
+    }
+}
+
+/// First field of every request body: the session `Token`, or the `Username` on login.
+#[derive(Deserialize, bincode::Decode)]
+#[allow(non_snake_case)]
+struct Caller {
+    #[serde(alias = "Username")]
+    Token: String,
+}
+
+/// Reads the caller out of a request body in whatever format it was sent.
+pub(crate) fn caller(headers: &HeaderMap, body: &[u8]) -> Option<String> {
+    let format = Format::from_content_type(headers).ok()?;
+    format
+        .decode::<Caller>(body)
+        .ok()
+        .map(|caller| caller.Token)
+        .filter(|token| !token.is_empty())
+}
+
+/// Refuses requests over their route group budget with `429 Too Many Requests` and `Retry-After`.
+pub(crate) async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let group = req.extensions().get::<MatchedPath>().and_then(|path| RouteGroup::of(path.as_str()));
+    let group = match group {
+        Some(group) if limiter.config.rate_limit.enabled => group,
+        _ => return next.run(req).await,
+    };
+
+    let (parts, body) = req.into_parts();
+    let bytes = match to_bytes(body, BODY_LIMIT).await {
+        Ok(bytes) => bytes,
+        Err(_) => return (StatusCode::PAYLOAD_TOO_LARGE, "Request body is too large".to_string()).into_response(),
+    };
+    let ip = connect_info.map(|ConnectInfo(addr)| addr.ip());
+
+    // The address is checked first, so a flood is refused before its tokens cost a query.
+    if let Err(refused) = limiter.acquire(group, None, ip, Instant::now()) {
+        return too_many_requests(group, refused);
+    }
+    let user = match (group, caller(&parts.headers, &bytes)) {
+        (RouteGroup::Login, Some(username)) => ip.map(|ip| Key::Login(ip, username)),
+        (_, Some(token)) => match limiter.user(&token).await {
+            Ok(user) => user,
+            Err(e) => return e.into_response(),
+        },
+        (_, None) => None,
+    };
+    if let Some(user) = &user {
+        if let Err(refused) = limiter.acquire(group, Some(user), None, Instant::now()) {
+            limiter.refund(group, None, ip, Instant::now());
+            return too_many_requests(group, refused);
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    // Only failed logins count, so an office sharing one address can sign everyone in.
+    if group == RouteGroup::Login && response.status().is_success() {
+        limiter.refund(group, user.as_ref(), ip, Instant::now());
+    }
+    response
+}
+
+fn too_many_requests(group: RouteGroup, (wait, kind): (Duration, &'static str)) -> Response {
+    let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+    METRICS.rate_limited.with_label_values(&[group.as_str(), kind]).inc();
+    warn!(group = group.as_str(), key = kind, retry_after, "Rate limit reached");
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        format!("Too many requests, retry in {retry_after} s"),
+    ).into_response()
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::config::Config;
+use crate::route::acquire_connection;
+use crate::schema::audit::{change, Audit, AuditAction};
+use crate::schema::details::TokenPresence;
+use crate::schema::format::Payload;
+use crate::schema::receipt;
+use crate::schema::user::Token;
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+/// A PDF offered for download as `name`.
+fn attachment(name: String, pdf: Vec<u8>) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{name}\"")),
+        ],
+        pdf,
+    ).into_response()
+}
+
+#[utoipa::path(post, path = "/payment/{id}/receipt", tag = "details",
+    params(("id" = i32, Path, description = "Payment id")),
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "The receipt of the payment", content_type = "application/pdf"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such payment"),
+        (status = 409, description = "The payment was voided"),
+    )
+)]
+pub(crate) async fn print_receipt(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, Path(id): Path<i32>, request_id: RequestId, payload: Payload<Token>) -> Result<Response, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" | "TS" | "User" => {
+                let audit = Audit::new(&user, request_id);
+                let receipt = receipt::receipt(acquire_connection(pool.clone()).await?, id, &user).await?;
+                audit.record(&pool, AuditAction::PrintReceipt, Some(receipt.Holder.Id), vec![change("PaymentId", None, Some(id.to_string()))]).await;
+                Ok(attachment(format!("receipt-{id}.pdf"), receipt.page(&config.organization).render()))
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/presence/slip", tag = "details",
+    request_body = v1::TokenPresence,
+    responses(
+        (status = 200, description = "The visit slip of the presence", content_type = "application/pdf"),
+        (status = 400, description = "Invalid date"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Invalid role"),
+        (status = 404, description = "No such presence"),
+    )
+)]
+pub(crate) async fn print_visit_slip(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, request_id: RequestId, payload: Payload<TokenPresence>) -> Result<Response, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Admin" | "Dev" | "TS" | "User" => {
+                let audit = Audit::new(&user, request_id);
+                let slip = receipt::visit_slip(acquire_connection(pool.clone()).await?, payload.Presence.BeneficiaryId, &payload.Presence.Date, &user, &config.eligibility).await?;
+                audit.record(&pool, AuditAction::PrintVisitSlip, Some(slip.Holder.Id), vec![change("Date", None, Some(slip.Date.clone()))]).await;
+                let name = format!("visit-{}-{}.pdf", slip.Holder.Id, slip.Date.get(..10).unwrap_or_default());
+                Ok(attachment(name, slip.page(&config.organization).render()))
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -1,23 +1,86 @@
 use std::sync::Arc;
-use axum::extract::State;
+use axum::extract::{FromRequest, Request, State};
 use axum::http::StatusCode;
-use axum::Json;
+use axum::Extension;
 use sqlx::MySqlPool;
+use harmony_protocol::{v1, v2, Version};
+use crate::config::Config;
 use crate::route::acquire_connection;
-use crate::schema::stats::Stats;
+use crate::schema::audit::{change, Audit, AuditAction};
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::snapshot;
+use crate::schema::stats::{self, Selection, TokenStatsQuery};
 use crate::schema::user::Token;
 use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+use tracing::error;
 
-pub(crate) async fn stats(State(pool): State<Arc<MySqlPool>>, payload: Json<Token>) -> Result<Vec<u8>, (StatusCode, String)> {
+#[utoipa::path(post, path = "/stats/select", tag = "stats",
+    request_body(content = v2::TokenStatsQuery, description = "From v2 on. A v1 body is only a `Token` and reads every row of every series"),
+    responses(
+        (status = 200, description = "The series asked for between From and To, one row per period", body = v1::Stats),
+        (status = 400, description = "Invalid dates, period or series"),
+        (status = 500, description = "A statistics table could not be read"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+    )
+)]
+pub(crate) async fn stats(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, Extension(version): Extension<Version>, format: Format, request: Request) -> Result<Encoded, (StatusCode, String)> {
+    let (token, query) = match version {
+        Version::V1 => (Payload::<Token>::from_request(request, &()).await?.0.Token, None),
+        Version::V2 => {
+            let Payload(query) = Payload::<TokenStatsQuery>::from_request(request, &()).await?;
+            (query.Token.clone(), Some(query))
+        },
+    };
     let conn = acquire_connection(pool.clone()).await?;
-    match validate_token(conn, &payload.Token).await {
+    match validate_token(conn, &token).await {
         Ok(user) => match user.Role.as_str(){
            "Dev" | "Admin" => {
+                let selection = match &query {
+                    Some(query) => Selection::parse(query)?,
+                    None => Selection::all(),
+                };
                 let conn = acquire_connection(pool.clone()).await?;
-                Stats::get_stats(conn).await
+                stats::select_stats(conn, &selection, &config.eligibility, format).await
                 },
             _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
         },
         Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
     }
 }
+
+#[utoipa::path(post, path = "/stats/compute", tag = "stats",
+    request_body = v1::TokenStatsBackfill,
+    responses(
+        (status = 200, description = "The statistics of every period from From to To were computed again"),
+        (status = 400, description = "Invalid dates or too many periods"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+    )
+)]
+pub(crate) async fn compute_stats(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, request_id: RequestId, payload: Payload<v1::TokenStatsBackfill>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let audit = Audit::new(&user, request_id);
+                let period = config.stats.period;
+                let (from, to) = snapshot::range(&payload.From, &payload.To, period, &config.eligibility)
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+                let computed = snapshot::backfill(&pool, from, to, period, &config.eligibility).await.map_err(|e| {
+                    error!(error = %e, "Compute statistics failed");
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Could not compute the statistics".to_string())
+                })?;
+                audit.record(&pool, AuditAction::ComputeStats, None, vec![
+                    change("From", None, Some(payload.From.clone())),
+                    change("To", None, Some(payload.To.clone())),
+                    change("Periods", None, Some(computed.to_string())),
+                ]).await;
+                Ok(StatusCode::OK)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
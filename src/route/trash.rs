@@ -0,0 +1,66 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Extension;
+use sqlx::MySqlPool;
+use harmony_protocol::v1;
+use crate::config::Config;
+use crate::route::acquire_connection;
+use crate::schema::audit::Audit;
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::household;
+use crate::schema::trash::{self, TokenTrashEntry, TokenTrashQuery, TrashKind};
+use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+
+#[utoipa::path(post, path = "/trash/select", tag = "trash",
+    request_body = v1::TokenTrashQuery,
+    responses(
+        (status = 200, description = "Deleted entries, most recent first", body = Vec<v1::TrashEntry>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+    )
+)]
+pub(crate) async fn select_trash(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, format: Format, payload: Payload<TokenTrashQuery>) -> Result<Encoded, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let conn = acquire_connection(pool.clone()).await?;
+                trash::select_trash(conn, &payload, &user, config.trash.retention, format).await
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
+
+#[utoipa::path(post, path = "/trash/restore", tag = "trash",
+    request_body = v1::TokenTrashEntry,
+    responses(
+        (status = 200, description = "Entry restored"),
+        (status = 400, description = "Unknown kind"),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+        (status = 404, description = "Not in the trash, or its retention is over"),
+    )
+)]
+pub(crate) async fn restore_trash(State(pool): State<Arc<MySqlPool>>, Extension(config): Extension<Arc<Config>>, request_id: RequestId, payload: Payload<TokenTrashEntry>) -> Result<StatusCode, (StatusCode, String)> {
+    let conn = acquire_connection(pool.clone()).await?;
+    match validate_token(conn, &payload.Token).await {
+        Ok(user) => match user.Role.as_str() {
+            "Dev" | "Admin" => {
+                let audit = Audit::new(&user, request_id);
+                let conn = acquire_connection(pool.clone()).await?;
+                let kind = trash::restore(conn, &payload.Entry, config.trash.retention).await?;
+                if matches!(kind, TrashKind::Allergy | TrashKind::Member) {
+                    household::refresh(acquire_connection(pool.clone()).await?, payload.Entry.BeneficiaryId, &user).await;
+                }
+                audit.record(&pool, kind.restored(), Some(payload.Entry.BeneficiaryId), kind.changes(&payload.Entry)).await;
+                Ok(StatusCode::OK)
+            },
+            _ => Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        },
+        Err(_) => Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
+    }
+}
@@ -1,36 +1,62 @@
 use std::sync::Arc;
 use axum::extract::State;
+use axum::Extension;
 use axum::http::StatusCode;
-use axum::Json;
 use sqlx::MySqlPool;
+use harmony_protocol::{v1, Version};
 use crate::route::acquire_connection;
+use crate::schema::format::{Encoded, Format, Payload};
+use crate::schema::audit::{change, Audit, AuditAction};
 use crate::schema::user::{Connection, Token, User, UserLogin, UserToken};
 use crate::schema::validate_token;
+use crate::telemetry::RequestId;
+use crate::metrics;
+use tracing::{debug, error, warn};
 
-pub(crate) async fn delete_user(State(pool) : State<Arc<MySqlPool>>, payload: Json<UserToken>) -> Result<StatusCode, (StatusCode, String)>{
-    println!();
-    println!("->> {:>12} - Delete User", "Handler");
+#[utoipa::path(delete, path = "/user", tag = "user",
+    request_body = v1::UserToken,
+    responses(
+        (status = 200, description = "User deleted"),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn delete_user(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<UserToken>) -> Result<StatusCode, (StatusCode, String)>{
+    debug!("Delete User");
     let conn = acquire_connection(pool.clone()).await?;
 
-    if validate_token(conn, &payload.Token).await.is_err(){
-        return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()));
-    }
+    let audit = match validate_token(conn, &payload.Token).await {
+        Ok(user) => Audit::new(&user, request_id),
+        Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string())),
+    };
 
+    let before = User::find(acquire_connection(pool.clone()).await?.as_mut(), payload.User.Id).await;
     let conn = acquire_connection(pool.clone()).await?;
     match payload.delete_user(conn).await {
         Ok(_) => {
-            println!("->> {:>12} - Delete User - SUCCESS", "Handler");
+            let changes = vec![
+                change("Username", before.as_ref().map(|user| user.Username.clone()), None),
+                change("Role", before.map(|user| user.Role), None),
+            ];
+            audit.record(&pool, AuditAction::DeleteUser, None, changes).await;
+            debug!("Delete User succeeded");
             Ok(StatusCode::OK)
         },
         Err(e) => {
-            println!("->> {:>12} - Delete User - FAILED : {}", "Handler", e);
+            error!(error = %e, "Delete User failed");
             Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not delete user".to_string()))
         },
     }
 }
-pub(crate) async fn get_users(State(pool): State<Arc<MySqlPool>>, payload: Json<Token>) -> Result<Vec<u8>, (StatusCode, String)> {
-    println!();
-    println!("->> {:>12} - Get Users", "Handler");
+#[utoipa::path(post, path = "/user/select", tag = "user",
+    request_body = v1::Token,
+    responses(
+        (status = 200, description = "Every user except developers and the caller", body = Vec<v1::User>),
+        (status = 401, description = "Invalid token"),
+        (status = 403, description = "Caller is not an Admin"),
+    )
+)]
+pub(crate) async fn get_users(State(pool): State<Arc<MySqlPool>>, format: Format, payload: Payload<Token>) -> Result<Encoded, (StatusCode, String)> {
+    debug!("Get Users");
     let conn = acquire_connection(pool.clone()).await?;
 
     return match validate_token(conn, &payload.Token).await {
@@ -38,95 +64,132 @@ pub(crate) async fn get_users(State(pool): State<Arc<MySqlPool>>, payload: Json<
             let conn = acquire_connection(pool.clone()).await?;
             match user.Role.as_str() {
                 "Admin" | "Dev" => {
-                    match User::get_users(conn, user.Username).await {
+                    match User::get_users(conn, user.Username, format).await {
                         Ok(val) => {
-                            println!("->> {:>12} - Get Users - SUCCESS", "Handler");
+                            debug!("Get Users succeeded");
                             Ok(val)
                         },
                         Err(e) => {
-                            println!("->> {:>12} - Get Users - FAILED - {:?}", "Handler", e);
+                            error!(error = ?e, "Get Users failed");
                             Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get users".to_string()))
                         }
                     }
                 },
                 _ => {
-                    println!("->> {:>12} - Get Users - FAILED - Invalid Role", "Handler");
+                    warn!("Get Users failed: Invalid Role");
                     Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
                 },
             }
         }
         Err(_) => {
-            println!("->> {:>12} - Get Users - FAILED - Invalid token", "Handler");
+            warn!("Get Users failed: Invalid token");
             Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()))
         }
     }
 }
-pub(crate) async fn login(State(pool) : State<Arc<MySqlPool>>, payload: Json<UserLogin>) -> Result<Vec<u8>, (StatusCode, String)> {
-    println!();
-    println!("->> {:>12} - Login", "Handler");
+#[utoipa::path(post, path = "/user/login", tag = "user",
+    request_body = v1::UserLogin,
+    responses(
+        (status = 200, description = "Session token and role. From v2 on, a `v2.Connection` that also counts overdue follow-ups", body = v1::Connection),
+        (status = 401, description = "Invalid credentials"),
+    )
+)]
+pub(crate) async fn login(State(pool) : State<Arc<MySqlPool>>, Extension(version): Extension<Version>, format: Format, payload: Payload<UserLogin>) -> Result<Encoded, (StatusCode, String)> {
+    debug!("Login");
     let conn = acquire_connection(pool.clone()).await?;
-    let user = payload.get_user(conn).await?;
+    let user = payload.get_user(conn).await.inspect_err(|_| metrics::login("failure"))?;
 
     match user.validate_password(&payload.Password).await {
         true => {
-            match Connection::get_or_create_connection(pool.clone(), user).await {
+            match Connection::get_or_create_connection(pool.clone(), user, version, format).await {
                 Ok(val) => {
-                    println!("->> {:>12} - Login - SUCCESS", "Handler");
+                    metrics::login("success");
+                    debug!("Login succeeded");
                     Ok(val)
                 },
                 Err(e) => {
-                    println!("->> {:>12} - Login - FAILED : {:?}", "Handler", e);
+                    error!(error = ?e, "Login failed");
                     Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not create token".to_string()))
                 }
             }
         },
         false => {
-            println!("->> {:>12} - Login - FAILED : Invalid credentials", "Handler");
+            metrics::login("failure");
+            warn!("Login failed: Invalid credentials");
             Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()))
         }
     }
 }
 
-pub(crate) async fn create_user(State(pool) : State<Arc<MySqlPool>>, payload: Json<UserToken>) -> Result<Vec<u8>, (StatusCode, String)>{
-    println!();
-    println!("->> {:>12} - Create User", "Handler");
+#[utoipa::path(post, path = "/user", tag = "user",
+    request_body = v1::UserToken,
+    responses(
+        (status = 200, description = "Created user", body = v1::User),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn create_user(State(pool) : State<Arc<MySqlPool>>, format: Format, request_id: RequestId, payload: Payload<UserToken>) -> Result<Encoded, (StatusCode, String)>{
+    debug!("Create User");
     let conn = acquire_connection(pool.clone()).await?;
 
-    if validate_token(conn, &payload.Token).await.is_err(){
-        return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()));
-    }
+    let audit = match validate_token(conn, &payload.Token).await {
+        Ok(user) => Audit::new(&user, request_id),
+        Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string())),
+    };
 
     let conn = acquire_connection(pool.clone()).await?;
-    match payload.create_user(conn).await{
+    match payload.create_user(conn, format).await{
         Ok(val) => {
-            println!("->> {:>12} - Create User - SUCCESS", "Handler");
+            let changes = vec![
+                change("Username", None, Some(payload.User.Username.clone())),
+                change("Role", None, Some(payload.User.Role.clone())),
+                change("Password", None, Some(payload.User.Password.clone())),
+            ];
+            audit.record(&pool, AuditAction::CreateUser, None, changes).await;
+            debug!("Create User succeeded");
             Ok(val)
         },
         Err(e) => {
-            println!("->> {:>12} - Create User - FAILED : {:?}", "Handler", e);
+            error!(error = ?e, "Create User failed");
             Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to create user".to_string()))
         }
     }
 }
 
-pub(crate) async fn update_user(State(pool) : State<Arc<MySqlPool>>, payload: Json<UserToken>) -> Result<StatusCode, (StatusCode, String)>{
-    println!();
-    println!("->> {:>12} - Update User", "Handler");
+#[utoipa::path(put, path = "/user", tag = "user",
+    request_body = v1::UserToken,
+    responses(
+        (status = 200, description = "User updated"),
+        (status = 401, description = "Invalid token"),
+    )
+)]
+pub(crate) async fn update_user(State(pool) : State<Arc<MySqlPool>>, request_id: RequestId, payload: Payload<UserToken>) -> Result<StatusCode, (StatusCode, String)>{
+    debug!("Update User");
     let conn = acquire_connection(pool.clone()).await?;
 
-    if validate_token(conn, &payload.Token).await.is_err(){
-        return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string()));
-    }
+    let audit = match validate_token(conn, &payload.Token).await {
+        Ok(user) => Audit::new(&user, request_id),
+        Err(_) => return Err((StatusCode::UNAUTHORIZED, "Invalid token".to_string())),
+    };
 
+    let before = User::find(acquire_connection(pool.clone()).await?.as_mut(), payload.User.Id).await;
     let conn = acquire_connection(pool.clone()).await?;
 
     match payload.update_user(conn).await {
         Ok(_) => {
-            println!("->> {:>12} - Update User - SUCCESS", "Handler");
+            let mut changes = vec![change("Username", before.as_ref().map(|user| user.Username.clone()), Some(payload.User.Username.clone()))];
+            if before.as_ref().is_none_or(|user| user.Role != payload.User.Role) {
+                changes.push(change("Role", before.map(|user| user.Role), Some(payload.User.Role.clone())));
+            }
+            if !payload.User.Password.is_empty() {
+                changes.push(change("Password", None, Some(payload.User.Password.clone())));
+            }
+            audit.record(&pool, AuditAction::UpdateUser, None, changes).await;
+            debug!("Update User succeeded");
             Ok(StatusCode::OK)
         },
         Err(_e) => {
-            println!("->> {:>12} - Update User - FAILED", "Handler");
+            error!(error = %_e, "Update User failed");
             Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not update user".to_string()))
         },
     }
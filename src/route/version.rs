@@ -0,0 +1,40 @@
+use axum::extract::Request;
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use harmony_protocol::{Version, VERSION_HEADER};
+
+/// Resolves the protocol version of a request from the `x-harmony-version` header or the
+/// `/v1` style path prefix, and echoes it on the response.
+///
+/// Requests naming no version are served as [`Version::LEGACY`], the contract of every
+/// client deployed before versioning. Handlers read the result with `Extension<Version>`.
+pub(crate) async fn negotiate_version(mut req: Request, next: Next) -> Result<Response, (StatusCode, String)> {
+    let path = req.uri().path();
+    let from_path = Version::SUPPORTED
+        .iter()
+        .copied()
+        .find(|version| path == version.path_prefix() || path.starts_with(&format!("{}/", version.path_prefix())));
+
+    let from_header = match req.headers().get(VERSION_HEADER) {
+        Some(value) => Some(value.to_str().ok().and_then(Version::parse).ok_or_else(unsupported)?),
+        None => None,
+    };
+
+    let version = match (from_path, from_header) {
+        (Some(path), Some(header)) if path != header => {
+            return Err((StatusCode::BAD_REQUEST, format!("Path is {path} but {VERSION_HEADER} asks for {header}")));
+        }
+        (path, header) => header.or(path).unwrap_or(Version::LEGACY),
+    };
+
+    req.extensions_mut().insert(version);
+    let mut response = next.run(req).await;
+    response.headers_mut().insert(VERSION_HEADER, HeaderValue::from_static(version.as_str()));
+    Ok(response)
+}
+
+fn unsupported() -> (StatusCode, String) {
+    let supported = Version::SUPPORTED.iter().map(|version| version.to_string()).collect::<Vec<_>>().join(", ");
+    (StatusCode::BAD_REQUEST, format!("Unsupported protocol version, this server speaks {supported}"))
+}
@@ -0,0 +1,399 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use sqlx::{Connection, Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{Allergen, AllergenLabel, TokenAllergen, TokenAllergenLink};
+use crate::schema::audit::{change, AuditChange};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::history;
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+/// Kinds of catalog entries.
+pub(crate) const KINDS: [&str; 2] = ["allergen", "diet"];
+
+/// Severities a link may carry.
+pub(crate) const SEVERITIES: [&str; 3] = ["mild", "moderate", "severe"];
+
+/// Allergy rows of the beneficiary or of a member who is not in the trash.
+pub(crate) const OF_LIVE_MEMBER: &str = "(BeneficiaryAllergies.MemberId IS NULL \
+    OR BeneficiaryAllergies.MemberId IN (SELECT HouseholdMember.Id FROM HouseholdMember WHERE HouseholdMember.DeletedAt IS NULL))";
+
+/// What `Beneficiary.HasAllergies` is worth, from the allergy rows of the household not in the trash.
+pub(crate) const HAS_ALLERGIES: &str = "EXISTS (SELECT 1 FROM BeneficiaryAllergies \
+    WHERE BeneficiaryAllergies.BeneficiaryId = Beneficiary.Id AND BeneficiaryAllergies.DeletedAt IS NULL \
+    AND (BeneficiaryAllergies.MemberId IS NULL \
+    OR BeneficiaryAllergies.MemberId IN (SELECT HouseholdMember.Id FROM HouseholdMember WHERE HouseholdMember.DeletedAt IS NULL)))";
+
+pub(crate) enum AllergenQueries {
+    SelectAllergens,
+    SelectLabels,
+    InsertAllergen,
+    UpdateAllergen,
+    DeleteLabels,
+    InsertLabel,
+    SelectActiveCode,
+    SelectAllergies,
+    CountLinks,
+    InsertLink,
+    UpdateLink,
+    DeleteLink,
+    RefreshHasAllergies,
+}
+
+impl Display for AllergenQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllergenQueries::SelectAllergens => write!(f,
+                "SELECT Id, Code, Kind, IsActive, \
+                (SELECT COUNT(DISTINCT BeneficiaryId) FROM BeneficiaryAllergies \
+                WHERE BeneficiaryAllergies.AllergenId = Allergen.Id AND BeneficiaryAllergies.DeletedAt IS NULL AND {OF_LIVE_MEMBER}) AS Beneficiaries \
+                FROM Allergen ORDER BY Kind ASC, Code ASC"
+            ),
+            AllergenQueries::SelectLabels => write!(f, "SELECT AllergenId, Language, Label FROM AllergenLabel ORDER BY AllergenId ASC, Language ASC"),
+            AllergenQueries::InsertAllergen => write!(f, "INSERT INTO Allergen (Code, Kind, IsActive) VALUES (?, ?, ?)"),
+            AllergenQueries::UpdateAllergen => write!(f, "UPDATE Allergen SET Code = ?, Kind = ?, IsActive = ? WHERE Id = ?"),
+            AllergenQueries::DeleteLabels => write!(f, "DELETE FROM AllergenLabel WHERE AllergenId = ?"),
+            AllergenQueries::InsertLabel => write!(f, "INSERT INTO AllergenLabel (AllergenId, Language, Label) VALUES (?, ?, ?)"),
+            AllergenQueries::SelectActiveCode => write!(f, "SELECT Code FROM Allergen WHERE Id = ? AND IsActive"),
+            AllergenQueries::SelectAllergies => write!(f,
+                "SELECT BeneficiaryAllergies.BeneficiaryId, BeneficiaryAllergies.AllergenId, Allergen.Kind, \
+                BeneficiaryAllergies.Allergy, BeneficiaryAllergies.Severity \
+                FROM BeneficiaryAllergies LEFT JOIN Allergen ON Allergen.Id = BeneficiaryAllergies.AllergenId \
+                WHERE BeneficiaryAllergies.BeneficiaryId = ? AND BeneficiaryAllergies.DeletedAt IS NULL AND {OF_LIVE_MEMBER} \
+                ORDER BY BeneficiaryAllergies.AllergenId IS NULL, BeneficiaryAllergies.Allergy ASC"
+            ),
+            AllergenQueries::CountLinks => write!(f,
+                "SELECT COUNT(*) FROM BeneficiaryAllergies WHERE BeneficiaryId = ? AND MemberId IS NULL AND AllergenId = ? AND DeletedAt IS NULL"
+            ),
+            AllergenQueries::InsertLink => write!(f,
+                "INSERT INTO BeneficiaryAllergies (BeneficiaryId, Allergy, AllergenId, Severity) VALUES (?, ?, ?, ?)"
+            ),
+            AllergenQueries::UpdateLink => write!(f,
+                "UPDATE BeneficiaryAllergies SET Severity = ? WHERE BeneficiaryId = ? AND MemberId IS NULL AND AllergenId = ? AND DeletedAt IS NULL"
+            ),
+            AllergenQueries::DeleteLink => write!(f,
+                "UPDATE BeneficiaryAllergies SET DeletedAt = NOW(), DeletedBy = ? \
+                WHERE BeneficiaryId = ? AND MemberId IS NULL AND AllergenId = ? AND DeletedAt IS NULL"
+            ),
+            AllergenQueries::RefreshHasAllergies => write!(f,
+                "UPDATE Beneficiary SET HasAllergies = NOT HasAllergies WHERE Id = ? AND HasAllergies <> {HAS_ALLERGIES}"
+            ),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AllergenRow {
+    Id: i32,
+    Code: String,
+    Kind: String,
+    IsActive: bool,
+    Beneficiaries: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct LabelRow {
+    AllergenId: i32,
+    Language: String,
+    Label: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct AllergyRow {
+    BeneficiaryId: i32,
+    AllergenId: Option<i32>,
+    Kind: Option<String>,
+    Allergy: String,
+    Severity: Option<String>,
+}
+
+impl From<AllergyRow> for v1::Allergy {
+    fn from(row: AllergyRow) -> Self {
+        v1::Allergy { BeneficiaryId: row.BeneficiaryId, AllergenId: row.AllergenId, Kind: row.Kind, Allergy: row.Allergy, Severity: row.Severity }
+    }
+}
+
+/// Joins every entry with its labels.
+fn assemble(allergens: Vec<AllergenRow>, labels: Vec<LabelRow>) -> Vec<Allergen> {
+    allergens
+        .into_iter()
+        .map(|row| Allergen {
+            Id: row.Id,
+            Code: row.Code,
+            Kind: row.Kind,
+            IsActive: row.IsActive,
+            Labels: labels
+                .iter()
+                .filter(|label| label.AllergenId == row.Id)
+                .map(|label| AllergenLabel { Language: label.Language.clone(), Label: label.Label.clone() })
+                .collect(),
+            Beneficiaries: u32::try_from(row.Beneficiaries).unwrap_or(u32::MAX),
+        })
+        .collect()
+}
+
+/// An entry needs a code, a known kind and at least one label, one per language.
+pub(crate) fn validate(allergen: &Allergen) -> Result<(), (StatusCode, String)> {
+    if allergen.Code.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "An allergen needs a code".to_string()));
+    }
+    if !KINDS.contains(&allergen.Kind.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown kind: {}", allergen.Kind)));
+    }
+    if allergen.Labels.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "An allergen needs a label".to_string()));
+    }
+    for (i, label) in allergen.Labels.iter().enumerate() {
+        if label.Language.trim().is_empty() || label.Label.trim().is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "A label needs a language and a text".to_string()));
+        }
+        if allergen.Labels[..i].iter().any(|other| other.Language == label.Language) {
+            return Err((StatusCode::BAD_REQUEST, format!("Language labelled twice: {}", label.Language)));
+        }
+    }
+    Ok(())
+}
+
+/// An empty severity is none, anything else must be known.
+pub(crate) fn validate_severity(severity: Option<&str>) -> Result<Option<String>, (StatusCode, String)> {
+    match severity.map(str::trim).filter(|severity| !severity.is_empty()) {
+        None => Ok(None),
+        Some(severity) if SEVERITIES.contains(&severity) => Ok(Some(severity.to_string())),
+        Some(severity) => Err((StatusCode::BAD_REQUEST, format!("Unknown severity: {severity}"))),
+    }
+}
+
+pub(crate) async fn all(conn: &mut MySqlConnection) -> Result<Vec<Allergen>, Error> {
+    let allergens = sqlx::query_as(&AllergenQueries::SelectAllergens.to_string()).fetch_all(&mut *conn).await?;
+    let labels = sqlx::query_as(&AllergenQueries::SelectLabels.to_string()).fetch_all(&mut *conn).await?;
+    Ok(assemble(allergens, labels))
+}
+
+pub(crate) async fn find(conn: &mut MySqlConnection, id: i32) -> Option<Allergen> {
+    all(conn).await.ok()?.into_iter().find(|allergen| allergen.Id == id)
+}
+
+pub(crate) async fn select_allergens(mut conn: PoolConnection<MySql>, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!("Select allergens");
+    match all(conn.as_mut()).await {
+        Ok(allergens) => encode(allergens, format),
+        Err(e) => {
+            error!(error = %e, "Select allergens failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get allergens".to_string()))
+        }
+    }
+}
+
+/// Creates the entry, or replaces it and all its labels when `create` is false. Returns
+/// the whole catalog afterwards.
+pub(crate) async fn save(mut conn: PoolConnection<MySql>, allergen: &Allergen, create: bool, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(allergen = allergen.Id, create, "Save allergen");
+    validate(allergen)?;
+    let failed = |e: Error| {
+        error!(error = %e, "Save allergen failed");
+        match e {
+            Error::Database(e) if e.is_unique_violation() => (StatusCode::CONFLICT, "An allergen already has this code".to_string()),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the allergen".to_string()),
+        }
+    };
+
+    let code = allergen.Code.trim();
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let id = if create {
+        let result = sqlx::query(&AllergenQueries::InsertAllergen.to_string())
+            .bind(code)
+            .bind(&allergen.Kind)
+            .bind(allergen.IsActive)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        result.last_insert_id() as i32
+    } else {
+        if find(&mut tx, allergen.Id).await.is_none() {
+            return Err((StatusCode::NOT_FOUND, "No such allergen".to_string()));
+        }
+        sqlx::query(&AllergenQueries::UpdateAllergen.to_string())
+            .bind(code)
+            .bind(&allergen.Kind)
+            .bind(allergen.IsActive)
+            .bind(allergen.Id)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        allergen.Id
+    };
+    sqlx::query(&AllergenQueries::DeleteLabels.to_string())
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    for label in &allergen.Labels {
+        sqlx::query(&AllergenQueries::InsertLabel.to_string())
+            .bind(id)
+            .bind(label.Language.trim())
+            .bind(label.Label.trim())
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+    }
+    tx.commit().await.map_err(failed)?;
+    debug!(allergen = id, "Save allergen succeeded");
+
+    let allergens = all(conn.as_mut()).await.map_err(failed)?;
+    encode(allergens, format)
+}
+
+/// The labels on one line, `language:label` each, as the audit keeps them.
+pub(crate) fn labels_text(allergen: &Allergen) -> String {
+    allergen
+        .Labels
+        .iter()
+        .map(|label| format!("{}:{}", label.Language, label.Label))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Kind, state and labels when they changed, the code always to tell entries apart.
+pub(crate) fn allergen_changes(before: Option<&Allergen>, after: &Allergen) -> Vec<AuditChange> {
+    let mut changes = vec![change("Code", before.map(|before| before.Code.clone()), Some(after.Code.clone()))];
+    let fields = [
+        ("Kind", before.map(|before| before.Kind.clone()), after.Kind.clone()),
+        ("IsActive", before.map(|before| before.IsActive.to_string()), after.IsActive.to_string()),
+        ("Labels", before.map(labels_text), labels_text(after)),
+    ];
+    for (field, before, after) in fields {
+        if before.as_ref() != Some(&after) {
+            changes.push(change(field, before, Some(after)));
+        }
+    }
+    changes
+}
+
+/// Allergies and diets of a beneficiary and its members, catalog entries first.
+pub(crate) async fn allergies(conn: &mut MySqlConnection, beneficiary_id: i32) -> Result<Vec<v1::Allergy>, Error> {
+    let allergies: Vec<AllergyRow> = sqlx::query_as(&AllergenQueries::SelectAllergies.to_string())
+        .bind(beneficiary_id)
+        .fetch_all(conn)
+        .await?;
+    Ok(allergies.into_iter().map(v1::Allergy::from).collect())
+}
+
+pub(crate) async fn select_allergies(mut conn: PoolConnection<MySql>, beneficiary_id: i32, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(beneficiary_id, "Select allergies");
+    match allergies(conn.as_mut(), beneficiary_id).await {
+        Ok(allergies) => encode(allergies, format),
+        Err(e) => {
+            error!(error = %e, "Select allergies failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get allergies".to_string()))
+        }
+    }
+}
+
+/// Links a beneficiary to an active catalog entry, or changes the severity of the link.
+/// Returns the code of the entry and whether the link is new.
+pub(crate) async fn link(mut conn: PoolConnection<MySql>, link: &TokenAllergenLink, user: &UserRole) -> Result<(String, bool), (StatusCode, String)> {
+    debug!(beneficiary_id = link.BeneficiaryId, allergen = link.AllergenId, "Link allergen");
+    let severity = validate_severity(link.Severity.as_deref())?;
+    let failed = |e: Error| {
+        error!(error = %e, "Link allergen failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not link the allergen".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let code: String = sqlx::query_scalar(&AllergenQueries::SelectActiveCode.to_string())
+        .bind(link.AllergenId)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(failed)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "No such active allergen".to_string()))?;
+    let links: i64 = sqlx::query_scalar(&AllergenQueries::CountLinks.to_string())
+        .bind(link.BeneficiaryId)
+        .bind(link.AllergenId)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(failed)?;
+    let created = links == 0;
+    if created {
+        sqlx::query(&AllergenQueries::InsertLink.to_string())
+            .bind(link.BeneficiaryId)
+            .bind(&code)
+            .bind(link.AllergenId)
+            .bind(&severity)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+    } else {
+        sqlx::query(&AllergenQueries::UpdateLink.to_string())
+            .bind(&severity)
+            .bind(link.BeneficiaryId)
+            .bind(link.AllergenId)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+    }
+    refresh_has_allergies(&mut tx, link.BeneficiaryId, user).await.map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+    debug!(created, "Link allergen succeeded");
+    Ok((code, created))
+}
+
+/// Moves the link to the trash, see `schema::trash`. Returns the code of the entry.
+pub(crate) async fn unlink(mut conn: PoolConnection<MySql>, link: &TokenAllergenLink, user: &UserRole) -> Result<String, (StatusCode, String)> {
+    debug!(beneficiary_id = link.BeneficiaryId, allergen = link.AllergenId, "Unlink allergen");
+    let failed = |e: Error| {
+        error!(error = %e, "Unlink allergen failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not unlink the allergen".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let code = find(&mut tx, link.AllergenId).await.map(|allergen| allergen.Code).unwrap_or_default();
+    let result = sqlx::query(&AllergenQueries::DeleteLink.to_string())
+        .bind(user.Id)
+        .bind(link.BeneficiaryId)
+        .bind(link.AllergenId)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "The beneficiary is not linked to this allergen".to_string()));
+    }
+    refresh_has_allergies(&mut tx, link.BeneficiaryId, user).await.map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+    debug!("Unlink allergen succeeded");
+    Ok(code)
+}
+
+/// Sets `HasAllergies` from the allergy rows, returns whether it changed.
+pub(crate) async fn update_has_allergies(conn: &mut MySqlConnection, beneficiary_id: i32) -> Result<bool, Error> {
+    let result = sqlx::query(&AllergenQueries::RefreshHasAllergies.to_string())
+        .bind(beneficiary_id)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// `update_has_allergies` inside the caller's transaction, recording a version of the
+/// beneficiary when the flag changed.
+pub(crate) async fn refresh_has_allergies(conn: &mut MySqlConnection, beneficiary_id: i32, user: &UserRole) -> Result<(), Error> {
+    if update_has_allergies(&mut *conn, beneficiary_id).await? {
+        debug!(beneficiary_id, "HasAllergies changed");
+        history::capture(conn, beneficiary_id, user).await?;
+    }
+    Ok(())
+}
+
+/// `refresh_has_allergies` in a transaction of its own, after a write that had none. A
+/// failure is only logged, the next allergy write of the beneficiary sets the flag again.
+pub(crate) async fn refresh(mut conn: PoolConnection<MySql>, beneficiary_id: i32, user: &UserRole) {
+    let result = async {
+        let mut tx = conn.begin().await?;
+        refresh_has_allergies(&mut tx, beneficiary_id, user).await?;
+        tx.commit().await
+    }.await;
+    if let Err(e) = result {
+        error!(error = %e, beneficiary_id, "Refresh HasAllergies failed");
+    }
+}
@@ -0,0 +1,313 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use axum::http::StatusCode;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{Error, MySql, MySqlPool};
+use sqlx::pool::PoolConnection;
+use sqlx::types::chrono::NaiveDate;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{AuditChange, TokenAuditQuery};
+use crate::route::acquire_connection;
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::user::UserRole;
+use crate::telemetry::{self, Pii};
+use tracing::{debug, error, warn};
+
+/// Most recent entries returned by one query, narrow the dates to see older ones.
+pub(crate) const MAX_ENTRIES: u32 = 1000;
+
+/// Fields whose values identify a beneficiary or hold confidential text: only their length is kept.
+const MASKED_FIELDS: &[&str] = &["FirstName", "LastName", "Email", "Phone", "Address", "PostalCode", "Birth", "Note", "Search"];
+
+enum AuditQueries {
+    InsertEntry,
+    SelectEntries,
+}
+
+impl Display for AuditQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditQueries::InsertEntry => write!(f,
+                "INSERT INTO AuditLog (UserId, Username, Role, Action, BeneficiaryId, RequestId, Changes) \
+                VALUES (?, ?, ?, ?, ?, ?, ?)"
+            ),
+            AuditQueries::SelectEntries => write!(f,
+                "SELECT Id, DATE_FORMAT(Date, '%Y-%m-%d %H:%i:%s') AS Date, UserId, Username, Role, Action, \
+                BeneficiaryId, RequestId, Changes \
+                FROM AuditLog \
+                WHERE (? IS NULL OR BeneficiaryId = ?) \
+                AND (? IS NULL OR UserId = ?) \
+                AND (? IS NULL OR Date >= ?) \
+                AND (? IS NULL OR Date < ? + INTERVAL 1 DAY) \
+                ORDER BY Date DESC, Id DESC \
+                LIMIT {MAX_ENTRIES}"
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum AuditAction {
+    ReadBeneficiary,
+    ListBeneficiaries,
+    ReadHistory,
+    SearchBeneficiaries,
+    CreateBeneficiary,
+    UpdateBeneficiary,
+    InsertAllergy,
+    DeleteAllergy,
+    RestoreAllergy,
+    ReadAllergies,
+    LinkAllergen,
+    UnlinkAllergen,
+    CreateAllergen,
+    UpdateAllergen,
+    InsertPresence,
+    CheckIn,
+    OverrideCheckIn,
+    RecordDistribution,
+    CorrectDistribution,
+    ReadDistributions,
+    RecordPayment,
+    VoidPayment,
+    ReadStatement,
+    PrintReceipt,
+    PrintVisitSlip,
+    IssueCard,
+    RevokeCard,
+    LookUpCard,
+    PrintCard,
+    ComputeStats,
+    DeletePresence,
+    RestorePresence,
+    ReadMembers,
+    CreateMember,
+    UpdateMember,
+    DeleteMember,
+    RestoreMember,
+    ReadNotes,
+    CreateNote,
+    UpdateNote,
+    DeleteNote,
+    CreateNoteType,
+    UpdateNoteType,
+    RestoreNote,
+    SetFollowUp,
+    ClearFollowUp,
+    CompleteFollowUp,
+    CreateCategory,
+    UpdateCategory,
+    DeleteCategory,
+    ReassignCategory,
+    SetCategoryFee,
+    CreateUser,
+    UpdateUser,
+    DeleteUser,
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let action = match self {
+            AuditAction::ReadBeneficiary => "beneficiary.read",
+            AuditAction::ListBeneficiaries => "beneficiary.list",
+            AuditAction::ReadHistory => "beneficiary.history",
+            AuditAction::SearchBeneficiaries => "beneficiary.search",
+            AuditAction::CreateBeneficiary => "beneficiary.create",
+            AuditAction::UpdateBeneficiary => "beneficiary.update",
+            AuditAction::InsertAllergy => "allergy.insert",
+            AuditAction::DeleteAllergy => "allergy.delete",
+            AuditAction::RestoreAllergy => "allergy.restore",
+            AuditAction::ReadAllergies => "allergy.read",
+            AuditAction::LinkAllergen => "allergy.link",
+            AuditAction::UnlinkAllergen => "allergy.unlink",
+            AuditAction::CreateAllergen => "allergen.create",
+            AuditAction::UpdateAllergen => "allergen.update",
+            AuditAction::InsertPresence => "presence.insert",
+            AuditAction::CheckIn => "presence.checkin",
+            AuditAction::OverrideCheckIn => "presence.override",
+            AuditAction::RecordDistribution => "distribution.record",
+            AuditAction::CorrectDistribution => "distribution.correct",
+            AuditAction::ReadDistributions => "distribution.read",
+            AuditAction::RecordPayment => "payment.record",
+            AuditAction::VoidPayment => "payment.void",
+            AuditAction::ReadStatement => "payment.statement",
+            AuditAction::PrintReceipt => "payment.receipt",
+            AuditAction::PrintVisitSlip => "presence.slip",
+            AuditAction::IssueCard => "card.issue",
+            AuditAction::RevokeCard => "card.revoke",
+            AuditAction::LookUpCard => "card.lookup",
+            AuditAction::PrintCard => "card.print",
+            AuditAction::ComputeStats => "stats.compute",
+            AuditAction::DeletePresence => "presence.delete",
+            AuditAction::RestorePresence => "presence.restore",
+            AuditAction::ReadMembers => "member.read",
+            AuditAction::CreateMember => "member.create",
+            AuditAction::UpdateMember => "member.update",
+            AuditAction::DeleteMember => "member.delete",
+            AuditAction::RestoreMember => "member.restore",
+            AuditAction::ReadNotes => "note.read",
+            AuditAction::CreateNote => "note.create",
+            AuditAction::UpdateNote => "note.update",
+            AuditAction::DeleteNote => "note.delete",
+            AuditAction::CreateNoteType => "notetype.create",
+            AuditAction::UpdateNoteType => "notetype.update",
+            AuditAction::RestoreNote => "note.restore",
+            AuditAction::SetFollowUp => "followup.set",
+            AuditAction::ClearFollowUp => "followup.clear",
+            AuditAction::CompleteFollowUp => "followup.complete",
+            AuditAction::CreateCategory => "category.create",
+            AuditAction::UpdateCategory => "category.update",
+            AuditAction::DeleteCategory => "category.delete",
+            AuditAction::ReassignCategory => "category.reassign",
+            AuditAction::SetCategoryFee => "category.fee",
+            AuditAction::CreateUser => "user.create",
+            AuditAction::UpdateUser => "user.update",
+            AuditAction::DeleteUser => "user.delete",
+        };
+        write!(f, "{action}")
+    }
+}
+
+/// Records a field change, masking personal data and never keeping a password.
+pub(crate) fn change(field: &str, before: Option<String>, after: Option<String>) -> AuditChange {
+    let mask = |value: Option<String>| match field {
+        "Password" => value.map(|_| "<redacted>".to_string()),
+        _ if MASKED_FIELDS.contains(&field) => value.map(|value| Pii(&value).to_string()),
+        _ => value,
+    };
+    AuditChange { Field: field.to_string(), Before: mask(before), After: mask(after) }
+}
+
+/// Fields that differ between two versions of the same record, personal data masked.
+pub(crate) fn diff<T: Serialize>(before: &T, after: &T) -> Vec<AuditChange> {
+    changed_fields(before, after)
+        .into_iter()
+        .map(|changed| change(&changed.Field, changed.Before, changed.After))
+        .collect()
+}
+
+/// Fields that differ between two versions of the same record, values as they are.
+pub(crate) fn changed_fields<T: Serialize>(before: &T, after: &T) -> Vec<AuditChange> {
+    let (Ok(Value::Object(before)), Ok(Value::Object(after))) = (serde_json::to_value(before), serde_json::to_value(after)) else {
+        return Vec::new();
+    };
+    after
+        .iter()
+        .filter(|(field, value)| before.get(*field) != Some(*value))
+        .map(|(field, value)| AuditChange { Field: field.clone(), Before: before.get(field).and_then(text), After: text(value) })
+        .collect()
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// The caller of an audited request, as authenticated by `validate_token`.
+pub(crate) struct Audit {
+    pub(crate) actor: UserRole,
+    pub(crate) request_id: String,
+}
+
+impl Audit {
+    pub(crate) fn new(actor: &UserRole, telemetry::RequestId(request_id): telemetry::RequestId) -> Audit {
+        Audit { actor: actor.clone(), request_id }
+    }
+
+    /// Writes an entry. A failure is logged but does not fail the request, which already happened.
+    pub(crate) async fn record(&self, pool: &Arc<MySqlPool>, action: AuditAction, beneficiary: Option<i32>, changes: Vec<AuditChange>) {
+        debug!(%action, beneficiary_id = beneficiary, "Audit");
+        let Ok(mut conn) = acquire_connection(pool.clone()).await else {
+            error!(%action, "Audit failed: no database connection");
+            return;
+        };
+        let result = sqlx::query(&AuditQueries::InsertEntry.to_string())
+            .bind(self.actor.Id)
+            .bind(&self.actor.Username)
+            .bind(&self.actor.Role)
+            .bind(action.to_string())
+            .bind(beneficiary)
+            .bind(&self.request_id)
+            .bind(serde_json::to_string(&changes).unwrap_or_else(|_| "[]".to_string()))
+            .execute(conn.as_mut())
+            .await;
+        if let Err(e) = result {
+            error!(error = %e, %action, "Audit failed");
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct AuditRow {
+    Id: i64,
+    Date: String,
+    UserId: i32,
+    Username: String,
+    Role: String,
+    Action: String,
+    BeneficiaryId: Option<i32>,
+    RequestId: String,
+    Changes: String,
+}
+
+impl From<AuditRow> for v1::AuditEntry {
+    fn from(row: AuditRow) -> Self {
+        let changes = serde_json::from_str(&row.Changes).unwrap_or_else(|e| {
+            warn!(error = %e, audit_id = row.Id, "Unreadable audit changes");
+            Vec::new()
+        });
+        v1::AuditEntry {
+            Id: row.Id,
+            Date: row.Date,
+            UserId: row.UserId,
+            Username: row.Username,
+            Role: row.Role,
+            Action: row.Action,
+            BeneficiaryId: row.BeneficiaryId,
+            RequestId: row.RequestId,
+            Changes: changes,
+        }
+    }
+}
+
+pub(crate) fn parse_date(date: &Option<String>) -> Result<Option<NaiveDate>, (StatusCode, String)> {
+    date.as_deref()
+        .filter(|date| !date.is_empty())
+        .map(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .transpose()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Dates must be written YYYY-MM-DD".to_string()))
+}
+
+pub(crate) async fn select_entries(mut conn: PoolConnection<MySql>, query: &TokenAuditQuery, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(beneficiary_id = query.BeneficiaryId, user_id = query.UserId, "Select audit log");
+    let from = parse_date(&query.From)?;
+    let to = parse_date(&query.To)?;
+
+    let entries: Result<Vec<AuditRow>, Error> = sqlx::query_as(&AuditQueries::SelectEntries.to_string())
+        .bind(query.BeneficiaryId)
+        .bind(query.BeneficiaryId)
+        .bind(query.UserId)
+        .bind(query.UserId)
+        .bind(from)
+        .bind(from)
+        .bind(to)
+        .bind(to)
+        .fetch_all(conn.as_mut())
+        .await;
+
+    match entries {
+        Ok(entries) => {
+            debug!(count = entries.len(), "Select audit log succeeded");
+            encode(entries.into_iter().map(v1::AuditEntry::from).collect::<Vec<_>>(), format)
+        }
+        Err(e) => {
+            error!(error = %e, "Select audit log failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get audit log".to_string()))
+        }
+    }
+}
@@ -2,17 +2,25 @@ use std::fmt::{Display, Formatter};
 use axum::http::StatusCode;
     use bincode::{Encode};
     use serde::{Deserialize, Serialize};
-    use sqlx::{Decode, Error, MySql, MySqlConnection, Row};
+    use sqlx::{Connection, Decode, Error, MySql, MySqlConnection, Row};
     use sqlx::pool::PoolConnection;
     use crate::schema::{encode, encrypt};
+    use crate::schema::format::{Encoded, Format};
+    use harmony_protocol::v1;
+use crate::schema::allergen::HAS_ALLERGIES;
+use crate::schema::audit::{diff, AuditChange};
 use crate::schema::details::Details;
+use crate::schema::history;
+use crate::schema::household::{adult_count, kid_count};
 use crate::schema::user::UserRole;
+use tracing::{debug, error, warn};
+use crate::telemetry::Pii;
 
 pub(crate) trait BeneficiaryAction{
-        async fn get_beneficiaries(conn: PoolConnection<MySql>, role: UserRole) -> Result<Vec<u8>, (StatusCode, String)>;
-        async fn search(conn: PoolConnection<MySql>, role: UserRole, search: &str) -> Result<Vec<u8>, (StatusCode, String)>;
-        async fn get_beneficiary(conn: PoolConnection<MySql>, role: UserRole, id: i32) -> Result<Vec<u8>, (StatusCode, String)>;
-        async fn update_beneficiary(conn: PoolConnection<MySql>, role : UserRole, beneficiary: Beneficiary) -> Result<StatusCode, (StatusCode, String)>;
+        async fn get_beneficiaries(conn: PoolConnection<MySql>, role: UserRole, format: Format) -> Result<Encoded, (StatusCode, String)>;
+        async fn search(conn: PoolConnection<MySql>, role: UserRole, search: &str, format: Format) -> Result<Encoded, (StatusCode, String)>;
+        async fn get_beneficiary(conn: PoolConnection<MySql>, role: UserRole, id: i32, format: Format) -> Result<Encoded, (StatusCode, String)>;
+        async fn update_beneficiary(conn: PoolConnection<MySql>, role : UserRole, beneficiary: Beneficiary) -> Result<Vec<AuditChange>, (StatusCode, String)>;
     }
 
 pub(crate) enum BeneficiaryQueries{
@@ -30,6 +38,7 @@ pub(crate) enum BeneficiaryQueries{
 
 impl Display for BeneficiaryQueries{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+       let (kid, adult) = (kid_count("?"), adult_count("?"));
        match self {
            BeneficiaryQueries::SelectUserBeneficiaries => {
                write!(f,
@@ -95,16 +104,16 @@ impl Display for BeneficiaryQueries{
            BeneficiaryQueries::UpdateUserBeneficiary => {
                write!(f,
                       "UPDATE `Beneficiary` \
-                       SET `FirstName` = ?, `LastName` = ?, `MonthlyAmount` = ?, `WeeklyAmount` = ?, WHERE `Id` = ?"
+                       SET `FirstName` = ?, `LastName` = ?, `MonthlyAmount` = ?, `WeeklyAmount` = ? WHERE `Id` = ?"
                )
            }
            BeneficiaryQueries::UpdateAdminBeneficiary => {
                 write!(f,
                        "UPDATE `Beneficiary` \
-                       SET `FirstName` = ?, `LastName` = ?, `Email` = ?, `Phone` = ?, `Address` = ?, `PostalCode` = ?,  `Kid` = ?, `Adult` = ?, \
+                       SET `FirstName` = ?, `LastName` = ?, `Email` = ?, `Phone` = ?, `Address` = ?, `PostalCode` = ?,  `Kid` = {kid}, `Adult` = {adult}, \
                        `MonthlyAmount` = ?, `WeeklyAmount` = ?, `Category` = ?, `MonthlyLimit` = ?, `WeeklyLimit` = ?, \
                        `Birth` = ?, `LastPresence` = ?, `Sexe` = ?, `Language` = ?, `Origin` = ?, \
-                       `City` = ?, `IsActive` = ?, `HasAllergies` = ?, `HasGeneralNote` = ? \
+                       `City` = ?, `IsActive` = ?, `HasAllergies` = {HAS_ALLERGIES}, `HasGeneralNote` = ? \
                        WHERE `Id` = ?"
                 )
            }
@@ -113,16 +122,16 @@ impl Display for BeneficiaryQueries{
                        "UPDATE `Beneficiary` \
                        SET `FirstName` = ?, `LastName` = ?, `Email` = ?,`Phone` = ?, `Address` = ?, `PostalCode` = ?, \
                        `MonthlyAmount` = ?, `WeeklyAmount` = ?, `Category` = ?, `MonthlyLimit` = ?, `WeeklyLimit` = ?, \
-                       `Kid` = ?, `Adult` = ?, `Birth` = ?, `LastPresence` = ?, `Sexe` = ?, `Language` = ?, \
+                       `Kid` = {kid}, `Adult` = {adult}, `Birth` = ?, `LastPresence` = ?, `Sexe` = ?, `Language` = ?, \
                        `Origin` = ?, `City` = ?, `Study` = ?, `Income` = ?, `FamilySituation` = ?, `IsActive` = ?, \
-                       `IsSdf` = ?, `IsEmployed` = ?, `HasAllergies` = ?, `HasGeneralNote` = ?,  \
+                       `IsSdf` = ?, `IsEmployed` = ?, `HasAllergies` = {HAS_ALLERGIES}, `HasGeneralNote` = ? \
                        WHERE `Id` = ?"
                 )
            },
        }
     }
 }
-    #[derive(sqlx::FromRow, Encode,Decode, Serialize, Deserialize, Clone)]
+    #[derive(sqlx::FromRow, Encode,Decode, bincode::Decode, Serialize, Deserialize, Clone)]
     pub(crate) struct Beneficiary {
         pub(crate) Id: i32,
         pub(crate) FirstName: String,
@@ -172,61 +181,149 @@ impl Display for BeneficiaryQueries{
         pub(crate) HasGeneralNote: bool,
     }
 
+    impl From<Beneficiary> for v1::Beneficiary {
+        fn from(bene: Beneficiary) -> Self {
+            v1::Beneficiary {
+                    Id: bene.Id,
+                    FirstName: bene.FirstName,
+                    LastName: bene.LastName,
+                    Email: bene.Email,
+                    Phone: bene.Phone,
+                    Address: bene.Address,
+                    PostalCode: bene.PostalCode,
+                    Kid: bene.Kid,
+                    Adult: bene.Adult,
+                    MonthlyAmount: bene.MonthlyAmount,
+                    WeeklyAmount: bene.WeeklyAmount,
+                    Category: bene.Category,
+                    MonthlyLimit: bene.MonthlyLimit,
+                    WeeklyLimit: bene.WeeklyLimit,
+                    Birth: bene.Birth,
+                    LastPresence: bene.LastPresence,
+                    Sexe: bene.Sexe,
+                    Language: bene.Language,
+                    Origin: bene.Origin,
+                    City: bene.City,
+                    Study: bene.Study,
+                    Income: bene.Income,
+                    FamilySituation: bene.FamilySituation,
+                    IsActive: bene.IsActive,
+                    IsSdf: bene.IsSdf,
+                    IsEmployed: bene.IsEmployed,
+                    HasAllergies: bene.HasAllergies,
+                    HasGeneralNote: bene.HasGeneralNote,
+            }
+        }
+    }
+
 
     impl Beneficiary{
-        pub(crate) async fn create_beneficiary(mut conn : PoolConnection<MySql>, user_role: UserRole) -> Result<Vec<u8>, (StatusCode, String)> {
-            println!("->> {:>12} - Create Beneficiary", "Handler");
+        /// Inserts an empty beneficiary and reads it back projected for the caller role.
+        pub(crate) async fn insert_beneficiary(mut conn : PoolConnection<MySql>, user_role: UserRole) -> Result<Beneficiary, (StatusCode, String)> {
+            debug!("Create Beneficiary");
+            let mut tx = conn.begin().await.map_err(|e| {
+                error!(error = %e, "Could not start transaction");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Could not create beneficiary".to_string())
+            })?;
             let is_created =
                     sqlx::query(&BeneficiaryQueries::CreateBeneficiary.to_string())
                         .bind(encrypt("".as_bytes()))
                         .bind(encrypt("".as_bytes()))
                         .bind(encrypt("".as_bytes()))
                         .bind(encrypt("".as_bytes()))
-                        .execute(conn.as_mut())
+                        .execute(&mut *tx)
                         .await;
 
             if is_created.is_err(){
-                println!("->> {:>12} - Error: {:?}", "Handler", is_created);
+                error!(error = ?is_created, "Query failed");
                 return Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not create beneficiary".to_string()))
             }
 
             if let Ok(id) = sqlx::query("SELECT LAST_INSERT_ID()")
-                .fetch_one(conn.as_mut())
+                .fetch_one(&mut *tx)
                 .await
             {
                 let id = id.get::<u32, usize>(0);
+                if let Err(e) = history::capture(&mut tx, id as i32, &user_role).await {
+                    error!(error = %e, "Could not record the first version");
+                    return Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not create beneficiary".to_string()))
+                }
 
                 let query = match user_role.Role.as_str() {
                     "User" =>{format!("{} WHERE Id = {id}", BeneficiaryQueries::SelectUserDetails)}
                     "Admin" | "Dev" =>{format!("{} WHERE Id = {id}", BeneficiaryQueries::SelectAdminDetails)}
                     "TS" =>{format!("{} WHERE Id = {id}", BeneficiaryQueries::SelectTsDetails)}
                     _ => {
-                        println!("->> {:>12} - Error: Invalid role", "Handler");
+                        warn!("Invalid role");
                         return Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
                     }
                 };
 
                 let bene: Result<Beneficiary, Error> = sqlx::query_as(&query)
-                    .fetch_one(conn.as_mut())
+                    .fetch_one(&mut *tx)
                     .await;
 
 
                 match bene {
                     Ok(bene) => {
-                        println!("->> {:>12} - Create Beneficiary - SUCCESS", "Handler");
-                        encode(bene)
+                        tx.commit().await.map_err(|e| {
+                            error!(error = %e, "Could not commit transaction");
+                            (StatusCode::INTERNAL_SERVER_ERROR, "Could not create beneficiary".to_string())
+                        })?;
+                        debug!("Create Beneficiary succeeded");
+                        Ok(bene)
                     },
                     Err(e) => {
-                        println!("->> {:>12} - Error: {:?}", "Handler", e);
+                        error!(error = ?e, "Query failed");
                         Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get beneficiary".to_string()))
                     }
                 }
             }else{
-                println!("->> {:>12} - Error: Could not get last insert id", "Handler");
+                warn!("Could not get last insert id");
                 Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get last insert id".to_string()))
             }
         }
 
+        /// Keeps the fields the role sees through its details query, None for an unknown role.
+        pub(crate) fn project(mut self, role: &UserRole) -> Option<Beneficiary> {
+            let (contact, social) = match role.Role.as_str() {
+                "TS" => (true, true),
+                "Admin" | "Dev" => (true, false),
+                "User" => (false, false),
+                _ => return None,
+            };
+            if !contact {
+                for field in [&mut self.Email, &mut self.Phone, &mut self.Address, &mut self.PostalCode, &mut self.Sexe, &mut self.Origin, &mut self.City] {
+                    field.clear();
+                }
+            }
+            if !social {
+                for field in [&mut self.Study, &mut self.Income, &mut self.FamilySituation] {
+                    field.clear();
+                }
+                self.IsSdf = false;
+                self.IsEmployed = false;
+            }
+            Some(self)
+        }
+
+        /// Every column of a beneficiary, whatever the caller role, to compare versions of it.
+        pub(crate) async fn snapshot(conn: &mut MySqlConnection, id: i32) -> Result<Option<Beneficiary>, Error> {
+            sqlx::query_as(&format!("{} WHERE Id = ?", BeneficiaryQueries::SelectTsDetails))
+                .bind(id)
+                .fetch_optional(conn)
+                .await
+        }
+
+        /// `snapshot` locking the row until the caller's transaction ends, so nothing else
+        /// changes it between this read and the caller's own write.
+        pub(crate) async fn lock(conn: &mut MySqlConnection, id: i32) -> Result<Option<Beneficiary>, Error> {
+            sqlx::query_as(&format!("{} WHERE Id = ? FOR UPDATE", BeneficiaryQueries::SelectTsDetails))
+                .bind(id)
+                .fetch_optional(conn)
+                .await
+        }
+
         async fn find_beneficiaries(conn: &mut MySqlConnection, condition: String, user: UserRole) -> Result<Vec<Beneficiary>, Error>{
             match user.Role.as_str() {
                 "Admin" | "Dev" => sqlx::query_as(&format!("{} {}",BeneficiaryQueries::SelectAdminDetails, condition))
@@ -239,7 +336,7 @@ impl Display for BeneficiaryQueries{
                     .fetch_all(conn)
                     .await,
                 _ => {
-                    println!("->> {:>12} - Get Beneficiary - FAILED : Invalid Role", "Handler");
+                    warn!("Get Beneficiary failed: Invalid Role");
                     Err(Error::RowNotFound)
                 }
             }
@@ -247,8 +344,8 @@ impl Display for BeneficiaryQueries{
 
     }
     impl BeneficiaryAction for Beneficiary {
-        async fn get_beneficiaries(mut conn: PoolConnection<MySql>, user: UserRole) -> Result<Vec<u8>, (StatusCode,String)>{
-            println!("->> {:>12} - Get Beneficiaries - Role : {}", "Handler", user.Role);
+        async fn get_beneficiaries(mut conn: PoolConnection<MySql>, user: UserRole, format: Format) -> Result<Encoded, (StatusCode,String)>{
+            debug!(role = %user.Role, "Get Beneficiaries");
             let bene: Result<Vec<Beneficiary>, Error> = match user.Role.as_str() {
                 "Admin" | "Dev" => sqlx::query_as(&format!("{} WHERE IsActive = 1" ,BeneficiaryQueries::SelectAdminBeneficiaries))
                     .fetch_all(conn.as_mut())
@@ -260,35 +357,35 @@ impl Display for BeneficiaryQueries{
                     .fetch_all(conn.as_mut())
                     .await,
                 _ => {
-                    println!("->> {:>12} - Error: Invalid role", "Handler");
+                    warn!("Invalid role");
                     return Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
                 }
             };
 
             match bene {
                 Ok(bene) => {
-                    println!("->> {:>12} - Get Beneficiaries - SUCCESS", "Handler");
-                    encode(bene)
+                    debug!("Get Beneficiaries succeeded");
+                    encode(bene.into_iter().map(v1::Beneficiary::from).collect::<Vec<_>>(), format)
                 },
                 Err(e) => {
-                    println!("->> {:>12} - Error: {:?}", "Handler", e);
+                    error!(error = ?e, "Query failed");
                     Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get beneficiaries".to_string()))
                 }
             }
         }
 
-        async fn search(mut conn: PoolConnection<MySql>, user: UserRole, search: &str) -> Result<Vec<u8>, (StatusCode, String)> {
-            println!("->> {:>12} - Search Beneficiaries - Role : {}", "Handler", user.Role);
+        async fn search(mut conn: PoolConnection<MySql>, user: UserRole, search: &str, format: Format) -> Result<Encoded, (StatusCode, String)> {
+            debug!(role = %user.Role, search = %Pii(search), "Search Beneficiaries");
             let condition = format!("WHERE IsActive = 0 AND FirstName LIKE {search} OR LastName LIKE {search}");
             let bene = Self::find_beneficiaries(conn.as_mut(), condition, user)
                 .await
                 .map_err(|_e| (StatusCode::INTERNAL_SERVER_ERROR, "Could not find any beneficiary".to_string()))?;
-            println!("->> {:>12} - Search Beneficiaries - SUCCESS", "Handler");
-            encode(bene)
+            debug!("Search Beneficiaries succeeded");
+            encode(bene.into_iter().map(v1::Beneficiary::from).collect::<Vec<_>>(), format)
         }
 
-        async fn get_beneficiary(mut conn: PoolConnection<MySql>, user: UserRole, id: i32) -> Result<Vec<u8>, (StatusCode, String)> {
-            println!("->> {:>12} - Get Beneficiary - Role : {}", "Handler", user.Role);
+        async fn get_beneficiary(mut conn: PoolConnection<MySql>, user: UserRole, id: i32, format: Format) -> Result<Encoded, (StatusCode, String)> {
+            debug!(role = %user.Role, beneficiary_id = id, "Get Beneficiary");
             let bene : Result<Beneficiary, Error> = match user.Role.as_str() {
                 "Admin" | "Dev" => sqlx::query_as(&format!("{} WHERE Id = {id}",BeneficiaryQueries::SelectAdminDetails))
                     .fetch_one(conn.as_mut())
@@ -300,7 +397,7 @@ impl Display for BeneficiaryQueries{
                     .fetch_one(conn.as_mut())
                     .await,
                 _ => {
-                    println!("->> {:>12} - Get Beneficiary - FAILED : Invalid Role", "Handler");
+                    warn!("Get Beneficiary failed: Invalid Role");
                     return Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
                 }
             };
@@ -308,16 +405,26 @@ impl Display for BeneficiaryQueries{
             let details = Details::get_details(conn, user, id).await.map_err(|_e| (StatusCode::INTERNAL_SERVER_ERROR, "Could not get details".to_string()))?;
 
             if let Ok(bene) = bene {
-                println!("->> {:>12} - Get Beneficiary - SUCCESS", "Handler");
-                encode((bene, details))
+                debug!("Get Beneficiary succeeded");
+                encode::<v1::BeneficiaryDetails>((bene.into(), details.into()), format)
             } else {
-                println!("->> {:>12} - Get Beneficiary - FAILED : {}", "Handler", bene.err().unwrap());
+                error!(error = %bene.err().unwrap(), "Get Beneficiary failed");
                 Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get beneficiary".to_string()))
             }
         }
 
-        async fn update_beneficiary(mut conn: PoolConnection<MySql>, user: UserRole, bene: Beneficiary) -> Result<StatusCode, (StatusCode, String)>{
-            println!("->> {:>12} - Update Beneficiary - Role : {}", "Handler", user.Role);
+        /// Returns the fields changed, both versions being read in the transaction of the write.
+        async fn update_beneficiary(mut conn: PoolConnection<MySql>, user: UserRole, bene: Beneficiary) -> Result<Vec<AuditChange>, (StatusCode, String)>{
+            debug!(role = %user.Role, beneficiary_id = bene.Id, "Update Beneficiary");
+            let id = bene.Id;
+            let mut tx = conn.begin().await.map_err(|e| {
+                error!(error = %e, "Could not start transaction");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Could not update beneficiary".to_string())
+            })?;
+            let before = Self::lock(&mut tx, id).await.map_err(|e| {
+                error!(error = %e, "Update Beneficiary failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Could not update beneficiary".to_string())
+            })?;
             let result = match user.Role.as_str(){
                 "User" => {
                     sqlx::query(&format!("{}", BeneficiaryQueries::UpdateUserBeneficiary))
@@ -326,7 +433,7 @@ impl Display for BeneficiaryQueries{
                         .bind(bene.MonthlyAmount)
                         .bind(bene.WeeklyAmount)
                         .bind(bene.Id)
-                        .execute(conn.as_mut())
+                        .execute(&mut *tx)
                         .await
                 }
                 "Admin" | "Dev" => {
@@ -351,10 +458,9 @@ impl Display for BeneficiaryQueries{
                         .bind(bene.Origin)
                         .bind(bene.City)
                         .bind(bene.IsActive)
-                        .bind(bene.HasAllergies)
                         .bind(bene.HasGeneralNote)
                         .bind(bene.Id)
-                        .execute(conn.as_mut())
+                        .execute(&mut *tx)
                         .await
                 }
                 "TS" => {
@@ -384,25 +490,39 @@ impl Display for BeneficiaryQueries{
                         .bind(bene.IsActive)
                         .bind(bene.IsSdf)
                         .bind(bene.IsEmployed)
-                        .bind(bene.HasAllergies)
                         .bind(bene.HasGeneralNote)
                         .bind(bene.Id)
-                        .execute(conn.as_mut())
+                        .execute(&mut *tx)
                         .await
                 }
                 _ => {
-                    println!("->> {:>12} - Error: Invalid role", "Handler");
+                    warn!("Invalid role");
                     return Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
                 }
             };
 
+            let result = match result {
+                Ok(_) => history::capture(&mut tx, id, &user).await,
+                Err(e) => Err(e),
+            };
+            let result = match result {
+                Ok(_) => Self::snapshot(&mut tx, id).await,
+                Err(e) => Err(e),
+            };
             match result {
-               Ok(_) => {
-                   println!("->> {:>12} - Update Beneficiary - SUCCESS", "Handler");
-                   Ok(StatusCode::OK)
+               Ok(after) => {
+                   tx.commit().await.map_err(|e| {
+                       error!(error = %e, "Could not commit transaction");
+                       (StatusCode::INTERNAL_SERVER_ERROR, "Could not update beneficiary".to_string())
+                   })?;
+                   debug!("Update Beneficiary succeeded");
+                   Ok(match (before, after) {
+                       (Some(before), Some(after)) => diff(&before, &after),
+                       _ => Vec::new(),
+                   })
                }
                 Err(e) => {
-                    println!("->> {:>12} - Update Beneficiary - FAILED : {}", "Handler", e);
+                    error!(error = %e, "Update Beneficiary failed");
                     Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not update beneficiary".to_string()))
                 }
             }
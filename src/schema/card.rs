@@ -0,0 +1,287 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use axum::http::StatusCode;
+use qrcode::{Color, QrCode};
+use rand::Rng;
+use sqlx::{Connection, Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{Card, TokenCardCode, TokenCardPrint};
+use crate::bitmap::Bitmap;
+use crate::config::OrganizationConfig;
+use crate::pdf::{Page, MM};
+use crate::schema::audit::{change, AuditChange};
+use crate::schema::beneficiary::Beneficiary;
+use crate::schema::receipt::Holder;
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+/// Crockford's base 32: digits and capitals without I, L, O and U, which read alike.
+pub(crate) const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Symbols in a code, 80 random bits.
+pub(crate) const LENGTH: usize = 16;
+
+pub(crate) enum CardQueries {
+    SelectBeneficiary,
+    LockBeneficiary,
+    SelectCurrent,
+    InsertCard,
+    RevokeCards,
+}
+
+impl Display for CardQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CardQueries::SelectBeneficiary => write!(f, "SELECT BeneficiaryId FROM BeneficiaryCard WHERE Code = ? AND RevokedAt IS NULL"),
+            CardQueries::LockBeneficiary => write!(f, "SELECT Id FROM Beneficiary WHERE Id = ? FOR UPDATE"),
+            CardQueries::SelectCurrent => write!(f,
+                "SELECT Code, BeneficiaryId, DATE_FORMAT(IssuedAt, '%Y-%m-%d %H:%i:%s') AS IssuedAt FROM BeneficiaryCard \
+                WHERE BeneficiaryId = ? AND RevokedAt IS NULL ORDER BY IssuedAt DESC LIMIT 1"
+            ),
+            CardQueries::InsertCard => write!(f, "INSERT INTO BeneficiaryCard (Code, BeneficiaryId, IssuedBy) VALUES (?, ?, ?)"),
+            CardQueries::RevokeCards => write!(f,
+                "UPDATE BeneficiaryCard SET RevokedAt = NOW(), RevokedBy = ? WHERE BeneficiaryId = ? AND RevokedAt IS NULL"
+            ),
+        }
+    }
+}
+
+/// How a card is printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CardFormat {
+    Png,
+    Pdf,
+}
+
+impl FromStr for CardFormat {
+    type Err = (StatusCode, String);
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "png" => Ok(CardFormat::Png),
+            "pdf" => Ok(CardFormat::Pdf),
+            _ => Err((StatusCode::BAD_REQUEST, format!("Unknown card format: {format}"))),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct CardRow {
+    Code: String,
+    BeneficiaryId: i32,
+    IssuedAt: String,
+}
+
+impl From<CardRow> for Card {
+    fn from(row: CardRow) -> Self {
+        Card { Code: display(&row.Code), BeneficiaryId: row.BeneficiaryId, IssuedAt: row.IssuedAt }
+    }
+}
+
+/// A code as stored, whatever the case, spaces and dashes it was typed or scanned with.
+pub(crate) fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// A new code, as stored.
+pub(crate) fn generate() -> String {
+    let mut rng = rand::thread_rng();
+    (0..LENGTH).map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char).collect()
+}
+
+/// A stored code grouped by four, as printed.
+pub(crate) fn display(code: &str) -> String {
+    code.as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Roles that may revoke a card, every role may look one up or print it.
+pub(crate) fn can_revoke(role: &UserRole) -> bool {
+    matches!(role.Role.as_str(), "TS" | "Admin" | "Dev")
+}
+
+/// The last group of a code, enough to tell cards apart in the audit log.
+pub(crate) fn card_changes(card: &Card) -> Vec<AuditChange> {
+    let last = card.Code.rsplit('-').next().unwrap_or_default();
+    vec![change("Card", None, Some(format!("****-****-****-{last}")))]
+}
+
+/// The beneficiary holding this card, None when the code is unknown or revoked.
+pub(crate) async fn resolve(conn: &mut MySqlConnection, code: &str) -> Result<Option<i32>, Error> {
+    sqlx::query_scalar(&CardQueries::SelectBeneficiary.to_string())
+        .bind(normalize(code))
+        .fetch_optional(conn)
+        .await
+}
+
+async fn select_current(conn: &mut MySqlConnection, beneficiary_id: i32) -> Result<Option<Card>, Error> {
+    let row: Option<CardRow> = sqlx::query_as(&CardQueries::SelectCurrent.to_string())
+        .bind(beneficiary_id)
+        .fetch_optional(conn)
+        .await?;
+    Ok(row.map(Card::from))
+}
+
+/// Locks the beneficiary, revokes its cards when asked to and makes sure one is current.
+/// Returns the current card and whether it was issued now.
+async fn issue(mut conn: PoolConnection<MySql>, beneficiary_id: i32, user: &UserRole, revoke: bool) -> Result<(Card, bool), (StatusCode, String)> {
+    let failed = |e: Error| {
+        error!(error = %e, "Issue card failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not issue the card".to_string())
+    };
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let known: Option<i32> = sqlx::query_scalar(&CardQueries::LockBeneficiary.to_string())
+        .bind(beneficiary_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if known.is_none() {
+        return Err((StatusCode::NOT_FOUND, "No such beneficiary".to_string()));
+    }
+    if revoke {
+        sqlx::query(&CardQueries::RevokeCards.to_string())
+            .bind(user.Id)
+            .bind(beneficiary_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+    } else if let Some(card) = select_current(&mut tx, beneficiary_id).await.map_err(failed)? {
+        return Ok((card, false));
+    }
+    sqlx::query(&CardQueries::InsertCard.to_string())
+        .bind(generate())
+        .bind(beneficiary_id)
+        .bind(user.Id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    let card = select_current(&mut tx, beneficiary_id)
+        .await
+        .map_err(failed)?
+        .ok_or_else(|| failed(Error::RowNotFound))?;
+    tx.commit().await.map_err(failed)?;
+    debug!(beneficiary_id, "Card issued");
+    Ok((card, true))
+}
+
+/// The card of the beneficiary, issued on the first request and kept until revoked.
+pub(crate) async fn current(conn: PoolConnection<MySql>, beneficiary_id: i32, user: &UserRole) -> Result<(Card, bool), (StatusCode, String)> {
+    debug!(beneficiary_id, "Select card");
+    issue(conn, beneficiary_id, user, false).await
+}
+
+/// Revokes the cards of the beneficiary and issues a new one.
+pub(crate) async fn reissue(conn: PoolConnection<MySql>, beneficiary_id: i32, user: &UserRole) -> Result<Card, (StatusCode, String)> {
+    debug!(beneficiary_id, "Reissue card");
+    issue(conn, beneficiary_id, user, true).await.map(|(card, _)| card)
+}
+
+/// Revokes the current card of the beneficiary, which then holds none. Returns the card revoked.
+pub(crate) async fn revoke(mut conn: PoolConnection<MySql>, beneficiary_id: i32, user: &UserRole) -> Result<Card, (StatusCode, String)> {
+    debug!(beneficiary_id, "Revoke card");
+    let failed = |e: Error| {
+        error!(error = %e, "Revoke card failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not revoke the card".to_string())
+    };
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let Some(card) = select_current(&mut tx, beneficiary_id).await.map_err(failed)? else {
+        return Err((StatusCode::NOT_FOUND, "The beneficiary holds no card".to_string()));
+    };
+    sqlx::query(&CardQueries::RevokeCards.to_string())
+        .bind(user.Id)
+        .bind(beneficiary_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+    Ok(card)
+}
+
+/// The beneficiary holding a card that is not revoked, as the caller role sees it.
+pub(crate) async fn lookup(mut conn: PoolConnection<MySql>, code: &str, user: &UserRole) -> Result<v1::Beneficiary, (StatusCode, String)> {
+    let failed = |e: Error| {
+        error!(error = %e, "Look up card failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not look up the card".to_string())
+    };
+    let id = resolve(conn.as_mut(), code)
+        .await
+        .map_err(failed)?
+        .ok_or((StatusCode::NOT_FOUND, "Unknown or revoked card".to_string()))?;
+    debug!(beneficiary_id = id, "Look up card");
+    let beneficiary = Beneficiary::snapshot(conn.as_mut(), id)
+        .await
+        .map_err(failed)?
+        .ok_or((StatusCode::NOT_FOUND, "Unknown or revoked card".to_string()))?;
+    beneficiary
+        .project(user)
+        .map(v1::Beneficiary::from)
+        .ok_or((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+}
+
+/// The QR code of a card, one bool per module, dark as true, row by row.
+fn modules(card: &Card) -> Result<(usize, Vec<bool>), (StatusCode, String)> {
+    let qr = QrCode::new(card.Code.as_bytes()).map_err(|e| {
+        error!(error = %e, "Encode card code failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not print the card".to_string())
+    })?;
+    Ok((qr.width(), qr.to_colors().into_iter().map(|color| color == Color::Dark).collect()))
+}
+
+/// A card the size of a bank card: the organization, the name and number of the holder and
+/// the code, written out and as a QR code.
+pub(crate) fn pdf(card: &Card, holder: &Holder, organization: &OrganizationConfig) -> Result<Vec<u8>, (StatusCode, String)> {
+    let mut page = Page::new(85.6 * MM, 54.0 * MM);
+    let left = 5.0 * MM;
+    page.text(left, 46.0 * MM, 9.0, true, &organization.name);
+    page.text(left, 41.5 * MM, 7.0, false, "Membership card");
+    page.text(left, 30.0 * MM, 11.0, true, &clip(&holder.Name, 22));
+    page.text(left, 25.0 * MM, 9.0, false, &format!("No. {}", holder.Id));
+    page.text(left, 6.0 * MM, 8.0, false, &card.Code);
+
+    let (width, dark) = modules(card)?;
+    let side = 34.0 * MM;
+    let module = side / (width + 8) as f32;
+    let (x, y) = (85.6 * MM - 4.0 * MM - side, (54.0 * MM - side) / 2.0);
+    for (i, _) in dark.iter().enumerate().filter(|(_, dark)| **dark) {
+        let (column, row) = ((i % width + 4) as f32, (i / width + 4) as f32);
+        page.rect(x + column * module, y + side - (row + 1.0) * module, module, module);
+    }
+    Ok(page.render())
+}
+
+/// The card of `pdf` as a picture of 856 by 540 pixels, 10 to the millimetre.
+pub(crate) fn png(card: &Card, holder: &Holder, organization: &OrganizationConfig) -> Result<Vec<u8>, (StatusCode, String)> {
+    let mut bitmap = Bitmap::new(856, 540);
+    bitmap.text(50, 50, 4, &clip(&organization.name, 18));
+    bitmap.text(50, 100, 2, "MEMBERSHIP CARD");
+    bitmap.text(50, 220, 3, &clip(&holder.Name, 24));
+    bitmap.text(50, 270, 3, &format!("NO. {}", holder.Id));
+    bitmap.text(50, 460, 3, &card.Code);
+
+    let (width, dark) = modules(card)?;
+    let module = 360 / (width as u32 + 8);
+    let side = module * (width as u32 + 8);
+    let (x, y) = (856 - 40 - side, (540 - side) / 2);
+    for (i, _) in dark.iter().enumerate().filter(|(_, dark)| **dark) {
+        let (column, row) = ((i % width) as u32 + 4, (i / width) as u32 + 4);
+        bitmap.rect(x + column * module, y + row * module, module, module);
+    }
+    bitmap.png().map_err(|e| {
+        error!(error = %e, "Encode card picture failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not print the card".to_string())
+    })
+}
+
+/// At most `length` characters, ending with a dot when cut.
+fn clip(text: &str, length: usize) -> String {
+    if text.chars().count() <= length {
+        return text.to_string();
+    }
+    text.chars().take(length - 1).chain(['.']).collect()
+}
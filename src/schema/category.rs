@@ -2,28 +2,74 @@
 use std::fmt::Display;
 use axum::http::StatusCode;
 use bincode::Encode;
-use sqlx::Error;
+use chrono::{Local, NaiveDate};
+use sqlx::{Connection, Error, MySqlConnection};
 use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::history;
+use crate::schema::user::UserRole;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{CategoryFee, TokenCategoryDelete, TokenCategoryFee, TokenCategoryId, TokenCategoryReassign};
+use tracing::{debug, error, warn};
 
-enum CategoryQueries{
+/// The day the fees of a category start from when it is created.
+const FIRST_DAY: &str = "1970-01-01";
+
+/// Joins `Categories` to the `CategoryFee` row in effect on the day bound, as `Fee`.
+pub(crate) const FEE_AT: &str = "LEFT JOIN CategoryFee AS Fee ON Fee.CategoryId = Categories.Id \
+    AND Fee.EffectiveFrom = (SELECT MAX(EffectiveFrom) FROM CategoryFee \
+    WHERE CategoryFee.CategoryId = Categories.Id AND CategoryFee.EffectiveFrom <= ?)";
+pub(crate) const MONTHLY_FEE: &str = "COALESCE(Fee.MonthlyFee, Categories.MonthlyFee)";
+pub(crate) const WEEKLY_FEE: &str = "COALESCE(Fee.WeeklyFee, Categories.WeeklyFee)";
+
+/// Categories with the fees in effect on the day bound first and how many beneficiaries
+/// are in each.
+fn columns() -> String {
+    format!(
+        "SELECT Categories.Id, Categories.Category, {MONTHLY_FEE} AS MonthlyFee, {WEEKLY_FEE} AS WeeklyFee, \
+        CAST((SELECT COUNT(*) FROM Beneficiary WHERE Beneficiary.Category = Categories.Id) AS UNSIGNED) AS UsedBy \
+        FROM Categories {FEE_AT}"
+    )
+}
+
+pub(crate) enum CategoryQueries{
     SelectCategories,
+    SelectCategory,
     CreateCategory,
     UpdateCategory,
     DeleteCategory,
+    CountUsers,
+    SelectUsers,
+    ReassignBeneficiary,
+    InsertFee,
+    SelectFees,
 }
 
 impl Display for CategoryQueries{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self{
-            CategoryQueries::SelectCategories => write!(f, "SELECT * FROM Categories"),
+            CategoryQueries::SelectCategories => write!(f, "{} ORDER BY Categories.Id", columns()),
+            CategoryQueries::SelectCategory => write!(f, "{} WHERE Categories.Id = ?", columns()),
             CategoryQueries::CreateCategory => write!(f, "INSERT INTO Categories (Category, MonthlyFee, WeeklyFee) VALUES (?, ?, ?)"),
             CategoryQueries::UpdateCategory => write!(f, "UPDATE Categories SET `Category` = ?, `MonthlyFee` = ?, `WeeklyFee` = ? WHERE `Id` = ?"),
             CategoryQueries::DeleteCategory => write!(f, "DELETE FROM Categories WHERE `Id` = ?"),
+            CategoryQueries::CountUsers => write!(f, "SELECT COUNT(*) FROM Beneficiary WHERE Category = ? FOR UPDATE"),
+            CategoryQueries::SelectUsers => write!(f, "SELECT Id FROM Beneficiary WHERE Category = ? ORDER BY Id FOR UPDATE"),
+            CategoryQueries::ReassignBeneficiary => write!(f, "UPDATE Beneficiary SET Category = ? WHERE Id = ? AND Category = ?"),
+            CategoryQueries::InsertFee => write!(f,
+                "INSERT INTO CategoryFee (CategoryId, EffectiveFrom, MonthlyFee, WeeklyFee, CreatedBy) VALUES (?, ?, ?, ?, ?) \
+                ON DUPLICATE KEY UPDATE MonthlyFee = VALUES(MonthlyFee), WeeklyFee = VALUES(WeeklyFee), \
+                CreatedAt = NOW(), CreatedBy = VALUES(CreatedBy)"
+            ),
+            CategoryQueries::SelectFees => write!(f,
+                "SELECT CategoryId, DATE_FORMAT(EffectiveFrom, '%Y-%m-%d') AS EffectiveFrom, MonthlyFee, WeeklyFee \
+                FROM CategoryFee WHERE CategoryId = ? ORDER BY EffectiveFrom"
+            ),
         }
     }
 }
 
-#[derive(Debug, sqlx::FromRow, serde::Serialize, serde::Deserialize, Encode)]
+#[derive(Debug, sqlx::FromRow, serde::Serialize, serde::Deserialize, Encode, bincode::Decode)]
 pub(crate) struct Categories {
     pub(crate) Id: i32,
     pub(crate) Category: String,
@@ -32,95 +78,296 @@ pub(crate) struct Categories {
     pub(crate) UsedBy : u32,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, Encode, bincode::Decode)]
 pub(crate) struct TokenCategory{
     pub(crate) Token: String,
     pub(crate) Category: Categories,
 }
 
+#[derive(sqlx::FromRow)]
+struct FeeRow {
+    CategoryId: i32,
+    EffectiveFrom: String,
+    MonthlyFee: f32,
+    WeeklyFee: f32,
+}
+
+impl From<Categories> for v1::Categories {
+    fn from(category: Categories) -> Self {
+        v1::Categories {
+            Id: category.Id,
+            Category: category.Category,
+            MonthlyFee: category.MonthlyFee,
+            WeeklyFee: category.WeeklyFee,
+            UsedBy: category.UsedBy,
+        }
+    }
+}
+
+impl From<FeeRow> for CategoryFee {
+    fn from(row: FeeRow) -> Self {
+        CategoryFee { CategoryId: row.CategoryId, EffectiveFrom: row.EffectiveFrom, MonthlyFee: row.MonthlyFee, WeeklyFee: row.WeeklyFee }
+    }
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+async fn insert_fee(conn: &mut MySqlConnection, id: i32, from: &str, monthly: f32, weekly: f32, user: Option<i32>) -> Result<(), Error> {
+    sqlx::query(&CategoryQueries::InsertFee.to_string())
+        .bind(id)
+        .bind(from)
+        .bind(monthly)
+        .bind(weekly)
+        .bind(user)
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
+
+/// Locks the category's range of `BeneficiaryCategory` until the transaction ends, so no
+/// beneficiary can join the category between the count and the delete.
+async fn count_users(conn: &mut MySqlConnection, id: i32) -> Result<i64, Error> {
+    sqlx::query_scalar(&CategoryQueries::CountUsers.to_string())
+        .bind(id)
+        .fetch_one(conn)
+        .await
+}
 
 impl Categories {
-    pub(crate) async fn select_categories(mut conn : sqlx::pool::PoolConnection<sqlx::MySql>) -> Result<Vec<u8>, (StatusCode, String)>{
-        println!("->> {:>12} - Select categories", "Handler");
+    /// The category with the fees in effect today.
+    pub(crate) async fn find(conn: &mut MySqlConnection, id: i32) -> Option<Categories>{
+        sqlx::query_as(&CategoryQueries::SelectCategory.to_string())
+            .bind(today())
+            .bind(id)
+            .fetch_optional(conn)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub(crate) async fn select_categories(mut conn : sqlx::pool::PoolConnection<sqlx::MySql>, format: Format) -> Result<Encoded, (StatusCode, String)>{
+        debug!("Select categories");
         let categories: Result<Vec<Categories>, Error> = sqlx::query_as(&CategoryQueries::SelectCategories.to_string())
+            .bind(today())
             .fetch_all(conn.as_mut())
-            .await.map_err(|e| {
-                println!("->> {:>12} - Select categories - FAILED : {}", "Handler", e);
-                e
-            });
+            .await;
         match categories {
             Ok(categories) => {
-                println!("->> {:>12} - Select categories - SUCCESS", "Handler");
-                if categories.is_empty(){
-                    println!("->> {:>12} - Select categories - FAILED : No categories found", "Handler");
-                    return Err((StatusCode::NOT_FOUND, "No categories found".to_string()));
-                }
-                encode(categories)
+                debug!("Select categories succeeded");
+                encode(categories.into_iter().map(v1::Categories::from).collect::<Vec<_>>(), format)
             },
             Err(e) => {
-                println!("->> {:>12} - Select categories - FAILED : {}", "Handler", e);
+                error!(error = %e, "Select categories failed");
                 Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not find categories".to_string()))
             },
         }
     }
 
-    pub(crate) async fn create_category(&self, mut conn : sqlx::pool::PoolConnection<sqlx::MySql>) -> Result<Vec<u8>, (StatusCode, String)>{
-        println!("->> {:>12} - Create category", "Handler");
-        let result = sqlx::query(&CategoryQueries::CreateCategory.to_string())
-            .bind(self.Category.clone())
-            .bind(self.MonthlyFee)
-            .bind(self.WeeklyFee)
-            .execute(conn.as_mut())
-            .await;
+    /// Creates the category with its fees in effect from the start.
+    pub(crate) async fn create_category(&self, mut conn : sqlx::pool::PoolConnection<sqlx::MySql>, format: Format) -> Result<Encoded, (StatusCode, String)>{
+        debug!("Create category");
+        let result = async {
+            let mut tx = conn.begin().await?;
+            let id = sqlx::query(&CategoryQueries::CreateCategory.to_string())
+                .bind(self.Category.clone())
+                .bind(self.MonthlyFee)
+                .bind(self.WeeklyFee)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_id() as i32;
+            insert_fee(&mut tx, id, FIRST_DAY, self.MonthlyFee, self.WeeklyFee, None).await?;
+            tx.commit().await
+        }.await;
 
         match result {
             Ok(_) => {
-                println!("->> {:>12} - Create category - SUCCESS", "Handler");
-                Self::select_categories(conn).await
+                debug!("Create category succeeded");
+                Self::select_categories(conn, format).await
             },
             Err(e) => {
-                println!("->> {:>12} - Create category - FAILED : {}", "Handler", e);
+                error!(error = %e, "Create category failed");
                 Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not create category".to_string()))
             }
         }
     }
 
-    pub(crate) async fn update_category(&self, mut conn : sqlx::pool::PoolConnection<sqlx::MySql>) -> Result<Vec<u8>, (StatusCode, String)>{
-        let result = sqlx::query(&CategoryQueries::UpdateCategory.to_string())
-            .bind(self.Category.clone())
-            .bind(self.MonthlyFee)
-            .bind(self.WeeklyFee)
-            .bind(self.Id)
-            .execute(conn.as_mut())
-            .await;
+    /// Renames the category. Fees that differ from today's take effect today, earlier
+    /// charges keeping the fees of their day.
+    pub(crate) async fn update_category(&self, mut conn : sqlx::pool::PoolConnection<sqlx::MySql>, format: Format) -> Result<Encoded, (StatusCode, String)>{
+        let result = async {
+            let mut tx = conn.begin().await?;
+            let before = Self::find(&mut tx, self.Id).await;
+            sqlx::query(&CategoryQueries::UpdateCategory.to_string())
+                .bind(self.Category.clone())
+                .bind(self.MonthlyFee)
+                .bind(self.WeeklyFee)
+                .bind(self.Id)
+                .execute(&mut *tx)
+                .await?;
+            if before.is_some_and(|before| before.MonthlyFee != self.MonthlyFee || before.WeeklyFee != self.WeeklyFee) {
+                insert_fee(&mut tx, self.Id, &today().to_string(), self.MonthlyFee, self.WeeklyFee, None).await?;
+            }
+            tx.commit().await
+        }.await;
 
         match result {
             Ok(_) => {
-                println!("->> {:>12} - Update category - SUCCESS", "Handler");
-                Self::select_categories(conn).await
+                debug!("Update category succeeded");
+                Self::select_categories(conn, format).await
             },
             Err(e) => {
-                println!("->> {:>12} - Update category - FAILED : {}", "Handler", e);
+                error!(error = %e, "Update category failed");
                 Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not update category".to_string()))
             }
         }
     }
 
-    pub(crate) async fn delete_category(&self, mut conn : sqlx::pool::PoolConnection<sqlx::MySql>) -> Result<Vec<u8>, (StatusCode, String)>{
-        let result = sqlx::query(&CategoryQueries::DeleteCategory.to_string())
-            .bind(self.Id)
-            .execute(conn.as_mut())
-            .await;
+    /// Deletes a category no beneficiary is in, see `delete` to move them first.
+    pub(crate) async fn delete_category(&self, mut conn : sqlx::pool::PoolConnection<sqlx::MySql>, format: Format) -> Result<Encoded, (StatusCode, String)>{
+        let failed = |e: Error| {
+            error!(error = %e, "Delete category failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not delete category".to_string())
+        };
+        let mut tx = conn.begin().await.map_err(failed)?;
+        let used_by = count_users(&mut tx, self.Id).await.map_err(failed)?;
+        if used_by > 0 {
+            warn!(category = self.Id, used_by, "Delete category failed: Category in use");
+            return Err((StatusCode::CONFLICT, in_use(used_by)));
+        }
+        remove(&mut tx, self.Id).await.map_err(failed)?;
+        tx.commit().await.map_err(failed)?;
+        debug!("Delete category succeeded");
+        Self::select_categories(conn, format).await
+    }
+}
 
-        match result {
-            Ok(_) => {
-                println!("->> {:>12} - Delete category - SUCCESS", "Handler");
-                Self::select_categories(conn).await
-            },
-            Err(e) => {
-                println!("->> {:>12} - Delete category - FAILED : {}", "Handler", e);
-                Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not delete category".to_string()))
-            }
+fn in_use(used_by: i64) -> String {
+    format!("{used_by} beneficiaries are in this category, reassign them first")
+}
+
+/// Keeps the fee history: distributions and payments charged in the category still name it.
+async fn remove(conn: &mut MySqlConnection, id: i32) -> Result<(), Error> {
+    sqlx::query(&CategoryQueries::DeleteCategory.to_string())
+        .bind(id)
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
+
+/// Moves the beneficiaries listed, or every one when the list is empty, that are still in
+/// `from`, keeping a version of each. Returns how many moved.
+async fn move_beneficiaries(conn: &mut MySqlConnection, from: i32, to: i32, ids: &[i32], user: &UserRole) -> Result<u64, Error> {
+    let ids = if ids.is_empty() {
+        sqlx::query_scalar(&CategoryQueries::SelectUsers.to_string())
+            .bind(from)
+            .fetch_all(&mut *conn)
+            .await?
+    } else {
+        ids.to_vec()
+    };
+    let mut moved = 0;
+    for id in ids {
+        let result = sqlx::query(&CategoryQueries::ReassignBeneficiary.to_string())
+            .bind(to)
+            .bind(id)
+            .bind(from)
+            .execute(&mut *conn)
+            .await?;
+        if result.rows_affected() > 0 {
+            history::capture(&mut *conn, id, user).await?;
+            moved += 1;
         }
     }
-}
\ No newline at end of file
+    Ok(moved)
+}
+
+/// Moves beneficiaries between two categories, the target having to exist. Returns how
+/// many moved, beneficiaries not in `From` being left where they are.
+pub(crate) async fn reassign(mut conn: sqlx::pool::PoolConnection<sqlx::MySql>, request: &TokenCategoryReassign, user: &UserRole) -> Result<u64, (StatusCode, String)> {
+    debug!(from = request.From, to = request.To, "Reassign category");
+    if request.From == request.To {
+        return Err((StatusCode::BAD_REQUEST, "Pick another category to move to".to_string()));
+    }
+    let failed = |e: Error| {
+        error!(error = %e, "Reassign category failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not reassign the category".to_string())
+    };
+    let mut tx = conn.begin().await.map_err(failed)?;
+    if Categories::find(&mut tx, request.To).await.is_none() {
+        return Err((StatusCode::NOT_FOUND, "No such category to move to".to_string()));
+    }
+    let moved = move_beneficiaries(&mut tx, request.From, request.To, &request.BeneficiaryIds, user).await.map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+    Ok(moved)
+}
+
+/// Deletes a category, first moving its beneficiaries to `reassign_to`, which a category
+/// in use needs. Returns the category as it was and how many beneficiaries moved.
+pub(crate) async fn delete(mut conn: sqlx::pool::PoolConnection<sqlx::MySql>, id: i32, reassign_to: Option<i32>, user: &UserRole) -> Result<(Categories, u64), (StatusCode, String)> {
+    debug!(category = id, ?reassign_to, "Delete category");
+    let failed = |e: Error| {
+        error!(error = %e, "Delete category failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not delete category".to_string())
+    };
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let Some(category) = Categories::find(&mut tx, id).await else {
+        return Err((StatusCode::NOT_FOUND, "No such category".to_string()));
+    };
+    let used_by = count_users(&mut tx, id).await.map_err(failed)?;
+    let moved = match reassign_to {
+        Some(to) if to == id => return Err((StatusCode::BAD_REQUEST, "Pick another category to move to".to_string())),
+        Some(to) => {
+            if Categories::find(&mut tx, to).await.is_none() {
+                return Err((StatusCode::NOT_FOUND, "No such category to move to".to_string()));
+            }
+            move_beneficiaries(&mut tx, id, to, &[], user).await.map_err(failed)?
+        },
+        None if used_by > 0 => return Err((StatusCode::CONFLICT, in_use(used_by))),
+        None => 0,
+    };
+    remove(&mut tx, id).await.map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+    Ok((category, moved))
+}
+
+/// The day a fee takes effect from, fees being finite and not negative.
+pub(crate) fn validate_fee(fee: &CategoryFee) -> Result<NaiveDate, (StatusCode, String)> {
+    let day = NaiveDate::parse_from_str(&fee.EffectiveFrom, "%Y-%m-%d")
+        .map_err(|_| (StatusCode::BAD_REQUEST, "EffectiveFrom must be written YYYY-MM-DD".to_string()))?;
+    if [fee.MonthlyFee, fee.WeeklyFee].iter().any(|fee| !fee.is_finite() || *fee < 0.0) {
+        return Err((StatusCode::BAD_REQUEST, "Fees must be zero or more".to_string()));
+    }
+    Ok(day)
+}
+
+/// Sets the fees of a category from a day on, replacing a change made for the same day.
+/// Charges already made keep their amount.
+pub(crate) async fn set_fee(mut conn: sqlx::pool::PoolConnection<sqlx::MySql>, fee: &CategoryFee, user: &UserRole) -> Result<(), (StatusCode, String)> {
+    debug!(category = fee.CategoryId, from = fee.EffectiveFrom, "Set category fee");
+    let day = validate_fee(fee)?;
+    if Categories::find(conn.as_mut(), fee.CategoryId).await.is_none() {
+        return Err((StatusCode::NOT_FOUND, "No such category".to_string()));
+    }
+    insert_fee(conn.as_mut(), fee.CategoryId, &day.to_string(), fee.MonthlyFee, fee.WeeklyFee, Some(user.Id))
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Set category fee failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not set the fee".to_string())
+        })
+}
+
+/// Every fee change of a category, oldest first.
+pub(crate) async fn select_fees(mut conn: sqlx::pool::PoolConnection<sqlx::MySql>, id: i32, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(category = id, "Select category fees");
+    let fees: Vec<FeeRow> = sqlx::query_as(&CategoryQueries::SelectFees.to_string())
+        .bind(id)
+        .fetch_all(conn.as_mut())
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Select category fees failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not find the fees".to_string())
+        })?;
+    encode(fees.into_iter().map(CategoryFee::from).collect::<Vec<_>>(), format)
+}
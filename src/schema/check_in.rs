@@ -0,0 +1,147 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use sqlx::{Connection, Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+use chrono::Local;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{CheckIn, TokenCheckIn, TokenCheckInOverride};
+use crate::config::{EligibilityBasis, EligibilityConfig, EligibilityMode};
+use crate::schema::allergen;
+use crate::schema::beneficiary::Beneficiary;
+use crate::schema::card;
+use crate::schema::details::{move_last_presence, DetailsQueries, READABLE};
+use crate::schema::eligibility::{self, allowance, EligibilityQueries};
+use crate::schema::user::UserRole;
+use tracing::{debug, error, warn};
+
+pub(crate) enum CheckInQueries {
+    CountSameDay,
+    SelectGeneralNote,
+}
+
+impl Display for CheckInQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckInQueries::CountSameDay => write!(f,
+                "SELECT COUNT(*) FROM BeneficiaryPresences \
+                WHERE BeneficiaryId = ? AND DeletedAt IS NULL AND PresenceDate >= ? AND PresenceDate < ? + INTERVAL 1 DAY"
+            ),
+            CheckInQueries::SelectGeneralNote => write!(f,
+                "SELECT Note FROM BeneficiaryNotes \
+                WHERE BeneficiaryId = ? AND DeletedAt IS NULL AND Type = (SELECT Id FROM NoteType WHERE Name = 'general') AND {READABLE} \
+                ORDER BY Date DESC, Id DESC LIMIT 1"
+            ),
+        }
+    }
+}
+
+/// The reason of an override, trimmed. It is required and fits `EligibilityOverride.Reason`.
+pub(crate) fn validate_reason(reason: &str) -> Result<&str, (StatusCode, String)> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "An override needs a reason".to_string()));
+    }
+    if reason.chars().count() > 512 {
+        return Err((StatusCode::BAD_REQUEST, "The reason is longer than 512 characters".to_string()));
+    }
+    Ok(reason)
+}
+
+/// The beneficiary named by id or by a card that is not revoked.
+async fn resolve(conn: &mut MySqlConnection, request: &TokenCheckIn) -> Result<i32, (StatusCode, String)> {
+    match (request.BeneficiaryId, request.CardCode.as_deref()) {
+        (Some(id), None) => Ok(id),
+        (None, Some(code)) => card::resolve(conn, code)
+            .await
+            .map_err(|e| {
+                error!(error = %e, "Resolve card failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Could not check in".to_string())
+            })?
+            .ok_or((StatusCode::NOT_FOUND, "Unknown or revoked card".to_string())),
+        _ => Err((StatusCode::BAD_REQUEST, "Name the beneficiary by id or by card code".to_string())),
+    }
+}
+
+/// Records a presence now, moves `LastPresence` forward and returns what the counter needs.
+/// A second check-in on the same day is refused with 409, and so is one past a limit in
+/// block mode unless `reason` overrides it. Overrides are kept in `EligibilityOverride`.
+pub(crate) async fn check_in(mut conn: PoolConnection<MySql>, request: &TokenCheckIn, reason: Option<&str>, user: &UserRole, config: &EligibilityConfig) -> Result<CheckIn, (StatusCode, String)> {
+    let failed = |e: Error| {
+        error!(error = %e, "Check in failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not check in".to_string())
+    };
+    let id = resolve(conn.as_mut(), request).await?;
+    let now = Local::now().naive_local();
+    let date = now.format("%Y-%m-%d %H:%M:%S").to_string();
+    let day = now.date();
+    debug!(beneficiary_id = id, %date, "Check in");
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let eligibility = eligibility::evaluate(&mut tx, id, day, true, config)
+        .await
+        .map_err(failed)?
+        .ok_or((StatusCode::NOT_FOUND, "No such beneficiary".to_string()))?;
+    let same_day: i64 = sqlx::query_scalar(&CheckInQueries::CountSameDay.to_string())
+        .bind(id)
+        .bind(day)
+        .bind(day)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if same_day > 0 {
+        return Err((StatusCode::CONFLICT, "Already checked in today".to_string()));
+    }
+    if !eligibility.Eligible {
+        match reason {
+            Some(reason) => {
+                sqlx::query(&EligibilityQueries::InsertOverride.to_string())
+                    .bind(id)
+                    .bind(&date)
+                    .bind(reason)
+                    .bind(user.Id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(failed)?;
+            },
+            None if config.mode == EligibilityMode::Block => {
+                return Err((StatusCode::CONFLICT, eligibility.Reasons.join(", ")));
+            },
+            None => warn!(beneficiary_id = id, reasons = ?eligibility.Reasons, "Check in past a limit"),
+        }
+    }
+    sqlx::query(&DetailsQueries::InsertPresence.to_string())
+        .bind(id)
+        .bind(&date)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    move_last_presence(&mut tx, id, &date, user).await.map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+    debug!(beneficiary_id = id, "Check in succeeded");
+
+    let beneficiary = Beneficiary::snapshot(conn.as_mut(), id)
+        .await
+        .map_err(failed)?
+        .and_then(|beneficiary| beneficiary.project(user))
+        .ok_or((StatusCode::FORBIDDEN, "Invalid role".to_string()))?;
+    let allergies = allergen::allergies(conn.as_mut(), id).await.map_err(failed)?;
+    let note = sqlx::query_scalar(&CheckInQueries::SelectGeneralNote.to_string())
+        .bind(id)
+        .bind(&user.Role)
+        .fetch_optional(conn.as_mut())
+        .await
+        .map_err(failed)?;
+
+    let visit = match config.basis {
+        EligibilityBasis::Visits => 1.0,
+        EligibilityBasis::Amount => 0.0,
+    };
+    Ok(CheckIn {
+        Date: date,
+        Weekly: allowance(eligibility.Weekly.Limit, eligibility.Weekly.Used + visit),
+        Monthly: allowance(eligibility.Monthly.Limit, eligibility.Monthly.Used + visit),
+        Beneficiary: v1::Beneficiary::from(beneficiary),
+        Allergies: allergies,
+        GeneralNote: note,
+    })
+}
@@ -2,63 +2,87 @@ use std::fmt::{Display, Formatter};
 use anyhow::Context;
 use bincode::Encode;
 use serde::{Deserialize, Serialize};
-use sqlx::{Decode, Error, MySql, MySqlConnection};
+use sqlx::{Connection, Decode, Error, MySql, MySqlConnection};
+use axum::http::StatusCode;
 use sqlx::pool::PoolConnection;
+use crate::schema::allergen::OF_LIVE_MEMBER;
+use crate::schema::encode;
+use crate::schema::history;
+use crate::schema::format::{Encoded, Format};
 use crate::schema::user::UserRole;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::TokenNoteText;
+use tracing::{debug, error};
+
+/// Columns of a `Note`, dates written like everywhere else.
+const NOTE_COLUMNS: &str = "Id, BeneficiaryId, DATE_FORMAT(Date, '%Y-%m-%d %H:%i:%s') AS Date, Type, Note, AuthorId, \
+    DATE_FORMAT(CreatedAt, '%Y-%m-%d %H:%i:%s') AS CreatedAt, \
+    DATE_FORMAT(EditedAt, '%Y-%m-%d %H:%i:%s') AS EditedAt, EditedBy";
+
+/// Note types the role, bound next, may read according to `NoteTypeAccess`.
+pub(crate) const READABLE: &str = "Type IN (SELECT NoteTypeId FROM NoteTypeAccess WHERE Role = ? AND CanRead)";
+
+/// Note types the role, bound next, may write according to `NoteTypeAccess`.
+pub(crate) const WRITABLE: &str = "Type IN (SELECT NoteTypeId FROM NoteTypeAccess WHERE Role = ? AND CanWrite)";
 
 pub(crate) enum DetailsQueries{
     SelectAllergies,
     SelectPresences,
-    SelectUserNotes,
-    SelectAdminNotes,
-    SelectTsNotes,
+    SelectNotes,
+    SelectNoteById,
     InsertAllergy,
     DeleteAllergy,
     InsertPresence,
+    UpdateLastPresence,
     DeletePresence,
     CreateNote,
-    UpdateNote,
-    DeleteNote,
+    SelectNoteIds,
+    UpdateNoteById,
+    DeleteNoteById,
 }
 
 impl Display for DetailsQueries{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             DetailsQueries::SelectAllergies => {
-                write!(f,"SELECT * FROM BeneficiaryAllergies WHERE BeneficiaryId = ?")
+                write!(f,"SELECT BeneficiaryId, Allergy FROM BeneficiaryAllergies WHERE BeneficiaryId = ? AND DeletedAt IS NULL AND {OF_LIVE_MEMBER}")
             }
             DetailsQueries::SelectPresences => {
-                write!(f, "SELECT BeneficiaryId, DATE_FORMAT(PresenceDate, '%Y-%m-%d %h:%m:%s') AS Date FROM BeneficiaryPresences WHERE BeneficiaryId = ? Order By Date ASC")
+                write!(f, "SELECT BeneficiaryId, DATE_FORMAT(PresenceDate, '%Y-%m-%d %H:%i:%s') AS Date FROM BeneficiaryPresences WHERE BeneficiaryId = ? AND DeletedAt IS NULL Order By Date ASC")
             }
-            DetailsQueries::SelectUserNotes => {
-                write!(f, "SELECT BeneficiaryId, DATE_FORMAT(Date, '%Y-%m-%d %h:%m:%s') as Date, Type, Note FROM BeneficiaryNotes WHERE BeneficiaryId = ? AND Type = 0")
+            DetailsQueries::SelectNotes => {
+                write!(f, "SELECT {NOTE_COLUMNS} FROM BeneficiaryNotes WHERE BeneficiaryId = ? AND {READABLE} AND DeletedAt IS NULL ORDER BY Date ASC, Id ASC")
             }
-            DetailsQueries::SelectAdminNotes => {
-                write!(f, "SELECT BeneficiaryId, DATE_FORMAT(Date, '%Y-%m-%d %h:%m:%s') as Date, Type, Note FROM BeneficiaryNotes WHERE BeneficiaryId = ? AND Type = 0 OR Type = 1")
-            }
-            DetailsQueries::SelectTsNotes => {
-                write!(f, "SELECT BeneficiaryId, DATE_FORMAT(Date, '%Y-%m-%d %h:%m:%s') as Date, Type, Note FROM BeneficiaryNotes WHERE BeneficiaryId = ? AND Type != 1")
+            DetailsQueries::SelectNoteById => {
+                write!(f, "SELECT {NOTE_COLUMNS} FROM BeneficiaryNotes WHERE Id = ? AND {READABLE} AND DeletedAt IS NULL")
             }
             DetailsQueries::InsertAllergy => {
                 write!(f, "INSERT INTO BeneficiaryAllergies (BeneficiaryId, Allergy) VALUES (?, ?)")
             }
             DetailsQueries::DeleteAllergy => {
-                write!(f, "DELETE FROM BeneficiaryAllergies WHERE BeneficiaryId = ? AND Allergy LIKE ? ESCAPE '#'")
+                write!(f, "UPDATE BeneficiaryAllergies SET DeletedAt = NOW(), DeletedBy = ? WHERE BeneficiaryId = ? AND MemberId IS NULL AND Allergy LIKE ? ESCAPE '#' AND DeletedAt IS NULL")
             }
             DetailsQueries::InsertPresence => {
                 write!(f, "INSERT INTO BeneficiaryPresences (BeneficiaryId, PresenceDate) VALUES (?, ?)")
             }
+            DetailsQueries::UpdateLastPresence => {
+                write!(f, "UPDATE Beneficiary SET LastPresence = DATE(?) WHERE Id = ? AND LastPresence < DATE(?)")
+            }
             DetailsQueries::DeletePresence => {
-                write!(f, "DELETE FROM BeneficiaryPresences WHERE BeneficiaryId = ? AND PresenceDate = ?")
+                write!(f, "UPDATE BeneficiaryPresences SET DeletedAt = NOW(), DeletedBy = ? WHERE BeneficiaryId = ? AND PresenceDate = ? AND DeletedAt IS NULL")
             }
             DetailsQueries::CreateNote => {
-                write!(f, "INSERT INTO BeneficiaryNotes (BeneficiaryId, Date, Type, Note) VALUES (?, ?, ?, ?)")
+                write!(f, "INSERT INTO BeneficiaryNotes (BeneficiaryId, Date, Type, Note, AuthorId) \
+                    SELECT ?, ?, ?, ?, ? FROM NoteTypeAccess WHERE NoteTypeId = ? AND Role = ? AND CanWrite")
             }
-            DetailsQueries::UpdateNote => {
-                write!(f, "UPDATE BeneficiaryNotes SET Note = ? WHERE BeneficiaryId = ? AND Date = ?")
+            DetailsQueries::SelectNoteIds => {
+                write!(f, "SELECT Id FROM BeneficiaryNotes WHERE BeneficiaryId = ? AND Date = ? AND {READABLE} AND DeletedAt IS NULL LIMIT 2")
             }
-            DetailsQueries::DeleteNote => {
-                write!(f, "DELETE FROM BeneficiaryNotes WHERE BeneficiaryId = ? AND Date = ?")
+            DetailsQueries::UpdateNoteById => {
+                write!(f, "UPDATE BeneficiaryNotes SET Note = ?, EditedAt = NOW(), EditedBy = ? WHERE Id = ? AND {WRITABLE} AND DeletedAt IS NULL")
+            }
+            DetailsQueries::DeleteNoteById => {
+                write!(f, "UPDATE BeneficiaryNotes SET DeletedAt = NOW(), DeletedBy = ? WHERE Id = ? AND {WRITABLE} AND DeletedAt IS NULL")
             }
         }
 
@@ -66,91 +90,114 @@ impl Display for DetailsQueries{
 }
 
 
-#[derive(sqlx::FromRow, Encode, Decode, Serialize, Deserialize, Debug)]
+#[derive(sqlx::FromRow, Encode, Decode, bincode::Decode, Serialize, Deserialize, Debug)]
 pub(crate) struct BeneficiaryAllergy{
     pub(crate) BeneficiaryId: i32,
     pub(crate) Allergy: String,
 }
 
-#[derive(sqlx::FromRow, Encode, Decode, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Encode, Decode, bincode::Decode, Serialize, Deserialize)]
 pub(crate) struct TokenAllergy{
     pub(crate) Token: String,
     pub(crate) Allergy: BeneficiaryAllergy,
 }
 impl TokenAllergy{
     pub(crate) async fn insert_allergy(&self,mut conn: PoolConnection<MySql>) -> Result<(), Error>{
-        println!("->> {:>12} - Insert Allergy - {}", "Handler", self.Allergy.Allergy.clone());
+        debug!(beneficiary_id = self.Allergy.BeneficiaryId, "Insert Allergy");
         let _ = sqlx::query(&DetailsQueries::InsertAllergy.to_string())
             .bind(self.Allergy.BeneficiaryId)
             .bind(self.Allergy.Allergy.clone())
             .execute(conn.as_mut())
             .await.map_err(|e| {
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
+            error!(error = ?e, "Query failed");
             e
         })?;
-        println!("->> {:>12} - Insert Allergy - SUCCESS", "Handler");
+        debug!("Insert Allergy succeeded");
         Ok(())
     }
 
-    pub(crate) async fn delete_allergy(&self, mut conn: PoolConnection<MySql>) -> Result<(), Error>{
+    /// Moves the row to the trash, see `schema::trash`.
+    pub(crate) async fn delete_allergy(&self, mut conn: PoolConnection<MySql>, user: &UserRole) -> Result<(), Error>{
         let _ = sqlx::query(&DetailsQueries::DeleteAllergy.to_string())
+            .bind(user.Id)
             .bind(self.Allergy.BeneficiaryId)
             .bind(self.Allergy.Allergy.clone())
             .execute(conn.as_mut())
             .await.map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
+            error!(error = ?e, "Query failed");
             e
         })?;
-        println!("->> {:>12} - Delete Allergy - SUCCESS", "Handler");
+        debug!("Delete Allergy succeeded");
         Ok(())
     }
 }
 
-#[derive(sqlx::FromRow, Encode, Decode, Serialize, Deserialize, Debug)]
+#[derive(sqlx::FromRow, Encode, Decode, bincode::Decode, Serialize, Deserialize, Debug)]
 pub(crate) struct BeneficiaryPresence{
     pub(crate) BeneficiaryId: i32,
     pub(crate) Date: String,
 }
 
-#[derive(sqlx::FromRow, Encode, Decode, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Encode, Decode, bincode::Decode, Serialize, Deserialize)]
 pub(crate) struct TokenPresence{
     pub(crate) Token: String,
     pub(crate) Presence: BeneficiaryPresence,
 }
 
+/// Moves `LastPresence` forward to the day of `date`, never back, and records the
+/// beneficiary's new version when it moved.
+pub(crate) async fn move_last_presence(conn: &mut MySqlConnection, id: i32, date: &str, user: &UserRole) -> Result<(), Error> {
+    let moved = sqlx::query(&DetailsQueries::UpdateLastPresence.to_string())
+        .bind(date)
+        .bind(id)
+        .bind(date)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+    if moved > 0 {
+        history::capture(conn, id, user).await?;
+    }
+    Ok(())
+}
 
 impl TokenPresence{
-    pub(crate) async fn insert_presence(&self,mut conn: PoolConnection<MySql>) -> Result<(), Error>{
-        println!("->> {:>12} - Insert Presence", "Handler");
-        let _ = sqlx::query(&DetailsQueries::InsertPresence.to_string())
+    /// Records the presence and moves `LastPresence` forward to its day, in one transaction.
+    pub(crate) async fn insert_presence(&self,mut conn: PoolConnection<MySql>, user: &UserRole) -> Result<(), Error>{
+        debug!("Insert Presence");
+        let failed = |e: Error| {
+            error!(error = ?e, "Insert Presence failed");
+            e
+        };
+        let mut tx = conn.begin().await.map_err(failed)?;
+        sqlx::query(&DetailsQueries::InsertPresence.to_string())
             .bind(self.Presence.BeneficiaryId)
             .bind(self.Presence.Date.clone())
-            .execute(conn.as_mut())
-            .await.map_err(|e| {
-            println!("->> {:>12} - Insert Presence -of FAILED: {:?}", "Handler", e);
-
-            e
-        })?;
-        println!("->> {:>12} - Insert Presence - SUCCESS", "Handler");
+            .execute(&mut *tx)
+            .await.map_err(failed)?;
+        move_last_presence(&mut tx, self.Presence.BeneficiaryId, &self.Presence.Date, user).await.map_err(failed)?;
+        tx.commit().await.map_err(failed)?;
+        debug!("Insert Presence succeeded");
         Ok(())
     }
 
-    pub(crate) async fn delete_presence(&self, mut conn: PoolConnection<MySql>) -> Result<(), Error>{
-        println!("->> {:>12} - Delete Presence", "Handler");
+    /// Moves the row to the trash, see `schema::trash`.
+    pub(crate) async fn delete_presence(&self, mut conn: PoolConnection<MySql>, user: &UserRole) -> Result<(), Error>{
+        debug!("Delete Presence");
         let _ = sqlx::query(&DetailsQueries::DeletePresence.to_string())
+            .bind(user.Id)
             .bind(self.Presence.BeneficiaryId)
             .bind(self.Presence.Date.clone())
             .execute(conn.as_mut())
             .await.map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
+            error!(error = ?e, "Query failed");
             e
         })?;
-        println!("->> {:>12} - Delete Presence - SUCCESS", "Handler");
+        debug!("Delete Presence succeeded");
         Ok(())
     }
 }
 
-#[derive(sqlx::FromRow, Encode, Decode, Serialize, Deserialize, Debug)]
+#[derive(sqlx::FromRow, Encode, Decode, bincode::Decode, Serialize, Deserialize, Debug)]
 pub(crate) struct BeneficiaryNotes{
     pub(crate) BeneficiaryId: i32,
     pub(crate) Date: String,
@@ -159,62 +206,183 @@ pub(crate) struct BeneficiaryNotes{
     pub(crate) Note: String,
 }
 
-#[derive(sqlx::FromRow, Encode, Decode, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Encode, Decode, bincode::Decode, Serialize, Deserialize)]
 pub(crate) struct TokenNote{
     pub(crate) Token: String,
     pub(crate) Content: BeneficiaryNotes,
 }
 
 impl TokenNote{
-    pub(crate) async fn create_note(&self, mut conn: PoolConnection<MySql>) -> Result<(), Error>{
-        println!("->> {:>12} - Insert Note", "Handler");
-        let _ = sqlx::query(&DetailsQueries::CreateNote.to_string())
+    /// Returns false, writing nothing, when the role may not write notes of this type.
+    pub(crate) async fn create_note(&self, mut conn: PoolConnection<MySql>, user: &UserRole) -> Result<bool, Error>{
+        debug!("Insert Note");
+        let result = sqlx::query(&DetailsQueries::CreateNote.to_string())
             .bind(self.Content.BeneficiaryId)
             .bind(self.Content.Date.clone())
             .bind(self.Content.Type)
             .bind(self.Content.Note.clone())
+            .bind(user.Id)
+            .bind(self.Content.Type)
+            .bind(&user.Role)
             .execute(conn.as_mut())
             .await.map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
+            error!(error = ?e, "Query failed");
             e
         })?;
-        println!("->> {:>12} - Insert Note - SUCCESS", "Handler");
-        Ok(())
+        debug!(written = result.rows_affected(), "Insert Note succeeded");
+        Ok(result.rows_affected() > 0)
     }
 
-    pub(crate) async fn update_note(&self, mut conn: PoolConnection<MySql>) -> Result<(), Error>{
-        println!("->> {:>12} - Update Note", "Handler");
-        let _ = sqlx::query(&DetailsQueries::UpdateNote.to_string())
-            .bind(self.Content.Note.clone())
+    /// The note of the beneficiary written at `Date` the role may read, 404 when there is
+    /// none. Notes written in the same second cannot be told apart here, so several are
+    /// refused with 409 and the caller is pointed to the routes by id.
+    pub(crate) async fn find_note(&self, conn: &mut MySqlConnection, user: &UserRole) -> Result<Note, (StatusCode, String)>{
+        let ids: Vec<i32> = sqlx::query_scalar(&DetailsQueries::SelectNoteIds.to_string())
             .bind(self.Content.BeneficiaryId)
             .bind(self.Content.Date.clone())
-            .execute(conn.as_mut())
-            .await.map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
-            e
-        })?;
-        println!("->> {:>12} - Update Note - SUCCESS", "Handler");
-        Ok(())
+            .bind(&user.Role)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(|e| {
+                error!(error = ?e, "Query failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Could not get note".to_string())
+            })?;
+        let id = match ids[..] {
+            [] => return Err((StatusCode::NOT_FOUND, "No such note".to_string())),
+            [id] => id,
+            _ => return Err((StatusCode::CONFLICT, "Several notes were written at this date, use the routes by id".to_string())),
+        };
+        match Note::find(conn, id, user).await {
+            Ok(Some(note)) => Ok(note),
+            Ok(None) => Err((StatusCode::NOT_FOUND, "No such note".to_string())),
+            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get note".to_string())),
+        }
     }
 
-    pub(crate) async fn delete_note(&self, mut conn: PoolConnection<MySql>) -> Result<(), Error>{
-        println!("->> {:>12} - Delete Note", "Handler");
-        let _ = sqlx::query(&DetailsQueries::DeleteNote.to_string())
-            .bind(self.Content.BeneficiaryId)
-            .bind(self.Content.Date.clone())
-            .execute(conn.as_mut())
-            .await.map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
-            e
-        })?;
-        println!("->> {:>12} - Delete Note - SUCCESS", "Handler");
-        Ok(())
+    /// Updates the note of the beneficiary written at `Date`, see `find_note`, and returns it
+    /// as it was before. 403 when the role may read but not write it. Prefer `Note::edit`.
+    pub(crate) async fn update_note(&self, mut conn: PoolConnection<MySql>, user: &UserRole) -> Result<Note, (StatusCode, String)>{
+        debug!("Update Note");
+        let note = self.find_note(conn.as_mut(), user).await?;
+        match Note::edit(conn.as_mut(), note.Id, &self.Content.Note, user).await {
+            Ok(true) => {
+                debug!(note_id = note.Id, "Update Note succeeded");
+                Ok(note)
+            },
+            Ok(false) => Err((StatusCode::FORBIDDEN, "This role may not write notes of this type".to_string())),
+            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not update note".to_string())),
+        }
+    }
+
+    /// Moves the note of the beneficiary written at `Date` to the trash, and returns it, as
+    /// `update_note` does. Prefer `Note::delete`.
+    pub(crate) async fn delete_note(&self, mut conn: PoolConnection<MySql>, user: &UserRole) -> Result<Note, (StatusCode, String)>{
+        debug!("Delete Note");
+        let note = self.find_note(conn.as_mut(), user).await?;
+        match Note::delete(conn.as_mut(), note.Id, user).await {
+            Ok(true) => {
+                debug!(note_id = note.Id, "Delete Note succeeded");
+                Ok(note)
+            },
+            Ok(false) => Err((StatusCode::FORBIDDEN, "This role may not write notes of this type".to_string())),
+            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not delete note".to_string())),
+        }
+    }
+}
+
+/// A note as stored, with its id and authorship.
+#[derive(sqlx::FromRow, Debug)]
+pub(crate) struct Note{
+    pub(crate) Id: i32,
+    pub(crate) BeneficiaryId: i32,
+    pub(crate) Date: String,
+    pub(crate) Type: i8,
+    pub(crate) Note: String,
+    pub(crate) AuthorId: Option<i32>,
+    pub(crate) CreatedAt: String,
+    pub(crate) EditedAt: Option<String>,
+    pub(crate) EditedBy: Option<i32>,
+}
+
+impl From<Note> for v1::Note {
+    fn from(note: Note) -> Self {
+        v1::Note {
+            Id: note.Id,
+            BeneficiaryId: note.BeneficiaryId,
+            Date: note.Date,
+            Type: note.Type,
+            Note: note.Note,
+            AuthorId: note.AuthorId,
+            CreatedAt: note.CreatedAt,
+            EditedAt: note.EditedAt,
+            EditedBy: note.EditedBy,
+        }
     }
 }
 
+impl From<Note> for BeneficiaryNotes {
+    fn from(note: Note) -> Self {
+        BeneficiaryNotes { BeneficiaryId: note.BeneficiaryId, Date: note.Date, Type: note.Type, Note: note.Note }
+    }
+}
 
+impl Note{
+    /// The note with this id if it is not deleted and the role may read it.
+    pub(crate) async fn find(conn: &mut MySqlConnection, id: i32, role: &UserRole) -> Result<Option<Note>, Error>{
+        sqlx::query_as(&DetailsQueries::SelectNoteById.to_string())
+            .bind(id)
+            .bind(&role.Role)
+            .fetch_optional(conn)
+            .await
+    }
 
-#[derive(sqlx::FromRow, Encode, Decode, Serialize, Deserialize)]
+    /// Returns false, writing nothing, when the role may not write notes of this type.
+    pub(crate) async fn edit(conn: &mut MySqlConnection, id: i32, text: &str, user: &UserRole) -> Result<bool, Error>{
+        debug!(note_id = id, "Edit Note");
+        let result = sqlx::query(&DetailsQueries::UpdateNoteById.to_string())
+            .bind(text)
+            .bind(user.Id)
+            .bind(id)
+            .bind(&user.Role)
+            .execute(conn)
+            .await
+            .map_err(|e|{
+                error!(error = ?e, "Query failed");
+                e
+            })?;
+        debug!("Edit Note succeeded");
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Moves the note to the trash, see `schema::trash`. Returns false as `edit` does.
+    pub(crate) async fn delete(conn: &mut MySqlConnection, id: i32, user: &UserRole) -> Result<bool, Error>{
+        debug!(note_id = id, "Delete Note");
+        let result = sqlx::query(&DetailsQueries::DeleteNoteById.to_string())
+            .bind(user.Id)
+            .bind(id)
+            .bind(&user.Role)
+            .execute(conn)
+            .await
+            .map_err(|e|{
+                error!(error = ?e, "Query failed");
+                e
+            })?;
+        debug!("Delete Note succeeded");
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Notes of a beneficiary the role may see, with their ids.
+    pub(crate) async fn select(mut conn: PoolConnection<MySql>, role: UserRole, beneficiary_id: i32, format: Format) -> Result<Encoded, (StatusCode, String)>{
+        debug!(beneficiary_id, "Select Notes");
+        let notes = Details::get_notes(conn.as_mut(), role, beneficiary_id).await.map_err(|_| {
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not get notes".to_string())
+        })?;
+        debug!(count = notes.len(), "Select Notes succeeded");
+        encode(notes.into_iter().map(v1::Note::from).collect::<Vec<_>>(), format)
+    }
+}
+
+#[derive(sqlx::FromRow, Encode, Decode, bincode::Decode, Serialize, Deserialize)]
 pub(crate) struct Details{
     pub(crate) Id: i32,
     pub(crate) Presences: Vec<BeneficiaryPresence>,
@@ -222,19 +390,36 @@ pub(crate) struct Details{
     pub(crate) Notes: Vec<BeneficiaryNotes>,
 }
 
+impl From<Details> for v1::Details {
+    fn from(details: Details) -> Self {
+        v1::Details {
+            Id: details.Id,
+            Presences: details.Presences.into_iter()
+                .map(|presence| v1::BeneficiaryPresence { BeneficiaryId: presence.BeneficiaryId, Date: presence.Date })
+                .collect(),
+            Allergies: details.Allergies.into_iter()
+                .map(|allergy| v1::BeneficiaryAllergy { BeneficiaryId: allergy.BeneficiaryId, Allergy: allergy.Allergy })
+                .collect(),
+            Notes: details.Notes.into_iter()
+                .map(|note| v1::BeneficiaryNotes { BeneficiaryId: note.BeneficiaryId, Date: note.Date, Type: note.Type, Note: note.Note })
+                .collect(),
+        }
+    }
+}
+
 impl Details {
     pub(crate) async fn get_details(mut conn: PoolConnection<MySql>, role: UserRole, id: i32) -> Result<Details, anyhow::Error>{
-        println!("->> {:>12} - Get Details - Beneficiary : {id}", "Handler");
+        debug!(beneficiary_id = id, "Get Details");
         let presences = Self::get_presences(conn.as_mut(), id).await?;
         let allergies = Self::get_allergies(conn.as_mut(), id).await?;
         let notes = Self::get_notes(conn.as_mut(), role, id).await?;
-        println!("->> {:>12} - Get Details - SUCCESS", "Handler");
+        debug!("Get Details succeeded");
         Ok(
             Self{
                 Id: id,
                 Presences: presences,
                 Allergies: allergies,
-                Notes: notes,
+                Notes: notes.into_iter().map(BeneficiaryNotes::from).collect(),
             }
         )
     }
@@ -245,7 +430,7 @@ impl Details {
             .fetch_all(conn)
             .await
             .context("Could not get allergy list").map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
+            error!(error = ?e, "Query failed");
             e
         })?;
         Ok(allergies)
@@ -257,24 +442,19 @@ impl Details {
             .fetch_all(conn)
             .await
             .context("Could not get presence list").map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
+            error!(error = ?e, "Query failed");
             e
         })?;
         Ok(presences)
     }
-    async fn get_notes(conn: &mut MySqlConnection, role: UserRole, id: i32) -> Result<Vec<BeneficiaryNotes>, anyhow::Error>{
-        let query = match role.Role.as_str() {
-            "Admin" | "Dev" => {DetailsQueries::SelectAdminNotes}
-            "TS" => {DetailsQueries::SelectTsNotes}
-            _ => {DetailsQueries::SelectUserNotes}
-        };
-
-        let notes = sqlx::query_as(&query.to_string())
+    async fn get_notes(conn: &mut MySqlConnection, role: UserRole, id: i32) -> Result<Vec<Note>, anyhow::Error>{
+        let notes = sqlx::query_as(&DetailsQueries::SelectNotes.to_string())
             .bind(id)
+            .bind(&role.Role)
             .fetch_all(conn)
             .await
             .context("Could not get notes").map_err(|e|{
-            println!("->> {:>12} - Error: {:?}", "Handler", e);
+            error!(error = ?e, "Query failed");
             e
         })?;
         Ok(notes)
@@ -0,0 +1,507 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use axum::http::StatusCode;
+use chrono::{Days, Months, NaiveDate, NaiveDateTime};
+use sqlx::{Connection, Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+pub(crate) use harmony_protocol::v1::{Distribution, DistributionItem, DistributionTotal, TokenDistribution, TokenDistributionTotals};
+use crate::config::{BillingMode, EligibilityConfig};
+use crate::schema::audit::{change, AuditChange};
+use crate::schema::category::{FEE_AT, MONTHLY_FEE, WEEKLY_FEE};
+use crate::schema::eligibility::periods;
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+/// Distributions that were not corrected since, of a presence not in the trash.
+pub(crate) const CURRENT: &str = "Distribution.SupersededBy IS NULL AND EXISTS (SELECT 1 FROM BeneficiaryPresences \
+    WHERE BeneficiaryPresences.BeneficiaryId = Distribution.BeneficiaryId \
+    AND BeneficiaryPresences.PresenceDate = Distribution.PresenceDate AND BeneficiaryPresences.DeletedAt IS NULL)";
+
+const COLUMNS: &str = "Id, BeneficiaryId, DATE_FORMAT(PresenceDate, '%Y-%m-%d %H:%i:%s') AS PresenceDate, Amount, CategoryId, Fee, \
+    VolunteerId, Note, DATE_FORMAT(CreatedAt, '%Y-%m-%d %H:%i:%s') AS CreatedAt, CreatedBy, Supersedes, SupersededBy";
+
+/// How totals are grouped, each group named by its first day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TotalsPeriod {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl FromStr for TotalsPeriod {
+    type Err = (StatusCode, String);
+
+    fn from_str(period: &str) -> Result<Self, Self::Err> {
+        match period {
+            "day" => Ok(TotalsPeriod::Day),
+            "week" => Ok(TotalsPeriod::Week),
+            "month" => Ok(TotalsPeriod::Month),
+            "year" => Ok(TotalsPeriod::Year),
+            _ => Err((StatusCode::BAD_REQUEST, format!("Unknown period: {period}"))),
+        }
+    }
+}
+
+pub(crate) enum DistributionQueries {
+    SelectDistributions,
+    SelectDistribution,
+    SelectItems,
+    SelectItemsOf,
+    LockCategory,
+    SelectFees,
+    CountPresence,
+    CountRecorded,
+    SelectCurrent,
+    CountInPeriods,
+    CountUser,
+    InsertDistribution,
+    InsertItem,
+    Supersede,
+    SelectTotals(TotalsPeriod),
+}
+
+impl Display for DistributionQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DistributionQueries::SelectDistributions => write!(f,
+                "SELECT {COLUMNS} FROM Distribution WHERE BeneficiaryId = ? AND {CURRENT} ORDER BY PresenceDate DESC, Id DESC"
+            ),
+            DistributionQueries::SelectDistribution => write!(f, "SELECT {COLUMNS} FROM Distribution WHERE Id = ?"),
+            DistributionQueries::SelectItems => write!(f,
+                "SELECT DistributionId, Item, Quantity FROM DistributionItem \
+                WHERE DistributionId IN (SELECT Id FROM Distribution WHERE BeneficiaryId = ? AND {CURRENT})"
+            ),
+            DistributionQueries::SelectItemsOf => write!(f, "SELECT DistributionId, Item, Quantity FROM DistributionItem WHERE DistributionId = ?"),
+            DistributionQueries::LockCategory => write!(f, "SELECT Category FROM Beneficiary WHERE Id = ? FOR UPDATE"),
+            DistributionQueries::SelectFees => write!(f,
+                "SELECT {WEEKLY_FEE} AS WeeklyFee, {MONTHLY_FEE} AS MonthlyFee FROM Categories {FEE_AT} WHERE Categories.Id = ?"
+            ),
+            DistributionQueries::CountPresence => write!(f,
+                "SELECT COUNT(*) FROM BeneficiaryPresences WHERE BeneficiaryId = ? AND PresenceDate = ? AND DeletedAt IS NULL"
+            ),
+            DistributionQueries::CountRecorded => write!(f,
+                "SELECT COUNT(*) FROM Distribution WHERE BeneficiaryId = ? AND PresenceDate = ? AND {CURRENT}"
+            ),
+            DistributionQueries::CountInPeriods => write!(f,
+                "SELECT COUNT(CASE WHEN PresenceDate >= ? AND PresenceDate < ? THEN 1 END) AS Weekly, \
+                COUNT(CASE WHEN PresenceDate >= ? AND PresenceDate < ? THEN 1 END) AS Monthly \
+                FROM Distribution WHERE BeneficiaryId = ? AND {CURRENT}"
+            ),
+            DistributionQueries::SelectCurrent => write!(f,
+                "SELECT Id FROM Distribution WHERE BeneficiaryId = ? AND PresenceDate = ? AND {CURRENT}"
+            ),
+            DistributionQueries::CountUser => write!(f, "SELECT COUNT(*) FROM User WHERE Id = ?"),
+            DistributionQueries::InsertDistribution => write!(f,
+                "INSERT INTO Distribution (BeneficiaryId, PresenceDate, Amount, CategoryId, Fee, VolunteerId, Note, CreatedBy, Supersedes) \
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            ),
+            DistributionQueries::InsertItem => write!(f, "INSERT INTO DistributionItem (DistributionId, Item, Quantity) VALUES (?, ?, ?)"),
+            DistributionQueries::Supersede => write!(f, "UPDATE Distribution SET SupersededBy = ? WHERE Id = ? AND SupersededBy IS NULL"),
+            DistributionQueries::SelectTotals(period) => {
+                let start = match period {
+                    TotalsPeriod::Day => "DATE(PresenceDate)",
+                    TotalsPeriod::Week => "DATE(PresenceDate) - INTERVAL (WEEKDAY(PresenceDate) + 7 - ?) % 7 DAY",
+                    TotalsPeriod::Month => "DATE(DATE_FORMAT(DATE(PresenceDate) - INTERVAL ? DAY, '%Y-%m-01')) + INTERVAL ? DAY",
+                    TotalsPeriod::Year => "MAKEDATE(YEAR(PresenceDate), 1)",
+                };
+                write!(f,
+                    "SELECT BeneficiaryId, DATE_FORMAT({start}, '%Y-%m-%d') AS PeriodStart, COUNT(*) AS Distributions, \
+                    COALESCE(SUM(Amount), 0e0) AS Amount, COALESCE(SUM(Fee), 0e0) AS Fees \
+                    FROM Distribution \
+                    WHERE {CURRENT} AND (? IS NULL OR BeneficiaryId = ?) AND PresenceDate >= ? AND PresenceDate < ? + INTERVAL 1 DAY \
+                    GROUP BY BeneficiaryId, PeriodStart \
+                    ORDER BY PeriodStart ASC, BeneficiaryId ASC"
+                )
+            },
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct DistributionRow {
+    Id: i32,
+    BeneficiaryId: i32,
+    PresenceDate: String,
+    Amount: f64,
+    CategoryId: Option<i32>,
+    Fee: f64,
+    VolunteerId: Option<i32>,
+    Note: String,
+    CreatedAt: String,
+    CreatedBy: Option<i32>,
+    Supersedes: Option<i32>,
+    SupersededBy: Option<i32>,
+}
+
+#[derive(sqlx::FromRow)]
+struct ItemRow {
+    DistributionId: i32,
+    Item: String,
+    Quantity: f64,
+}
+
+#[derive(sqlx::FromRow)]
+struct Fees {
+    WeeklyFee: f32,
+    MonthlyFee: f32,
+}
+
+/// Current distributions of the same beneficiary in the week and in the month of a new one.
+#[derive(sqlx::FromRow, Clone, Copy)]
+pub(crate) struct Recorded {
+    pub(crate) Weekly: i64,
+    pub(crate) Monthly: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct TotalRow {
+    BeneficiaryId: i32,
+    PeriodStart: String,
+    Distributions: i64,
+    Amount: f64,
+    Fees: f64,
+}
+
+impl From<TotalRow> for DistributionTotal {
+    fn from(row: TotalRow) -> Self {
+        DistributionTotal {
+            BeneficiaryId: row.BeneficiaryId,
+            PeriodStart: row.PeriodStart,
+            Distributions: row.Distributions as u32,
+            Amount: row.Amount,
+            Fees: row.Fees,
+        }
+    }
+}
+
+/// Joins every distribution with its items.
+fn assemble(distributions: Vec<DistributionRow>, items: Vec<ItemRow>) -> Vec<Distribution> {
+    distributions
+        .into_iter()
+        .map(|row| Distribution {
+            Id: row.Id,
+            BeneficiaryId: row.BeneficiaryId,
+            PresenceDate: row.PresenceDate,
+            Amount: row.Amount,
+            Items: items
+                .iter()
+                .filter(|item| item.DistributionId == row.Id)
+                .map(|item| DistributionItem { Item: item.Item.clone(), Quantity: item.Quantity })
+                .collect(),
+            CategoryId: row.CategoryId,
+            Fee: row.Fee,
+            VolunteerId: row.VolunteerId,
+            Note: row.Note,
+            CreatedAt: row.CreatedAt,
+            CreatedBy: row.CreatedBy,
+            Supersedes: row.Supersedes,
+        })
+        .collect()
+}
+
+/// Roles that may correct a distribution, every role may record one.
+pub(crate) fn can_correct(role: &UserRole) -> bool {
+    matches!(role.Role.as_str(), "TS" | "Admin" | "Dev")
+}
+
+/// A distribution names its presence, gives a non-negative amount and lists each item once
+/// with a positive quantity.
+pub(crate) fn validate(distribution: &Distribution) -> Result<NaiveDateTime, (StatusCode, String)> {
+    let date = NaiveDateTime::parse_from_str(&distribution.PresenceDate, "%Y-%m-%d %H:%M:%S")
+        .map_err(|_| (StatusCode::BAD_REQUEST, "PresenceDate must be written YYYY-MM-DD HH:MM:SS".to_string()))?;
+    if !distribution.Amount.is_finite() || distribution.Amount < 0.0 {
+        return Err((StatusCode::BAD_REQUEST, "The amount cannot be negative".to_string()));
+    }
+    if distribution.Note.chars().count() > 512 {
+        return Err((StatusCode::BAD_REQUEST, "The note is longer than 512 characters".to_string()));
+    }
+    for (i, item) in distribution.Items.iter().enumerate() {
+        if item.Item.trim().is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "An item needs a name".to_string()));
+        }
+        if !item.Quantity.is_finite() || item.Quantity <= 0.0 {
+            return Err((StatusCode::BAD_REQUEST, format!("The quantity of {} must be positive", item.Item)));
+        }
+        if distribution.Items[..i].iter().any(|other| other.Item.trim() == item.Item.trim()) {
+            return Err((StatusCode::BAD_REQUEST, format!("Item listed twice: {}", item.Item)));
+        }
+    }
+    Ok(date)
+}
+
+/// The weekly fee is charged with the first distribution of a week, the monthly fee with
+/// the first of a month.
+pub(crate) fn fee(weekly_fee: f64, monthly_fee: f64, recorded: Recorded) -> f64 {
+    let weekly = if recorded.Weekly == 0 { weekly_fee } else { 0.0 };
+    let monthly = if recorded.Monthly == 0 { monthly_fee } else { 0.0 };
+    weekly + monthly
+}
+
+/// A distribution with its items, whether or not it was corrected since.
+async fn find(conn: &mut MySqlConnection, id: i32) -> Result<Option<(Distribution, bool)>, Error> {
+    let row: Option<DistributionRow> = sqlx::query_as(&DistributionQueries::SelectDistribution.to_string())
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let corrected = row.SupersededBy.is_some();
+    let items = sqlx::query_as(&DistributionQueries::SelectItemsOf.to_string())
+        .bind(id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(assemble(vec![row], items).pop().map(|distribution| (distribution, corrected)))
+}
+
+/// The current distribution of a presence, if one was recorded.
+pub(crate) async fn current(conn: &mut MySqlConnection, beneficiary_id: i32, presence_date: &str) -> Result<Option<Distribution>, Error> {
+    let id: Option<i32> = sqlx::query_scalar(&DistributionQueries::SelectCurrent.to_string())
+        .bind(beneficiary_id)
+        .bind(presence_date)
+        .fetch_optional(&mut *conn)
+        .await?;
+    match id {
+        Some(id) => Ok(find(conn, id).await?.map(|(distribution, _)| distribution)),
+        None => Ok(None),
+    }
+}
+
+pub(crate) async fn select_distributions(mut conn: PoolConnection<MySql>, beneficiary_id: i32, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(beneficiary_id, "Select distributions");
+    let distributions = sqlx::query_as(&DistributionQueries::SelectDistributions.to_string())
+        .bind(beneficiary_id)
+        .fetch_all(conn.as_mut())
+        .await;
+    let items = sqlx::query_as(&DistributionQueries::SelectItems.to_string())
+        .bind(beneficiary_id)
+        .fetch_all(conn.as_mut())
+        .await;
+    match distributions.and_then(|distributions| Ok(assemble(distributions, items?))) {
+        Ok(distributions) => encode(distributions, format),
+        Err(e) => {
+            error!(error = %e, "Select distributions failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get distributions".to_string()))
+        }
+    }
+}
+
+async fn insert(conn: &mut MySqlConnection, distribution: &Distribution, category: Option<i32>, fee: f64, volunteer: i32, user: &UserRole, supersedes: Option<i32>) -> Result<i32, Error> {
+    let result = sqlx::query(&DistributionQueries::InsertDistribution.to_string())
+        .bind(distribution.BeneficiaryId)
+        .bind(&distribution.PresenceDate)
+        .bind(distribution.Amount)
+        .bind(category)
+        .bind(fee)
+        .bind(volunteer)
+        .bind(distribution.Note.trim())
+        .bind(user.Id)
+        .bind(supersedes)
+        .execute(&mut *conn)
+        .await?;
+    let id = result.last_insert_id() as i32;
+    for item in &distribution.Items {
+        sqlx::query(&DistributionQueries::InsertItem.to_string())
+            .bind(id)
+            .bind(item.Item.trim())
+            .bind(item.Quantity)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(id)
+}
+
+/// The volunteer named, or the caller.
+async fn volunteer(conn: &mut MySqlConnection, distribution: &Distribution, user: &UserRole) -> Result<i32, (StatusCode, String)> {
+    let Some(id) = distribution.VolunteerId else {
+        return Ok(user.Id);
+    };
+    let known: i64 = sqlx::query_scalar(&DistributionQueries::CountUser.to_string())
+        .bind(id)
+        .fetch_one(conn)
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Check volunteer failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the distribution".to_string())
+        })?;
+    if known == 0 {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown volunteer: {id}")));
+    }
+    Ok(id)
+}
+
+/// Records what was given at a presence, charging the fees of the category of the
+/// beneficiary when billing per visit. A presence has one current distribution, which
+/// corrections replace.
+pub(crate) async fn record(mut conn: PoolConnection<MySql>, distribution: &Distribution, user: &UserRole, config: &EligibilityConfig, billing: BillingMode) -> Result<Distribution, (StatusCode, String)> {
+    debug!(beneficiary_id = distribution.BeneficiaryId, "Record distribution");
+    let date = validate(distribution)?;
+    let failed = |e: Error| {
+        error!(error = %e, "Record distribution failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the distribution".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let category: Option<i32> = sqlx::query_scalar(&DistributionQueries::LockCategory.to_string())
+        .bind(distribution.BeneficiaryId)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(failed)?;
+    let Some(category) = category else {
+        return Err((StatusCode::NOT_FOUND, "No such beneficiary".to_string()));
+    };
+    let presences: i64 = sqlx::query_scalar(&DistributionQueries::CountPresence.to_string())
+        .bind(distribution.BeneficiaryId)
+        .bind(&distribution.PresenceDate)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if presences == 0 {
+        return Err((StatusCode::NOT_FOUND, "No such presence".to_string()));
+    }
+    let recorded: i64 = sqlx::query_scalar(&DistributionQueries::CountRecorded.to_string())
+        .bind(distribution.BeneficiaryId)
+        .bind(&distribution.PresenceDate)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if recorded > 0 {
+        return Err((StatusCode::CONFLICT, "This presence already has a distribution, correct it instead".to_string()));
+    }
+    let volunteer = volunteer(&mut tx, distribution, user).await?;
+
+    let fees: Option<Fees> = sqlx::query_as(&DistributionQueries::SelectFees.to_string())
+        .bind(date.date())
+        .bind(category)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(failed)?;
+    let (week, month) = periods(date.date(), config);
+    let weeks_end = week + Days::new(7);
+    let months_end = month + Months::new(1);
+    let in_periods: Recorded = sqlx::query_as(&DistributionQueries::CountInPeriods.to_string())
+        .bind(week)
+        .bind(weeks_end)
+        .bind(month)
+        .bind(months_end)
+        .bind(distribution.BeneficiaryId)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(failed)?;
+    let (category, charged) = match fees {
+        Some(_) if billing == BillingMode::Period => (Some(category), 0.0),
+        Some(fees) => (Some(category), fee(fees.WeeklyFee.into(), fees.MonthlyFee.into(), in_periods)),
+        None => (None, 0.0),
+    };
+
+    let id = insert(&mut tx, distribution, category, charged, volunteer, user, None).await.map_err(failed)?;
+    let (saved, _) = find(&mut tx, id).await.map_err(failed)?.ok_or_else(|| failed(Error::RowNotFound))?;
+    tx.commit().await.map_err(failed)?;
+    debug!(distribution = id, "Record distribution succeeded");
+    Ok(saved)
+}
+
+/// Replaces the amount, items, volunteer and note of a current distribution. The presence,
+/// category and fee are kept. Returns the distribution before and after.
+pub(crate) async fn correct(mut conn: PoolConnection<MySql>, distribution: &Distribution, user: &UserRole) -> Result<(Distribution, Distribution), (StatusCode, String)> {
+    debug!(distribution = distribution.Id, "Correct distribution");
+    validate(distribution)?;
+    let failed = |e: Error| {
+        error!(error = %e, "Correct distribution failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the distribution".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let Some((before, corrected)) = find(&mut tx, distribution.Id).await.map_err(failed)? else {
+        return Err((StatusCode::NOT_FOUND, "No such distribution".to_string()));
+    };
+    if corrected {
+        return Err((StatusCode::CONFLICT, "This distribution was corrected already, correct the latest one".to_string()));
+    }
+    let volunteer = volunteer(&mut tx, distribution, user).await?;
+    let replacement = Distribution { BeneficiaryId: before.BeneficiaryId, PresenceDate: before.PresenceDate.clone(), ..distribution.clone() };
+    let id = insert(&mut tx, &replacement, before.CategoryId, before.Fee, volunteer, user, Some(before.Id)).await.map_err(failed)?;
+    let superseded = sqlx::query(&DistributionQueries::Supersede.to_string())
+        .bind(id)
+        .bind(before.Id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if superseded.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "This distribution was corrected already, correct the latest one".to_string()));
+    }
+    let (after, _) = find(&mut tx, id).await.map_err(failed)?.ok_or_else(|| failed(Error::RowNotFound))?;
+    tx.commit().await.map_err(failed)?;
+    debug!(distribution = id, supersedes = before.Id, "Correct distribution succeeded");
+    Ok((before, after))
+}
+
+pub(crate) fn parse_range(from: &str, to: &str) -> Result<(NaiveDate, NaiveDate), (StatusCode, String)> {
+    let parse = |day: &str| NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid date: {day}")));
+    let (from, to) = (parse(from)?, parse(to)?);
+    if from > to {
+        return Err((StatusCode::BAD_REQUEST, "From is after To".to_string()));
+    }
+    Ok((from, to))
+}
+
+/// Number, amount and fees of the current distributions between two days, per beneficiary
+/// and per period. Weeks and months start as for eligibility.
+pub(crate) async fn select_totals(mut conn: PoolConnection<MySql>, query: &TokenDistributionTotals, config: &EligibilityConfig, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    let period: TotalsPeriod = query.Period.parse()?;
+    let (from, to) = parse_range(&query.From, &query.To)?;
+    debug!(beneficiary_id = query.BeneficiaryId, ?period, %from, %to, "Select distribution totals");
+    let sql = DistributionQueries::SelectTotals(period).to_string();
+    let mut select = sqlx::query_as::<_, TotalRow>(&sql);
+    select = match period {
+        TotalsPeriod::Week => select.bind(config.week_start.num_days_from_monday()),
+        TotalsPeriod::Month => select.bind(config.month_start - 1).bind(config.month_start - 1),
+        TotalsPeriod::Day | TotalsPeriod::Year => select,
+    };
+    let totals = select
+        .bind(query.BeneficiaryId)
+        .bind(query.BeneficiaryId)
+        .bind(from)
+        .bind(to)
+        .fetch_all(conn.as_mut())
+        .await;
+    match totals {
+        Ok(totals) => encode(totals.into_iter().map(DistributionTotal::from).collect::<Vec<_>>(), format),
+        Err(e) => {
+            error!(error = %e, "Select distribution totals failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get distribution totals".to_string()))
+        }
+    }
+}
+
+/// The items on one line, `item:quantity` for each, as the audit keeps them.
+pub(crate) fn items_text(distribution: &Distribution) -> String {
+    distribution
+        .Items
+        .iter()
+        .map(|item| format!("{}:{}", item.Item, item.Quantity))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The presence always, to tell distributions apart, and every other field that changed.
+pub(crate) fn distribution_changes(before: Option<&Distribution>, after: &Distribution) -> Vec<AuditChange> {
+    let mut changes = vec![change("PresenceDate", None, Some(after.PresenceDate.clone()))];
+    let fields = [
+        ("Amount", before.map(|before| before.Amount.to_string()), after.Amount.to_string()),
+        ("Items", before.map(items_text), items_text(after)),
+        ("Fee", before.map(|before| before.Fee.to_string()), after.Fee.to_string()),
+        ("VolunteerId", before.map(|before| format!("{:?}", before.VolunteerId)), format!("{:?}", after.VolunteerId)),
+        ("Note", before.map(|before| before.Note.clone()), after.Note.clone()),
+    ];
+    for (field, before, after) in fields {
+        if before.as_ref() != Some(&after) {
+            changes.push(change(field, before, Some(after)));
+        }
+    }
+    changes
+}
@@ -0,0 +1,153 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use sqlx::{Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+pub(crate) use harmony_protocol::v1::{Allowance, Eligibility, TokenEligibility};
+use crate::config::{EligibilityBasis, EligibilityConfig, EligibilityMode};
+use crate::schema::distribution::CURRENT;
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use tracing::{debug, error};
+
+pub(crate) enum EligibilityQueries {
+    SelectLimits,
+    LockLimits,
+    CountVisits,
+    SumAmounts,
+    InsertOverride,
+}
+
+impl Display for EligibilityQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let limits = "SELECT WeeklyLimit, MonthlyLimit FROM Beneficiary WHERE Id = ?";
+        match self {
+            EligibilityQueries::SelectLimits => write!(f, "{limits}"),
+            EligibilityQueries::LockLimits => write!(f, "{limits} FOR UPDATE"),
+            EligibilityQueries::CountVisits => write!(f,
+                "SELECT {} FROM BeneficiaryPresences \
+                WHERE BeneficiaryId = ? AND DeletedAt IS NULL AND PresenceDate >= LEAST(?, ?) AND PresenceDate < ? + INTERVAL 1 DAY",
+                usage("1e0")
+            ),
+            EligibilityQueries::SumAmounts => write!(f,
+                "SELECT {} FROM Distribution \
+                WHERE BeneficiaryId = ? AND {CURRENT} AND PresenceDate >= LEAST(?, ?) AND PresenceDate < ? + INTERVAL 1 DAY",
+                usage("Amount")
+            ),
+            EligibilityQueries::InsertOverride => write!(f,
+                "INSERT INTO EligibilityOverride (BeneficiaryId, PresenceDate, Reason, CreatedBy) VALUES (?, ?, ?, ?)"
+            ),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Clone, Copy)]
+pub(crate) struct Limits {
+    pub(crate) WeeklyLimit: f64,
+    pub(crate) MonthlyLimit: f64,
+}
+
+/// Sums of `value` since the week start and since the month start, bound in that order.
+fn usage(value: &str) -> String {
+    format!(
+        "COALESCE(SUM(CASE WHEN PresenceDate >= ? THEN {value} ELSE 0e0 END), 0e0) AS Weekly, \
+        COALESCE(SUM(CASE WHEN PresenceDate >= ? THEN {value} ELSE 0e0 END), 0e0) AS Monthly"
+    )
+}
+
+/// What the limits count, visits or amounts, since the start of each period.
+#[derive(sqlx::FromRow, Clone, Copy)]
+pub(crate) struct Usage {
+    pub(crate) Weekly: f64,
+    pub(crate) Monthly: f64,
+}
+
+/// First day of the week and of the month holding `day`, as the configuration sets them.
+pub(crate) fn periods(day: NaiveDate, config: &EligibilityConfig) -> (NaiveDate, NaiveDate) {
+    let back = (day.weekday().num_days_from_monday() + 7 - config.week_start.num_days_from_monday()) % 7;
+    let week = day - Days::new(back.into());
+    let start = day.with_day(config.month_start).unwrap_or(day);
+    let month = if day.day() >= config.month_start { start } else { start - Months::new(1) };
+    (week, month)
+}
+
+pub(crate) fn allowance(limit: f64, used: f64) -> Allowance {
+    Allowance { Limit: limit, Used: used, Remaining: (limit > 0.0).then(|| (limit - used).max(0.0)) }
+}
+
+/// Whether one more visit fits the limits, given what each period already used. With the
+/// `amount` basis, any amount left is enough.
+pub(crate) fn assess(beneficiary_id: i32, day: NaiveDate, limits: Limits, used: Usage, config: &EligibilityConfig) -> Eligibility {
+    let (week, month) = periods(day, config);
+    let weekly = allowance(limits.WeeklyLimit, used.Weekly);
+    let monthly = allowance(limits.MonthlyLimit, used.Monthly);
+    let needed = match config.basis {
+        EligibilityBasis::Visits => 1.0,
+        EligibilityBasis::Amount => f64::MIN_POSITIVE,
+    };
+    let reasons: Vec<String> = [("Weekly", &weekly), ("Monthly", &monthly)]
+        .into_iter()
+        .filter(|(_, allowance)| allowance.Remaining.is_some_and(|remaining| remaining < needed))
+        .map(|(period, allowance)| format!("{period} limit of {} reached", allowance.Limit))
+        .collect();
+    Eligibility {
+        BeneficiaryId: beneficiary_id,
+        Date: day.to_string(),
+        WeekStart: week.to_string(),
+        MonthStart: month.to_string(),
+        Weekly: weekly,
+        Monthly: monthly,
+        Eligible: reasons.is_empty(),
+        Blocking: !reasons.is_empty() && config.mode == EligibilityMode::Block,
+        Reasons: reasons,
+    }
+}
+
+/// Counts what was used up to the end of `day` and assesses it, None for an unknown beneficiary.
+/// With `lock`, the beneficiary row stays locked until the transaction of `conn` ends.
+pub(crate) async fn evaluate(conn: &mut MySqlConnection, beneficiary_id: i32, day: NaiveDate, lock: bool, config: &EligibilityConfig) -> Result<Option<Eligibility>, Error> {
+    let query = if lock { EligibilityQueries::LockLimits } else { EligibilityQueries::SelectLimits };
+    let limits: Option<Limits> = sqlx::query_as(&query.to_string())
+        .bind(beneficiary_id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(limits) = limits else {
+        return Ok(None);
+    };
+    let (week, month) = periods(day, config);
+    let usage = match config.basis {
+        EligibilityBasis::Visits => EligibilityQueries::CountVisits,
+        EligibilityBasis::Amount => EligibilityQueries::SumAmounts,
+    };
+    let used: Usage = sqlx::query_as(&usage.to_string())
+        .bind(week)
+        .bind(month)
+        .bind(beneficiary_id)
+        .bind(week)
+        .bind(month)
+        .bind(day)
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(Some(assess(beneficiary_id, day, limits, used, config)))
+}
+
+pub(crate) fn parse_day(day: Option<&str>) -> Result<NaiveDate, (StatusCode, String)> {
+    match day {
+        Some(day) => NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid date: {day}"))),
+        None => Ok(Local::now().date_naive()),
+    }
+}
+
+pub(crate) async fn select_eligibility(mut conn: PoolConnection<MySql>, query: &TokenEligibility, config: &EligibilityConfig, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    let day = parse_day(query.Date.as_deref())?;
+    debug!(beneficiary_id = query.BeneficiaryId, %day, "Select eligibility");
+    match evaluate(conn.as_mut(), query.BeneficiaryId, day, false, config).await {
+        Ok(Some(eligibility)) => encode(eligibility, format),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "No such beneficiary".to_string())),
+        Err(e) => {
+            error!(error = %e, "Select eligibility failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get eligibility".to_string()))
+        }
+    }
+}
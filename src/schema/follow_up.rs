@@ -0,0 +1,179 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use sqlx::{Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+use sqlx::types::chrono::NaiveDate;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{TokenFollowUp, TokenFollowUpQuery};
+use crate::schema::details::{READABLE, WRITABLE};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+pub(crate) enum FollowUpQueries {
+    SelectOpen,
+    SelectByNote,
+    CountOverdue,
+    AssigneeCanRead,
+    SetFollowUp,
+    ClearFollowUp,
+    Complete,
+}
+
+impl Display for FollowUpQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let select = "SELECT Id AS NoteId, BeneficiaryId, Type, Note, DATE_FORMAT(DueDate, '%Y-%m-%d') AS DueDate, AssigneeId, \
+            CompletedAt IS NULL AND DueDate < CURDATE() AS Overdue, \
+            DATE_FORMAT(CompletedAt, '%Y-%m-%d %H:%i:%s') AS CompletedAt, CompletedBy \
+            FROM BeneficiaryNotes";
+        match self {
+            FollowUpQueries::SelectOpen => write!(f,
+                "{select} WHERE AssigneeId = ? AND CompletedAt IS NULL AND DeletedAt IS NULL AND {READABLE} \
+                ORDER BY DueDate ASC, Id ASC"
+            ),
+            FollowUpQueries::SelectByNote => write!(f, "{select} WHERE Id = ? AND DueDate IS NOT NULL AND DeletedAt IS NULL"),
+            FollowUpQueries::CountOverdue => write!(f,
+                "SELECT COUNT(*) FROM BeneficiaryNotes \
+                WHERE AssigneeId = ? AND CompletedAt IS NULL AND DeletedAt IS NULL AND DueDate < CURDATE() AND {READABLE}"
+            ),
+            FollowUpQueries::AssigneeCanRead => write!(f,
+                "SELECT COUNT(*) FROM NoteTypeAccess JOIN User ON User.Role = NoteTypeAccess.Role \
+                WHERE User.Id = ? AND NoteTypeAccess.NoteTypeId = ? AND NoteTypeAccess.CanRead"
+            ),
+            FollowUpQueries::SetFollowUp => write!(f,
+                "UPDATE BeneficiaryNotes SET DueDate = ?, AssigneeId = ?, CompletedAt = NULL, CompletedBy = NULL \
+                WHERE Id = ? AND {WRITABLE} AND DeletedAt IS NULL"
+            ),
+            FollowUpQueries::ClearFollowUp => write!(f,
+                "UPDATE BeneficiaryNotes SET DueDate = NULL, AssigneeId = NULL, CompletedAt = NULL, CompletedBy = NULL \
+                WHERE Id = ? AND {WRITABLE} AND DeletedAt IS NULL"
+            ),
+            FollowUpQueries::Complete => write!(f,
+                "UPDATE BeneficiaryNotes SET CompletedAt = NOW(), CompletedBy = ? \
+                WHERE Id = ? AND DueDate IS NOT NULL AND CompletedAt IS NULL AND DeletedAt IS NULL \
+                AND (AssigneeId = ? OR {WRITABLE})"
+            ),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct FollowUp {
+    pub(crate) NoteId: i32,
+    pub(crate) BeneficiaryId: i32,
+    pub(crate) Type: i8,
+    pub(crate) Note: String,
+    pub(crate) DueDate: String,
+    pub(crate) AssigneeId: i32,
+    pub(crate) Overdue: i64,
+    pub(crate) CompletedAt: Option<String>,
+    pub(crate) CompletedBy: Option<i32>,
+}
+
+impl From<FollowUp> for v1::FollowUp {
+    fn from(follow_up: FollowUp) -> Self {
+        v1::FollowUp {
+            NoteId: follow_up.NoteId,
+            BeneficiaryId: follow_up.BeneficiaryId,
+            Type: follow_up.Type,
+            Note: follow_up.Note,
+            DueDate: follow_up.DueDate,
+            AssigneeId: follow_up.AssigneeId,
+            Overdue: follow_up.Overdue != 0,
+            CompletedAt: follow_up.CompletedAt,
+            CompletedBy: follow_up.CompletedBy,
+        }
+    }
+}
+
+pub(crate) fn parse_due_date(date: &str) -> Result<NaiveDate, (StatusCode, String)> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|_| (StatusCode::BAD_REQUEST, "DueDate must be written YYYY-MM-DD".to_string()))
+}
+
+/// The follow-up of a note, completed or not, if it has one.
+pub(crate) async fn find(conn: &mut MySqlConnection, note_id: i32) -> Result<Option<FollowUp>, Error> {
+    sqlx::query_as(&FollowUpQueries::SelectByNote.to_string())
+        .bind(note_id)
+        .fetch_optional(conn)
+        .await
+}
+
+/// Open follow-ups of `assignee`, earliest due first, on notes the caller role may read.
+pub(crate) async fn select_open(mut conn: PoolConnection<MySql>, role: &UserRole, assignee: i32, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(assignee, "Select follow-ups");
+    let follow_ups: Result<Vec<FollowUp>, Error> = sqlx::query_as(&FollowUpQueries::SelectOpen.to_string())
+        .bind(assignee)
+        .bind(&role.Role)
+        .fetch_all(conn.as_mut())
+        .await;
+    match follow_ups {
+        Ok(follow_ups) => {
+            debug!(count = follow_ups.len(), "Select follow-ups succeeded");
+            encode(follow_ups.into_iter().map(v1::FollowUp::from).collect::<Vec<_>>(), format)
+        }
+        Err(e) => {
+            error!(error = %e, "Select follow-ups failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get follow-ups".to_string()))
+        }
+    }
+}
+
+/// Open follow-ups of the user past their due date, on notes their role may read.
+pub(crate) async fn count_overdue(conn: &mut MySqlConnection, user_id: i32, role: &str) -> Result<u32, Error> {
+    let count: i64 = sqlx::query_scalar(&FollowUpQueries::CountOverdue.to_string())
+        .bind(user_id)
+        .bind(role)
+        .fetch_one(conn)
+        .await?;
+    Ok(u32::try_from(count).unwrap_or(u32::MAX))
+}
+
+/// Whether `assignee` exists and may read notes of `note_type`.
+pub(crate) async fn assignee_can_read(conn: &mut MySqlConnection, assignee: i32, note_type: i8) -> Result<bool, Error> {
+    let count: i64 = sqlx::query_scalar(&FollowUpQueries::AssigneeCanRead.to_string())
+        .bind(assignee)
+        .bind(note_type)
+        .fetch_one(conn)
+        .await?;
+    Ok(count > 0)
+}
+
+/// Sets or replaces the follow-up of a note, reopening it. Returns false, writing nothing,
+/// when the role may not write notes of this type.
+pub(crate) async fn set(conn: &mut MySqlConnection, note_id: i32, due: NaiveDate, assignee: i32, role: &UserRole) -> Result<bool, Error> {
+    debug!(note_id, assignee, "Set follow-up");
+    let result = sqlx::query(&FollowUpQueries::SetFollowUp.to_string())
+        .bind(due)
+        .bind(assignee)
+        .bind(note_id)
+        .bind(&role.Role)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Removes the follow-up of a note. Returns false as `set` does.
+pub(crate) async fn clear(conn: &mut MySqlConnection, note_id: i32, role: &UserRole) -> Result<bool, Error> {
+    debug!(note_id, "Clear follow-up");
+    let result = sqlx::query(&FollowUpQueries::ClearFollowUp.to_string())
+        .bind(note_id)
+        .bind(&role.Role)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Marks an open follow-up done. The assignee may do it without write access to the note.
+pub(crate) async fn complete(conn: &mut MySqlConnection, note_id: i32, user: &UserRole) -> Result<bool, Error> {
+    debug!(note_id, "Complete follow-up");
+    let result = sqlx::query(&FollowUpQueries::Complete.to_string())
+        .bind(user.Id)
+        .bind(note_id)
+        .bind(user.Id)
+        .bind(&user.Role)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
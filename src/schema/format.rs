@@ -0,0 +1,168 @@
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, FromRequestParts, Request};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use bincode::{config, Decode, Encode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+pub(crate) use harmony_protocol::media::{BINCODE, JSON, MSGPACK};
+
+/// Wire format of a request or response body.
+///
+/// Bincode stays the default so that clients which do not send an `Accept`
+/// header keep receiving what they always did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Format {
+    #[default]
+    Bincode,
+    Json,
+    MessagePack,
+}
+
+impl Format {
+    pub(crate) fn media_type(&self) -> &'static str {
+        match self {
+            Format::Bincode => BINCODE,
+            Format::Json => JSON,
+            Format::MessagePack => MSGPACK,
+        }
+    }
+
+    /// Matches a single media type, ignoring parameters such as `charset`.
+    pub(crate) fn from_media_type(media_type: &str) -> Option<Format> {
+        let essence = media_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+        match essence.as_str() {
+            BINCODE | "application/x-bincode" => Some(Format::Bincode),
+            JSON => Some(Format::Json),
+            MSGPACK | "application/x-msgpack" | "application/vnd.msgpack" => Some(Format::MessagePack),
+            "*/*" | "application/*" => Some(Format::default()),
+            _ => None,
+        }
+    }
+
+    /// Picks the preferred supported format of an `Accept` header, honoring `q` weights.
+    pub(crate) fn from_accept(accept: &str) -> Option<Format> {
+        if accept.trim().is_empty() {
+            return Some(Format::default());
+        }
+
+        let mut ranges: Vec<(&str, f32)> = accept
+            .split(',')
+            .map(|range| {
+                let mut parts = range.split(';');
+                let media_type = parts.next().unwrap_or("").trim();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (media_type, quality)
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges.into_iter().find_map(|(media_type, _)| Format::from_media_type(media_type))
+    }
+
+    pub(crate) fn encode<T: Encode + Serialize>(&self, data: T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Bincode => bincode::encode_to_vec(data, config::standard()).map_err(|e| e.to_string()),
+            Format::Json => serde_json::to_vec(&data).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::to_vec_named(&data).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub(crate) fn decode<T: Decode + DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            Format::Bincode => bincode::decode_from_slice(bytes, config::standard())
+                .map(|(data, _)| data)
+                .map_err(|e| e.to_string()),
+            Format::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub(crate) fn from_content_type(headers: &HeaderMap) -> Result<Format, (StatusCode, String)> {
+        match headers.get(header::CONTENT_TYPE) {
+            None => Ok(Format::Json),
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(Format::from_media_type)
+                .ok_or((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("Supported content types are {JSON}, {MSGPACK} and {BINCODE}"))),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Format {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accept = parts
+            .headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        Format::from_accept(&accept)
+            .ok_or((StatusCode::NOT_ACCEPTABLE, format!("Supported response types are {JSON}, {MSGPACK} and {BINCODE}")))
+    }
+}
+
+/// An encoded response body tagged with its content type.
+#[derive(Debug)]
+pub(crate) struct Encoded {
+    pub(crate) format: Format,
+    pub(crate) bytes: Vec<u8>,
+}
+
+impl IntoResponse for Encoded {
+    fn into_response(self) -> Response {
+        (
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static(self.format.media_type())),
+                (header::VARY, HeaderValue::from_static("accept")),
+            ],
+            self.bytes,
+        ).into_response()
+    }
+}
+
+/// Request body extractor decoding JSON, MessagePack or bincode according to `Content-Type`.
+///
+/// A request without `Content-Type` is read as JSON, which is what every client sent so far.
+pub(crate) struct Payload<T>(pub(crate) T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Payload<T>
+where
+    T: Decode + DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = Format::from_content_type(req.headers())?;
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+
+        format
+            .decode(&bytes)
+            .map(Payload)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to decode body : {e}")))
+    }
+}
+
+impl<T> std::ops::Deref for Payload<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
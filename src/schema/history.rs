@@ -0,0 +1,176 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use sqlx::{Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+use sqlx::types::chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::TokenBeneAsOf;
+use crate::schema::audit::changed_fields;
+use crate::schema::beneficiary::Beneficiary;
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::user::UserRole;
+use tracing::{debug, error, warn};
+
+/// Every column of `Beneficiary` but its id, in the same order in both tables.
+const COLUMNS: &str = "FirstName, LastName, Email, Phone, Address, PostalCode, Kid, Adult, \
+    MonthlyAmount, WeeklyAmount, Category, MonthlyLimit, WeeklyLimit, \
+    Birth, LastPresence, Sexe, Language, Origin, City, Study, Income, FamilySituation, \
+    IsActive, IsSdf, IsEmployed, HasAllergies, HasGeneralNote";
+
+enum HistoryQueries {
+    CaptureVersion,
+    SelectVersions,
+    SelectVersionsAsOf,
+    SelectFirstValidFrom,
+}
+
+impl Display for HistoryQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let select = "SELECT Version, DATE_FORMAT(ValidFrom, '%Y-%m-%d %H:%i:%s') AS ValidFrom, UserId, \
+            BeneficiaryId AS Id, FirstName, LastName, Email, Phone, Address, PostalCode, Kid, Adult, \
+            MonthlyAmount, WeeklyAmount, Category, MonthlyLimit, WeeklyLimit, \
+            DATE_FORMAT(Birth, '%Y-%m-%d') AS Birth, \
+            DATE_FORMAT(LastPresence, '%Y-%m-%d') AS LastPresence, \
+            Sexe, Language, Origin, City, Study, Income, FamilySituation, \
+            IsActive, IsSdf, IsEmployed, HasAllergies, HasGeneralNote \
+            FROM BeneficiaryHistory";
+        match self {
+            HistoryQueries::CaptureVersion => write!(f,
+                "INSERT INTO BeneficiaryHistory (BeneficiaryId, Version, UserId, {COLUMNS}) \
+                SELECT Id, (SELECT COALESCE(MAX(Version), 0) + 1 FROM BeneficiaryHistory WHERE BeneficiaryId = ?), ?, {COLUMNS} \
+                FROM Beneficiary WHERE Id = ?"
+            ),
+            HistoryQueries::SelectVersions => write!(f, "{select} WHERE BeneficiaryId = ? ORDER BY Version ASC"),
+            HistoryQueries::SelectVersionsAsOf => write!(f,
+                "{select} WHERE BeneficiaryId = ? AND ValidFrom <= ? ORDER BY Version DESC LIMIT 2"
+            ),
+            HistoryQueries::SelectFirstValidFrom => write!(f,
+                "SELECT DATE_FORMAT(MIN(ValidFrom), '%Y-%m-%d %H:%i:%s') FROM BeneficiaryHistory WHERE BeneficiaryId = ?"
+            ),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct Version {
+    pub(crate) Version: i32,
+    pub(crate) ValidFrom: String,
+    pub(crate) UserId: Option<i32>,
+    #[sqlx(flatten)]
+    pub(crate) Beneficiary: Beneficiary,
+}
+
+/// Copies the current row of a beneficiary as its next version, inside the caller's transaction.
+pub(crate) async fn capture(conn: &mut MySqlConnection, id: i32, user: &UserRole) -> Result<(), Error> {
+    capture_by(conn, id, Some(user.Id)).await
+}
+
+/// `capture` for a change with no user behind it, such as a job, when `user_id` is None.
+pub(crate) async fn capture_by(conn: &mut MySqlConnection, id: i32, user_id: Option<i32>) -> Result<(), Error> {
+    sqlx::query(&HistoryQueries::CaptureVersion.to_string())
+        .bind(id)
+        .bind(user_id)
+        .bind(id)
+        .execute(conn)
+        .await
+        .map(|_| ())
+}
+
+/// Projects versions, oldest first, for the caller role and lists what changed since the previous one.
+pub(crate) fn with_changes(versions: Vec<Version>, role: &UserRole) -> Vec<v1::BeneficiaryVersion> {
+    let mut previous: Option<Beneficiary> = None;
+    versions
+        .into_iter()
+        .filter_map(|version| {
+            let beneficiary = version.Beneficiary.project(role)?;
+            let changes = previous.as_ref().map(|previous| changed_fields(previous, &beneficiary)).unwrap_or_default();
+            previous = Some(beneficiary.clone());
+            Some(v1::BeneficiaryVersion {
+                Version: version.Version,
+                ValidFrom: version.ValidFrom,
+                UserId: version.UserId,
+                Beneficiary: beneficiary.into(),
+                Changes: changes,
+            })
+        })
+        .collect()
+}
+
+/// A date alone means the end of that day, so that its own changes are included.
+pub(crate) fn parse_as_of(date: &str) -> Result<NaiveDateTime, (StatusCode, String)> {
+    let date = date.trim();
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d").map(|day| day.and_time(NaiveTime::from_hms_opt(23, 59, 59).unwrap())))
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Date must be written YYYY-MM-DD or YYYY-MM-DD HH:MM:SS".to_string()))
+}
+
+fn check_role(role: &UserRole) -> Result<(), (StatusCode, String)> {
+    match role.Role.as_str() {
+        "Admin" | "Dev" | "TS" | "User" => Ok(()),
+        _ => {
+            warn!("Invalid role");
+            Err((StatusCode::FORBIDDEN, "Invalid role".to_string()))
+        }
+    }
+}
+
+pub(crate) async fn select_versions(mut conn: PoolConnection<MySql>, role: UserRole, id: i32, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(role = %role.Role, beneficiary_id = id, "Select Beneficiary history");
+    check_role(&role)?;
+    let versions: Vec<Version> = sqlx::query_as(&HistoryQueries::SelectVersions.to_string())
+        .bind(id)
+        .fetch_all(conn.as_mut())
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Select Beneficiary history failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not get beneficiary history".to_string())
+        })?;
+
+    if versions.is_empty() {
+        return Err((StatusCode::NOT_FOUND, "No history for this beneficiary".to_string()));
+    }
+    debug!(count = versions.len(), "Select Beneficiary history succeeded");
+    encode(with_changes(versions, &role), format)
+}
+
+pub(crate) async fn select_as_of(mut conn: PoolConnection<MySql>, role: UserRole, id: i32, date: &str, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(role = %role.Role, beneficiary_id = id, "Select Beneficiary as of");
+    check_role(&role)?;
+    let as_of = parse_as_of(date)?;
+    let mut versions: Vec<Version> = sqlx::query_as(&HistoryQueries::SelectVersionsAsOf.to_string())
+        .bind(id)
+        .bind(as_of)
+        .fetch_all(conn.as_mut())
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Select Beneficiary as of failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not get beneficiary history".to_string())
+        })?;
+
+    if versions.is_empty() {
+        return Err(before_history(conn.as_mut(), id).await);
+    }
+    versions.reverse();
+    match with_changes(versions, &role).pop() {
+        Some(version) => encode(version, format),
+        None => Err((StatusCode::NOT_FOUND, "No version of this beneficiary at that date".to_string())),
+    }
+}
+
+/// Why there is no version at a date: history starts later, or there is none at all.
+/// Seeded versions are dated from when history was set up, not backdated to guess earlier.
+async fn before_history(conn: &mut MySqlConnection, id: i32) -> (StatusCode, String) {
+    let first: Result<Option<String>, Error> = sqlx::query_scalar(&HistoryQueries::SelectFirstValidFrom.to_string())
+        .bind(id)
+        .fetch_one(conn)
+        .await;
+    match first {
+        Ok(Some(first)) => (StatusCode::NOT_FOUND, format!("No history before {first}")),
+        Ok(None) => (StatusCode::NOT_FOUND, "No history for this beneficiary".to_string()),
+        Err(e) => {
+            error!(error = %e, "Select Beneficiary as of failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not get beneficiary history".to_string())
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use sqlx::{Connection, Error, MySql, MySqlConnection, MySqlPool};
+use sqlx::pool::PoolConnection;
+use sqlx::types::chrono::{Local, NaiveDate};
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{HouseholdMember, TokenMember};
+use crate::schema::allergen::{self, validate_severity, AllergenQueries};
+use crate::schema::audit::{change, AuditChange};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::history;
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+/// Relationships a member may have with the beneficiary.
+pub(crate) const RELATIONSHIPS: [&str; 5] = ["spouse", "child", "parent", "sibling", "other"];
+
+/// Members of the beneficiary of the enclosing query that are not in the trash.
+const MEMBERS: &str = "FROM HouseholdMember WHERE HouseholdMember.BeneficiaryId = Beneficiary.Id AND HouseholdMember.DeletedAt IS NULL";
+
+/// A member counts as a kid under 18, or as a `child` when the birth date is unknown.
+const IS_KID: &str = "(CASE WHEN HouseholdMember.Birth IS NULL THEN HouseholdMember.Relationship = 'child' \
+    ELSE TIMESTAMPDIFF(YEAR, HouseholdMember.Birth, CURDATE()) < 18 END)";
+
+/// `Kid` counted from the members, or `fallback` for a beneficiary without any.
+pub(crate) fn kid_count(fallback: &str) -> String {
+    format!("IF(EXISTS (SELECT 1 {MEMBERS}), (SELECT COUNT(*) {MEMBERS} AND {IS_KID}), {fallback})")
+}
+
+/// `Adult` counted from the members and the beneficiary, or `fallback` for a beneficiary without any.
+pub(crate) fn adult_count(fallback: &str) -> String {
+    format!("IF(EXISTS (SELECT 1 {MEMBERS}), (SELECT 1 + COUNT(*) {MEMBERS} AND NOT {IS_KID}), {fallback})")
+}
+
+pub(crate) enum HouseholdQueries {
+    SelectMembers,
+    SelectMember,
+    SelectAllergies,
+    InsertMember,
+    UpdateMember,
+    DeleteMember,
+    InsertAllergy,
+    UpdateAllergy,
+    DeleteAllergy,
+    RefreshCounts,
+    SelectStaleCounts,
+}
+
+impl Display for HouseholdQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let select = "SELECT Id, BeneficiaryId, FirstName, LastName, DATE_FORMAT(Birth, '%Y-%m-%d') AS Birth, Relationship, Sexe \
+            FROM HouseholdMember";
+        let (kids, adults) = (kid_count("Kid"), adult_count("Adult"));
+        match self {
+            HouseholdQueries::SelectMembers => write!(f,
+                "{select} WHERE BeneficiaryId = ? AND DeletedAt IS NULL ORDER BY Birth IS NULL, Birth ASC, Id ASC"
+            ),
+            HouseholdQueries::SelectMember => write!(f, "{select} WHERE Id = ? AND DeletedAt IS NULL"),
+            HouseholdQueries::SelectAllergies => write!(f,
+                "SELECT BeneficiaryAllergies.MemberId, BeneficiaryAllergies.BeneficiaryId, BeneficiaryAllergies.AllergenId, Allergen.Kind, \
+                BeneficiaryAllergies.Allergy, BeneficiaryAllergies.Severity \
+                FROM BeneficiaryAllergies LEFT JOIN Allergen ON Allergen.Id = BeneficiaryAllergies.AllergenId \
+                WHERE BeneficiaryAllergies.BeneficiaryId = ? AND BeneficiaryAllergies.MemberId IS NOT NULL \
+                AND BeneficiaryAllergies.DeletedAt IS NULL \
+                ORDER BY BeneficiaryAllergies.AllergenId IS NULL, BeneficiaryAllergies.Allergy ASC"
+            ),
+            HouseholdQueries::InsertMember => write!(f,
+                "INSERT INTO HouseholdMember (BeneficiaryId, FirstName, LastName, Birth, Relationship, Sexe, CreatedBy) \
+                VALUES (?, ?, ?, ?, ?, ?, ?)"
+            ),
+            HouseholdQueries::UpdateMember => write!(f,
+                "UPDATE HouseholdMember SET FirstName = ?, LastName = ?, Birth = ?, Relationship = ?, Sexe = ? \
+                WHERE Id = ? AND DeletedAt IS NULL"
+            ),
+            HouseholdQueries::DeleteMember => write!(f,
+                "UPDATE HouseholdMember SET DeletedAt = NOW(), DeletedBy = ? WHERE Id = ? AND DeletedAt IS NULL"
+            ),
+            HouseholdQueries::InsertAllergy => write!(f,
+                "INSERT INTO BeneficiaryAllergies (BeneficiaryId, MemberId, Allergy, AllergenId, Severity) VALUES (?, ?, ?, ?, ?)"
+            ),
+            HouseholdQueries::UpdateAllergy => write!(f,
+                "UPDATE BeneficiaryAllergies SET Severity = ? \
+                WHERE MemberId = ? AND AllergenId <=> ? AND Allergy = ? AND DeletedAt IS NULL"
+            ),
+            HouseholdQueries::DeleteAllergy => write!(f,
+                "UPDATE BeneficiaryAllergies SET DeletedAt = NOW(), DeletedBy = ? \
+                WHERE MemberId = ? AND AllergenId <=> ? AND Allergy = ? AND DeletedAt IS NULL"
+            ),
+            HouseholdQueries::RefreshCounts => write!(f,
+                "UPDATE Beneficiary SET Kid = {kids}, Adult = {adults} WHERE Id = ? AND (Kid <> {kids} OR Adult <> {adults})"
+            ),
+            HouseholdQueries::SelectStaleCounts => write!(f,
+                "SELECT Id FROM Beneficiary WHERE Kid <> {kids} OR Adult <> {adults}"
+            ),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct MemberRow {
+    Id: i32,
+    BeneficiaryId: i32,
+    FirstName: String,
+    LastName: String,
+    Birth: Option<String>,
+    Relationship: String,
+    Sexe: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct AllergyRow {
+    MemberId: i32,
+    BeneficiaryId: i32,
+    AllergenId: Option<i32>,
+    Kind: Option<String>,
+    Allergy: String,
+    Severity: Option<String>,
+}
+
+/// Joins every member with its allergies.
+fn assemble(members: Vec<MemberRow>, allergies: Vec<AllergyRow>) -> Vec<HouseholdMember> {
+    members
+        .into_iter()
+        .map(|row| HouseholdMember {
+            Id: row.Id,
+            BeneficiaryId: row.BeneficiaryId,
+            FirstName: row.FirstName,
+            LastName: row.LastName,
+            Birth: row.Birth,
+            Relationship: row.Relationship,
+            Sexe: row.Sexe,
+            Allergies: allergies
+                .iter()
+                .filter(|allergy| allergy.MemberId == row.Id)
+                .map(|allergy| v1::Allergy {
+                    BeneficiaryId: allergy.BeneficiaryId,
+                    AllergenId: allergy.AllergenId,
+                    Kind: allergy.Kind.clone(),
+                    Allergy: allergy.Allergy.clone(),
+                    Severity: allergy.Severity.clone(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Keeps the fields the role sees of a member, as for the beneficiary. None for an unknown role.
+pub(crate) fn project(mut member: HouseholdMember, role: &UserRole) -> Option<HouseholdMember> {
+    match role.Role.as_str() {
+        "TS" | "Admin" | "Dev" => {},
+        "User" => member.Sexe.clear(),
+        _ => return None,
+    }
+    Some(member)
+}
+
+/// Roles that may add, change and delete members.
+pub(crate) fn can_write(role: &UserRole) -> bool {
+    matches!(role.Role.as_str(), "TS" | "Admin" | "Dev")
+}
+
+/// A member needs a first name and a known relationship. A birth date may not be in the
+/// future, an allergy is a catalog entry or some text, each listed once.
+pub(crate) fn validate(member: &HouseholdMember) -> Result<Option<NaiveDate>, (StatusCode, String)> {
+    if member.FirstName.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A member needs a first name".to_string()));
+    }
+    if !RELATIONSHIPS.contains(&member.Relationship.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown relationship: {}", member.Relationship)));
+    }
+    let birth = match member.Birth.as_deref().map(str::trim).filter(|birth| !birth.is_empty()) {
+        None => None,
+        Some(birth) => {
+            let birth = NaiveDate::parse_from_str(birth, "%Y-%m-%d")
+                .map_err(|_| (StatusCode::BAD_REQUEST, "Birth must be written YYYY-MM-DD".to_string()))?;
+            if birth > Local::now().date_naive() {
+                return Err((StatusCode::BAD_REQUEST, "Birth is in the future".to_string()));
+            }
+            Some(birth)
+        }
+    };
+    for (i, allergy) in member.Allergies.iter().enumerate() {
+        validate_severity(allergy.Severity.as_deref())?;
+        if allergy.AllergenId.is_none() && allergy.Allergy.trim().is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "An allergy needs an allergen or a text".to_string()));
+        }
+        let same = |other: &v1::Allergy| match allergy.AllergenId {
+            Some(id) => other.AllergenId == Some(id),
+            None => other.AllergenId.is_none() && other.Allergy.trim() == allergy.Allergy.trim(),
+        };
+        if member.Allergies[..i].iter().any(same) {
+            return Err((StatusCode::BAD_REQUEST, format!("Allergy listed twice: {}", allergy.Allergy)));
+        }
+    }
+    Ok(birth)
+}
+
+pub(crate) async fn members(conn: &mut MySqlConnection, beneficiary_id: i32) -> Result<Vec<HouseholdMember>, Error> {
+    let members = sqlx::query_as(&HouseholdQueries::SelectMembers.to_string())
+        .bind(beneficiary_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    let allergies = sqlx::query_as(&HouseholdQueries::SelectAllergies.to_string())
+        .bind(beneficiary_id)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(assemble(members, allergies))
+}
+
+/// A member not in the trash, with its allergies.
+pub(crate) async fn find(conn: &mut MySqlConnection, id: i32) -> Result<Option<HouseholdMember>, Error> {
+    let member: Option<MemberRow> = sqlx::query_as(&HouseholdQueries::SelectMember.to_string())
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let Some(member) = member else {
+        return Ok(None);
+    };
+    let allergies = sqlx::query_as(&HouseholdQueries::SelectAllergies.to_string())
+        .bind(member.BeneficiaryId)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(assemble(vec![member], allergies).pop())
+}
+
+/// Members of a beneficiary, eldest first, as the role sees them.
+pub(crate) async fn select_members(mut conn: PoolConnection<MySql>, beneficiary_id: i32, role: &UserRole, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(beneficiary_id, "Select members");
+    match members(conn.as_mut(), beneficiary_id).await {
+        Ok(members) => encode(members.into_iter().filter_map(|member| project(member, role)).collect::<Vec<_>>(), format),
+        Err(e) => {
+            error!(error = %e, "Select members failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get members".to_string()))
+        }
+    }
+}
+
+/// Creates the member, or replaces its fields and allergies when `create` is false, then
+/// derives the counts of the household. Returns the member as stored.
+pub(crate) async fn save(mut conn: PoolConnection<MySql>, member: &HouseholdMember, create: bool, user: &UserRole) -> Result<HouseholdMember, (StatusCode, String)> {
+    debug!(member = member.Id, create, "Save member");
+    let birth = validate(member)?;
+    let failed = |e: Error| {
+        error!(error = %e, "Save member failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the member".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let (id, beneficiary_id) = if create {
+        let result = sqlx::query(&HouseholdQueries::InsertMember.to_string())
+            .bind(member.BeneficiaryId)
+            .bind(member.FirstName.trim())
+            .bind(member.LastName.trim())
+            .bind(birth)
+            .bind(&member.Relationship)
+            .bind(&member.Sexe)
+            .bind(user.Id)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        (result.last_insert_id() as i32, member.BeneficiaryId)
+    } else {
+        let Some(before) = find(&mut tx, member.Id).await.map_err(failed)? else {
+            return Err((StatusCode::NOT_FOUND, "No such member".to_string()));
+        };
+        sqlx::query(&HouseholdQueries::UpdateMember.to_string())
+            .bind(member.FirstName.trim())
+            .bind(member.LastName.trim())
+            .bind(birth)
+            .bind(&member.Relationship)
+            .bind(&member.Sexe)
+            .bind(member.Id)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        (member.Id, before.BeneficiaryId)
+    };
+    sync_allergies(&mut tx, beneficiary_id, id, &member.Allergies, user).await?;
+    refresh_derived(&mut tx, beneficiary_id, user).await.map_err(failed)?;
+    let saved = find(&mut tx, id).await.map_err(failed)?.ok_or_else(|| failed(Error::RowNotFound))?;
+    tx.commit().await.map_err(failed)?;
+    debug!(member = id, "Save member succeeded");
+    Ok(saved)
+}
+
+/// Makes the allergy rows of a member those of `wanted`. Rows no longer wanted go to the
+/// trash, a catalog entry must be active unless the member already has it.
+async fn sync_allergies(conn: &mut MySqlConnection, beneficiary_id: i32, member_id: i32, wanted: &[v1::Allergy], user: &UserRole) -> Result<(), (StatusCode, String)> {
+    let failed = |e: Error| {
+        error!(error = %e, "Save member allergies failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the member".to_string())
+    };
+    let current: Vec<AllergyRow> = sqlx::query_as(&HouseholdQueries::SelectAllergies.to_string())
+        .bind(beneficiary_id)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(failed)?
+        .into_iter()
+        .filter(|row: &AllergyRow| row.MemberId == member_id)
+        .collect();
+
+    let mut kept = Vec::new();
+    for allergy in wanted {
+        let severity = validate_severity(allergy.Severity.as_deref())?;
+        let existing = current.iter().find(|row| match allergy.AllergenId {
+            Some(id) => row.AllergenId == Some(id),
+            None => row.AllergenId.is_none() && row.Allergy == allergy.Allergy.trim(),
+        });
+        match existing {
+            Some(row) => {
+                if row.Severity != severity {
+                    sqlx::query(&HouseholdQueries::UpdateAllergy.to_string())
+                        .bind(&severity)
+                        .bind(member_id)
+                        .bind(row.AllergenId)
+                        .bind(&row.Allergy)
+                        .execute(&mut *conn)
+                        .await
+                        .map_err(failed)?;
+                }
+                kept.push((row.AllergenId, row.Allergy.clone()));
+            },
+            None => {
+                let text = match allergy.AllergenId {
+                    Some(id) => sqlx::query_scalar(&AllergenQueries::SelectActiveCode.to_string())
+                        .bind(id)
+                        .fetch_optional(&mut *conn)
+                        .await
+                        .map_err(failed)?
+                        .ok_or_else(|| (StatusCode::NOT_FOUND, "No such active allergen".to_string()))?,
+                    None => allergy.Allergy.trim().to_string(),
+                };
+                sqlx::query(&HouseholdQueries::InsertAllergy.to_string())
+                    .bind(beneficiary_id)
+                    .bind(member_id)
+                    .bind(&text)
+                    .bind(allergy.AllergenId)
+                    .bind(&severity)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(failed)?;
+                kept.push((allergy.AllergenId, text));
+            },
+        }
+    }
+    for row in current.iter().filter(|row| !kept.contains(&(row.AllergenId, row.Allergy.clone()))) {
+        sqlx::query(&HouseholdQueries::DeleteAllergy.to_string())
+            .bind(user.Id)
+            .bind(member_id)
+            .bind(row.AllergenId)
+            .bind(&row.Allergy)
+            .execute(&mut *conn)
+            .await
+            .map_err(failed)?;
+    }
+    Ok(())
+}
+
+/// Moves a member to the trash with its allergies, then derives the counts of the household.
+/// Returns the member as it was.
+pub(crate) async fn delete(mut conn: PoolConnection<MySql>, id: i32, user: &UserRole) -> Result<HouseholdMember, (StatusCode, String)> {
+    debug!(member = id, "Delete member");
+    let failed = |e: Error| {
+        error!(error = %e, "Delete member failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not delete the member".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let Some(member) = find(&mut tx, id).await.map_err(failed)? else {
+        return Err((StatusCode::NOT_FOUND, "No such member".to_string()));
+    };
+    sqlx::query(&HouseholdQueries::DeleteMember.to_string())
+        .bind(user.Id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    refresh_derived(&mut tx, member.BeneficiaryId, user).await.map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+    debug!("Delete member succeeded");
+    Ok(member)
+}
+
+/// Sets `Kid` and `Adult` from the members, returns whether they changed.
+async fn update_counts(conn: &mut MySqlConnection, beneficiary_id: i32) -> Result<bool, Error> {
+    let result = sqlx::query(&HouseholdQueries::RefreshCounts.to_string())
+        .bind(beneficiary_id)
+        .execute(conn)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Derives `Kid`, `Adult` and `HasAllergies` inside the caller's transaction, recording a
+/// version of the beneficiary when one of them changed.
+pub(crate) async fn refresh_derived(conn: &mut MySqlConnection, beneficiary_id: i32, user: &UserRole) -> Result<(), Error> {
+    let counts = update_counts(&mut *conn, beneficiary_id).await?;
+    let allergies = allergen::update_has_allergies(&mut *conn, beneficiary_id).await?;
+    if counts || allergies {
+        debug!(beneficiary_id, counts, allergies, "Derived fields changed");
+        history::capture(conn, beneficiary_id, user).await?;
+    }
+    Ok(())
+}
+
+/// `refresh_derived` in a transaction of its own, after a write that had none. A failure
+/// is only logged, the next write to the household derives the fields again.
+pub(crate) async fn refresh(mut conn: PoolConnection<MySql>, beneficiary_id: i32, user: &UserRole) {
+    let result = async {
+        let mut tx = conn.begin().await?;
+        refresh_derived(&mut tx, beneficiary_id, user).await?;
+        tx.commit().await
+    }.await;
+    if let Err(e) = result {
+        error!(error = %e, beneficiary_id, "Refresh derived fields failed");
+    }
+}
+
+/// Derives again the counts that went stale as members grew up, returns how many
+/// beneficiaries changed. Their new versions have no author.
+pub(crate) async fn refresh_counts(pool: &MySqlPool) -> Result<u64, Error> {
+    let stale: Vec<i32> = sqlx::query_scalar(&HouseholdQueries::SelectStaleCounts.to_string())
+        .fetch_all(pool)
+        .await?;
+    let mut changed = 0;
+    for id in stale {
+        let mut tx = pool.begin().await?;
+        if update_counts(&mut tx, id).await? {
+            history::capture_by(&mut tx, id, None).await?;
+            changed += 1;
+        }
+        tx.commit().await?;
+    }
+    Ok(changed)
+}
+
+/// The allergies of a member on one line, `allergy:severity` each, as the audit keeps them.
+pub(crate) fn allergies_text(member: &HouseholdMember) -> String {
+    member
+        .Allergies
+        .iter()
+        .map(|allergy| match &allergy.Severity {
+            Some(severity) => format!("{}:{severity}", allergy.Allergy),
+            None => allergy.Allergy.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Fields of a member that changed, the id always to tell members apart.
+pub(crate) fn member_changes(before: Option<&HouseholdMember>, after: Option<&HouseholdMember>) -> Vec<AuditChange> {
+    let id = |member: &HouseholdMember| member.Id.to_string();
+    let mut changes = vec![change("MemberId", before.map(id), after.map(id))];
+    let fields = |member: Option<&HouseholdMember>| match member {
+        Some(member) => [
+            Some(member.FirstName.clone()),
+            Some(member.LastName.clone()),
+            member.Birth.clone(),
+            Some(member.Relationship.clone()),
+            Some(member.Sexe.clone()),
+            Some(allergies_text(member)),
+        ],
+        None => Default::default(),
+    };
+    let names = ["FirstName", "LastName", "Birth", "Relationship", "Sexe", "Allergies"];
+    for ((field, before), after) in names.into_iter().zip(fields(before)).zip(fields(after)) {
+        if before != after {
+            changes.push(change(field, before, after));
+        }
+    }
+    changes
+}
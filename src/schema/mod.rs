@@ -5,10 +5,27 @@ pub(crate) mod user;
 pub(crate) mod stats;
 pub(crate) mod details;
 pub(crate) mod category;
+pub(crate) mod format;
+pub(crate) mod audit;
+pub(crate) mod history;
+pub(crate) mod trash;
+pub(crate) mod note_type;
+pub(crate) mod follow_up;
+pub(crate) mod allergen;
+pub(crate) mod household;
+pub(crate) mod card;
+pub(crate) mod check_in;
+pub(crate) mod eligibility;
+pub(crate) mod distribution;
+pub(crate) mod payment;
+pub(crate) mod receipt;
+pub(crate) mod snapshot;
 
+use anyhow::Context;
 use std::fmt::Display;
 use axum::http::StatusCode;
-use bincode::{config, Encode};
+use bincode::Encode;
+use serde::Serialize;
 
 use sqlx::MySql;
 use sqlx::pool::PoolConnection;
@@ -17,6 +34,9 @@ use base64::{Engine as _, engine::general_purpose};
 use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
 use chacha20poly1305::aead::Aead;
 use rand::Rng;
+use crate::schema::format::{Encoded, Format};
+use tracing::{debug, error, warn};
+use crate::telemetry::{record_user, Secret};
 
 enum TokenValidation{
     ValidateToken
@@ -26,7 +46,7 @@ impl Display for TokenValidation{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self{
             TokenValidation::ValidateToken => write!(f, "
-                SELECT User.Role, User.Username
+                SELECT User.Id, User.Role, User.Username
                 FROM UserSession
                 INNER JOIN User ON
                 UserSession.UserId = User.Id where Token = ? and Now() < UserSession.Expires
@@ -36,41 +56,47 @@ impl Display for TokenValidation{
 }
 
 pub(crate) async fn validate_token(mut conn : PoolConnection<MySql>, token: &String) -> Result<UserRole, anyhow::Error> {
-    println!();
-    println!("->> {:>12} - Token validation : {}", "Handler", token);
-    let user_token = sqlx::query_as(&TokenValidation::ValidateToken.to_string())
+    debug!(token = %Secret(token), "Token validation");
+
+    let user_token: Option<UserRole> = sqlx::query_as(&TokenValidation::ValidateToken.to_string())
         .bind(token)
         .fetch_optional(conn.as_mut())
         .await.map_err(|e| {
-            println!("->> {:>12} - Token validation - FAILED : {}", "Handler", e);
+            error!(error = %e, "Token validation failed");
             e
         })?;
     
     match user_token { 
         Some(user_token) => {
-            println!("->> {:>12} - Token validation - SUCCESS", "Handler");
+            record_user(user_token.Id);
+            debug!("Token validation succeeded");
             Ok(user_token)
         },
         None => {
-            println!("->> {:>12} - Token validation - FAILED : Token not found", "Handler");
+            warn!(token = %Secret(token), "Token validation failed: Token not found");
             Err(anyhow::anyhow!("Token not found"))
         }
     }
 }
-pub(crate) fn encode<T: Encode>(data: T) -> Result<Vec<u8>, (StatusCode, String)>{
-    let config = config::standard();
-    match bincode::encode_to_vec(data, config){
-        Ok(encoded) => Ok(encoded),
+pub(crate) fn encode<T: Encode + Serialize>(data: T, format: Format) -> Result<Encoded, (StatusCode, String)>{
+    match format.encode(data){
+        Ok(bytes) => Ok(Encoded { format, bytes }),
         Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to encode data".to_string())),
     }
 }
 
-pub(crate) fn encrypt(plaintext: &[u8]) -> String {
-    let env_key = dotenv::var("ENCRYPTION_KEY").expect("ENCRYPTION_KEY must be set");
-    let key_bytes = general_purpose::STANDARD.decode(env_key).expect("failed to decode key");
-    let key = Key::from_slice(&key_bytes);
+/// Builds the cipher from `ENCRYPTION_KEY`, a base64 encoded 32 byte key.
+pub(crate) fn cipher() -> Result<ChaCha20Poly1305, anyhow::Error> {
+    let env_key = dotenv::var("ENCRYPTION_KEY").context("ENCRYPTION_KEY must be set")?;
+    let key_bytes = general_purpose::STANDARD.decode(env_key).context("ENCRYPTION_KEY is not valid base64")?;
+    if key_bytes.len() != 32 {
+        anyhow::bail!("ENCRYPTION_KEY must decode to 32 bytes, not {}", key_bytes.len());
+    }
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key_bytes)))
+}
 
-    let cipher = ChaCha20Poly1305::new(key);
+pub(crate) fn encrypt(plaintext: &[u8]) -> String {
+    let cipher = cipher().expect("failed to load the encryption key");
 
     let mut nonce_bytes = [0u8; 12];
     rand::rngs::OsRng.fill(&mut nonce_bytes);
@@ -84,3 +110,16 @@ pub(crate) fn encrypt(plaintext: &[u8]) -> String {
     format!("{}:{}", nonce_b64, ciphertext_b64)
 }
 
+/// Reverses [`encrypt`] on a `nonce:ciphertext` value.
+pub(crate) fn decrypt(sealed: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let (nonce_b64, ciphertext_b64) = sealed.split_once(':').context("missing nonce separator")?;
+    let nonce_bytes = general_purpose::STANDARD.decode(nonce_b64).context("nonce is not valid base64")?;
+    let ciphertext = general_purpose::STANDARD.decode(ciphertext_b64).context("ciphertext is not valid base64")?;
+    if nonce_bytes.len() != 12 {
+        anyhow::bail!("nonce must be 12 bytes, not {}", nonce_bytes.len());
+    }
+
+    cipher()?
+        .decrypt(Nonce::from_slice(&nonce_bytes), ciphertext.as_slice())
+        .map_err(|_| anyhow::anyhow!("failed to decrypt content"))
+}
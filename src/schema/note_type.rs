@@ -0,0 +1,198 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use sqlx::{Connection, Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+pub(crate) use harmony_protocol::v1::{NoteType, NoteTypeAccess, TokenNoteType};
+use crate::schema::audit::{change, AuditChange};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use tracing::{debug, error};
+
+/// Roles a note type can grant access to.
+pub(crate) const ROLES: [&str; 4] = ["Admin", "Dev", "TS", "User"];
+
+pub(crate) enum NoteTypeQueries {
+    SelectTypes,
+    SelectAccess,
+    SelectTypeId,
+    InsertType,
+    UpdateType,
+    DeleteAccess,
+    InsertAccess,
+}
+
+impl Display for NoteTypeQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoteTypeQueries::SelectTypes => write!(f, "SELECT Id, Name, Description FROM NoteType ORDER BY Id ASC"),
+            NoteTypeQueries::SelectAccess => write!(f, "SELECT NoteTypeId, Role, CanRead, CanWrite FROM NoteTypeAccess ORDER BY NoteTypeId ASC, Role ASC"),
+            NoteTypeQueries::SelectTypeId => write!(f, "SELECT Id FROM NoteType WHERE Name = ?"),
+            NoteTypeQueries::InsertType => write!(f,
+                "INSERT INTO NoteType (Id, Name, Description) SELECT COALESCE(MAX(Id), -1) + 1, ?, ? FROM NoteType"
+            ),
+            NoteTypeQueries::UpdateType => write!(f, "UPDATE NoteType SET Name = ?, Description = ? WHERE Id = ?"),
+            NoteTypeQueries::DeleteAccess => write!(f, "DELETE FROM NoteTypeAccess WHERE NoteTypeId = ?"),
+            NoteTypeQueries::InsertAccess => write!(f,
+                "INSERT INTO NoteTypeAccess (NoteTypeId, Role, CanRead, CanWrite) VALUES (?, ?, ?, ?)"
+            ),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TypeRow {
+    Id: i8,
+    Name: String,
+    Description: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct AccessRow {
+    NoteTypeId: i8,
+    Role: String,
+    CanRead: bool,
+    CanWrite: bool,
+}
+
+/// Joins every type with its access rows.
+fn assemble(types: Vec<TypeRow>, access: Vec<AccessRow>) -> Vec<NoteType> {
+    types
+        .into_iter()
+        .map(|row| NoteType {
+            Id: row.Id,
+            Name: row.Name,
+            Description: row.Description,
+            Access: access
+                .iter()
+                .filter(|access| access.NoteTypeId == row.Id)
+                .map(|access| NoteTypeAccess { Role: access.Role.clone(), CanRead: access.CanRead, CanWrite: access.CanWrite })
+                .collect(),
+        })
+        .collect()
+}
+
+/// A type needs a name, and may only name known roles once each. Writing implies reading.
+pub(crate) fn validate(note_type: &NoteType) -> Result<(), (StatusCode, String)> {
+    if note_type.Name.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A note type needs a name".to_string()));
+    }
+    for (i, access) in note_type.Access.iter().enumerate() {
+        if !ROLES.contains(&access.Role.as_str()) {
+            return Err((StatusCode::BAD_REQUEST, format!("Unknown role: {}", access.Role)));
+        }
+        if note_type.Access[..i].iter().any(|other| other.Role == access.Role) {
+            return Err((StatusCode::BAD_REQUEST, format!("Role listed twice: {}", access.Role)));
+        }
+        if access.CanWrite && !access.CanRead {
+            return Err((StatusCode::BAD_REQUEST, format!("{} cannot write a type it cannot read", access.Role)));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn all(conn: &mut MySqlConnection) -> Result<Vec<NoteType>, Error> {
+    let types = sqlx::query_as(&NoteTypeQueries::SelectTypes.to_string()).fetch_all(&mut *conn).await?;
+    let access = sqlx::query_as(&NoteTypeQueries::SelectAccess.to_string()).fetch_all(&mut *conn).await?;
+    Ok(assemble(types, access))
+}
+
+pub(crate) async fn find(conn: &mut MySqlConnection, id: i8) -> Option<NoteType> {
+    all(conn).await.ok()?.into_iter().find(|note_type| note_type.Id == id)
+}
+
+pub(crate) async fn select_types(mut conn: PoolConnection<MySql>, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!("Select note types");
+    match all(conn.as_mut()).await {
+        Ok(types) => encode(types, format),
+        Err(e) => {
+            error!(error = %e, "Select note types failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get note types".to_string()))
+        }
+    }
+}
+
+/// Creates the type, or replaces it and its whole access matrix when `create` is false.
+/// Returns every type afterwards.
+pub(crate) async fn save(mut conn: PoolConnection<MySql>, note_type: &NoteType, create: bool, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(note_type = note_type.Id, create, "Save note type");
+    validate(note_type)?;
+    let failed = |e: Error| {
+        error!(error = %e, "Save note type failed");
+        match e {
+            Error::Database(e) if e.is_unique_violation() => (StatusCode::CONFLICT, "A note type already has this name".to_string()),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the note type".to_string()),
+        }
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let id = if create {
+        sqlx::query(&NoteTypeQueries::InsertType.to_string())
+            .bind(&note_type.Name)
+            .bind(&note_type.Description)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        sqlx::query_scalar(&NoteTypeQueries::SelectTypeId.to_string())
+            .bind(&note_type.Name)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(failed)?
+    } else {
+        if find(&mut tx, note_type.Id).await.is_none() {
+            return Err((StatusCode::NOT_FOUND, "No such note type".to_string()));
+        }
+        sqlx::query(&NoteTypeQueries::UpdateType.to_string())
+            .bind(&note_type.Name)
+            .bind(&note_type.Description)
+            .bind(note_type.Id)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        note_type.Id
+    };
+    sqlx::query(&NoteTypeQueries::DeleteAccess.to_string())
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    for access in &note_type.Access {
+        sqlx::query(&NoteTypeQueries::InsertAccess.to_string())
+            .bind(id)
+            .bind(&access.Role)
+            .bind(access.CanRead)
+            .bind(access.CanWrite)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+    }
+    tx.commit().await.map_err(failed)?;
+    debug!(note_type = id, "Save note type succeeded");
+
+    let types = all(conn.as_mut()).await.map_err(failed)?;
+    encode(types, format)
+}
+
+/// The access matrix on one line, `Role:rw` for each role, as the audit keeps it.
+pub(crate) fn access_text(note_type: &NoteType) -> String {
+    note_type
+        .Access
+        .iter()
+        .map(|access| format!("{}:{}{}", access.Role, if access.CanRead { "r" } else { "" }, if access.CanWrite { "w" } else { "" }))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Name, description and access matrix when they changed, the name always to tell types apart.
+pub(crate) fn note_type_changes(before: Option<&NoteType>, after: &NoteType) -> Vec<AuditChange> {
+    let mut changes = vec![change("Name", before.map(|before| before.Name.clone()), Some(after.Name.clone()))];
+    let fields = [
+        ("Description", before.map(|before| before.Description.clone()), after.Description.clone()),
+        ("Access", before.map(access_text), access_text(after)),
+    ];
+    for (field, before, after) in fields {
+        if before.as_ref() != Some(&after) {
+            changes.push(change(field, before, Some(after)));
+        }
+    }
+    changes
+}
@@ -0,0 +1,354 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use chrono::NaiveDate;
+use sqlx::{Connection, Error, MySql, MySqlConnection, MySqlPool};
+use sqlx::pool::PoolConnection;
+pub(crate) use harmony_protocol::v1::{Balance, Payment, Statement, StatementEntry, TokenPayment, TokenStatementQuery};
+use crate::config::EligibilityConfig;
+use crate::schema::audit::{change, AuditChange};
+use crate::schema::category::{FEE_AT, MONTHLY_FEE, WEEKLY_FEE};
+use crate::schema::distribution::CURRENT;
+use crate::schema::eligibility::periods;
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+pub(crate) const METHODS: [&str; 5] = ["cash", "card", "cheque", "transfer", "other"];
+
+const COLUMNS: &str = "Id, BeneficiaryId, Amount, Method, DATE_FORMAT(PaidAt, '%Y-%m-%d %H:%i:%s') AS PaidAt, Note, CreatedBy, VoidedAt IS NOT NULL AS Voided";
+
+/// Every charge and payment, oldest first: fees of current distributions, period charges
+/// and payments not voided. With `one`, of the beneficiary bound three times.
+fn ledger(one: bool) -> String {
+    let filter = if one { "BeneficiaryId = ? AND " } else { "" };
+    format!(
+        "SELECT BeneficiaryId, DATE_FORMAT(PresenceDate, '%Y-%m-%d %H:%i:%s') AS Date, 'visit' AS Kind, Id AS Reference, \
+        CAST(NULL AS CHAR(16)) AS Method, Fee AS Charge, 0e0 AS Payment \
+        FROM Distribution WHERE {filter}Fee > 0 AND {CURRENT} \
+        UNION ALL \
+        SELECT BeneficiaryId, DATE_FORMAT(PeriodStart, '%Y-%m-%d 00:00:00'), Kind, Id, NULL, Amount, 0e0 \
+        FROM Charge WHERE {filter}Amount > 0 \
+        UNION ALL \
+        SELECT BeneficiaryId, DATE_FORMAT(PaidAt, '%Y-%m-%d %H:%i:%s'), 'payment', Id, Method, 0e0, Amount \
+        FROM Payment WHERE {filter}VoidedAt IS NULL"
+    )
+}
+
+pub(crate) enum PaymentQueries {
+    CountBeneficiary,
+    SelectPayment,
+    InsertPayment,
+    VoidPayment,
+    SelectLedger,
+    SelectBalances,
+    InsertCharges(&'static str),
+}
+
+impl Display for PaymentQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaymentQueries::CountBeneficiary => write!(f, "SELECT COUNT(*) FROM Beneficiary WHERE Id = ?"),
+            PaymentQueries::SelectPayment => write!(f, "SELECT {COLUMNS} FROM Payment WHERE Id = ?"),
+            PaymentQueries::InsertPayment => write!(f,
+                "INSERT INTO Payment (BeneficiaryId, Amount, Method, Note, CreatedBy) VALUES (?, ?, ?, ?, ?)"
+            ),
+            PaymentQueries::VoidPayment => write!(f,
+                "UPDATE Payment SET VoidedAt = NOW(), VoidedBy = ? WHERE Id = ? AND VoidedAt IS NULL"
+            ),
+            PaymentQueries::SelectLedger => write!(f,
+                "SELECT Date, Kind, Reference, Method, Charge, Payment FROM ({}) AS Ledger \
+                ORDER BY Date ASC, Kind = 'payment' ASC, Reference ASC",
+                ledger(true)
+            ),
+            PaymentQueries::SelectBalances => write!(f,
+                "SELECT BeneficiaryId, SUM(Charge) AS Charged, SUM(Payment) AS Paid, \
+                MAX(CASE WHEN Kind = 'payment' THEN Date END) AS LastPayment \
+                FROM ({}) AS Ledger \
+                GROUP BY BeneficiaryId \
+                HAVING SUM(Charge) - SUM(Payment) >= 0.005 \
+                ORDER BY SUM(Charge) - SUM(Payment) DESC, BeneficiaryId ASC",
+                ledger(false)
+            ),
+            PaymentQueries::InsertCharges(kind) => {
+                let fee = if *kind == "week" { WEEKLY_FEE } else { MONTHLY_FEE };
+                write!(f,
+                    "INSERT IGNORE INTO Charge (BeneficiaryId, Kind, PeriodStart, CategoryId, Amount) \
+                    SELECT Beneficiary.Id, '{kind}', ?, Categories.Id, {fee} \
+                    FROM Beneficiary JOIN Categories ON Categories.Id = Beneficiary.Category {FEE_AT} \
+                    WHERE Beneficiary.IsActive AND {fee} > 0"
+                )
+            },
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PaymentRow {
+    Id: i32,
+    BeneficiaryId: i32,
+    Amount: f64,
+    Method: String,
+    PaidAt: String,
+    Note: String,
+    CreatedBy: Option<i32>,
+    Voided: bool,
+}
+
+impl From<PaymentRow> for Payment {
+    fn from(row: PaymentRow) -> Self {
+        Payment {
+            Id: row.Id,
+            BeneficiaryId: row.BeneficiaryId,
+            Amount: row.Amount,
+            Method: row.Method,
+            PaidAt: row.PaidAt,
+            Note: row.Note,
+            CreatedBy: row.CreatedBy,
+        }
+    }
+}
+
+/// A line of the ledger, before the running balance.
+#[derive(sqlx::FromRow, Clone, Debug, PartialEq)]
+pub(crate) struct LedgerRow {
+    pub(crate) Date: String,
+    pub(crate) Kind: String,
+    pub(crate) Reference: i32,
+    pub(crate) Method: Option<String>,
+    pub(crate) Charge: f64,
+    pub(crate) Payment: f64,
+}
+
+#[derive(sqlx::FromRow)]
+struct BalanceRow {
+    BeneficiaryId: i32,
+    Charged: f64,
+    Paid: f64,
+    LastPayment: Option<String>,
+}
+
+impl From<BalanceRow> for Balance {
+    fn from(row: BalanceRow) -> Self {
+        Balance {
+            BeneficiaryId: row.BeneficiaryId,
+            Charged: cents(row.Charged),
+            Paid: cents(row.Paid),
+            Balance: cents(row.Charged - row.Paid),
+            LastPayment: row.LastPayment,
+        }
+    }
+}
+
+/// Rounds to the cent, so sums of fees stored as floats read as written.
+pub(crate) fn cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Roles that may void a payment, every role may record one.
+pub(crate) fn can_void(role: &UserRole) -> bool {
+    matches!(role.Role.as_str(), "Admin" | "Dev")
+}
+
+pub(crate) fn validate(payment: &Payment) -> Result<(), (StatusCode, String)> {
+    if !payment.Amount.is_finite() || payment.Amount <= 0.0 {
+        return Err((StatusCode::BAD_REQUEST, "The amount must be positive".to_string()));
+    }
+    if !METHODS.contains(&payment.Method.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown payment method: {}", payment.Method)));
+    }
+    if payment.Note.chars().count() > 512 {
+        return Err((StatusCode::BAD_REQUEST, "The note is longer than 512 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// `From` and `To` of a statement, either may be left open.
+pub(crate) fn parse_bounds(from: Option<&str>, to: Option<&str>) -> Result<(Option<NaiveDate>, Option<NaiveDate>), (StatusCode, String)> {
+    let parse = |day: Option<&str>| day
+        .filter(|day| !day.is_empty())
+        .map(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid date: {day}"))))
+        .transpose();
+    let (from, to) = (parse(from)?, parse(to)?);
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err((StatusCode::BAD_REQUEST, "From is after To".to_string()));
+        }
+    }
+    Ok((from, to))
+}
+
+/// Lines before `from` make the opening balance, lines up to `to` included are listed with
+/// the balance after each.
+pub(crate) fn statement(beneficiary_id: i32, ledger: Vec<LedgerRow>, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Statement {
+    let from = from.map(|day| day.format("%Y-%m-%d").to_string());
+    let to = to.map(|day| day.format("%Y-%m-%d").to_string());
+    let mut opening = 0.0;
+    let mut balance = 0.0;
+    let mut entries = Vec::new();
+    for row in ledger {
+        let day = &row.Date[..row.Date.len().min(10)];
+        if to.as_deref().is_some_and(|to| day > to) {
+            break;
+        }
+        balance += row.Charge - row.Payment;
+        if from.as_deref().is_some_and(|from| day < from) {
+            opening = balance;
+            continue;
+        }
+        entries.push(StatementEntry {
+            Date: row.Date,
+            Kind: row.Kind,
+            Reference: row.Reference,
+            Method: row.Method,
+            Charge: cents(row.Charge),
+            Payment: cents(row.Payment),
+            Balance: cents(balance),
+        });
+    }
+    Statement { BeneficiaryId: beneficiary_id, OpeningBalance: cents(opening), Entries: entries, ClosingBalance: cents(balance) }
+}
+
+async fn find(conn: &mut MySqlConnection, id: i32) -> Result<Option<PaymentRow>, Error> {
+    sqlx::query_as(&PaymentQueries::SelectPayment.to_string())
+        .bind(id)
+        .fetch_optional(conn)
+        .await
+}
+
+/// A payment and whether it was voided.
+pub(crate) async fn payment(conn: &mut MySqlConnection, id: i32) -> Result<Option<(Payment, bool)>, Error> {
+    Ok(find(conn, id).await?.map(|row| {
+        let voided = row.Voided;
+        (Payment::from(row), voided)
+    }))
+}
+
+/// What the beneficiary owes today.
+pub(crate) async fn balance(conn: &mut MySqlConnection, beneficiary_id: i32) -> Result<f64, Error> {
+    let ledger = sqlx::query_as(&PaymentQueries::SelectLedger.to_string())
+        .bind(beneficiary_id)
+        .bind(beneficiary_id)
+        .bind(beneficiary_id)
+        .fetch_all(conn)
+        .await?;
+    Ok(statement(beneficiary_id, ledger, None, None).ClosingBalance)
+}
+
+/// Records a payment received now by the caller.
+pub(crate) async fn record(mut conn: PoolConnection<MySql>, payment: &Payment, user: &UserRole) -> Result<Payment, (StatusCode, String)> {
+    debug!(beneficiary_id = payment.BeneficiaryId, "Record payment");
+    validate(payment)?;
+    let failed = |e: Error| {
+        error!(error = %e, "Record payment failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not save the payment".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let known: i64 = sqlx::query_scalar(&PaymentQueries::CountBeneficiary.to_string())
+        .bind(payment.BeneficiaryId)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if known == 0 {
+        return Err((StatusCode::NOT_FOUND, "No such beneficiary".to_string()));
+    }
+    let result = sqlx::query(&PaymentQueries::InsertPayment.to_string())
+        .bind(payment.BeneficiaryId)
+        .bind(payment.Amount)
+        .bind(&payment.Method)
+        .bind(payment.Note.trim())
+        .bind(user.Id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    let id = result.last_insert_id() as i32;
+    let saved = find(&mut tx, id).await.map_err(failed)?.ok_or_else(|| failed(Error::RowNotFound))?;
+    tx.commit().await.map_err(failed)?;
+    debug!(payment = id, "Record payment succeeded");
+    Ok(saved.into())
+}
+
+/// Voids a payment, which then leaves every statement and balance. Returns it as it was.
+pub(crate) async fn void(mut conn: PoolConnection<MySql>, id: i32, user: &UserRole) -> Result<Payment, (StatusCode, String)> {
+    debug!(payment = id, "Void payment");
+    let failed = |e: Error| {
+        error!(error = %e, "Void payment failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not void the payment".to_string())
+    };
+
+    let mut tx = conn.begin().await.map_err(failed)?;
+    let Some(payment) = find(&mut tx, id).await.map_err(failed)? else {
+        return Err((StatusCode::NOT_FOUND, "No such payment".to_string()));
+    };
+    let voided = sqlx::query(&PaymentQueries::VoidPayment.to_string())
+        .bind(user.Id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+    if payment.Voided || voided.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "This payment was voided already".to_string()));
+    }
+    tx.commit().await.map_err(failed)?;
+    debug!("Void payment succeeded");
+    Ok(payment.into())
+}
+
+pub(crate) async fn select_statement(mut conn: PoolConnection<MySql>, query: &TokenStatementQuery, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    let (from, to) = parse_bounds(query.From.as_deref(), query.To.as_deref())?;
+    debug!(beneficiary_id = query.BeneficiaryId, ?from, ?to, "Select statement");
+    let ledger = sqlx::query_as(&PaymentQueries::SelectLedger.to_string())
+        .bind(query.BeneficiaryId)
+        .bind(query.BeneficiaryId)
+        .bind(query.BeneficiaryId)
+        .fetch_all(conn.as_mut())
+        .await;
+    match ledger {
+        Ok(ledger) => encode(statement(query.BeneficiaryId, ledger, from, to), format),
+        Err(e) => {
+            error!(error = %e, "Select statement failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get the statement".to_string()))
+        }
+    }
+}
+
+/// Beneficiaries owing something, the largest balance first.
+pub(crate) async fn select_balances(mut conn: PoolConnection<MySql>, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!("Select outstanding balances");
+    let balances = sqlx::query_as::<_, BalanceRow>(&PaymentQueries::SelectBalances.to_string())
+        .fetch_all(conn.as_mut())
+        .await;
+    match balances {
+        Ok(balances) => encode(balances.into_iter().map(Balance::from).collect::<Vec<_>>(), format),
+        Err(e) => {
+            error!(error = %e, "Select outstanding balances failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get the balances".to_string()))
+        }
+    }
+}
+
+/// Charges the fees in effect when the week and month containing `day` start to every
+/// active beneficiary whose category has one. Periods charged already are skipped.
+/// Returns the charges made.
+pub(crate) async fn charge_periods(pool: &MySqlPool, day: NaiveDate, config: &EligibilityConfig) -> Result<u64, Error> {
+    let (week, month) = periods(day, config);
+    let mut charged = 0;
+    for (kind, start) in [("week", week), ("month", month)] {
+        let result = sqlx::query(&PaymentQueries::InsertCharges(kind).to_string())
+            .bind(start)
+            .bind(start)
+            .execute(pool)
+            .await?;
+        charged += result.rows_affected();
+    }
+    Ok(charged)
+}
+
+pub(crate) fn payment_changes(payment: &Payment) -> Vec<AuditChange> {
+    vec![
+        change("PaymentId", None, Some(payment.Id.to_string())),
+        change("Amount", None, Some(payment.Amount.to_string())),
+        change("Method", None, Some(payment.Method.clone())),
+    ]
+}
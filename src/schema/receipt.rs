@@ -0,0 +1,200 @@
+use std::fmt::{Display, Formatter};
+use axum::http::StatusCode;
+use chrono::NaiveDateTime;
+use sqlx::{Error, MySql, MySqlConnection};
+use sqlx::pool::PoolConnection;
+use harmony_protocol::v1::{Allowance, Distribution, Eligibility, Payment};
+use crate::config::{EligibilityBasis, EligibilityConfig, OrganizationConfig};
+use crate::pdf::{Page, MM};
+use crate::schema::beneficiary::Beneficiary;
+use crate::schema::distribution::{self, DistributionQueries};
+use crate::schema::eligibility;
+use crate::schema::payment;
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+pub(crate) enum ReceiptQueries {
+    SelectCategoryName,
+}
+
+impl Display for ReceiptQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReceiptQueries::SelectCategoryName => write!(f, "SELECT Category FROM Categories WHERE Id = ?"),
+        }
+    }
+}
+
+/// Who a receipt or a slip is for, as the caller role sees them.
+pub(crate) struct Holder {
+    pub(crate) Id: i32,
+    pub(crate) Name: String,
+    pub(crate) Category: Option<String>,
+}
+
+pub(crate) struct Receipt {
+    pub(crate) Holder: Holder,
+    pub(crate) Payment: Payment,
+    /// What is still owed once the payment is counted.
+    pub(crate) Balance: f64,
+}
+
+pub(crate) struct VisitSlip {
+    pub(crate) Holder: Holder,
+    pub(crate) Date: String,
+    pub(crate) Distribution: Option<Distribution>,
+    /// Counted up to the end of the day of the visit.
+    pub(crate) Eligibility: Eligibility,
+    pub(crate) Basis: EligibilityBasis,
+}
+
+/// The organization, a title, then one labelled value per line.
+fn document(organization: &OrganizationConfig, title: &str, lines: &[(&str, String)]) -> Page {
+    let mut page = Page::a4();
+    let left = 20.0 * MM;
+    let mut y = page.height() - 25.0 * MM;
+    page.text(left, y, 16.0, true, &organization.name);
+    if !organization.address.is_empty() {
+        y -= 14.0;
+        page.text(left, y, 10.0, false, &organization.address);
+    }
+    y -= 36.0;
+    page.text(left, y, 14.0, true, title);
+    y -= 12.0;
+    for (label, value) in lines {
+        y -= 18.0;
+        page.text(left, y, 11.0, true, label);
+        page.text(left + 55.0 * MM, y, 11.0, false, value);
+    }
+    page
+}
+
+fn money(value: f64) -> String {
+    format!("{value:.2}")
+}
+
+fn remaining(allowance: &Allowance, basis: EligibilityBasis) -> String {
+    let count = |value: f64| match basis {
+        EligibilityBasis::Visits => format!("{value:.0}"),
+        EligibilityBasis::Amount => money(value),
+    };
+    match allowance.Remaining {
+        Some(remaining) => format!("{} of {}", count(remaining), count(allowance.Limit)),
+        None => "No limit".to_string(),
+    }
+}
+
+impl Holder {
+    fn lines(&self) -> [(&'static str, String); 2] {
+        [
+            ("Beneficiary", format!("{} (No. {})", self.Name, self.Id)),
+            ("Category", self.Category.clone().unwrap_or_else(|| "-".to_string())),
+        ]
+    }
+}
+
+impl Receipt {
+    pub(crate) fn page(&self, organization: &OrganizationConfig) -> Page {
+        let [beneficiary, category] = self.Holder.lines();
+        let mut lines = vec![
+            beneficiary,
+            ("Date", self.Payment.PaidAt.clone()),
+            category,
+            ("Amount paid", money(self.Payment.Amount)),
+            ("Method", self.Payment.Method.clone()),
+            ("Balance due", money(self.Balance)),
+        ];
+        if !self.Payment.Note.is_empty() {
+            lines.push(("Note", self.Payment.Note.clone()));
+        }
+        document(organization, &format!("Receipt No. {}", self.Payment.Id), &lines)
+    }
+}
+
+impl VisitSlip {
+    pub(crate) fn page(&self, organization: &OrganizationConfig) -> Page {
+        let [beneficiary, category] = self.Holder.lines();
+        let mut lines = vec![beneficiary, ("Date", self.Date.clone()), category];
+        if let Some(distribution) = &self.Distribution {
+            lines.push(("Amount", money(distribution.Amount)));
+            for (i, item) in distribution.Items.iter().enumerate() {
+                let label = if i == 0 { "Items" } else { "" };
+                lines.push((label, format!("{} x {}", item.Item, item.Quantity)));
+            }
+            if distribution.Fee > 0.0 {
+                lines.push(("Fee", money(distribution.Fee)));
+            }
+        }
+        lines.push(("Remaining this week", remaining(&self.Eligibility.Weekly, self.Basis)));
+        lines.push(("Remaining this month", remaining(&self.Eligibility.Monthly, self.Basis)));
+        document(organization, "Visit slip", &lines)
+    }
+}
+
+/// The beneficiary projected for the caller with the name of a category, None when unknown.
+pub(crate) async fn holder(conn: &mut MySqlConnection, beneficiary_id: i32, category: Option<i32>, user: &UserRole) -> Result<Option<Holder>, Error> {
+    let Some(beneficiary) = Beneficiary::snapshot(&mut *conn, beneficiary_id).await? else {
+        return Ok(None);
+    };
+    let Some(beneficiary) = beneficiary.project(user) else {
+        return Ok(None);
+    };
+    let category: Option<String> = sqlx::query_scalar(&ReceiptQueries::SelectCategoryName.to_string())
+        .bind(category.unwrap_or(beneficiary.Category))
+        .fetch_optional(&mut *conn)
+        .await?;
+    let name = format!("{} {}", beneficiary.FirstName, beneficiary.LastName).trim().to_string();
+    Ok(Some(Holder { Id: beneficiary_id, Name: if name.is_empty() { "-".to_string() } else { name }, Category: category }))
+}
+
+/// The receipt of a payment that was not voided.
+pub(crate) async fn receipt(mut conn: PoolConnection<MySql>, id: i32, user: &UserRole) -> Result<Receipt, (StatusCode, String)> {
+    debug!(payment = id, "Print receipt");
+    let failed = |e: Error| {
+        error!(error = %e, "Print receipt failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not print the receipt".to_string())
+    };
+    let Some((payment, voided)) = payment::payment(conn.as_mut(), id).await.map_err(failed)? else {
+        return Err((StatusCode::NOT_FOUND, "No such payment".to_string()));
+    };
+    if voided {
+        return Err((StatusCode::CONFLICT, "This payment was voided".to_string()));
+    }
+    let holder = holder(conn.as_mut(), payment.BeneficiaryId, None, user)
+        .await
+        .map_err(failed)?
+        .ok_or((StatusCode::NOT_FOUND, "No such beneficiary".to_string()))?;
+    let balance = payment::balance(conn.as_mut(), payment.BeneficiaryId).await.map_err(failed)?;
+    Ok(Receipt { Holder: holder, Payment: payment, Balance: balance })
+}
+
+/// The slip of a presence, with what was given if a distribution was recorded.
+pub(crate) async fn visit_slip(mut conn: PoolConnection<MySql>, beneficiary_id: i32, date: &str, user: &UserRole, config: &EligibilityConfig) -> Result<VisitSlip, (StatusCode, String)> {
+    debug!(beneficiary_id, date, "Print visit slip");
+    let day = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S")
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Date must be written YYYY-MM-DD HH:MM:SS".to_string()))?;
+    let failed = |e: Error| {
+        error!(error = %e, "Print visit slip failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not print the visit slip".to_string())
+    };
+    let presences: i64 = sqlx::query_scalar(&DistributionQueries::CountPresence.to_string())
+        .bind(beneficiary_id)
+        .bind(date)
+        .fetch_one(conn.as_mut())
+        .await
+        .map_err(failed)?;
+    if presences == 0 {
+        return Err((StatusCode::NOT_FOUND, "No such presence".to_string()));
+    }
+    let distribution = distribution::current(conn.as_mut(), beneficiary_id, date).await.map_err(failed)?;
+    let category = distribution.as_ref().and_then(|distribution| distribution.CategoryId);
+    let holder = holder(conn.as_mut(), beneficiary_id, category, user)
+        .await
+        .map_err(failed)?
+        .ok_or((StatusCode::NOT_FOUND, "No such beneficiary".to_string()))?;
+    let eligibility = eligibility::evaluate(conn.as_mut(), beneficiary_id, day.date(), false, config)
+        .await
+        .map_err(failed)?
+        .ok_or((StatusCode::NOT_FOUND, "No such beneficiary".to_string()))?;
+    Ok(VisitSlip { Holder: holder, Date: date.to_string(), Distribution: distribution, Eligibility: eligibility, Basis: config.basis })
+}
@@ -0,0 +1,245 @@
+use std::fmt::{Display, Formatter};
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
+use sqlx::{Connection, Error, MySqlPool};
+use crate::config::{EligibilityConfig, StatsPeriod};
+use crate::schema::distribution::CURRENT;
+use crate::schema::eligibility::periods;
+use tracing::debug;
+
+/// The statistics tables and their columns after `Date`. Values of a text field equal to a
+/// column name, ignoring case and punctuation, count in that column.
+pub(crate) const SERIES: [(&str, &[&str]); 12] = [
+    ("Presence", &["Total", "Active", "Visits"]),
+    ("Amounts", &["TotalWeekly", "TotalMonthly"]),
+    ("Age", &["Age_0_19", "Age_20_29", "Age_30_39", "Age_40_49", "Age_50_59", "Age_60_69", "Age_70_Plus"]),
+    ("City", &["Carignan", "Chambly", "Marieville", "Richelieu", "StMathias", "Other"]),
+    ("Employment", &["Unemployed", "Employed"]),
+    ("FamilySituation", &["Single", "Couple", "CoupleKids", "Recomposed", "SingleParent", "Other"]),
+    ("Income", &["NoIncome", "Income_1_14999", "Income_15000_29999", "Income_30000_More"]),
+    ("Kid", &["NoKids", "OneKid", "TwoKids", "ThreeToFourKids", "FivePlusKids"]),
+    ("Language", &["French", "English", "Spanish", "Arabic", "Mandarin", "Other"]),
+    ("Origin", &["NorthAmerican", "SouthAmerican", "CentralAmerican", "Asian", "African", "European", "Other"]),
+    ("Sexe", &["Male", "Female", "Other"]),
+    ("Study", &["NoStudy", "PrimarySchool", "HighSchool", "College", "University", "Other"]),
+];
+
+/// Most periods a backfill computes at once.
+pub(crate) const MAX_BACKFILL: usize = 3660;
+
+pub(crate) enum SnapshotQueries {
+    SelectPeople,
+    CountVisits,
+    SumAmounts,
+    Delete(&'static str),
+    Insert(&'static str, &'static [&'static str]),
+}
+
+impl Display for SnapshotQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // The version of each beneficiary valid at the instant bound first. Beneficiaries
+            // older than their history count with their first version once they visited.
+            SnapshotQueries::SelectPeople => write!(f,
+                "SELECT DATE_FORMAT(h.Birth, '%Y-%m-%d') AS Birth, h.Kid, \
+                h.Sexe, h.Language, h.Origin, h.City, h.Study, h.Income, h.FamilySituation, h.IsActive, h.IsEmployed \
+                FROM BeneficiaryHistory AS h \
+                WHERE h.Version = COALESCE( \
+                    (SELECT MAX(v.Version) FROM BeneficiaryHistory AS v WHERE v.BeneficiaryId = h.BeneficiaryId AND v.ValidFrom < ?), \
+                    (SELECT MIN(v.Version) FROM BeneficiaryHistory AS v WHERE v.BeneficiaryId = h.BeneficiaryId AND EXISTS ( \
+                        SELECT 1 FROM BeneficiaryPresences AS p \
+                        WHERE p.BeneficiaryId = h.BeneficiaryId AND p.PresenceDate < ? AND p.DeletedAt IS NULL)))"
+            ),
+            SnapshotQueries::CountVisits => write!(f,
+                "SELECT COUNT(*) FROM BeneficiaryPresences WHERE PresenceDate >= ? AND PresenceDate < ? AND DeletedAt IS NULL"
+            ),
+            // What current distributions gave out since the week start and since the month
+            // start bound first, up to the day bound last.
+            SnapshotQueries::SumAmounts => write!(f,
+                "SELECT COALESCE(SUM(CASE WHEN PresenceDate >= ? THEN Amount END), 0e0) AS Weekly, \
+                COALESCE(SUM(CASE WHEN PresenceDate >= ? THEN Amount END), 0e0) AS Monthly \
+                FROM Distribution WHERE {CURRENT} AND PresenceDate >= LEAST(?, ?) AND PresenceDate < ?"
+            ),
+            SnapshotQueries::Delete(table) => write!(f, "DELETE FROM {table} WHERE Date = ?"),
+            SnapshotQueries::Insert(table, columns) => write!(f,
+                "INSERT INTO {table} (Date, {}) VALUES (?{})",
+                columns.join(", "),
+                ", ?".repeat(columns.len())
+            ),
+        }
+    }
+}
+
+/// A beneficiary as recorded at the time of a snapshot.
+#[derive(Clone, Debug, Default, sqlx::FromRow, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Person {
+    pub(crate) Birth: Option<String>,
+    pub(crate) Kid: u8,
+    pub(crate) Sexe: String,
+    pub(crate) Language: String,
+    pub(crate) Origin: String,
+    pub(crate) City: String,
+    pub(crate) Study: String,
+    pub(crate) Income: String,
+    pub(crate) FamilySituation: String,
+    pub(crate) IsActive: bool,
+    pub(crate) IsEmployed: bool,
+}
+
+/// The first day of the period containing `day` and the first day after it.
+pub(crate) fn bounds(day: NaiveDate, period: StatsPeriod, config: &EligibilityConfig) -> (NaiveDate, NaiveDate) {
+    let (week, month) = periods(day, config);
+    match period {
+        StatsPeriod::Day => (day, day + Days::new(1)),
+        StatsPeriod::Week => (week, week + Days::new(7)),
+        StatsPeriod::Month => (month, month + Months::new(1)),
+    }
+}
+
+/// The first day of the period starting `start`, the first day after it and the day it is
+/// counted on: its last day, or `today` while it is not over.
+pub(crate) fn span(start: NaiveDate, period: StatsPeriod, config: &EligibilityConfig, today: NaiveDate) -> (NaiveDate, NaiveDate, NaiveDate) {
+    let (start, end) = bounds(start, period, config);
+    (start, end, (end - Days::new(1)).min(today))
+}
+
+/// The start of every period overlapping `from` to `to` included.
+pub(crate) fn starts(from: NaiveDate, to: NaiveDate, period: StatsPeriod, config: &EligibilityConfig) -> Vec<NaiveDate> {
+    let mut starts = Vec::new();
+    let (mut start, mut end) = bounds(from, period, config);
+    while start <= to {
+        starts.push(start);
+        (start, end) = bounds(end, period, config);
+    }
+    starts
+}
+
+fn key(value: &str) -> String {
+    value.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// The column of `value`, `Other` when the series has one, nothing for an empty value.
+fn column(value: &str, columns: &[&str]) -> Option<usize> {
+    let value = match key(value).as_str() {
+        "" => return None,
+        "m" => "male".to_string(),
+        "f" => "female".to_string(),
+        value => value.to_string(),
+    };
+    columns.iter().position(|column| key(column) == value).or_else(|| columns.iter().position(|column| *column == "Other"))
+}
+
+fn age(birth: &str, on: NaiveDate) -> Option<u32> {
+    let birth = NaiveDate::parse_from_str(birth, "%Y-%m-%d").ok()?;
+    on.years_since(birth)
+}
+
+/// The counts of every series, in the order of `SERIES`. Totals count everyone, the other
+/// series active beneficiaries only, ages being taken on `as_of`. `amounts` are what was
+/// distributed over the week and the month up to `as_of`.
+pub(crate) fn tally(people: &[Person], visits: u32, amounts: (f64, f64), as_of: NaiveDate) -> Vec<Vec<u32>> {
+    let mut counts: Vec<Vec<u32>> = SERIES.iter().map(|(_, columns)| vec![0; columns.len()]).collect();
+    counts[0] = vec![people.len() as u32, people.iter().filter(|person| person.IsActive).count() as u32, visits];
+    let round = |amount: f64| amount.round().clamp(0.0, u32::MAX as f64) as u32;
+    counts[1] = vec![round(amounts.0), round(amounts.1)];
+    let active: Vec<&Person> = people.iter().filter(|person| person.IsActive).collect();
+    for person in active {
+        if let Some(age) = person.Birth.as_deref().and_then(|birth| age(birth, as_of)) {
+            counts[2][((age.max(10) - 10) / 10).min(6) as usize] += 1;
+        }
+        counts[4][person.IsEmployed as usize] += 1;
+        counts[7][match person.Kid { 0 => 0, 1 => 1, 2 => 2, 3 | 4 => 3, _ => 4 }] += 1;
+        for (series, value) in [
+            (3, &person.City),
+            (5, &person.FamilySituation),
+            (6, &person.Income),
+            (8, &person.Language),
+            (9, &person.Origin),
+            (10, &person.Sexe),
+            (11, &person.Study),
+        ] {
+            if let Some(column) = column(value, SERIES[series].1) {
+                counts[series][column] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Computes the snapshot of the period starting `start` and replaces the rows of that date.
+/// A period not over yet is counted up to now.
+pub(crate) async fn compute(pool: &MySqlPool, start: NaiveDate, period: StatsPeriod, config: &EligibilityConfig) -> Result<(), Error> {
+    let (start, end, as_of) = span(start, period, config, Local::now().date_naive());
+    let until = as_of + Days::new(1);
+    let (week, month) = periods(as_of, config);
+    debug!(%start, %end, "Compute statistics snapshot");
+    let mut conn = pool.acquire().await?;
+    let mut tx = conn.begin().await?;
+    let people: Vec<Person> = sqlx::query_as(&SnapshotQueries::SelectPeople.to_string())
+        .bind(until)
+        .bind(until)
+        .fetch_all(&mut *tx)
+        .await?;
+    let visits: i64 = sqlx::query_scalar(&SnapshotQueries::CountVisits.to_string())
+        .bind(start)
+        .bind(end)
+        .fetch_one(&mut *tx)
+        .await?;
+    let amounts: (f64, f64) = sqlx::query_as(&SnapshotQueries::SumAmounts.to_string())
+        .bind(week)
+        .bind(month)
+        .bind(week)
+        .bind(month)
+        .bind(until)
+        .fetch_one(&mut *tx)
+        .await?;
+    let counts = tally(&people, visits as u32, amounts, as_of);
+    // Deleting first keeps a rerun from adding rows whatever the keys of the table.
+    for ((table, columns), counts) in SERIES.iter().zip(&counts) {
+        sqlx::query(&SnapshotQueries::Delete(table).to_string())
+            .bind(start)
+            .execute(&mut *tx)
+            .await?;
+        let query = SnapshotQueries::Insert(table, columns).to_string();
+        let mut query = sqlx::query(&query).bind(start);
+        for count in counts {
+            query = query.bind(count);
+        }
+        query.execute(&mut *tx).await?;
+    }
+    tx.commit().await
+}
+
+/// Computes the period containing `day` and the one before, which may have ended since it
+/// was last computed.
+pub(crate) async fn refresh(pool: &MySqlPool, day: NaiveDate, period: StatsPeriod, config: &EligibilityConfig) -> Result<(), Error> {
+    let (start, _) = bounds(day, period, config);
+    let (previous, _) = bounds(start.pred_opt().unwrap_or(start), period, config);
+    compute(pool, previous, period, config).await?;
+    compute(pool, start, period, config).await
+}
+
+/// Computes every period overlapping `from` to `to` included, oldest first. Returns the
+/// snapshots written.
+pub(crate) async fn backfill(pool: &MySqlPool, from: NaiveDate, to: NaiveDate, period: StatsPeriod, config: &EligibilityConfig) -> Result<u32, Error> {
+    let starts = starts(from, to, period, config);
+    for start in &starts {
+        compute(pool, *start, period, config).await?;
+    }
+    Ok(starts.len() as u32)
+}
+
+/// The days of a backfill request, `From` not after `To` and `To` not in the future.
+pub(crate) fn range(from: &str, to: &str, period: StatsPeriod, config: &EligibilityConfig) -> Result<(NaiveDate, NaiveDate), String> {
+    let parse = |day: &str| NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|_| "Dates must be written YYYY-MM-DD".to_string());
+    let (from, to) = (parse(from)?, parse(to)?);
+    if from > to {
+        return Err("From is after To".to_string());
+    }
+    if to > Local::now().date_naive() {
+        return Err("To is in the future".to_string());
+    }
+    if to.year() - from.year() > 100 || starts(from, to, period, config).len() > MAX_BACKFILL {
+        return Err(format!("At most {MAX_BACKFILL} periods are computed at once"));
+    }
+    Ok((from, to))
+}
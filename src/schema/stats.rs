@@ -1,185 +1,196 @@
+use std::fmt::{Display, Formatter};
 use axum::http::StatusCode;
-use bincode::{Encode};
-    use serde::{Deserialize, Serialize};
-    use sqlx::MySql;
-    use sqlx::pool::PoolConnection;
-    use crate::schema::encode;
+use chrono::{Datelike, NaiveDate};
+use sqlx::{MySql, Row};
+use sqlx::pool::PoolConnection;
+use crate::config::EligibilityConfig;
+use crate::schema::distribution::TotalsPeriod;
+use crate::schema::eligibility::periods;
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::snapshot::SERIES;
+pub(crate) use harmony_protocol::v1::{Age, Amounts, City, Employment, FamilySituation, Income, Kid, Language, Origin, Presence, Sexe, Stats, Study};
+pub(crate) use harmony_protocol::v2::TokenStatsQuery;
+use tracing::{debug, error};
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug)]
-    pub(crate) struct Stats {
-        pub(crate) Presences: Vec<Presence>,
-        pub(crate) Amounts: Vec<Amounts>,
-        pub(crate) Ages: Vec<Age>,
-        pub(crate) Cities: Vec<City>,
-        pub(crate) Employments: Vec<Employment>,
-        pub(crate) FamilySituations: Vec<FamilySituation>,
-        pub(crate) Incomes: Vec<Income>,
-        pub(crate) Kids: Vec<Kid>,
-        pub(crate) Languages: Vec<Language>,
-        pub(crate) Origins: Vec<Origin>,
-        pub(crate) Sexes: Vec<Sexe>,
-        pub(crate) Studies: Vec<Study>,
-    }
-
-    impl Stats{
-        pub(crate) async fn get_stats(mut conn: PoolConnection<MySql>) -> Result<Vec<u8>, (StatusCode, String)> {
-            let stats = Self {
-                Presences: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, Total, Active, Visits FROM Presence ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Amounts: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, TotalWeekly, TotalMonthly FROM Amounts ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Ages: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, Age_0_19, Age_20_29, Age_30_39, Age_40_49, Age_50_59, Age_60_69, Age_70_Plus FROM Age ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Cities: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, Carignan, Chambly, Marieville, Richelieu, StMathias, Other FROM City ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Employments: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, Unemployed, Employed FROM Employment ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                FamilySituations: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, Single, Couple, CoupleKids, Recomposed, SingleParent, Other FROM FamilySituation ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Incomes: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, NoIncome, Income_1_14999, Income_15000_29999, Income_30000_More FROM Income ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Kids: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, NoKids, OneKid, TwoKids, ThreeToFourKids, FivePlusKids FROM Kid ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Languages: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, French, English, Spanish, Arabic, Mandarin, Other FROM Language ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Origins: sqlx::query_as("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, NorthAmerican, SouthAmerican, CentralAmerican, Asian, African, European, Other FROM Origin ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Sexes: sqlx::query_as("SELECT  DATE_FORMAT(Date, '%Y-%m-%d') AS Date, Male, Female, Other FROM Sexe ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-                Studies: sqlx::query_as("SELECT  DATE_FORMAT(Date, '%Y-%m-%d') AS Date, NoStudy, PrimarySchool, HighSchool, College, University, Other FROM Study ORDER BY Date ASC")
-                    .fetch_all(conn.as_mut())
-                    .await.unwrap_or_default(),
-            };
-            match encode(stats) {
-                Ok(b) => Ok(b),
-                Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to get stats".to_string()))
-            }
-        }
-    }
+/// The series of `v1::Stats`, in the order of `SERIES`.
+pub(crate) const NAMES: [&str; 12] = [
+    "Presences", "Amounts", "Ages", "Cities", "Employments", "FamilySituations",
+    "Incomes", "Kids", "Languages", "Origins", "Sexes", "Studies",
+];
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Presence {
-        pub(crate) Date: String,
-        pub(crate) Total: u32,
-        pub(crate) Active: u32,
-        pub(crate) Visits: u32,
-    }
+/// `Visits` counts what happened during a period and is summed when rolling up. Every other
+/// column is taken from the last snapshot of the period: head counts, and `Amounts`, which
+/// are already running totals over the week and the month up to their snapshot.
+const SUMMED: (usize, usize) = (0, 2);
 
-#[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-pub(crate) struct Amounts {
-    pub(crate) Date: String,
-    pub(crate) TotalWeekly: u32,
-    pub(crate) TotalMonthly: u32,
+pub(crate) enum StatsQueries {
+    SelectSeries(&'static str, &'static [&'static str]),
 }
-    #[derive(sqlx::FromRow, Encode,Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Age {
-        pub(crate) Date: String,
-        pub(crate) Age_0_19: u32,
-        pub(crate) Age_20_29: u32,
-        pub(crate) Age_30_39: u32,
-        pub(crate) Age_40_49: u32,
-        pub(crate) Age_50_59: u32,
-        pub(crate) Age_60_69: u32,
-        pub(crate) Age_70_Plus: u32,
-    }
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct City {
-        pub(crate) Date: String,
-        pub(crate) Carignan: u32,
-        pub(crate) Chambly: u32,
-        pub(crate) Marieville: u32,
-        pub(crate) Richelieu: u32,
-        pub(crate) StMathias: u32,
-        pub(crate) Other: u32,
+impl Display for StatsQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatsQueries::SelectSeries(table, columns) => write!(f,
+                "SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, {} FROM {table} \
+                WHERE (? IS NULL OR Date >= ?) AND (? IS NULL OR Date <= ?) ORDER BY Date ASC",
+                columns.join(", ")
+            ),
+        }
     }
+}
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Employment {
-        pub(crate) Date: String,
-        pub(crate) Unemployed: u32,
-        pub(crate) Employed: u32,
-    }
+/// A statistics query once checked: the days included, the rollup and which series to read.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Selection {
+    pub(crate) from: Option<NaiveDate>,
+    pub(crate) to: Option<NaiveDate>,
+    pub(crate) period: TotalsPeriod,
+    pub(crate) series: [bool; 12],
+}
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct FamilySituation {
-        pub(crate) Date: String,
-        pub(crate) Single: u32,
-        pub(crate) Couple: u32,
-        pub(crate) CoupleKids: u32,
-        pub(crate) Recomposed: u32,
-        pub(crate) SingleParent: u32,
-        pub(crate) Other: u32,
+impl Selection {
+    /// Every row of every series.
+    pub(crate) fn all() -> Selection {
+        Selection { from: None, to: None, period: TotalsPeriod::Day, series: [true; 12] }
     }
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Income {
-        pub(crate) Date: String,
-        pub(crate) NoIncome: u32,
-        pub(crate) Income_1_14999: u32,
-        pub(crate) Income_15000_29999: u32,
-        pub(crate) Income_30000_More: u32,
+    /// An empty `Period` is `day`, an empty `Series` every series.
+    pub(crate) fn parse(query: &TokenStatsQuery) -> Result<Selection, (StatusCode, String)> {
+        let day = |day: &Option<String>| match day.as_deref() {
+            None | Some("") => Ok(None),
+            Some(day) => NaiveDate::parse_from_str(day, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid date: {day}"))),
+        };
+        let (from, to) = (day(&query.From)?, day(&query.To)?);
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return Err((StatusCode::BAD_REQUEST, "From is after To".to_string()));
+            }
+        }
+        let period = if query.Period.is_empty() { TotalsPeriod::Day } else { query.Period.parse()? };
+        let mut series = [query.Series.is_empty(); 12];
+        for name in &query.Series {
+            let i = NAMES
+                .iter()
+                .position(|known| known.eq_ignore_ascii_case(name))
+                .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unknown series: {name}")))?;
+            series[i] = true;
+        }
+        Ok(Selection { from, to, period, series })
     }
+}
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Kid {
-        pub(crate) Date: String,
-        pub(crate) NoKids: u32,
-        pub(crate) OneKid: u32,
-        pub(crate) TwoKids: u32,
-        pub(crate) ThreeToFourKids: u32,
-        pub(crate) FivePlusKids: u32,
+/// The first day of the period `day` falls in. Weeks and months start as for eligibility.
+fn start(day: NaiveDate, period: TotalsPeriod, config: &EligibilityConfig) -> NaiveDate {
+    let (week, month) = periods(day, config);
+    match period {
+        TotalsPeriod::Day => day,
+        TotalsPeriod::Week => week,
+        TotalsPeriod::Month => month,
+        TotalsPeriod::Year => day.with_ordinal(1).unwrap_or(day),
     }
+}
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Language {
-        pub(crate) Date: String,
-        pub(crate) French: u32,
-        pub(crate) English: u32,
-        pub(crate) Spanish: u32,
-        pub(crate) Arabic: u32,
-        pub(crate) Mandarin: u32,
-        pub(crate) Other: u32,
+/// One row per period, dated from its first day, out of rows sorted by date.
+pub(crate) fn rollup(series: usize, rows: Vec<(NaiveDate, Vec<u32>)>, period: TotalsPeriod, config: &EligibilityConfig) -> Vec<(NaiveDate, Vec<u32>)> {
+    let mut rolled: Vec<(NaiveDate, Vec<u32>)> = Vec::new();
+    for (day, counts) in rows {
+        let start = start(day, period, config);
+        match rolled.last_mut() {
+            Some((last, previous)) if *last == start => {
+                let summed = (series == SUMMED.0).then(|| previous[SUMMED.1].saturating_add(counts[SUMMED.1]));
+                *previous = counts;
+                if let Some(summed) = summed {
+                    previous[SUMMED.1] = summed;
+                }
+            },
+            _ => rolled.push((start, counts)),
+        }
     }
+    rolled
+}
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Origin {
-        pub(crate) Date: String,
-        pub(crate) NorthAmerican: u32,
-        pub(crate) SouthAmerican: u32,
-        pub(crate) CentralAmerican: u32,
-        pub(crate) Asian: u32,
-        pub(crate) African: u32,
-        pub(crate) European: u32,
-        pub(crate) Other: u32,
+/// The rows of every series as `v1::Stats`, a series not read being left empty.
+pub(crate) fn stats(mut series: Vec<Vec<(NaiveDate, Vec<u32>)>>) -> Stats {
+    fn rows<T>(rows: Vec<(NaiveDate, Vec<u32>)>, row: impl Fn(String, &[u32]) -> T) -> Vec<T> {
+        rows.into_iter().map(|(day, c)| row(day.format("%Y-%m-%d").to_string(), &c)).collect()
     }
-
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Study {
-        pub(crate) Date: String,
-        pub(crate) NoStudy: u32,
-        pub(crate) PrimarySchool: u32,
-        pub(crate) HighSchool: u32,
-        pub(crate) College: u32,
-        pub(crate) University: u32,
-        pub(crate) Other: u32,
+    let mut next = || series.remove(0);
+    Stats {
+        Presences: rows(next(), |Date, c| Presence { Date, Total: c[0], Active: c[1], Visits: c[2] }),
+        Amounts: rows(next(), |Date, c| Amounts { Date, TotalWeekly: c[0], TotalMonthly: c[1] }),
+        Ages: rows(next(), |Date, c| Age {
+            Date,
+            Age_0_19: c[0],
+            Age_20_29: c[1],
+            Age_30_39: c[2],
+            Age_40_49: c[3],
+            Age_50_59: c[4],
+            Age_60_69: c[5],
+            Age_70_Plus: c[6],
+        }),
+        Cities: rows(next(), |Date, c| City { Date, Carignan: c[0], Chambly: c[1], Marieville: c[2], Richelieu: c[3], StMathias: c[4], Other: c[5] }),
+        Employments: rows(next(), |Date, c| Employment { Date, Unemployed: c[0], Employed: c[1] }),
+        FamilySituations: rows(next(), |Date, c| FamilySituation {
+            Date,
+            Single: c[0],
+            Couple: c[1],
+            CoupleKids: c[2],
+            Recomposed: c[3],
+            SingleParent: c[4],
+            Other: c[5],
+        }),
+        Incomes: rows(next(), |Date, c| Income { Date, NoIncome: c[0], Income_1_14999: c[1], Income_15000_29999: c[2], Income_30000_More: c[3] }),
+        Kids: rows(next(), |Date, c| Kid { Date, NoKids: c[0], OneKid: c[1], TwoKids: c[2], ThreeToFourKids: c[3], FivePlusKids: c[4] }),
+        Languages: rows(next(), |Date, c| Language { Date, French: c[0], English: c[1], Spanish: c[2], Arabic: c[3], Mandarin: c[4], Other: c[5] }),
+        Origins: rows(next(), |Date, c| Origin {
+            Date,
+            NorthAmerican: c[0],
+            SouthAmerican: c[1],
+            CentralAmerican: c[2],
+            Asian: c[3],
+            African: c[4],
+            European: c[5],
+            Other: c[6],
+        }),
+        Sexes: rows(next(), |Date, c| Sexe { Date, Male: c[0], Female: c[1], Other: c[2] }),
+        Studies: rows(next(), |Date, c| Study { Date, NoStudy: c[0], PrimarySchool: c[1], HighSchool: c[2], College: c[3], University: c[4], Other: c[5] }),
     }
+}
 
-    #[derive(sqlx::FromRow, Encode, Serialize, Deserialize, Debug, Default)]
-    pub(crate) struct Sexe {
-        pub(crate) Date: String,
-        pub(crate) Male: u32,
-        pub(crate) Female: u32,
-        pub(crate) Other: u32,
-    }
\ No newline at end of file
+/// The series selected between the days selected, rolled up to the period selected. A
+/// failing series fails the whole request.
+pub(crate) async fn select_stats(mut conn: PoolConnection<MySql>, selection: &Selection, config: &EligibilityConfig, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(from = ?selection.from, to = ?selection.to, period = ?selection.period, "Select stats");
+    let mut series = Vec::with_capacity(SERIES.len());
+    for (i, (table, columns)) in SERIES.iter().enumerate() {
+        if !selection.series[i] {
+            series.push(Vec::new());
+            continue;
+        }
+        let failed = |e: sqlx::Error| {
+            error!(error = %e, series = NAMES[i], "Select stats failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Could not get the {} statistics", NAMES[i]))
+        };
+        let found = sqlx::query(&StatsQueries::SelectSeries(table, columns).to_string())
+            .bind(selection.from)
+            .bind(selection.from)
+            .bind(selection.to)
+            .bind(selection.to)
+            .fetch_all(conn.as_mut())
+            .await
+            .map_err(failed)?;
+        let mut rows = Vec::with_capacity(found.len());
+        for row in found {
+            let day: String = row.try_get("Date").map_err(failed)?;
+            let day = NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+                .map_err(|e| failed(sqlx::Error::Decode(Box::new(e))))?;
+            let counts = columns.iter().map(|column| row.try_get::<u32, _>(*column)).collect::<Result<Vec<_>, _>>().map_err(failed)?;
+            rows.push((day, counts));
+        }
+        series.push(rollup(i, rows, selection.period, config));
+    }
+    encode(stats(series), format)
+}
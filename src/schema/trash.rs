@@ -0,0 +1,215 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+use axum::http::StatusCode;
+use sqlx::{Error, MySql, MySqlPool};
+use sqlx::pool::PoolConnection;
+use harmony_protocol::v1;
+pub(crate) use harmony_protocol::v1::{TokenTrashEntry, TokenTrashQuery, TrashEntry};
+use crate::schema::audit::{change, AuditAction, AuditChange};
+use crate::schema::encode;
+use crate::schema::format::{Encoded, Format};
+use crate::schema::user::UserRole;
+use tracing::{debug, error};
+
+/// What a trash entry was before it was deleted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TrashKind {
+    Allergy,
+    Presence,
+    Member,
+    Note,
+}
+
+impl TrashKind {
+    /// In the order of `TrashQueries::SelectTrash`.
+    pub(crate) const ALL: [TrashKind; 4] = [TrashKind::Allergy, TrashKind::Presence, TrashKind::Member, TrashKind::Note];
+
+    fn table(self) -> &'static str {
+        match self {
+            TrashKind::Allergy => "BeneficiaryAllergies",
+            TrashKind::Presence => "BeneficiaryPresences",
+            TrashKind::Member => "HouseholdMember",
+            TrashKind::Note => "BeneficiaryNotes",
+        }
+    }
+
+    pub(crate) fn restored(self) -> AuditAction {
+        match self {
+            TrashKind::Allergy => AuditAction::RestoreAllergy,
+            TrashKind::Presence => AuditAction::RestorePresence,
+            TrashKind::Member => AuditAction::RestoreMember,
+            TrashKind::Note => AuditAction::RestoreNote,
+        }
+    }
+
+    /// What the audit keeps of a restored entry, as for its deletion.
+    pub(crate) fn changes(self, entry: &TrashEntry) -> Vec<AuditChange> {
+        match self {
+            TrashKind::Allergy => vec![change("Id", None, Some(entry.Key.clone()))],
+            TrashKind::Presence => vec![change("Date", None, Some(entry.Key.clone()))],
+            TrashKind::Member => vec![change("MemberId", None, Some(entry.Key.clone()))],
+            TrashKind::Note => vec![change("Id", None, Some(entry.Key.clone()))],
+        }
+    }
+}
+
+impl Display for TrashKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            TrashKind::Allergy => "allergy",
+            TrashKind::Presence => "presence",
+            TrashKind::Member => "member",
+            TrashKind::Note => "note",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+impl FromStr for TrashKind {
+    type Err = (StatusCode, String);
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        TrashKind::ALL
+            .into_iter()
+            .find(|known| known.to_string() == kind)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Unknown trash kind: {kind}")))
+    }
+}
+
+pub(crate) enum TrashQueries {
+    SelectTrash,
+    Restore(TrashKind),
+    Purge(TrashKind),
+    PurgeOrphanAllergies,
+}
+
+impl Display for TrashQueries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let deleted = "DATE_FORMAT(DeletedAt, '%Y-%m-%d %H:%i:%s') AS DeletedAt, DeletedBy, \
+            DATE_FORMAT(DeletedAt + INTERVAL ? SECOND, '%Y-%m-%d %H:%i:%s') AS PurgeAfter";
+        let filter = "DeletedAt IS NOT NULL AND (? IS NULL OR BeneficiaryId = ?)";
+        match self {
+            TrashQueries::SelectTrash => write!(f,
+                "SELECT 'allergy' AS Kind, BeneficiaryId, CAST(Id AS CHAR) AS `Key`, Allergy AS Content, {deleted} \
+                FROM BeneficiaryAllergies WHERE {filter} \
+                UNION ALL \
+                SELECT 'presence' AS Kind, BeneficiaryId, DATE_FORMAT(PresenceDate, '%Y-%m-%d %H:%i:%s') AS `Key`, '' AS Content, {deleted} \
+                FROM BeneficiaryPresences WHERE {filter} \
+                UNION ALL \
+                SELECT 'member' AS Kind, BeneficiaryId, CAST(Id AS CHAR) AS `Key`, CONCAT(FirstName, ' ', LastName) AS Content, {deleted} \
+                FROM HouseholdMember WHERE {filter} \
+                UNION ALL \
+                SELECT 'note' AS Kind, BeneficiaryId, CAST(Id AS CHAR) AS `Key`, Note AS Content, {deleted} \
+                FROM BeneficiaryNotes WHERE {filter} \
+                AND Type IN (SELECT NoteTypeId FROM NoteTypeAccess WHERE Role = ? AND CanRead) \
+                ORDER BY DeletedAt DESC"
+            ),
+            TrashQueries::Restore(kind) => {
+                let key = match kind {
+                    TrashKind::Presence => "PresenceDate",
+                    TrashKind::Allergy | TrashKind::Member | TrashKind::Note => "Id",
+                };
+                write!(f,
+                    "UPDATE {} SET DeletedAt = NULL, DeletedBy = NULL \
+                    WHERE BeneficiaryId = ? AND {key} = ? AND DeletedAt = ? AND DeletedAt >= NOW() - INTERVAL ? SECOND",
+                    kind.table()
+                )
+            },
+            TrashQueries::Purge(kind) => write!(f,
+                "DELETE FROM {} WHERE DeletedAt < NOW() - INTERVAL ? SECOND",
+                kind.table()
+            ),
+            TrashQueries::PurgeOrphanAllergies => write!(f,
+                "DELETE FROM BeneficiaryAllergies WHERE MemberId IS NOT NULL AND MemberId NOT IN (SELECT Id FROM HouseholdMember)"
+            ),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct TrashRow {
+    Kind: String,
+    BeneficiaryId: i32,
+    Key: String,
+    Content: String,
+    DeletedAt: String,
+    DeletedBy: Option<i32>,
+    PurgeAfter: String,
+}
+
+impl From<TrashRow> for v1::TrashEntry {
+    fn from(row: TrashRow) -> Self {
+        v1::TrashEntry {
+            Kind: row.Kind,
+            BeneficiaryId: row.BeneficiaryId,
+            Key: row.Key,
+            Content: row.Content,
+            DeletedAt: row.DeletedAt,
+            DeletedBy: row.DeletedBy,
+            PurgeAfter: row.PurgeAfter,
+        }
+    }
+}
+
+/// Deleted rows, most recent first, notes only of the types the role may read. Rows past
+/// retention but not purged yet are listed too.
+pub(crate) async fn select_trash(mut conn: PoolConnection<MySql>, query: &TokenTrashQuery, role: &UserRole, retention: Duration, format: Format) -> Result<Encoded, (StatusCode, String)> {
+    debug!(beneficiary_id = query.BeneficiaryId, "Select trash");
+    let sql = TrashQueries::SelectTrash.to_string();
+    let mut select = sqlx::query_as::<_, TrashRow>(&sql);
+    for _ in TrashKind::ALL {
+        select = select.bind(retention.as_secs()).bind(query.BeneficiaryId).bind(query.BeneficiaryId);
+    }
+    select = select.bind(&role.Role);
+    match select.fetch_all(conn.as_mut()).await {
+        Ok(rows) => {
+            debug!(count = rows.len(), "Select trash succeeded");
+            encode(rows.into_iter().map(v1::TrashEntry::from).collect::<Vec<_>>(), format)
+        }
+        Err(e) => {
+            error!(error = %e, "Select trash failed");
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Could not get the trash".to_string()))
+        }
+    }
+}
+
+/// Puts an entry back in the details, if it is still in the trash and within retention.
+pub(crate) async fn restore(mut conn: PoolConnection<MySql>, entry: &TrashEntry, retention: Duration) -> Result<TrashKind, (StatusCode, String)> {
+    let kind: TrashKind = entry.Kind.parse()?;
+    debug!(%kind, beneficiary_id = entry.BeneficiaryId, "Restore from trash");
+    let result = sqlx::query(&TrashQueries::Restore(kind).to_string())
+        .bind(entry.BeneficiaryId)
+        .bind(&entry.Key)
+        .bind(&entry.DeletedAt)
+        .bind(retention.as_secs())
+        .execute(conn.as_mut())
+        .await
+        .map_err(|e| {
+            error!(error = %e, "Restore from trash failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, "Could not restore".to_string())
+        })?;
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Not in the trash, or its retention is over".to_string()));
+    }
+    debug!("Restore from trash succeeded");
+    Ok(kind)
+}
+
+/// Removes for good what was deleted more than `retention` ago, and the allergies of the
+/// members removed, returns how many rows.
+pub(crate) async fn purge(pool: &MySqlPool, retention: Duration) -> Result<u64, Error> {
+    let mut removed = 0;
+    for kind in TrashKind::ALL {
+        removed += sqlx::query(&TrashQueries::Purge(kind).to_string())
+            .bind(retention.as_secs())
+            .execute(pool)
+            .await?
+            .rows_affected();
+    }
+    removed += sqlx::query(&TrashQueries::PurgeOrphanAllergies.to_string())
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(removed)
+}
@@ -3,7 +3,7 @@
     use anyhow::Context;
     use axum::http::StatusCode;
     use serde::{Deserialize, Serialize};
-    use sqlx::{Decode, Error, MySql, MySqlPool};
+    use sqlx::{Decode, Error, MySql, MySqlConnection, MySqlPool};
     use sqlx::pool::PoolConnection;
     use bcrypt::{DEFAULT_COST, verify};
     use bincode::{Encode};
@@ -11,8 +11,13 @@
     use crate::route::acquire_connection;
     use crate::schema::beneficiary::{Beneficiary};
     use crate::schema::encode;
+    use crate::schema::format::{Encoded, Format};
+    use harmony_protocol::{v1, v2, Version};
+    use tracing::{debug, error};
+    use crate::telemetry::{record_user, Secret};
+    use crate::schema::follow_up;
 
-    #[derive(sqlx::FromRow,Encode,Decode, Serialize, Deserialize)]
+    #[derive(sqlx::FromRow,Encode,Decode, bincode::Decode, Serialize, Deserialize)]
     pub(crate) struct User{
         pub(crate) Id: i32,
         pub(crate) Username: String,
@@ -20,38 +25,54 @@
         pub(crate) Role: String,
     }
 
+    impl From<User> for v1::User {
+        fn from(user: User) -> Self {
+            v1::User { Id: user.Id, Username: user.Username, Password: user.Password, Role: user.Role }
+        }
+    }
+
     impl User{
         pub(crate) async fn validate_password(&self, other: &str) -> bool {
             verify(other, &self.Password).unwrap_or(false) || other == self.Password
         }
 
-        pub(crate) async fn get_users(mut conn: PoolConnection<MySql>, username: String) -> Result<Vec<u8>, (StatusCode, String)>{
+        pub(crate) async fn find(conn: &mut MySqlConnection, id: i32) -> Option<User>{
+            sqlx::query_as("SELECT Id, Username, '' as Password, Role FROM User WHERE Id = ?")
+                .bind(id)
+                .fetch_optional(conn)
+                .await
+                .ok()
+                .flatten()
+        }
+
+        pub(crate) async fn get_users(mut conn: PoolConnection<MySql>, username: String, format: Format) -> Result<Encoded, (StatusCode, String)>{
             let users: Result<Vec<User>, Error> = sqlx::query_as("SELECT Id, Username, '' as Password, Role FROM User WHERE Role NOT LIKE 'Dev' AND Username != 'admin' AND Username != ?")
                 .bind(username)
                 .fetch_all(conn.as_mut())
                 .await;
             match users {
-                Ok(users) => encode(users),
+                Ok(users) => encode(users.into_iter().map(v1::User::from).collect::<Vec<_>>(), format),
                 Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
             }
         }
 
     }
 
-    #[derive(sqlx::FromRow,Encode, Decode, Serialize, Deserialize)]
+    #[derive(sqlx::FromRow,Encode, Decode, Serialize, Deserialize, Clone)]
     pub(crate) struct UserRole{
+        pub(crate) Id: i32,
         pub(crate) Username: String,
         pub(crate) Role: String,
     }
 
-    #[derive(sqlx::FromRow,Encode, Decode, Serialize, Deserialize)]
+    #[derive(sqlx::FromRow,Encode, Decode, bincode::Decode, Serialize, Deserialize)]
     pub(crate) struct UserToken{
         pub(crate) Token: String,
         pub(crate) User: User
     }
 
     impl UserToken{
-        pub(crate) async fn create_user(&self, mut conn: PoolConnection<MySql>) -> Result<Vec<u8>, anyhow::Error>{
+        pub(crate) async fn create_user(&self, mut conn: PoolConnection<MySql>, format: Format) -> Result<Encoded, anyhow::Error>{
             let result = sqlx::query("INSERT INTO User (Username, Password, Role) VALUES (?, ?, ?)")
                 .bind(&self.User.Username)
                 .bind(bcrypt::hash(&self.User.Password, DEFAULT_COST).unwrap())
@@ -71,7 +92,7 @@
                 .context("Failed to get user")?;
 
 
-            let encoded = encode(user);
+            let encoded = encode(v1::User::from(user), format);
 
             match encoded {
                 Ok(val) => Ok(val),
@@ -124,29 +145,29 @@
 
 
 
-    #[derive( sqlx::FromRow, Serialize, Deserialize)]
+    #[derive(sqlx::FromRow, Encode, bincode::Decode, Serialize, Deserialize)]
     pub(crate) struct Token {
         pub(crate) Token: String,
     }
 
-    #[derive(sqlx::FromRow, Serialize, Deserialize)]
+    #[derive(sqlx::FromRow, Encode, bincode::Decode, Serialize, Deserialize)]
     pub(crate) struct TokenBeneId{
         pub(crate) Token: String,
         pub(crate) Id: i32,
     }
 
-    #[derive(sqlx::FromRow, Serialize, Deserialize)]
+    #[derive(sqlx::FromRow, Encode, bincode::Decode, Serialize, Deserialize)]
     pub(crate) struct TokenBene{
         pub(crate) Token: String,
         pub(crate) Beneficiary: Beneficiary,
     }
 
-    #[derive(sqlx::FromRow, Serialize, Deserialize)]
+    #[derive(sqlx::FromRow, Encode, bincode::Decode, Serialize, Deserialize)]
     pub(crate) struct TokenSearch{
         pub(crate) Token: String,
         pub(crate) Search: String,
     }
-    #[derive(sqlx::FromRow,Serialize, Deserialize)]
+    #[derive(sqlx::FromRow, Encode, bincode::Decode, Serialize, Deserialize)]
     pub(crate) struct UserLogin{
         pub(crate) Username: String,
         pub(crate) Password: String,
@@ -172,8 +193,9 @@
     }
 
     impl Connection{
-        pub(crate) async fn get_or_create_connection(pool: Arc<MySqlPool>, user: User) -> Result<Vec<u8>, (StatusCode, String)>{
-            println!("->> {:>12} - Login - User : {}", "Handler", user.Username);
+        /// From v2 on, the response counts the user's overdue follow-ups.
+        pub(crate) async fn get_or_create_connection(pool: Arc<MySqlPool>, user: User, version: Version, format: Format) -> Result<Encoded, (StatusCode, String)>{
+            record_user(user.Id);
 
             let mut connection = Connection{
                 Token: format!("{}-{}", user.Username, Uuid::new_v4()),
@@ -184,17 +206,26 @@
 
             if session.is_none() {
                 let conn = acquire_connection(pool.clone()).await?;
-                connection.create_session(conn, user.Id, connection.Token.clone()).await.map_err(|e| {
-                    println!("->> {:>12} - Login - Error : {}", "Handler", e.1);
-                    e
+                connection.create_session(conn, user.Id, connection.Token.clone()).await.inspect_err(|e| {
+                    error!(error = %e.1, "Login failed");
                 })?;
             }
-            println!("->> {:>12} - Login - Token : {}", "Handler", connection.Token);
-            encode(connection)
+            debug!(token = %Secret(&connection.Token), "Login session ready");
+            match version {
+                Version::V1 => encode(v1::Connection { Token: connection.Token, Role: connection.Role }, format),
+                Version::V2 => {
+                    let mut conn = acquire_connection(pool.clone()).await?;
+                    let overdue = follow_up::count_overdue(conn.as_mut(), user.Id, &user.Role).await.unwrap_or_else(|e| {
+                        error!(error = %e, "Count overdue follow-ups failed");
+                        0
+                    });
+                    encode(v2::Connection { Token: connection.Token, Role: connection.Role, OverdueFollowUps: overdue }, format)
+                },
+            }
         }
 
         async fn create_session(&mut self, mut conn : PoolConnection<MySql>, id: i32, session: String) -> Result<(), (StatusCode, String)> {
-            println!("->> {:>12} - Create Session - User : {}", "Handler", id);
+            debug!(user_id = id, "Create Session");
             let res = sqlx::query("INSERT INTO UserSession (UserId, Token, ConnectionDate, Expires) VALUES (?, ?, NOW(), NOW() + INTERVAL 1 DAY)")
                 .bind(id)
                 .bind(session)
@@ -203,17 +234,17 @@
 
             match res {
                 Ok(_) => {
-                    println!("->> {:>12} - Create Session - Success", "Handler");
+                    debug!("Create Session succeeded");
                     Ok(())
                 },
                 Err(_) => {
-                    println!("->> {:>12} - Create Session - Failed", "Handler");
+                    error!("Create Session failed");
                     Err((StatusCode::INTERNAL_SERVER_ERROR, "Failed to create session".to_string()))
                 },
             }
         }
         async fn get_token(&mut self, mut conn: PoolConnection<MySql>, id: i32) -> Option<Token>{
-            println!("->> {:>12} - Get Token - User : {}", "Handler", id);
+            debug!(user_id = id, "Get Token");
             let session: Result<Option<Token>, Error>= sqlx::query_as("SELECT Token FROM UserSession WHERE UserId = ? AND Expires > NOW()")
                 .bind(id)
                 .fetch_optional(conn.as_mut())
@@ -221,16 +252,16 @@
 
             match session {
                 Ok(Some(session)) => {
-                    println!("->> {:>12} - Get Token - Success", "Handler");
+                    debug!("Get Token succeeded");
                     self.Token = session.Token.clone();
                     Some(session)
                 },
                 Ok(None) => {
-                    println!("->> {:>12} - Get Token - NO TOKEN", "Handler");
+                    debug!("Get Token found no session");
                     None
                 },
                 Err(e) => {
-                    println!("->> {:>12} - Get Token - FAILED : {:?}", "Handler",e);
+                    error!(error = ?e, "Get Token failed");
                     None
                 },
             }
@@ -0,0 +1,54 @@
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use std::time::Duration;
+use axum::Router;
+use tokio::net::TcpListener;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::{timeout_at, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Cancels `shutdown` on the first SIGTERM or SIGINT.
+///
+/// The handlers are installed before returning, so a signal sent right after is not lost.
+pub(crate) fn cancel_on_signal(shutdown: CancellationToken) {
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+    let mut interrupt = signal(SignalKind::interrupt()).expect("failed to listen for SIGINT");
+    tokio::spawn(async move {
+        let name = tokio::select! {
+            _ = terminate.recv() => "SIGTERM",
+            _ = interrupt.recv() => "SIGINT",
+            _ = shutdown.cancelled() => return,
+        };
+        info!(signal = name, "Shutdown requested");
+        shutdown.cancel();
+    });
+}
+
+/// Serves `app` until `shutdown` is cancelled, then stops accepting connections and lets
+/// in-flight requests finish for at most `drain`.
+///
+/// Returns the drain deadline, which the rest of the shutdown shares.
+pub(crate) async fn serve(listener: TcpListener, app: Router, shutdown: CancellationToken, drain: Duration) -> Instant {
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+        .into_future();
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => {
+            if let Err(e) = result {
+                warn!(error = %e, "Server stopped");
+            }
+            Instant::now() + drain
+        },
+        _ = shutdown.cancelled() => {
+            let deadline = Instant::now() + drain;
+            match timeout_at(deadline, &mut server).await {
+                Ok(_) => info!("Every request drained"),
+                Err(_) => warn!(drain_secs = drain.as_secs(), "Drain deadline reached, dropping the remaining requests"),
+            }
+            deadline
+        },
+    }
+}
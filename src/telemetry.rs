@@ -0,0 +1,124 @@
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+use axum::async_trait;
+use axum::body::Body;
+use axum::extract::{FromRequestParts, MatchedPath};
+use axum::http::request::Parts;
+use axum::http::{HeaderName, Request, Response};
+use axum::Router;
+use dotenv::dotenv;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+use tracing::{error, field, info, warn, Span};
+use tracing_subscriber::EnvFilter;
+
+pub(crate) const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// sqlx logs statement text, and the beneficiary search still interpolates what was typed.
+const DEFAULT_FILTER: &str = "info,sqlx=warn";
+
+/// Installs the global subscriber.
+///
+/// `LOG_LEVEL` takes `EnvFilter` directives (`debug`, `middleman=debug,info`, ...), and
+/// `LOG_FORMAT=json` prints one JSON object per line instead of human readable text.
+pub fn init() {
+    dotenv().ok();
+    let directives = match dotenv::var("LOG_LEVEL") {
+        Ok(level) if level.contains("sqlx") => level,
+        Ok(level) => format!("{level},sqlx=warn"),
+        Err(_) => DEFAULT_FILTER.to_string(),
+    };
+    let filter = EnvFilter::try_new(directives).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match dotenv::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber.json().flatten_event(true).with_current_span(true).with_span_list(false).init(),
+        _ => subscriber.init(),
+    }
+}
+
+/// Gives every request an `x-request-id` (kept when the client sent one) and a `request` span
+/// carrying method, route, request id, user id, status and latency.
+///
+/// The span never records headers or bodies, which hold tokens, passwords and beneficiary files.
+pub(crate) fn instrument(router: Router) -> Router {
+    let request_id = HeaderName::from_static(REQUEST_ID_HEADER);
+    router
+        .layer(PropagateRequestIdLayer::new(request_id.clone()))
+        .layer(TraceLayer::new_for_http()
+            .make_span_with(make_span)
+            .on_request(())
+            .on_response(on_response)
+            .on_failure(()))
+        .layer(SetRequestIdLayer::new(request_id, MakeRequestUuid))
+}
+
+fn make_span(request: &Request<Body>) -> Span {
+    let route = request.extensions().get::<MatchedPath>().map(MatchedPath::as_str).unwrap_or("unmatched");
+    let request_id = request.headers().get(REQUEST_ID_HEADER).and_then(|id| id.to_str().ok()).unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route,
+        request_id,
+        user_id = field::Empty,
+        status = field::Empty,
+        latency_ms = field::Empty,
+    )
+}
+
+fn on_response(response: &Response<Body>, latency: Duration, span: &Span) {
+    let status = response.status();
+    span.record("status", status.as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    if status.is_server_error() {
+        error!("request failed");
+    } else if status.is_client_error() {
+        warn!("request rejected");
+    } else {
+        info!("request served");
+    }
+}
+
+/// Attaches the authenticated user to the current request span.
+pub(crate) fn record_user(id: i32) {
+    Span::current().record("user_id", id);
+}
+
+/// The `x-request-id` of the current request, empty outside of [`instrument`].
+pub(crate) struct RequestId(pub(crate) String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let id = parts.headers.get(REQUEST_ID_HEADER).and_then(|id| id.to_str().ok()).unwrap_or_default();
+        Ok(RequestId(id.to_string()))
+    }
+}
+
+/// Logs a token or password as a short fingerprint, enough to correlate lines without the secret.
+pub(crate) struct Secret<'a>(pub(crate) &'a str);
+
+impl Display for Secret<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "<empty>");
+        }
+        let mut hasher = DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        write!(f, "<secret:{:08x}>", hasher.finish() as u32)
+    }
+}
+
+/// Logs personal data of a beneficiary as its length only.
+pub(crate) struct Pii<'a>(pub(crate) &'a str);
+
+impl Display for Pii<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<redacted:{} chars>", self.0.chars().count())
+    }
+}
@@ -0,0 +1,84 @@
+use axum::http::StatusCode;
+use crate::schema::allergen::{self, allergen_changes, labels_text, validate, validate_severity, Allergen, AllergenLabel, TokenAllergenLink};
+use crate::schema::audit::AuditAction;
+use crate::schema::beneficiary::{Beneficiary, BeneficiaryAction};
+use crate::test::beneficiary::{as_role, fresh_beneficiary, get_conn};
+
+#[cfg(test)]
+fn label(language: &str, label: &str) -> AllergenLabel {
+    AllergenLabel { Language: language.to_string(), Label: label.to_string() }
+}
+
+#[cfg(test)]
+fn allergen(labels: Vec<AllergenLabel>) -> Allergen {
+    Allergen { Id: 5, Code: "peanuts".to_string(), Kind: "allergen".to_string(), IsActive: true, Labels: labels, Beneficiaries: 0 }
+}
+
+#[cfg(test)]
+#[test]
+fn allergens_need_a_code_a_kind_and_one_label_per_language(){
+    assert!(validate(&allergen(vec![label("fr", "Arachides"), label("en", "Peanuts")])).is_ok());
+    assert!(validate(&allergen(Vec::new())).is_err());
+    assert!(validate(&allergen(vec![label("fr", "Arachides"), label("fr", "Cacahuètes")])).is_err());
+    assert!(validate(&allergen(vec![label("fr", " ")])).is_err());
+
+    let mut unknown = allergen(vec![label("fr", "Arachides")]);
+    unknown.Kind = "religion".to_string();
+    assert!(validate(&unknown).is_err());
+    let mut diet = allergen(vec![label("fr", "Halal")]);
+    diet.Kind = "diet".to_string();
+    assert!(validate(&diet).is_ok());
+}
+
+#[cfg(test)]
+#[test]
+fn severities_are_known_or_empty(){
+    assert_eq!(validate_severity(None).unwrap(), None);
+    assert_eq!(validate_severity(Some(" ")).unwrap(), None);
+    assert_eq!(validate_severity(Some("severe")).unwrap().as_deref(), Some("severe"));
+    assert!(validate_severity(Some("deadly")).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn allergen_changes_keep_the_labels_on_one_line(){
+    let before = allergen(vec![label("fr", "Arachides")]);
+    let after = allergen(vec![label("fr", "Arachides"), label("en", "Peanuts")]);
+    assert_eq!(labels_text(&after), "fr:Arachides,en:Peanuts");
+
+    let changes = allergen_changes(Some(&before), &after);
+    let fields: Vec<_> = changes.iter().map(|change| change.Field.as_str()).collect();
+    assert_eq!(fields, ["Code", "Labels"]);
+    assert_eq!(allergen_changes(None, &after).len(), 4);
+    assert_eq!(AuditAction::LinkAllergen.to_string(), "allergy.link");
+}
+
+#[cfg(test)]
+async fn has_allergies(id: i32) -> bool {
+    Beneficiary::snapshot(get_conn().await.as_mut(), id).await.unwrap().unwrap().HasAllergies
+}
+
+#[cfg(test)]
+pub(crate) async fn has_allergies_follows_the_links(){
+    let beneficiary = fresh_beneficiary().await;
+    let admin = as_role("Admin").await;
+    let active = allergen::all(get_conn().await.as_mut()).await.unwrap()
+        .into_iter()
+        .find(|allergen| allergen.IsActive)
+        .expect("the catalog is seeded");
+    let link = TokenAllergenLink { Token: String::new(), BeneficiaryId: beneficiary.Id, AllergenId: active.Id, Severity: Some("severe".to_string()) };
+    assert!(!has_allergies(beneficiary.Id).await);
+
+    assert_eq!(allergen::link(get_conn().await, &link, &admin).await.unwrap(), (active.Code.clone(), true));
+    assert!(has_allergies(beneficiary.Id).await);
+    assert_eq!(allergen::link(get_conn().await, &link, &admin).await.unwrap(), (active.Code.clone(), false), "linked once");
+
+    let mut sent = Beneficiary::snapshot(get_conn().await.as_mut(), beneficiary.Id).await.unwrap().unwrap();
+    sent.HasAllergies = false;
+    Beneficiary::update_beneficiary(get_conn().await, admin.clone(), sent).await.unwrap();
+    assert!(has_allergies(beneficiary.Id).await, "the client does not set it");
+
+    assert_eq!(allergen::unlink(get_conn().await, &link, &admin).await.unwrap(), active.Code);
+    assert!(!has_allergies(beneficiary.Id).await);
+    assert_eq!(allergen::unlink(get_conn().await, &link, &admin).await.unwrap_err().0, StatusCode::NOT_FOUND);
+}
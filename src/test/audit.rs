@@ -0,0 +1,101 @@
+use axum::body::Body;
+use axum::extract::FromRequestParts;
+use axum::http::Request;
+use crate::schema::audit::{change, diff, parse_date, AuditAction};
+use crate::schema::beneficiary::Beneficiary;
+use crate::telemetry::{RequestId, REQUEST_ID_HEADER};
+
+#[cfg(test)]
+pub(crate) fn beneficiary() -> Beneficiary {
+    Beneficiary {
+        Id: 42,
+        FirstName: "Marie".to_string(),
+        LastName: "Tremblay".to_string(),
+        Email: "marie@example.com".to_string(),
+        Phone: "450-555-0100".to_string(),
+        Address: "12 rue Principale".to_string(),
+        PostalCode: "J3L 1A1".to_string(),
+        Kid: 2,
+        Adult: 1,
+        MonthlyAmount: 40.0,
+        WeeklyAmount: 10.0,
+        Category: 1,
+        MonthlyLimit: 4.0,
+        WeeklyLimit: 1.0,
+        Birth: Some("1985-04-12".to_string()),
+        LastPresence: "2024-02-01".to_string(),
+        Sexe: "F".to_string(),
+        Language: "French".to_string(),
+        Origin: "NorthAmerican".to_string(),
+        City: "Chambly".to_string(),
+        Study: "College".to_string(),
+        Income: "Income_15000_29999".to_string(),
+        FamilySituation: "SingleParent".to_string(),
+        IsActive: true,
+        IsSdf: false,
+        IsEmployed: true,
+        HasAllergies: true,
+        HasGeneralNote: true,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn personal_data_is_masked(){
+    let name = change("LastName", Some("Tremblay".to_string()), Some("Roy".to_string()));
+    assert_eq!(name.Before.as_deref(), Some("<redacted:8 chars>"));
+    assert_eq!(name.After.as_deref(), Some("<redacted:3 chars>"));
+
+    let password = change("Password", None, Some("hunter2".to_string()));
+    assert_eq!(password.After.as_deref(), Some("<redacted>"));
+
+    let limit = change("WeeklyLimit", Some("1".to_string()), None);
+    assert_eq!(limit.Before.as_deref(), Some("1"));
+    assert_eq!(limit.After, None);
+}
+
+#[cfg(test)]
+#[test]
+fn diff_lists_changed_fields_only(){
+    let before = beneficiary();
+    let mut after = beneficiary();
+    after.LastName = "Roy".to_string();
+    after.WeeklyLimit = 2.0;
+    after.Birth = None;
+
+    let mut changes = diff(&before, &after);
+    changes.sort_by(|a, b| a.Field.cmp(&b.Field));
+    let fields: Vec<_> = changes.iter().map(|change| change.Field.as_str()).collect();
+    assert_eq!(fields, ["Birth", "LastName", "WeeklyLimit"]);
+
+    assert_eq!(changes[0].Before.as_deref(), Some("<redacted:10 chars>"));
+    assert_eq!(changes[0].After, None);
+    assert_eq!(changes[2].Before.as_deref(), Some("1.0"));
+    assert_eq!(changes[2].After.as_deref(), Some("2.0"));
+    assert!(diff(&before, &beneficiary()).is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn actions_are_named_by_entity(){
+    assert_eq!(AuditAction::ReadBeneficiary.to_string(), "beneficiary.read");
+    assert_eq!(AuditAction::ListBeneficiaries.to_string(), "beneficiary.list");
+    assert_eq!(AuditAction::DeleteNote.to_string(), "note.delete");
+}
+
+#[cfg(test)]
+#[test]
+fn query_dates_are_validated(){
+    assert_eq!(parse_date(&None).unwrap(), None);
+    assert_eq!(parse_date(&Some(String::new())).unwrap(), None);
+    assert!(parse_date(&Some("2024-02-01".to_string())).unwrap().is_some());
+    assert!(parse_date(&Some("01/02/2024".to_string())).is_err());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn request_id_is_read_from_the_header(){
+    let (mut parts, _) = Request::get("/").header(REQUEST_ID_HEADER, "abc-123").body(Body::empty()).unwrap().into_parts();
+    let RequestId(id) = RequestId::from_request_parts(&mut parts, &()).await.unwrap();
+    assert_eq!(id, "abc-123");
+}
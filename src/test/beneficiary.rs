@@ -3,15 +3,17 @@ use sqlx::pool::PoolConnection;
 use crate::get_db_url;
 use crate::schema::beneficiary::{Beneficiary, BeneficiaryAction, BeneficiaryQueries};
 use crate::schema::user::{UserRole};
+use crate::schema::format::Format;
 
 #[cfg(test)]
 pub(crate) async fn make_user_role() -> UserRole{
-    let user: Option<UserRole> = sqlx::query_as("SELECT Username, Role FROM User")
+    let user: Option<UserRole> = sqlx::query_as("SELECT Id, Username, Role FROM User")
         .fetch_optional(get_conn().await.as_mut())
         .await
         .unwrap();
 
     user.unwrap_or_else(|| UserRole {
+        Id: 0,
         Username: "soap".to_string(),
         Role: "Dev".to_string(),
     })
@@ -33,7 +35,7 @@ pub(crate) async fn make_beneficiary() -> Beneficiary{
     match beneficiary {
         Some(beneficiary) => beneficiary,
         None => {
-            let _ = Beneficiary::create_beneficiary(get_conn().await, make_user_role().await).await;
+            let _ = Beneficiary::insert_beneficiary(get_conn().await, make_user_role().await).await;
             sqlx::query_as(&format!("{} ORDER BY Id DESC LIMIT 1",BeneficiaryQueries::SelectAdminDetails))
                 .fetch_one(get_conn().await.as_mut())
                 .await
@@ -42,11 +44,22 @@ pub(crate) async fn make_beneficiary() -> Beneficiary{
     }
 }
 
+/// A beneficiary of its own, so tests do not see each other's rows.
+#[cfg(test)]
+pub(crate) async fn fresh_beneficiary() -> Beneficiary{
+    Beneficiary::insert_beneficiary(get_conn().await, as_role("Admin").await).await.unwrap()
+}
+
+#[cfg(test)]
+pub(crate) async fn as_role(role: &str) -> UserRole{
+    UserRole { Role: role.to_string(), ..make_user_role().await }
+}
+
 #[cfg(test)]
 pub(crate) async fn create_beneficiary(){
     let user = make_user_role().await;
     let conn= get_conn().await;
-    let res = Beneficiary::create_beneficiary(conn, user).await;
+    let res = Beneficiary::insert_beneficiary(conn, user).await;
     assert!(res.is_ok());
 }
 
@@ -65,13 +78,13 @@ pub(crate) async fn select_beneficiary(){
     let user = make_user_role().await;
     let beneficiary = make_beneficiary().await;
     let conn = get_conn().await;
-    let res = Beneficiary::get_beneficiary(conn, user, beneficiary.Id).await;
+    let res = Beneficiary::get_beneficiary(conn, user, beneficiary.Id, Format::Bincode).await;
     assert!(res.is_ok());
 }
 #[cfg(test)]
 pub(crate) async fn select_beneficiaries(){
     let user = make_user_role().await;
     let conn = get_conn().await;
-    let res = Beneficiary::get_beneficiaries(conn, user).await;
+    let res = Beneficiary::get_beneficiaries(conn, user, Format::Bincode).await;
     assert!(res.is_ok());
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+use crate::bitmap::glyph;
+use crate::config::OrganizationConfig;
+use crate::schema::card::{self, display, generate, normalize, Card, CardFormat, ALPHABET, LENGTH};
+use crate::schema::receipt::Holder;
+
+#[cfg(test)]
+fn card() -> (Card, Holder, OrganizationConfig) {
+    (
+        Card { Code: "7KQ2-M9XD-4HPT-R8WB".to_string(), BeneficiaryId: 42, IssuedAt: "2024-01-15 10:32:07".to_string() },
+        Holder { Id: 42, Name: "Marie-Hélène Dupont".to_string(), Category: None },
+        OrganizationConfig { name: "Épicerie solidaire".to_string(), address: String::new() },
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn codes_are_random_and_read_back_as_printed(){
+    let code = generate();
+    assert_eq!(code.len(), LENGTH);
+    assert!(code.bytes().all(|symbol| ALPHABET.contains(&symbol)));
+    assert_ne!(code, generate());
+
+    let printed = display(&code);
+    assert_eq!(printed.len(), LENGTH + 3);
+    assert_eq!(normalize(&printed), code);
+    assert_eq!(normalize(&printed.to_lowercase().replace('-', " ")), code);
+    for symbol in ALPHABET.iter().chain(b"-") {
+        assert_ne!(glyph(*symbol as char), glyph('?'), "{}", *symbol as char);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn cards_print_as_png_and_pdf(){
+    let (card, holder, organization) = card();
+    assert_eq!("png".parse::<CardFormat>(), Ok(CardFormat::Png));
+    assert!("svg".parse::<CardFormat>().is_err());
+
+    let png = card::png(&card, &holder, &organization).unwrap();
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    assert_eq!(&png[16..24], [0, 0, 3, 88, 0, 0, 2, 28], "856 by 540 pixels");
+    assert_eq!(png, card::png(&card, &holder, &organization).unwrap());
+
+    let pdf = card::pdf(&card, &holder, &organization).unwrap();
+    let text = String::from_utf8_lossy(&pdf);
+    assert!(text.contains("/MediaBox [0 0 242.65 153.07]"));
+    assert!(text.contains("(7KQ2-M9XD-4HPT-R8WB) Tj"));
+    assert!(text.contains("(Marie-H\\351l\\350ne Dupont) Tj"));
+    assert!(text.matches(" re f").count() > 100, "the QR code is drawn");
+}
@@ -1,7 +1,11 @@
 use sqlx::MySql;
 use sqlx::pool::PoolConnection;
 use crate::get_db_url;
-use crate::schema::category::Categories;
+use axum::http::StatusCode;
+use crate::schema::beneficiary::Beneficiary;
+use crate::schema::category::{delete, validate_fee, Categories, CategoryFee};
+use crate::schema::format::Format;
+use crate::test::beneficiary::{as_role, fresh_beneficiary};
 
 #[cfg(test)]
 async fn make_category()-> Categories {
@@ -46,7 +50,7 @@ pub(crate) async fn create_category(){
         WeeklyFee: 50.0,
         UsedBy: 0,
     };
-    let res = category.create_category(conn).await;
+    let res = category.create_category(conn, Format::Bincode).await;
     assert!(res.is_ok());
 }
 #[cfg(test)]
@@ -54,19 +58,67 @@ pub(crate) async fn update_category(){
     let mut category = make_category().await;
     category.Category = "B".to_string();
     let conn = get_conn().await;
-    let res = category.update_category(conn).await;
+    let res = category.update_category(conn, Format::Bincode).await;
     assert!(res.is_ok());
 }
 #[cfg(test)]
 pub(crate) async fn select_category(){
     let conn = get_conn().await;
-    let res = Categories::select_categories(conn).await;
+    let res = Categories::select_categories(conn, Format::Bincode).await;
     assert!(res.is_ok());
 }
 #[cfg(test)]
 pub(crate) async fn delete_category(){
     let category = make_category().await;
     let conn = get_conn().await;
-    let res = category.delete_category(conn).await;
+    let res = category.delete_category(conn, Format::Bincode).await;
     assert!(res.is_ok());
-}
\ No newline at end of file
+}
+
+/// Creates a category and returns its id, the name telling it from earlier runs.
+#[cfg(test)]
+async fn named_category(name: String) -> i32 {
+    let category = Categories { Id: 0, Category: name.clone(), MonthlyFee: 10.0, WeeklyFee: 2.5, UsedBy: 0 };
+    category.create_category(get_conn().await, Format::Json).await.unwrap();
+    sqlx::query_scalar("SELECT Id FROM Categories WHERE Category = ? ORDER BY Id DESC LIMIT 1")
+        .bind(name)
+        .fetch_one(get_conn().await.as_mut())
+        .await
+        .unwrap()
+}
+
+#[cfg(test)]
+pub(crate) async fn a_category_in_use_is_deleted_once_reassigned(){
+    let beneficiary = fresh_beneficiary().await;
+    let admin = as_role("Admin").await;
+    let from = named_category(format!("used-{}", beneficiary.Id)).await;
+    let to = named_category(format!("target-{}", beneficiary.Id)).await;
+    sqlx::query("UPDATE Beneficiary SET Category = ? WHERE Id = ?")
+        .bind(from)
+        .bind(beneficiary.Id)
+        .execute(get_conn().await.as_mut())
+        .await
+        .unwrap();
+    let used_by = Categories::find(get_conn().await.as_mut(), from).await.unwrap().UsedBy;
+    assert_eq!(used_by, 1);
+
+    let refused = delete(get_conn().await, from, None, &admin).await.unwrap_err();
+    assert_eq!(refused, (StatusCode::CONFLICT, "1 beneficiaries are in this category, reassign them first".to_string()));
+    assert!(Categories::find(get_conn().await.as_mut(), from).await.is_some());
+
+    let (deleted, moved) = delete(get_conn().await, from, Some(to), &admin).await.unwrap();
+    assert_eq!((deleted.Id, moved), (from, 1));
+    assert!(Categories::find(get_conn().await.as_mut(), from).await.is_none());
+    let moved_to = Beneficiary::snapshot(get_conn().await.as_mut(), beneficiary.Id).await.unwrap().unwrap().Category;
+    assert_eq!(moved_to, to);
+}
+
+#[cfg(test)]
+#[test]
+fn fees_need_a_day_and_no_negative_amount(){
+    let fee = |from: &str, monthly: f32| CategoryFee { CategoryId: 1, EffectiveFrom: from.to_string(), MonthlyFee: monthly, WeeklyFee: 0.0 };
+    assert_eq!(validate_fee(&fee("2026-03-01", 12.5)).unwrap().to_string(), "2026-03-01");
+    assert_eq!(validate_fee(&fee("01/03/2026", 12.5)).unwrap_err().0, StatusCode::BAD_REQUEST);
+    assert_eq!(validate_fee(&fee("2026-03-01", -1.0)).unwrap_err().0, StatusCode::BAD_REQUEST);
+    assert_eq!(validate_fee(&fee("2026-03-01", f32::NAN)).unwrap_err().0, StatusCode::BAD_REQUEST);
+}
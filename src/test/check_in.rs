@@ -0,0 +1,135 @@
+use axum::http::StatusCode;
+use chrono::{Datelike, Days, Local, NaiveDate, Weekday};
+use crate::config::{EligibilityBasis, EligibilityConfig, EligibilityMode};
+use crate::schema::card::normalize;
+use crate::schema::check_in::{check_in, validate_reason, TokenCheckIn};
+use crate::schema::details::DetailsQueries;
+use crate::schema::eligibility::{allowance, assess, periods, Limits, Usage};
+use crate::test::beneficiary::{as_role, fresh_beneficiary, get_conn};
+
+#[cfg(test)]
+fn day(date: &str) -> NaiveDate {
+    date.parse().unwrap()
+}
+
+#[cfg(test)]
+fn config(week_start: Weekday, month_start: u32, mode: EligibilityMode) -> EligibilityConfig {
+    EligibilityConfig { week_start, month_start, basis: EligibilityBasis::Visits, mode }
+}
+
+#[cfg(test)]
+#[test]
+fn periods_start_where_configured(){
+    let default = config(Weekday::Mon, 1, EligibilityMode::Warn);
+    assert_eq!(periods(day("2024-01-17"), &default), (day("2024-01-15"), day("2024-01-01")));
+    assert_eq!(periods(day("2024-01-15"), &default), (day("2024-01-15"), day("2024-01-01")));
+    assert_eq!(periods(day("2024-03-03"), &default), (day("2024-02-26"), day("2024-03-01")));
+
+    let shifted = config(Weekday::Sun, 15, EligibilityMode::Warn);
+    assert_eq!(periods(day("2024-01-17"), &shifted), (day("2024-01-14"), day("2024-01-15")));
+    assert_eq!(periods(day("2024-01-14"), &shifted), (day("2024-01-14"), day("2023-12-15")));
+    assert_eq!(periods(day("2024-03-10"), &shifted), (day("2024-03-10"), day("2024-02-15")));
+}
+
+#[cfg(test)]
+#[test]
+fn allowance_is_unlimited_at_zero_and_never_negative(){
+    assert_eq!(allowance(0.0, 3.0).Remaining, None);
+    assert_eq!(allowance(4.0, 1.0).Remaining, Some(3.0));
+    assert_eq!(allowance(1.0, 2.0).Remaining, Some(0.0));
+}
+
+#[cfg(test)]
+#[test]
+fn a_reached_limit_warns_or_blocks(){
+    let limits = Limits { WeeklyLimit: 1.0, MonthlyLimit: 4.0 };
+    let warn = config(Weekday::Mon, 1, EligibilityMode::Warn);
+    let block = config(Weekday::Mon, 1, EligibilityMode::Block);
+
+    let fresh = assess(42, day("2024-01-17"), limits, Usage { Weekly: 0.0, Monthly: 3.0 }, &block);
+    assert!(fresh.Eligible && !fresh.Blocking);
+    assert_eq!(fresh.Monthly.Remaining, Some(1.0));
+
+    let reached = assess(42, day("2024-01-17"), limits, Usage { Weekly: 1.0, Monthly: 4.0 }, &warn);
+    assert!(!reached.Eligible && !reached.Blocking);
+    assert_eq!(reached.Reasons, ["Weekly limit of 1 reached", "Monthly limit of 4 reached"]);
+    assert_eq!((reached.WeekStart.as_str(), reached.MonthStart.as_str()), ("2024-01-15", "2024-01-01"));
+    assert!(assess(42, day("2024-01-17"), limits, Usage { Weekly: 1.0, Monthly: 1.0 }, &block).Blocking);
+
+    let mut amounts = config(Weekday::Mon, 1, EligibilityMode::Block);
+    amounts.basis = EligibilityBasis::Amount;
+    let spent = Limits { WeeklyLimit: 50.0, MonthlyLimit: 0.0 };
+    assert!(assess(42, day("2024-01-17"), spent, Usage { Weekly: 49.5, Monthly: 49.5 }, &amounts).Eligible);
+    assert!(assess(42, day("2024-01-17"), spent, Usage { Weekly: 50.0, Monthly: 50.0 }, &amounts).Blocking);
+
+    let unlimited = Limits { WeeklyLimit: 0.0, MonthlyLimit: 0.0 };
+    assert!(assess(42, day("2024-01-17"), unlimited, Usage { Weekly: 9.0, Monthly: 30.0 }, &block).Eligible);
+}
+
+#[cfg(test)]
+#[test]
+fn overrides_need_a_reason(){
+    assert_eq!(validate_reason("  Urgence  ").unwrap(), "Urgence");
+    assert!(validate_reason(" ").is_err());
+    assert!(validate_reason(&"x".repeat(513)).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn card_codes_ignore_case_spaces_and_dashes(){
+    assert_eq!(normalize(" k7qm-2xwd 9f4t "), "K7QM2XWD9F4T");
+}
+
+#[cfg(test)]
+fn by_id(id: i32) -> TokenCheckIn {
+    TokenCheckIn { Token: String::new(), BeneficiaryId: Some(id), CardCode: None }
+}
+
+#[cfg(test)]
+pub(crate) async fn a_second_check_in_the_same_day_is_refused(){
+    let beneficiary = fresh_beneficiary().await;
+    let user = as_role("User").await;
+    let warn = config(Weekday::Mon, 1, EligibilityMode::Warn);
+    assert!(check_in(get_conn().await, &by_id(beneficiary.Id), None, &user, &warn).await.is_ok());
+    let again = check_in(get_conn().await, &by_id(beneficiary.Id), None, &user, &warn).await.unwrap_err();
+    assert_eq!(again, (StatusCode::CONFLICT, "Already checked in today".to_string()));
+
+    let presences: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM BeneficiaryPresences WHERE BeneficiaryId = ?")
+        .bind(beneficiary.Id)
+        .fetch_one(get_conn().await.as_mut())
+        .await
+        .unwrap();
+    assert_eq!(presences, 1);
+}
+
+#[cfg(test)]
+pub(crate) async fn a_reached_limit_blocks_until_an_admin_overrides(){
+    let beneficiary = fresh_beneficiary().await;
+    let admin = as_role("Admin").await;
+    let yesterday = Local::now().naive_local() - Days::new(1);
+    sqlx::query("UPDATE Beneficiary SET WeeklyLimit = 1, MonthlyLimit = 0 WHERE Id = ?")
+        .bind(beneficiary.Id)
+        .execute(get_conn().await.as_mut())
+        .await
+        .unwrap();
+    sqlx::query(&DetailsQueries::InsertPresence.to_string())
+        .bind(beneficiary.Id)
+        .bind(yesterday.format("%Y-%m-%d %H:%M:%S").to_string())
+        .execute(get_conn().await.as_mut())
+        .await
+        .unwrap();
+
+    // the week starts yesterday, so yesterday's visit used it up
+    let block = config(yesterday.weekday(), 1, EligibilityMode::Block);
+    let blocked = check_in(get_conn().await, &by_id(beneficiary.Id), None, &admin, &block).await.unwrap_err();
+    assert_eq!(blocked, (StatusCode::CONFLICT, "Weekly limit of 1 reached".to_string()));
+
+    let checked_in = check_in(get_conn().await, &by_id(beneficiary.Id), Some("Urgence"), &admin, &block).await.unwrap();
+    assert_eq!(checked_in.Weekly.Remaining, Some(0.0));
+    let reasons: Vec<String> = sqlx::query_scalar("SELECT Reason FROM EligibilityOverride WHERE BeneficiaryId = ?")
+        .bind(beneficiary.Id)
+        .fetch_all(get_conn().await.as_mut())
+        .await
+        .unwrap();
+    assert_eq!(reasons, ["Urgence"]);
+}
@@ -8,12 +8,13 @@ use crate::schema::user::UserRole;
 
 #[cfg(test)]
 pub(crate) async fn make_user_role() -> UserRole{
-    let user: Option<UserRole> = sqlx::query_as("SELECT Username, Role FROM User")
+    let user: Option<UserRole> = sqlx::query_as("SELECT Id, Username, Role FROM User")
         .fetch_optional(crate::test::beneficiary::get_conn().await.as_mut())
         .await
         .unwrap();
 
     user.unwrap_or_else(|| UserRole {
+        Id: 0,
         Username: "soap".to_string(),
         Role: "Dev".to_string(),
     })
@@ -28,7 +29,7 @@ pub(crate) async fn make_beneficiary() -> Beneficiary{
     match beneficiary {
         Some(beneficiary) => beneficiary,
         None => {
-            let _ = Beneficiary::create_beneficiary(crate::test::beneficiary::get_conn().await, crate::test::beneficiary::make_user_role().await).await;
+            let _ = Beneficiary::insert_beneficiary(crate::test::beneficiary::get_conn().await, crate::test::beneficiary::make_user_role().await).await;
             sqlx::query_as(&format!("{} ORDER BY Id DESC LIMIT 1",BeneficiaryQueries::SelectAdminDetails))
                 .fetch_one(crate::test::beneficiary::get_conn().await.as_mut())
                 .await
@@ -60,8 +61,9 @@ pub(crate) async fn insert_presence(){
         Token: "test".to_string(),
         Presence: BeneficiaryPresence { BeneficiaryId: beneficiary.Id, Date: "2023-02-10".to_string() },
     };
+    let user = make_user_role().await;
     let conn = get_conn().await;
-    let res = token_presence.insert_presence(conn).await;
+    let res = token_presence.insert_presence(conn, &user).await;
 
     assert!(res.is_ok());
 }
@@ -80,26 +82,32 @@ pub(crate) async fn insert_note(){
         },
     };
     let conn = get_conn().await;
-    let res = beneficiary_note.create_note(conn).await;
+    let user = make_user_role().await;
+    let res = beneficiary_note.create_note(conn, &user).await;
 
     assert!(res.is_ok());
 }
 #[cfg(test)]
 pub(crate) async fn update_note(){
     let beneficiary = make_beneficiary().await;
-    let beneficiary_note = TokenNote {
+    // A second of its own, so reruns on the same database find one note at this date.
+    let date = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut beneficiary_note = TokenNote {
         Token : "test".to_string(),
         Content: BeneficiaryNotes {
             BeneficiaryId: beneficiary.Id,
-            Date: "2023-08-02 00:00:00".to_string(),
-            Type: 1,
-            Note: "Not for you".to_string(),
+            Date: date,
+            Type: 0,
+            Note: "This is for youuuuuuuuuu".to_string(),
         },
     };
-    let conn = get_conn().await;
-    let res = beneficiary_note.update_note(conn).await;
+    let user = make_user_role().await;
+    assert!(beneficiary_note.create_note(get_conn().await, &user).await.unwrap());
 
-    assert!(res.is_ok());
+    beneficiary_note.Content.Note = "Not for you".to_string();
+    let res = beneficiary_note.update_note(get_conn().await, &user).await;
+
+    assert_eq!(res.unwrap().Note, "This is for youuuuuuuuuu");
 }
 
 
@@ -117,7 +125,7 @@ pub(crate) async fn select_details(){
             assert!(!val.Allergies.is_empty());
             assert!(!val.Presences.is_empty());
         }
-        Err(_) => assert!(false),
+        Err(_) => panic!(),
     }
 }
 
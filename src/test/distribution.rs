@@ -0,0 +1,74 @@
+use crate::schema::distribution::{distribution_changes, fee, parse_range, validate, Distribution, DistributionItem, DistributionQueries, Recorded, TotalsPeriod};
+
+#[cfg(test)]
+fn distribution() -> Distribution {
+    Distribution {
+        Id: 12,
+        BeneficiaryId: 42,
+        PresenceDate: "2024-01-15 10:32:07".to_string(),
+        Amount: 35.5,
+        Items: vec![DistributionItem { Item: "conserves".to_string(), Quantity: 2.0 }],
+        CategoryId: Some(1),
+        Fee: 2.5,
+        VolunteerId: Some(7),
+        Note: String::new(),
+        CreatedAt: "2024-01-15 10:40:00".to_string(),
+        CreatedBy: Some(7),
+        Supersedes: None,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn distributions_name_their_presence_and_list_items_once(){
+    assert!(validate(&distribution()).is_ok());
+
+    let mut undated = distribution();
+    undated.PresenceDate = "2024-01-15".to_string();
+    assert!(validate(&undated).is_err());
+    let mut negative = distribution();
+    negative.Amount = -1.0;
+    assert!(validate(&negative).is_err());
+    let mut empty = distribution();
+    empty.Items[0].Quantity = 0.0;
+    assert!(validate(&empty).is_err());
+    let mut twice = distribution();
+    twice.Items.push(DistributionItem { Item: " conserves ".to_string(), Quantity: 1.0 });
+    assert!(validate(&twice).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn fees_are_charged_once_per_period(){
+    assert_eq!(fee(2.5, 10.0, Recorded { Weekly: 0, Monthly: 0 }), 12.5);
+    assert_eq!(fee(2.5, 10.0, Recorded { Weekly: 0, Monthly: 2 }), 2.5);
+    assert_eq!(fee(2.5, 10.0, Recorded { Weekly: 1, Monthly: 1 }), 0.0);
+}
+
+#[cfg(test)]
+#[test]
+fn totals_group_by_the_start_of_each_period(){
+    assert_eq!("month".parse::<TotalsPeriod>(), Ok(TotalsPeriod::Month));
+    assert!("quarter".parse::<TotalsPeriod>().is_err());
+    assert!(parse_range("2024-02-01", "2024-01-01").is_err());
+    assert!(parse_range("2024-01-01", "2024-01-01").is_ok());
+
+    for (period, binds) in [(TotalsPeriod::Day, 4), (TotalsPeriod::Week, 5), (TotalsPeriod::Month, 6), (TotalsPeriod::Year, 4)] {
+        let query = DistributionQueries::SelectTotals(period).to_string();
+        assert_eq!(query.matches('?').count(), binds, "{query}");
+        assert!(query.contains("SupersededBy IS NULL"));
+        assert!(query.contains("GROUP BY BeneficiaryId, PeriodStart"));
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn corrections_show_what_changed(){
+    let mut after = distribution();
+    after.Amount = 40.0;
+    after.Items.push(DistributionItem { Item: "pâtes".to_string(), Quantity: 1.0 });
+    let changes = distribution_changes(Some(&distribution()), &after);
+    let fields: Vec<_> = changes.iter().map(|change| change.Field.as_str()).collect();
+    assert_eq!(fields, ["PresenceDate", "Amount", "Items"]);
+    assert_eq!(changes[2].After.as_deref(), Some("conserves:2,pâtes:1"));
+}
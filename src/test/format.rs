@@ -0,0 +1,86 @@
+use axum::body::Body;
+use axum::extract::{FromRequest, Request};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use crate::schema::category::Categories;
+use crate::schema::encode;
+use crate::schema::format::{Format, Payload, BINCODE, JSON, MSGPACK};
+use crate::schema::user::Token;
+
+#[cfg(test)]
+fn make_categories() -> Vec<Categories> {
+    vec![
+        Categories { Id: 1, Category: "A".to_string(), MonthlyFee: 10.0, WeeklyFee: 2.5, UsedBy: 3 },
+        Categories { Id: 2, Category: "B".to_string(), MonthlyFee: 20.0, WeeklyFee: 5.0, UsedBy: 0 },
+    ]
+}
+
+#[cfg(test)]
+#[test]
+fn accept_defaults_to_bincode(){
+    assert_eq!(Format::from_accept(""), Some(Format::Bincode));
+    assert_eq!(Format::from_accept("*/*"), Some(Format::Bincode));
+    assert_eq!(Format::from_accept("application/*"), Some(Format::Bincode));
+}
+
+#[cfg(test)]
+#[test]
+fn accept_honors_media_types_and_weights(){
+    assert_eq!(Format::from_accept(JSON), Some(Format::Json));
+    assert_eq!(Format::from_accept("application/json; charset=utf-8"), Some(Format::Json));
+    assert_eq!(Format::from_accept(BINCODE), Some(Format::Bincode));
+    assert_eq!(Format::from_accept("application/x-msgpack"), Some(Format::MessagePack));
+    assert_eq!(Format::from_accept("application/msgpack;q=0.5, application/json;q=0.9"), Some(Format::Json));
+    assert_eq!(Format::from_accept("text/html, application/msgpack;q=0.1"), Some(Format::MessagePack));
+    assert_eq!(Format::from_accept("application/json;q=0, */*"), Some(Format::Bincode));
+    assert_eq!(Format::from_accept("text/html"), None);
+}
+
+#[cfg(test)]
+#[test]
+fn formats_round_trip(){
+    for format in [Format::Bincode, Format::Json, Format::MessagePack] {
+        let bytes = format.encode(make_categories()).unwrap();
+        let decoded: Vec<Categories> = format.decode(&bytes).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].Category, "A");
+        assert_eq!(decoded[1].MonthlyFee, 20.0);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn encoded_sets_content_type(){
+    for (format, media_type) in [(Format::Bincode, BINCODE), (Format::Json, JSON), (Format::MessagePack, MSGPACK)] {
+        let response = encode(make_categories(), format).unwrap().into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], media_type);
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn payload_follows_content_type(){
+    let token = Token { Token: "test".to_string() };
+    for format in [Format::Bincode, Format::Json, Format::MessagePack] {
+        let request = Request::builder()
+            .header(header::CONTENT_TYPE, format.media_type())
+            .body(Body::from(format.encode(&token).unwrap()))
+            .unwrap();
+        let payload = Payload::<Token>::from_request(request, &()).await.unwrap();
+        assert_eq!(payload.Token, "test");
+    }
+
+    let request = Request::builder()
+        .body(Body::from(r#"{"Token":"legacy"}"#))
+        .unwrap();
+    let payload = Payload::<Token>::from_request(request, &()).await.unwrap();
+    assert_eq!(payload.Token, "legacy");
+
+    let request = Request::builder()
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Body::from("test"))
+        .unwrap();
+    let rejection = Payload::<Token>::from_request(request, &()).await.err().unwrap();
+    assert_eq!(rejection.0, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
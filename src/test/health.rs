@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use serde_json::Value;
+use tower::ServiceExt;
+use crate::config::Config;
+use crate::schema::{decrypt, encrypt};
+use crate::test::openapi::{make_offline_router, make_offline_router_with};
+
+/// Key shared by every test touching the cipher, setting it twice is harmless.
+#[cfg(test)]
+pub(crate) fn set_test_key() {
+    std::env::set_var("ENCRYPTION_KEY", "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=");
+}
+
+#[cfg(test)]
+async fn get_json(router: axum::Router, path: &str) -> (StatusCode, Value) {
+    let response = router.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[cfg(test)]
+#[test]
+fn encryption_round_trips(){
+    set_test_key();
+    let sealed = encrypt(b"Dupont");
+    assert_eq!(decrypt(&sealed).unwrap(), b"Dupont");
+
+    let (nonce, ciphertext) = sealed.split_once(':').unwrap();
+    let tampered = format!("{nonce}:{}", ciphertext.replacen(|c: char| c.is_ascii_alphabetic(), "0", 1));
+    assert!(decrypt(&tampered).is_err());
+    assert!(decrypt("not sealed").is_err());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn live_only_needs_the_process(){
+    let (status, body) = get_json(make_offline_router(), "/health/live").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["Status"], "ok");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn ready_reports_each_check(){
+    set_test_key();
+    let mut config = Config::default();
+    config.health.timeout = Duration::from_millis(300);
+
+    let start = Instant::now();
+    let (status, body) = get_json(make_offline_router_with(config), "/health/ready").await;
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["Status"], "fail");
+
+    let check = |name: &str| body["Checks"].as_array().unwrap().iter().find(|check| check["Name"] == name).unwrap().clone();
+    assert_eq!(check("database")["Status"], "fail");
+    assert!(check("database")["Detail"].is_string());
+    assert_eq!(check("migrations")["Status"], "fail");
+    assert_eq!(check("encryption_key")["Status"], "ok");
+    assert!(check("encryption_key").get("Detail").is_none());
+    assert!(check("database")["LatencyMs"].is_u64());
+}
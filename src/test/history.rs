@@ -0,0 +1,62 @@
+use crate::schema::beneficiary::Beneficiary;
+use crate::schema::history::{parse_as_of, with_changes, Version};
+use crate::schema::user::UserRole;
+use crate::test::audit::beneficiary;
+
+#[cfg(test)]
+fn role(role: &str) -> UserRole {
+    UserRole { Id: 7, Username: "benevole".to_string(), Role: role.to_string() }
+}
+
+#[cfg(test)]
+fn version(number: i32, valid_from: &str, beneficiary: Beneficiary) -> Version {
+    Version { Version: number, ValidFrom: valid_from.to_string(), UserId: Some(7), Beneficiary: beneficiary }
+}
+
+#[cfg(test)]
+#[test]
+fn projection_follows_the_details_queries(){
+    let ts = beneficiary().project(&role("TS")).unwrap();
+    assert_eq!(ts.Income, "Income_15000_29999");
+
+    let admin = beneficiary().project(&role("Admin")).unwrap();
+    assert_eq!(admin.Email, "marie@example.com");
+    assert!(admin.Income.is_empty() && !admin.IsEmployed);
+
+    let user = beneficiary().project(&role("User")).unwrap();
+    assert!(user.Email.is_empty() && user.City.is_empty() && user.Study.is_empty());
+    assert_eq!(user.WeeklyLimit, 1.0);
+
+    assert!(beneficiary().project(&role("Guest")).is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn versions_list_changes_since_the_previous_one(){
+    let mut second = beneficiary();
+    second.Category = 2;
+    second.MonthlyLimit = 2.0;
+    let mut third = second.clone();
+    third.Income = "NoIncome".to_string();
+
+    let versions = with_changes(vec![
+        version(1, "2024-01-01 09:00:00", beneficiary()),
+        version(2, "2024-01-10 09:00:00", second),
+        version(3, "2024-01-20 09:00:00", third),
+    ], &role("Admin"));
+
+    assert!(versions[0].Changes.is_empty());
+    let mut fields: Vec<_> = versions[1].Changes.iter().map(|change| change.Field.as_str()).collect();
+    fields.sort();
+    assert_eq!(fields, ["Category", "MonthlyLimit"]);
+    assert_eq!(versions[1].Changes.iter().find(|change| change.Field == "Category").unwrap().Before.as_deref(), Some("1"));
+    assert!(versions[2].Changes.is_empty(), "Admins do not see Income, so nothing changed for them");
+}
+
+#[cfg(test)]
+#[test]
+fn as_of_accepts_a_day_or_an_instant(){
+    assert_eq!(parse_as_of("2024-01-15").unwrap().to_string(), "2024-01-15 23:59:59");
+    assert_eq!(parse_as_of("2024-01-15 08:30:00").unwrap().to_string(), "2024-01-15 08:30:00");
+    assert!(parse_as_of("15/01/2024").is_err());
+}
@@ -0,0 +1,93 @@
+use harmony_protocol::v1::Allergy;
+use crate::schema::beneficiary::{Beneficiary, BeneficiaryAction};
+use crate::schema::household::{self, member_changes, project, validate, HouseholdMember};
+use crate::schema::user::UserRole;
+use crate::test::beneficiary::{as_role, fresh_beneficiary, get_conn};
+
+#[cfg(test)]
+fn member() -> HouseholdMember {
+    HouseholdMember {
+        Id: 3,
+        BeneficiaryId: 42,
+        FirstName: "Léa".to_string(),
+        LastName: "Tremblay".to_string(),
+        Birth: Some("2016-05-04".to_string()),
+        Relationship: "child".to_string(),
+        Sexe: "F".to_string(),
+        Allergies: vec![Allergy { BeneficiaryId: 42, AllergenId: Some(5), Kind: None, Allergy: "peanuts".to_string(), Severity: Some("severe".to_string()) }],
+    }
+}
+
+#[cfg(test)]
+fn role(role: &str) -> UserRole {
+    UserRole { Id: 7, Username: "tester".to_string(), Role: role.to_string() }
+}
+
+#[cfg(test)]
+#[test]
+fn members_need_a_name_a_relationship_and_a_past_birth(){
+    assert_eq!(validate(&member()).unwrap().map(|birth| birth.to_string()).as_deref(), Some("2016-05-04"));
+
+    let mut unnamed = member();
+    unnamed.FirstName = " ".to_string();
+    assert!(validate(&unnamed).is_err());
+    let mut cousin = member();
+    cousin.Relationship = "cousin".to_string();
+    assert!(validate(&cousin).is_err());
+    let mut unborn = member();
+    unborn.Birth = Some("2999-01-01".to_string());
+    assert!(validate(&unborn).is_err());
+    let mut twice = member();
+    twice.Allergies.push(twice.Allergies[0].clone());
+    assert!(validate(&twice).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn members_are_projected_like_beneficiaries(){
+    assert_eq!(project(member(), &role("TS")), Some(member()));
+    let seen = project(member(), &role("User")).unwrap();
+    assert!(seen.Sexe.is_empty());
+    assert_eq!(seen.Allergies, member().Allergies, "every role sees allergies");
+    assert_eq!(project(member(), &role("Guest")), None);
+}
+
+#[cfg(test)]
+#[test]
+fn member_changes_mask_names(){
+    let mut after = member();
+    after.Relationship = "other".to_string();
+    let changes = member_changes(Some(&member()), Some(&after));
+    let fields: Vec<_> = changes.iter().map(|change| change.Field.as_str()).collect();
+    assert_eq!(fields, ["MemberId", "Relationship"]);
+
+    let created = member_changes(None, Some(&member()));
+    let first_name = created.iter().find(|change| change.Field == "FirstName").unwrap();
+    assert_ne!(first_name.After.as_deref(), Some("Léa"));
+    assert_eq!(created.iter().find(|change| change.Field == "Allergies").unwrap().After.as_deref(), Some("peanuts:severe"));
+}
+
+#[cfg(test)]
+async fn counts(id: i32) -> (u8, u8) {
+    let beneficiary = Beneficiary::snapshot(get_conn().await.as_mut(), id).await.unwrap().unwrap();
+    (beneficiary.Kid, beneficiary.Adult)
+}
+
+#[cfg(test)]
+pub(crate) async fn counts_follow_the_members(){
+    let beneficiary = fresh_beneficiary().await;
+    let admin = as_role("Admin").await;
+    let kid = HouseholdMember { Id: 0, BeneficiaryId: beneficiary.Id, Allergies: Vec::new(), ..member() };
+    let spouse = HouseholdMember { Birth: Some("1980-11-02".to_string()), Relationship: "spouse".to_string(), ..kid.clone() };
+    let kid = household::save(get_conn().await, &kid, true, &admin).await.unwrap();
+    household::save(get_conn().await, &spouse, true, &admin).await.unwrap();
+    assert_eq!(counts(beneficiary.Id).await, (1, 2), "the beneficiary counts as an adult");
+
+    let mut sent = Beneficiary::snapshot(get_conn().await.as_mut(), beneficiary.Id).await.unwrap().unwrap();
+    (sent.Kid, sent.Adult) = (5, 5);
+    Beneficiary::update_beneficiary(get_conn().await, admin.clone(), sent).await.unwrap();
+    assert_eq!(counts(beneficiary.Id).await, (1, 2), "counts sent by the client are ignored");
+
+    household::delete(get_conn().await, kid.Id, &admin).await.unwrap();
+    assert_eq!(counts(beneficiary.Id).await, (0, 2));
+}
@@ -0,0 +1,56 @@
+use std::net::SocketAddr;
+use axum::body::{to_bytes, Body};
+use axum::extract::ConnectInfo;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+use crate::config::Config;
+use crate::test::openapi::{make_offline_router, make_offline_router_with};
+
+#[cfg(test)]
+async fn scrape(router: &Router, from: &str, token: Option<&str>) -> (StatusCode, String) {
+    let mut request = Request::get("/metrics");
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let mut request = request.body(Body::empty()).unwrap();
+    request.extensions_mut().insert(ConnectInfo(from.parse::<SocketAddr>().unwrap()));
+
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn metrics_are_guarded(){
+    let router = make_offline_router();
+    assert_eq!(scrape(&router, "127.0.0.1:50000", None).await.0, StatusCode::OK);
+    assert_eq!(scrape(&router, "192.168.2.40:50000", None).await.0, StatusCode::FORBIDDEN);
+
+    let mut config = Config::default();
+    config.metrics.allowlist.clear();
+    let router = make_offline_router_with(config);
+    assert_eq!(scrape(&router, "127.0.0.1:50000", None).await.0, StatusCode::FORBIDDEN);
+    // A bearer is a session token, checked against the database the offline router lacks.
+    assert_eq!(scrape(&router, "192.168.2.40:50000", Some("guess")).await.0, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn requests_are_counted_per_route_template(){
+    let router = make_offline_router();
+    let request = Request::post("/beneficiary/select/42")
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body(Body::empty())
+        .unwrap();
+    router.clone().oneshot(request).await.unwrap();
+
+    let (status, body) = scrape(&router, "127.0.0.1:50000", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#"harmony_http_requests_total{method="POST",route="/beneficiary/select/:id",status="415"}"#), "{body}");
+    assert!(body.contains("harmony_http_request_duration_seconds_bucket"));
+    assert!(body.contains("harmony_db_pool_connections"));
+    assert!(body.contains("harmony_db_pool_acquire_seconds"));
+}
@@ -3,6 +3,26 @@
  mod details;
  mod stats;
 mod category;
+mod format;
+mod protocol;
+mod openapi;
+mod telemetry;
+mod metrics;
+mod health;
+mod shutdown;
+mod rate_limit;
+mod audit;
+mod history;
+mod trash;
+mod note;
+mod allergen;
+mod household;
+mod check_in;
+mod distribution;
+mod payment;
+mod receipt;
+mod card;
+mod snapshot;
 
  #[cfg(test)]
 #[tokio::test]
@@ -18,5 +38,13 @@ async fn test(){
     details::insert_note().await;
     details::update_note().await;
     details::select_details().await;
+    note::refused_note_writes_change_nothing().await;
+    trash::restored_allergies_come_back_once().await;
+    check_in::a_second_check_in_the_same_day_is_refused().await;
+    check_in::a_reached_limit_blocks_until_an_admin_overrides().await;
+    household::counts_follow_the_members().await;
+    category::a_category_in_use_is_deleted_once_reassigned().await;
+    payment::voided_payments_leave_the_balance().await;
+    allergen::has_allergies_follows_the_links().await;
     user::delete_user().await;
 }
\ No newline at end of file
@@ -0,0 +1,152 @@
+use harmony_protocol::v1;
+use axum::http::StatusCode;
+use crate::schema::details::{BeneficiaryNotes, Note, TokenNote};
+use crate::schema::format::Format;
+use crate::schema::note_type;
+use crate::schema::follow_up::{parse_due_date, FollowUp, FollowUpQueries};
+use crate::schema::note_type::{access_text, note_type_changes, validate, NoteType, NoteTypeAccess};
+use crate::test::beneficiary::{as_role, fresh_beneficiary, get_conn};
+
+#[cfg(test)]
+fn note(note_type: i8) -> Note {
+    Note {
+        Id: 12,
+        BeneficiaryId: 42,
+        Date: "2024-02-01 14:05:09".to_string(),
+        Type: note_type,
+        Note: "Rappeler lundi".to_string(),
+        AuthorId: Some(7),
+        CreatedAt: "2024-02-01 14:05:10".to_string(),
+        EditedAt: None,
+        EditedBy: None,
+    }
+}
+
+#[cfg(test)]
+fn access(role: &str, read: bool, write: bool) -> NoteTypeAccess {
+    NoteTypeAccess { Role: role.to_string(), CanRead: read, CanWrite: write }
+}
+
+#[cfg(test)]
+fn note_type(access: Vec<NoteTypeAccess>) -> NoteType {
+    NoteType { Id: 2, Name: "confidential-social-work".to_string(), Description: String::new(), Access: access }
+}
+
+/// A type every role reads and only Admin writes, created by the first run.
+#[cfg(test)]
+async fn read_only_type() -> i8 {
+    let read_only = NoteType { Id: 0, Name: "test-read-only".to_string(), Description: String::new(), Access: vec![access("Admin", true, true), access("User", true, false)] };
+    let _ = note_type::save(get_conn().await, &read_only, true, Format::Json).await;
+    note_type::all(get_conn().await.as_mut()).await.unwrap()
+        .into_iter()
+        .find(|saved| saved.Name == read_only.Name)
+        .unwrap()
+        .Id
+}
+
+#[cfg(test)]
+pub(crate) async fn refused_note_writes_change_nothing(){
+    let beneficiary = fresh_beneficiary().await;
+    let (admin, user) = (as_role("Admin").await, as_role("User").await);
+    let mut legacy = TokenNote {
+        Token: String::new(),
+        Content: BeneficiaryNotes { BeneficiaryId: beneficiary.Id, Date: "2024-02-01 14:05:09".to_string(), Type: read_only_type().await, Note: "Rappeler lundi".to_string() },
+    };
+    assert!(legacy.create_note(get_conn().await, &admin).await.unwrap());
+    assert!(!legacy.create_note(get_conn().await, &user).await.unwrap());
+
+    legacy.Content.Note = "Effacé".to_string();
+    assert_eq!(legacy.update_note(get_conn().await, &user).await.unwrap_err().0, StatusCode::FORBIDDEN);
+    assert_eq!(legacy.delete_note(get_conn().await, &user).await.unwrap_err().0, StatusCode::FORBIDDEN);
+    let stored = legacy.find_note(get_conn().await.as_mut(), &user).await.unwrap();
+    assert!(!Note::edit(get_conn().await.as_mut(), stored.Id, "Effacé", &user).await.unwrap());
+    assert!(!Note::delete(get_conn().await.as_mut(), stored.Id, &user).await.unwrap());
+
+    let stored = Note::find(get_conn().await.as_mut(), stored.Id, &admin).await.unwrap().unwrap();
+    assert_eq!((stored.Date.as_str(), stored.Note.as_str()), ("2024-02-01 14:05:09", "Rappeler lundi"), "dates keep a 24 hour clock");
+    assert_eq!(stored.AuthorId, Some(admin.Id));
+
+    // a second note at the same second makes the legacy routes ambiguous, the id still works
+    assert!(legacy.create_note(get_conn().await, &admin).await.unwrap());
+    assert_eq!(legacy.update_note(get_conn().await, &admin).await.unwrap_err().0, StatusCode::CONFLICT);
+    assert!(Note::edit(get_conn().await.as_mut(), stored.Id, "Effacé", &admin).await.unwrap());
+    assert!(Note::delete(get_conn().await.as_mut(), stored.Id, &admin).await.unwrap());
+    assert!(legacy.update_note(get_conn().await, &admin).await.is_ok(), "one note is left at that second");
+
+    // a type the role cannot read is not there at all
+    let hidden = TokenNote { Token: String::new(), Content: BeneficiaryNotes { Type: 1, ..legacy.Content } };
+    assert!(hidden.create_note(get_conn().await, &admin).await.unwrap());
+    assert_eq!(hidden.update_note(get_conn().await, &user).await.unwrap_err().0, StatusCode::NOT_FOUND);
+}
+
+#[cfg(test)]
+#[test]
+fn note_types_name_known_roles_once(){
+    assert!(validate(&note_type(vec![access("TS", true, true), access("Admin", true, false)])).is_ok());
+    assert!(validate(&note_type(vec![access("Guest", true, false)])).is_err());
+    assert!(validate(&note_type(vec![access("TS", true, true), access("TS", true, false)])).is_err());
+    assert!(validate(&note_type(vec![access("User", false, true)])).is_err(), "writing implies reading");
+
+    let mut unnamed = note_type(Vec::new());
+    unnamed.Name = " ".to_string();
+    assert!(validate(&unnamed).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn note_type_changes_keep_the_matrix_on_one_line(){
+    let before = note_type(vec![access("TS", true, true)]);
+    let after = note_type(vec![access("TS", true, true), access("Admin", true, false)]);
+    assert_eq!(access_text(&after), "TS:rw,Admin:r");
+
+    let changes = note_type_changes(Some(&before), &after);
+    let fields: Vec<_> = changes.iter().map(|change| change.Field.as_str()).collect();
+    assert_eq!(fields, ["Name", "Access"]);
+    assert_eq!(changes[1].Before.as_deref(), Some("TS:rw"));
+
+    let created = note_type_changes(None, &after);
+    assert_eq!(created.len(), 3);
+    assert!(created.iter().all(|change| change.Before.is_none()));
+}
+
+#[cfg(test)]
+#[test]
+fn legacy_notes_drop_the_id(){
+    let legacy = BeneficiaryNotes::from(note(0));
+    assert_eq!((legacy.BeneficiaryId, legacy.Date.as_str(), legacy.Type), (42, "2024-02-01 14:05:09", 0));
+}
+
+#[cfg(test)]
+#[test]
+fn open_follow_ups_are_sorted_by_due_date_and_readable(){
+    let select = FollowUpQueries::SelectOpen.to_string();
+    assert!(select.contains("AssigneeId = ? AND CompletedAt IS NULL AND DeletedAt IS NULL"), "{select}");
+    assert!(select.contains("WHERE Role = ? AND CanRead"), "{select}");
+    assert!(select.ends_with("ORDER BY DueDate ASC, Id ASC"), "{select}");
+    assert!(FollowUpQueries::CountOverdue.to_string().contains("DueDate < CURDATE()"));
+    for query in [FollowUpQueries::SetFollowUp, FollowUpQueries::ClearFollowUp] {
+        assert!(query.to_string().contains("WHERE Role = ? AND CanWrite"), "{query}");
+    }
+    assert!(FollowUpQueries::Complete.to_string().contains("AND (AssigneeId = ? OR Type IN"), "the assignee completes without write access");
+}
+
+#[cfg(test)]
+#[test]
+fn follow_ups_due_dates_are_plain_dates(){
+    assert_eq!(parse_due_date(" 2024-03-15 ").unwrap().to_string(), "2024-03-15");
+    assert!(parse_due_date("15/03/2024").is_err());
+    assert!(parse_due_date("2024-02-30").is_err());
+
+    let follow_up = v1::FollowUp::from(FollowUp {
+        NoteId: 12,
+        BeneficiaryId: 42,
+        Type: 3,
+        Note: "Rappeler pour le logement".to_string(),
+        DueDate: "2024-03-15".to_string(),
+        AssigneeId: 7,
+        Overdue: 1,
+        CompletedAt: None,
+        CompletedBy: None,
+    });
+    assert!(follow_up.Overdue);
+}
@@ -0,0 +1,90 @@
+use axum::http::StatusCode;
+use chrono::NaiveDate;
+use crate::schema::payment::{balance, parse_bounds, record, statement, validate, void, LedgerRow, Payment};
+use crate::test::beneficiary::{as_role, fresh_beneficiary, get_conn};
+
+#[cfg(test)]
+fn row(date: &str, kind: &str, charge: f64, payment: f64) -> LedgerRow {
+    LedgerRow {
+        Date: date.to_string(),
+        Kind: kind.to_string(),
+        Reference: 1,
+        Method: (kind == "payment").then(|| "cash".to_string()),
+        Charge: charge,
+        Payment: payment,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn payments_are_positive_with_a_known_method(){
+    let payment = Payment {
+        Id: 0,
+        BeneficiaryId: 42,
+        Amount: 5.0,
+        Method: "cash".to_string(),
+        PaidAt: String::new(),
+        Note: String::new(),
+        CreatedBy: None,
+    };
+    assert!(validate(&payment).is_ok());
+    assert!(validate(&Payment { Amount: 0.0, ..payment.clone() }).is_err());
+    assert!(validate(&Payment { Amount: f64::NAN, ..payment.clone() }).is_err());
+    assert!(validate(&Payment { Method: "bitcoin".to_string(), ..payment }).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn statements_carry_the_balance_from_before_the_range(){
+    let ledger = vec![
+        row("2024-01-01 00:00:00", "month", 10.0, 0.0),
+        row("2024-01-08 09:12:00", "visit", 2.5, 0.0),
+        row("2024-01-15 10:32:07", "visit", 2.5, 0.0),
+        row("2024-01-15 10:45:12", "payment", 0.0, 5.0),
+        row("2024-02-01 00:00:00", "month", 10.0, 0.0),
+    ];
+    let day = |day: &str| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok();
+
+    let all = statement(42, ledger.clone(), None, None);
+    assert_eq!(all.OpeningBalance, 0.0);
+    assert_eq!(all.Entries.len(), 5);
+    assert_eq!(all.ClosingBalance, 20.0);
+
+    let january = statement(42, ledger, day("2024-01-15"), day("2024-01-31"));
+    assert_eq!(january.OpeningBalance, 12.5);
+    let balances: Vec<_> = january.Entries.iter().map(|entry| entry.Balance).collect();
+    assert_eq!(balances, [15.0, 10.0]);
+    assert_eq!(january.Entries[1].Method.as_deref(), Some("cash"));
+    assert_eq!(january.ClosingBalance, 10.0);
+
+    assert!(parse_bounds(Some("2024-02-01"), Some("2024-01-01")).is_err());
+    assert!(parse_bounds(Some("01/02/2024"), None).is_err());
+    assert_eq!(parse_bounds(Some(""), None), Ok((None, None)));
+}
+
+#[cfg(test)]
+pub(crate) async fn voided_payments_leave_the_balance(){
+    let beneficiary = fresh_beneficiary().await;
+    let admin = as_role("Admin").await;
+    let paid = |amount: f64| Payment {
+        Id: 0,
+        BeneficiaryId: beneficiary.Id,
+        Amount: amount,
+        Method: "cash".to_string(),
+        PaidAt: String::new(),
+        Note: String::new(),
+        CreatedBy: None,
+    };
+    assert_eq!(balance(get_conn().await.as_mut(), beneficiary.Id).await.unwrap(), 0.0);
+
+    let first = record(get_conn().await, &paid(5.0), &admin).await.unwrap();
+    record(get_conn().await, &paid(2.5), &admin).await.unwrap();
+    assert_eq!(first.CreatedBy, Some(admin.Id));
+    assert_eq!(balance(get_conn().await.as_mut(), beneficiary.Id).await.unwrap(), -7.5, "payments ahead of the charges");
+
+    assert_eq!(void(get_conn().await, first.Id, &admin).await.unwrap().Amount, 5.0);
+    assert_eq!(balance(get_conn().await.as_mut(), beneficiary.Id).await.unwrap(), -2.5);
+    let again = void(get_conn().await, first.Id, &admin).await.unwrap_err();
+    assert_eq!(again, (StatusCode::CONFLICT, "This payment was voided already".to_string()));
+    assert_eq!(record(get_conn().await, &Payment { BeneficiaryId: -1, ..paid(1.0) }, &admin).await.unwrap_err().0, StatusCode::NOT_FOUND);
+}
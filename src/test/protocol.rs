@@ -0,0 +1,68 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use axum::{middleware, Extension, Router};
+use tower::ServiceExt;
+use harmony_protocol::{Version, VERSION_HEADER};
+use crate::route::version::negotiate_version;
+use crate::schema::category::TokenCategory;
+use crate::schema::details::{TokenAllergy, TokenNote, TokenPresence};
+use crate::schema::format::Format;
+use crate::schema::user::{Token, TokenBene, TokenBeneId, TokenSearch, UserLogin, UserToken};
+
+#[cfg(test)]
+macro_rules! v1_fixture {
+    ($name:literal) => {
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/protocol/tests/fixtures/v1/", $name, ".bin")).as_slice()
+    };
+}
+
+#[cfg(test)]
+#[test]
+fn server_requests_decode_v1_fixtures(){
+    let format = Format::Bincode;
+    assert!(format.decode::<Token>(v1_fixture!("token")).is_ok());
+    assert!(format.decode::<TokenBeneId>(v1_fixture!("token_bene_id")).is_ok());
+    assert!(format.decode::<TokenBene>(v1_fixture!("token_bene")).is_ok());
+    assert!(format.decode::<TokenSearch>(v1_fixture!("token_search")).is_ok());
+    assert!(format.decode::<UserLogin>(v1_fixture!("user_login")).is_ok());
+    assert!(format.decode::<UserToken>(v1_fixture!("user_token")).is_ok());
+    assert!(format.decode::<TokenCategory>(v1_fixture!("token_category")).is_ok());
+    assert!(format.decode::<TokenAllergy>(v1_fixture!("token_allergy")).is_ok());
+    assert!(format.decode::<TokenPresence>(v1_fixture!("token_presence")).is_ok());
+    assert!(format.decode::<TokenNote>(v1_fixture!("token_note")).is_ok());
+}
+
+#[cfg(test)]
+fn make_router() -> Router {
+    let api = Router::new().route("/version", get(|Extension(version): Extension<Version>| async move { version.to_string() }));
+    Router::new()
+        .merge(api.clone())
+        .nest("/v1", api.clone())
+        .nest("/v2", api)
+        .layer(middleware::from_fn(negotiate_version))
+}
+
+#[cfg(test)]
+async fn version_of(uri: &str, header: Option<&str>) -> (StatusCode, Option<String>) {
+    let mut request = Request::builder().uri(uri);
+    if let Some(header) = header {
+        request = request.header(VERSION_HEADER, header);
+    }
+    let response = make_router().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+    let echoed = response.headers().get(VERSION_HEADER).map(|value| value.to_str().unwrap().to_string());
+    (response.status(), echoed)
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn version_negotiation(){
+    assert_eq!(version_of("/version", None).await, (StatusCode::OK, Some("1".to_string())));
+    assert_eq!(version_of("/v1/version", None).await, (StatusCode::OK, Some("1".to_string())));
+    assert_eq!(version_of("/version", Some("v1")).await, (StatusCode::OK, Some("1".to_string())));
+    assert_eq!(version_of("/v1/version", Some("1")).await, (StatusCode::OK, Some("1".to_string())));
+    assert_eq!(version_of("/v2/version", None).await, (StatusCode::OK, Some("2".to_string())));
+    assert_eq!(version_of("/version", Some("2")).await, (StatusCode::OK, Some("2".to_string())));
+    assert_eq!(version_of("/v1/version", Some("2")).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(version_of("/version", Some("99")).await.0, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,175 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+use crate::config::{Budget, Config};
+use crate::route::rate_limit::{caller, Key, RateLimiter, RouteGroup};
+use crate::schema::format::{Format, BINCODE, MSGPACK};
+use crate::schema::user::{Token, UserLogin};
+use crate::test::openapi::{make_offline_pool, make_offline_router_with};
+
+#[cfg(test)]
+const OFFICE: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 2, 40));
+
+#[cfg(test)]
+const ELSEWHERE: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+
+#[cfg(test)]
+fn tight_config() -> Config {
+    let mut config = Config::default();
+    config.rate_limit.login.per_user = Budget::new(2, 60);
+    config.rate_limit.login.per_ip = Budget::new(3, 60);
+    config
+}
+
+#[cfg(test)]
+async fn login(router: &Router, username: &str, from: IpAddr) -> axum::response::Response {
+    let mut request = Request::post("/v1/user/login")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(format!(r#"{{"Username":"{username}","Password":"nope"}}"#)))
+        .unwrap();
+    request.extensions_mut().insert(ConnectInfo(SocketAddr::new(from, 50000)));
+    router.clone().oneshot(request).await.unwrap()
+}
+
+#[cfg(test)]
+#[test]
+fn budgets_parse(){
+    assert_eq!("5/60".parse::<Budget>(), Ok(Budget::new(5, 60)));
+    assert!("0/60".parse::<Budget>().is_err());
+    assert!("5".parse::<Budget>().is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn routes_fall_in_groups(){
+    assert_eq!(RouteGroup::of("/user/login"), Some(RouteGroup::Login));
+    assert_eq!(RouteGroup::of("/v1/beneficiary/select/:id"), Some(RouteGroup::Read));
+    assert_eq!(RouteGroup::of("/beneficiary/search"), Some(RouteGroup::Read));
+    assert_eq!(RouteGroup::of("/presence"), Some(RouteGroup::Write));
+    assert_eq!(RouteGroup::of("/trash/select"), Some(RouteGroup::Read));
+    assert_eq!(RouteGroup::of("/trash/restore"), Some(RouteGroup::Write));
+    assert_eq!(RouteGroup::of("/health/ready"), None);
+    assert_eq!(RouteGroup::of("/v1/metrics"), None);
+}
+
+#[cfg(test)]
+#[test]
+fn caller_is_read_in_every_format(){
+    let token = Token { Token: "admin-1234".to_string() };
+    let login = UserLogin { Username: "benevole".to_string(), Password: "secret".to_string() };
+    let headers = |media_type: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(media_type));
+        headers
+    };
+
+    assert_eq!(caller(&headers(BINCODE), &Format::Bincode.encode(&token).unwrap()).as_deref(), Some("admin-1234"));
+    assert_eq!(caller(&headers(MSGPACK), &Format::MessagePack.encode(&login).unwrap()).as_deref(), Some("benevole"));
+    assert_eq!(caller(&HeaderMap::new(), br#"{"Token":"admin-1234","Id":3}"#).as_deref(), Some("admin-1234"));
+    assert_eq!(caller(&HeaderMap::new(), b"garbage"), None);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn buckets_refill_over_time(){
+    let limiter = RateLimiter::new(Arc::new(tight_config()), make_offline_pool());
+    let a = Key::Login(OFFICE, "a".to_string());
+    let start = Instant::now();
+    assert!(limiter.acquire(RouteGroup::Login, Some(&a), None, start).is_ok());
+    assert!(limiter.acquire(RouteGroup::Login, Some(&a), None, start).is_ok());
+
+    let (wait, key) = limiter.acquire(RouteGroup::Login, Some(&a), None, start).unwrap_err();
+    assert_eq!(key, "user");
+    assert!(wait > Duration::from_secs(29) && wait <= Duration::from_secs(30));
+
+    assert!(limiter.acquire(RouteGroup::Login, Some(&a), None, start + Duration::from_secs(30)).is_ok());
+    assert!(limiter.acquire(RouteGroup::Read, Some(&a), None, start).is_ok());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn refunds_give_tokens_back(){
+    let limiter = RateLimiter::new(Arc::new(tight_config()), make_offline_pool());
+    let a = Key::Login(OFFICE, "a".to_string());
+    let start = Instant::now();
+    for _ in 0..5 {
+        assert!(limiter.acquire(RouteGroup::Login, Some(&a), Some(OFFICE), start).is_ok());
+        limiter.refund(RouteGroup::Login, Some(&a), Some(OFFICE), start);
+    }
+    limiter.refund(RouteGroup::Login, Some(&a), Some(OFFICE), start);
+    assert!(limiter.acquire(RouteGroup::Login, Some(&a), Some(OFFICE), start).is_ok());
+    assert!(limiter.acquire(RouteGroup::Login, Some(&a), Some(OFFICE), start).is_ok());
+    assert!(limiter.acquire(RouteGroup::Login, Some(&a), Some(OFFICE), start).is_err());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn login_is_limited_per_user_and_per_address(){
+    let router = make_offline_router_with(tight_config());
+
+    assert_ne!(login(&router, "alice", OFFICE).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_ne!(login(&router, "alice", OFFICE).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let refused = login(&router, "alice", OFFICE).await;
+    assert_eq!(refused.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = refused.headers()[header::RETRY_AFTER].to_str().unwrap().parse().unwrap();
+    assert!((1..=30).contains(&retry_after));
+
+    assert_ne!(login(&router, "bob", OFFICE).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(login(&router, "carol", OFFICE).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Failures from the office do not lock alice out elsewhere.
+    assert_ne!(login(&router, "alice", ELSEWHERE).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let probe = router.clone().oneshot(Request::get("/health/live").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(probe.status(), StatusCode::OK);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn unknown_tokens_only_count_against_the_address(){
+    let mut config = Config::default();
+    config.rate_limit.read.per_user = Budget::new(1, 60);
+    config.rate_limit.read.per_ip = Budget::new(3, 60);
+    let router = make_offline_router_with(config);
+
+    let select = |token: &str| {
+        let mut request = Request::post("/v1/user/select")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(format!(r#"{{"Token":"{token}"}}"#)))
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::new(OFFICE, 50000)));
+        router.clone().oneshot(request)
+    };
+    for token in ["forged-1", "forged-2", "forged-1"] {
+        assert_ne!(select(token).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+    assert_eq!(select("forged-3").await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn a_flood_is_refused_before_tokens_are_resolved(){
+    let mut config = Config::default();
+    config.rate_limit.read.per_ip = Budget::new(1, 60);
+    let router = make_offline_router_with(config);
+
+    let select = || {
+        let mut request = Request::post("/v1/user/select")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"Token":"forged"}"#))
+            .unwrap();
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::new(OFFICE, 50000)));
+        router.clone().oneshot(request)
+    };
+    // The offline pool cannot hand out a connection, so resolving the token ends in 503.
+    assert_eq!(select().await.unwrap().status(), StatusCode::SERVICE_UNAVAILABLE);
+    let start = Instant::now();
+    assert_eq!(select().await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(start.elapsed() < Duration::from_millis(100), "no connection was waited for");
+}
@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+use harmony_protocol::v1::{Allowance, Distribution, DistributionItem, Eligibility, Payment};
+use crate::config::{EligibilityBasis, OrganizationConfig};
+use crate::pdf::Page;
+use crate::schema::receipt::{Holder, Receipt, VisitSlip};
+
+/// The text of a page as a reader sees it, one line per baseline.
+#[cfg(test)]
+fn text(pdf: &[u8]) -> String {
+    let pdf = String::from_utf8_lossy(pdf);
+    let mut lines: Vec<(String, Vec<String>)> = Vec::new();
+    for op in pdf.lines().filter(|line| line.starts_with("BT ") && line.ends_with(" Tj ET")) {
+        let (position, string) = op.split_once(" Td (").unwrap();
+        let y = position.split(' ').nth_back(0).unwrap().to_string();
+        let string = string.strip_suffix(") Tj ET").unwrap();
+        let mut decoded = String::new();
+        let mut chars = string.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next().unwrap() {
+                    digit @ '0'..='7' => {
+                        let octal: String = [digit, chars.next().unwrap(), chars.next().unwrap()].into_iter().collect();
+                        decoded.push(u8::from_str_radix(&octal, 8).unwrap() as char);
+                    },
+                    escaped => decoded.push(escaped),
+                },
+                c => decoded.push(c),
+            }
+        }
+        match lines.last_mut() {
+            Some((last, strings)) if *last == y => strings.push(decoded),
+            _ => lines.push((y, vec![decoded])),
+        }
+    }
+    lines
+        .into_iter()
+        .map(|(_, strings)| strings.into_iter().filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" | ") + "\n")
+        .collect()
+}
+
+/// Compares with `src/test/fixtures/<name>.txt`, rewritten when `HARMONY_WRITE_FIXTURES` is set.
+#[cfg(test)]
+fn golden(name: &str, page: &Page) {
+    let pdf = page.render();
+    assert!(pdf.starts_with(b"%PDF-1.4\n"));
+    assert!(pdf.ends_with(b"%%EOF\n"));
+    let actual = text(&pdf);
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test/fixtures").join(format!("{name}.txt"));
+    if std::env::var_os("HARMONY_WRITE_FIXTURES").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing fixture {}: {e}", path.display()));
+    assert_eq!(actual, expected, "{name} no longer prints as {}", path.display());
+}
+
+#[cfg(test)]
+fn organization() -> OrganizationConfig {
+    OrganizationConfig { name: "Épicerie solidaire".to_string(), address: "12 rue des Lilas, 75020 Paris".to_string() }
+}
+
+#[cfg(test)]
+fn holder() -> Holder {
+    Holder { Id: 42, Name: "Marie Dupont".to_string(), Category: Some("Famille (2)".to_string()) }
+}
+
+#[cfg(test)]
+#[test]
+fn receipts_print_the_payment_and_what_is_left_to_pay(){
+    let receipt = Receipt {
+        Holder: holder(),
+        Payment: Payment {
+            Id: 9,
+            BeneficiaryId: 42,
+            Amount: 5.0,
+            Method: "cash".to_string(),
+            PaidAt: "2024-01-15 10:45:12".to_string(),
+            Note: String::new(),
+            CreatedBy: Some(7),
+        },
+        Balance: 7.5,
+    };
+    golden("receipt", &receipt.page(&organization()));
+    assert_eq!(receipt.page(&organization()).render(), receipt.page(&organization()).render());
+}
+
+#[cfg(test)]
+#[test]
+fn visit_slips_print_what_was_given_and_the_remaining_allowance(){
+    let slip = VisitSlip {
+        Holder: holder(),
+        Date: "2024-01-15 10:32:07".to_string(),
+        Distribution: Some(Distribution {
+            Id: 12,
+            BeneficiaryId: 42,
+            PresenceDate: "2024-01-15 10:32:07".to_string(),
+            Amount: 35.5,
+            Items: vec![
+                DistributionItem { Item: "conserves".to_string(), Quantity: 2.0 },
+                DistributionItem { Item: "pâtes".to_string(), Quantity: 1.5 },
+            ],
+            CategoryId: Some(1),
+            Fee: 2.5,
+            VolunteerId: Some(7),
+            Note: String::new(),
+            CreatedAt: "2024-01-15 10:40:00".to_string(),
+            CreatedBy: Some(7),
+            Supersedes: None,
+        }),
+        Eligibility: Eligibility {
+            BeneficiaryId: 42,
+            Date: "2024-01-15".to_string(),
+            WeekStart: "2024-01-15".to_string(),
+            MonthStart: "2024-01-01".to_string(),
+            Weekly: Allowance { Limit: 1.0, Used: 1.0, Remaining: Some(0.0) },
+            Monthly: Allowance { Limit: 0.0, Used: 3.0, Remaining: None },
+            Eligible: false,
+            Blocking: false,
+            Reasons: vec!["Weekly limit of 1 reached".to_string()],
+        },
+        Basis: EligibilityBasis::Visits,
+    };
+    golden("visit_slip", &slip.page(&organization()));
+}
@@ -0,0 +1,115 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use axum::routing::get;
+use axum::Router;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use crate::jobs::Jobs;
+use crate::shutdown::{cancel_on_signal, serve};
+
+#[cfg(test)]
+fn slow_router(delay: Duration) -> Router {
+    Router::new().route("/slow", get(move || async move {
+        tokio::time::sleep(delay).await;
+        "done"
+    }))
+}
+
+#[cfg(test)]
+async fn get_slow(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"GET /slow HTTP/1.1\r\nHost: harmony\r\nConnection: close\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+/// Serves the slow route in a process of its own until SIGTERM, see
+/// `signal_drains_in_flight_requests`, printing its address once signals are handled.
+#[cfg(test)]
+#[tokio::test]
+#[ignore = "run by signal_drains_in_flight_requests in a child process"]
+async fn serve_until_signalled(){
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let shutdown = CancellationToken::new();
+    cancel_on_signal(shutdown.clone());
+    println!("listening on {}", listener.local_addr().unwrap());
+    serve(listener, slow_router(Duration::from_millis(500)), shutdown.clone(), Duration::from_secs(5)).await;
+    assert!(shutdown.is_cancelled());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn signal_drains_in_flight_requests(){
+    let mut child = Command::new(std::env::current_exe().unwrap())
+        .args(["test::shutdown::serve_until_signalled", "--exact", "--ignored", "--nocapture", "--test-threads=1"])
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let addr: SocketAddr = loop {
+        let line = lines.next_line().await.unwrap().expect("the child stopped before listening");
+        // libtest writes the test name first, on the same line.
+        if let Some((_, addr)) = line.split_once("listening on ") {
+            break addr.parse().unwrap();
+        }
+    };
+
+    let request = tokio::spawn(get_slow(addr));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let pid = child.id().unwrap().to_string();
+    assert!(Command::new("kill").args(["-TERM", &pid]).status().await.unwrap().success());
+
+    let response = request.await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with("done"));
+
+    let status = tokio::time::timeout(Duration::from_secs(5), child.wait()).await.unwrap().unwrap();
+    assert!(status.success(), "{status}");
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn drain_deadline_bounds_the_shutdown(){
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let shutdown = CancellationToken::new();
+    let server = tokio::spawn(serve(listener, slow_router(Duration::from_secs(30)), shutdown.clone(), Duration::from_millis(200)));
+
+    let _stuck = tokio::spawn(get_slow(addr));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let start = Instant::now();
+    shutdown.cancel();
+
+    let deadline = tokio::time::timeout(Duration::from_secs(2), server).await.unwrap().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(deadline <= Instant::now());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn jobs_stop_on_shutdown(){
+    let runs = Arc::new(AtomicU32::new(0));
+    let jobs = Jobs::new(CancellationToken::new());
+    let counter = runs.clone();
+    jobs.every("count", Duration::from_millis(20), move || {
+        let counter = counter.clone();
+        async move { counter.fetch_add(1, Ordering::SeqCst); }
+    });
+
+    tokio::time::sleep(Duration::from_millis(70)).await;
+    assert!(jobs.stop(Instant::now() + Duration::from_secs(1)).await);
+    let stopped_at = runs.load(Ordering::SeqCst);
+    assert!(stopped_at >= 1);
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(runs.load(Ordering::SeqCst), stopped_at);
+}
@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+use chrono::{Local, NaiveDate, Weekday};
+use serde_json::{json, Map, Value};
+use crate::config::{EligibilityBasis, EligibilityConfig, EligibilityMode, StatsPeriod};
+use crate::get_db_url;
+use crate::schema::details::DetailsQueries;
+use crate::schema::snapshot::{bounds, compute, range, span, starts, tally, Person, SERIES};
+use crate::test::beneficiary::fresh_beneficiary;
+
+#[cfg(test)]
+fn day(day: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+}
+
+#[cfg(test)]
+fn config() -> EligibilityConfig {
+    EligibilityConfig { week_start: Weekday::Mon, month_start: 1, basis: EligibilityBasis::Visits, mode: EligibilityMode::Warn }
+}
+
+/// Counts `src/test/fixtures/stats_people.json` and compares them with `stats_snapshot.json`,
+/// rewritten when `HARMONY_WRITE_FIXTURES` is set.
+#[cfg(test)]
+#[test]
+fn snapshot_counts_fixture_people(){
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test/fixtures");
+    let people: Vec<Person> = serde_json::from_str(&std::fs::read_to_string(fixtures.join("stats_people.json")).unwrap()).unwrap();
+    let counts = tally(&people, 7, (27.6, 105.2), day("2024-03-31"));
+    let mut actual = Map::new();
+    for ((table, columns), counts) in SERIES.iter().zip(&counts) {
+        let row: Map<String, Value> = columns.iter().zip(counts).map(|(column, count)| (column.to_string(), json!(count))).collect();
+        actual.insert(table.to_string(), Value::Object(row));
+    }
+    let actual = serde_json::to_string_pretty(&actual).unwrap() + "\n";
+    let path = fixtures.join("stats_snapshot.json");
+    if std::env::var_os("HARMONY_WRITE_FIXTURES").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing fixture {}: {e}", path.display()));
+    assert_eq!(actual, expected);
+}
+
+#[cfg(test)]
+#[test]
+fn periods_are_dated_from_their_first_day(){
+    let config = config();
+    assert_eq!(bounds(day("2024-03-14"), StatsPeriod::Day, &config), (day("2024-03-14"), day("2024-03-15")));
+    assert_eq!(bounds(day("2024-03-14"), StatsPeriod::Week, &config), (day("2024-03-11"), day("2024-03-18")));
+    assert_eq!(bounds(day("2024-03-14"), StatsPeriod::Month, &config), (day("2024-03-01"), day("2024-04-01")));
+    assert_eq!(starts(day("2024-01-31"), day("2024-03-01"), StatsPeriod::Month, &config), vec![day("2024-01-01"), day("2024-02-01"), day("2024-03-01")]);
+    assert_eq!(starts(day("2024-03-14"), day("2024-03-14"), StatsPeriod::Week, &config), vec![day("2024-03-11")]);
+    assert_eq!(starts(day("2024-03-01"), day("2024-03-31"), StatsPeriod::Day, &config).len(), 31);
+
+    assert!(range("2024-01-01", "2024-03-31", StatsPeriod::Day, &config).is_ok());
+    assert!(range("2024-03-31", "2024-01-01", StatsPeriod::Day, &config).is_err());
+    assert!(range("2024-01-01", "31/03/2024", StatsPeriod::Day, &config).is_err());
+    assert!(range("2024-01-01", "9999-12-31", StatsPeriod::Month, &config).is_err());
+    assert!(range("1990-01-01", "2024-01-01", StatsPeriod::Day, &config).is_err());
+    assert!(range("1990-01-01", "2024-01-01", StatsPeriod::Month, &config).is_ok());
+}
+
+#[cfg(test)]
+#[test]
+fn periods_end_on_their_boundaries(){
+    // Weeks start on Wednesday and months on the 15th.
+    let config = EligibilityConfig { week_start: Weekday::Wed, month_start: 15, ..config() };
+    let today = day("2024-06-30");
+    assert_eq!(span(day("2024-03-13"), StatsPeriod::Week, &config, today), (day("2024-03-13"), day("2024-03-20"), day("2024-03-19")));
+    assert_eq!(span(day("2024-03-19"), StatsPeriod::Week, &config, today), (day("2024-03-13"), day("2024-03-20"), day("2024-03-19")));
+    assert_eq!(span(day("2024-03-20"), StatsPeriod::Week, &config, today).0, day("2024-03-20"));
+    assert_eq!(span(day("2024-03-14"), StatsPeriod::Month, &config, today), (day("2024-02-15"), day("2024-03-15"), day("2024-03-14")));
+    assert_eq!(span(day("2024-03-15"), StatsPeriod::Month, &config, today), (day("2024-03-15"), day("2024-04-15"), day("2024-04-14")));
+    assert_eq!(span(day("2024-12-31"), StatsPeriod::Month, &config, today).1, day("2025-01-15"));
+
+    // A period not over yet is counted on today.
+    assert_eq!(span(day("2024-06-26"), StatsPeriod::Week, &config, today), (day("2024-06-26"), day("2024-07-03"), today));
+    assert_eq!(span(day("2024-06-15"), StatsPeriod::Month, &config, today).2, today);
+
+    assert_eq!(starts(day("2024-03-19"), day("2024-03-20"), StatsPeriod::Week, &config), vec![day("2024-03-13"), day("2024-03-20")]);
+    assert_eq!(starts(day("2024-02-14"), day("2024-03-15"), StatsPeriod::Month, &config), vec![day("2024-01-15"), day("2024-02-15"), day("2024-03-15")]);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn computing_a_period_again_keeps_one_row(){
+    let pool = sqlx::mysql::MySqlPool::connect(&get_db_url()).await.unwrap();
+    let config = config();
+    let (start, _) = bounds(Local::now().date_naive(), StatsPeriod::Week, &config);
+    compute(&pool, start, StatsPeriod::Week, &config).await.unwrap();
+    compute(&pool, start, StatsPeriod::Week, &config).await.unwrap();
+    for (table, _) in SERIES {
+        let rows: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table} WHERE Date = ?"))
+            .bind(start)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1, "{table}");
+    }
+}
+
+/// Visits recorded on a day long past, where nothing else in the tests writes.
+#[cfg(test)]
+#[tokio::test]
+async fn deleted_presences_are_not_visits(){
+    let pool = sqlx::mysql::MySqlPool::connect(&get_db_url()).await.unwrap();
+    let config = config();
+    let start = day("2001-02-03");
+    let visits = || async {
+        compute(&pool, start, StatsPeriod::Day, &config).await.unwrap();
+        let visits: i64 = sqlx::query_scalar("SELECT CAST(Visits AS SIGNED) FROM Presence WHERE Date = ?")
+            .bind(start)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        visits
+    };
+    let before = visits().await;
+
+    let beneficiary = fresh_beneficiary().await;
+    for time in ["09:15:00", "14:40:00"] {
+        sqlx::query(&DetailsQueries::InsertPresence.to_string())
+            .bind(beneficiary.Id)
+            .bind(format!("{start} {time}"))
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query("UPDATE BeneficiaryPresences SET DeletedAt = NOW() WHERE BeneficiaryId = ? AND PresenceDate = ?")
+        .bind(beneficiary.Id)
+        .bind(format!("{start} 14:40:00"))
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(visits().await, before + 1);
+}
@@ -1,20 +1,28 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use chrono::NaiveDate;
 use sqlx::MySql;
 use sqlx::pool::PoolConnection;
+use crate::config::Config;
 use crate::get_db_url;
-use crate::schema::stats::Stats;
+use crate::schema::distribution::TotalsPeriod;
+use crate::schema::stats::{rollup, select_stats as select, stats, Selection, StatsQueries, TokenStatsQuery};
+use crate::schema::format::Format;
+use crate::test::openapi::make_offline_router;
+use tower::ServiceExt;
 
 #[cfg(test)]
 #[tokio::test]
 async fn select_stats(){
     let conn = get_conn().await;
-    let stats = Stats::get_stats(conn).await;
+    let stats = select(conn, &Selection::all(), &Config::default().eligibility, Format::Bincode).await;
 
     match stats {
         Ok(stat) => {
             println!("{:?}", stat)
         }
         Err(_) => {
-            assert!(false, "Failed to get stats")
+            panic!("Failed to get stats")
         }
     }
 }
@@ -24,3 +32,94 @@ pub(crate) async fn get_conn() -> PoolConnection<MySql>{
     let pool = sqlx::mysql::MySqlPool::connect(&db).await.unwrap();
     pool.acquire().await.unwrap()
 }
+
+#[cfg(test)]
+fn day(day: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+}
+
+#[cfg(test)]
+fn query(from: Option<&str>, to: Option<&str>, period: &str, series: &[&str]) -> TokenStatsQuery {
+    TokenStatsQuery {
+        Token: String::new(),
+        From: from.map(str::to_string),
+        To: to.map(str::to_string),
+        Period: period.to_string(),
+        Series: series.iter().map(|name| name.to_string()).collect(),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn stats_queries_are_checked(){
+    assert_eq!(Selection::parse(&query(None, None, "", &[])).unwrap(), Selection::all());
+    let selection = Selection::parse(&query(Some("2024-01-01"), Some(""), "month", &["presences", "Ages"])).unwrap();
+    assert_eq!(selection.from, Some(day("2024-01-01")));
+    assert_eq!(selection.to, None);
+    assert_eq!(selection.period, TotalsPeriod::Month);
+    assert_eq!(selection.series.iter().filter(|read| **read).count(), 2);
+    assert!(selection.series[0] && selection.series[2]);
+
+    for bad in [
+        query(Some("2024-02-01"), Some("2024-01-01"), "day", &[]),
+        query(Some("01/02/2024"), None, "day", &[]),
+        query(None, None, "quarter", &[]),
+        query(None, None, "day", &["Weather"]),
+    ] {
+        assert_eq!(Selection::parse(&bad).unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+    let sql = StatsQueries::SelectSeries("Sexe", &["Male", "Female", "Other"]).to_string();
+    assert!(sql.starts_with("SELECT DATE_FORMAT(Date, '%Y-%m-%d') AS Date, Male, Female, Other FROM Sexe"), "{sql}");
+    assert_eq!(sql.matches('?').count(), 4);
+}
+
+#[cfg(test)]
+#[test]
+fn rollup_keeps_the_last_head_count_and_sums_visits(){
+    let config = Config::default().eligibility;
+    let presences = vec![
+        (day("2024-01-30"), vec![10, 8, 3]),
+        (day("2024-01-31"), vec![11, 9, 4]),
+        (day("2024-02-01"), vec![12, 9, 5]),
+        (day("2024-02-29"), vec![12, 7, 1]),
+    ];
+    assert_eq!(rollup(0, presences.clone(), TotalsPeriod::Day, &config), presences);
+    assert_eq!(rollup(0, presences.clone(), TotalsPeriod::Month, &config), vec![
+        (day("2024-01-01"), vec![11, 9, 7]),
+        (day("2024-02-01"), vec![12, 7, 6]),
+    ]);
+    assert_eq!(rollup(0, presences.clone(), TotalsPeriod::Year, &config), vec![(day("2024-01-01"), vec![12, 7, 13])]);
+    assert_eq!(rollup(0, presences, TotalsPeriod::Week, &config)[0], (day("2024-01-29"), vec![12, 9, 12]));
+
+    let sexes = vec![(day("2024-01-30"), vec![1, 2, 0]), (day("2024-01-31"), vec![3, 4, 1])];
+    assert_eq!(rollup(10, sexes, TotalsPeriod::Month, &config), vec![(day("2024-01-01"), vec![3, 4, 1])]);
+
+    let amounts = vec![(day("2024-01-30"), vec![20, 90]), (day("2024-01-31"), vec![25, 95])];
+    assert_eq!(rollup(1, amounts, TotalsPeriod::Month, &config), vec![(day("2024-01-01"), vec![25, 95])]);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn the_range_is_a_v2_body(){
+    let router = make_offline_router();
+    let select = |path: &'static str, body: &'static str| {
+        let request = Request::post(path).header(header::CONTENT_TYPE, "application/json").body(Body::from(body)).unwrap();
+        router.clone().oneshot(request)
+    };
+    assert_ne!(select("/v1/stats/select", r#"{"Token":"admin-1234"}"#).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_ne!(select("/v2/stats/select", r#"{"Token":"admin-1234"}"#).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_ne!(select("/v2/stats/select", r#"{"Token":"admin-1234","From":"2024-01-01","Period":"month"}"#).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_eq!(select("/v2/stats/select", r#"{"Period":"month"}"#).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert_eq!(select("/stats/rollup/select", r#"{"Token":"admin-1234"}"#).await.unwrap().status(), StatusCode::NOT_FOUND);
+}
+
+#[cfg(test)]
+#[test]
+fn series_not_read_are_left_empty(){
+    let mut series = vec![Vec::new(); 12];
+    series[10] = vec![(day("2024-01-01"), vec![3, 4, 1])];
+    let stats = stats(series);
+    assert!(stats.Presences.is_empty() && stats.Studies.is_empty());
+    assert_eq!(stats.Sexes.len(), 1);
+    assert_eq!((stats.Sexes[0].Date.as_str(), stats.Sexes[0].Male, stats.Sexes[0].Female, stats.Sexes[0].Other), ("2024-01-01", 3, 4, 1));
+}
@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use tower::ServiceExt;
+use crate::telemetry::{Pii, Secret, REQUEST_ID_HEADER};
+use crate::test::openapi::make_offline_router;
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn secrets_are_fingerprinted(){
+    let token = "admin-0b8f4a52-6d1e-4c3a-9f7e-2a1b3c4d5e6f";
+    let logged = Secret(token).to_string();
+    assert!(!logged.contains("admin"));
+    assert!(!logged.contains("0b8f"));
+    assert_eq!(logged, Secret(token).to_string());
+    assert_ne!(logged, Secret("admin-other").to_string());
+    assert_eq!(Secret("").to_string(), "<empty>");
+}
+
+#[cfg(test)]
+#[test]
+fn pii_is_redacted(){
+    assert_eq!(Pii("Dupont").to_string(), "<redacted:6 chars>");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn requests_carry_an_id(){
+    let router = make_offline_router();
+
+    let response = router.clone()
+        .oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+
+    let response = router
+        .oneshot(Request::get("/openapi.json").header(REQUEST_ID_HEADER, "abc-123").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn request_span_has_route_status_and_latency(){
+    let capture = Capture::default();
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_current_span(true)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let response = make_offline_router()
+        .oneshot(Request::get("/v1/openapi.json").header(REQUEST_ID_HEADER, "req-42").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    let line = logs.lines().find(|line| line.contains("request served")).expect(&logs);
+    let event: serde_json::Value = serde_json::from_str(line).unwrap();
+    let span = &event["span"];
+    assert_eq!(span["method"], "GET");
+    assert_eq!(span["route"], "/v1/openapi.json");
+    assert_eq!(span["request_id"], "req-42");
+    assert_eq!(span["status"], 200);
+    assert!(span["latency_ms"].is_u64());
+}
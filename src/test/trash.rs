@@ -0,0 +1,60 @@
+use axum::http::StatusCode;
+use crate::schema::audit::AuditAction;
+use std::time::Duration;
+use crate::schema::details::{BeneficiaryAllergy, Details, TokenAllergy};
+use crate::schema::format::Format;
+use crate::schema::trash::{restore, select_trash, TokenTrashQuery, TrashEntry, TrashKind};
+use crate::test::beneficiary::{as_role, fresh_beneficiary, get_conn};
+
+#[cfg(test)]
+#[test]
+fn kinds_round_trip(){
+    for kind in TrashKind::ALL {
+        assert_eq!(kind.to_string().parse::<TrashKind>(), Ok(kind));
+    }
+    assert_eq!("Note".parse::<TrashKind>().unwrap_err().0, StatusCode::BAD_REQUEST);
+    assert_eq!(TrashKind::Presence.restored(), AuditAction::RestorePresence);
+    assert_eq!(AuditAction::RestoreNote.to_string(), "note.restore");
+}
+
+#[cfg(test)]
+#[test]
+fn allergies_are_restored_by_row_id(){
+    let entry = TrashEntry {
+        Kind: "allergy".to_string(),
+        BeneficiaryId: 42,
+        Key: "12".to_string(),
+        Content: "Arachides".to_string(),
+        DeletedAt: "2024-02-03 09:00:00".to_string(),
+        DeletedBy: Some(7),
+        PurgeAfter: "2024-03-04 09:00:00".to_string(),
+    };
+    let changes = TrashKind::Allergy.changes(&entry);
+    assert_eq!(changes[0].Field, "Id");
+    assert_eq!(changes[0].After.as_deref(), Some("12"));
+}
+
+#[cfg(test)]
+pub(crate) async fn restored_allergies_come_back_once(){
+    let beneficiary = fresh_beneficiary().await;
+    let admin = as_role("Admin").await;
+    let retention = Duration::from_secs(30 * 24 * 60 * 60);
+    let allergy = TokenAllergy {
+        Token: String::new(),
+        Allergy: BeneficiaryAllergy { BeneficiaryId: beneficiary.Id, Allergy: "Arachides".to_string() },
+    };
+    allergy.insert_allergy(get_conn().await).await.unwrap();
+    allergy.delete_allergy(get_conn().await, &admin).await.unwrap();
+    let allergies = |details: Details| details.Allergies.into_iter().map(|allergy| allergy.Allergy).collect::<Vec<_>>();
+    assert!(allergies(Details::get_details(get_conn().await, admin.clone(), beneficiary.Id).await.unwrap()).is_empty());
+
+    let query = TokenTrashQuery { Token: String::new(), BeneficiaryId: Some(beneficiary.Id) };
+    let trash = select_trash(get_conn().await, &query, &admin, retention, Format::Json).await.unwrap();
+    let trash: Vec<TrashEntry> = serde_json::from_slice(&trash.bytes).unwrap();
+    assert_eq!(trash.len(), 1);
+    assert_eq!((trash[0].Kind.as_str(), trash[0].Content.as_str(), trash[0].DeletedBy), ("allergy", "Arachides", Some(admin.Id)));
+
+    assert_eq!(restore(get_conn().await, &trash[0], retention).await, Ok(TrashKind::Allergy));
+    assert_eq!(allergies(Details::get_details(get_conn().await, admin.clone(), beneficiary.Id).await.unwrap()), ["Arachides"]);
+    assert_eq!(restore(get_conn().await, &trash[0], retention).await.unwrap_err().0, StatusCode::NOT_FOUND, "restored once only");
+}
@@ -2,6 +2,7 @@ use sqlx::{MySql, Row};
 use sqlx::pool::PoolConnection;
 use crate::get_db_url;
 use crate::schema::user::{User, UserToken};
+use crate::schema::format::Format;
 
 
 #[cfg(test)]
@@ -31,7 +32,7 @@ pub(crate) async fn make_user_token() -> UserToken {
 pub(crate) async fn create_user(){
     let user = make_user_token().await;
     let conn = get_conn().await;
-    let res = user.create_user(conn).await;
+    let res = user.create_user(conn, Format::Bincode).await;
     assert!(res.is_ok());
 }
 